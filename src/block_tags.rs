@@ -0,0 +1,165 @@
+//! Support for the block tags the zkSync types don't parse.
+//!
+//! The block parameters of the `eth_*` and `debug_*` methods are parsed into the zkSync
+//! `BlockNumber` types before any node method runs, and those types reject tags like `safe`. The
+//! [`BlockTagMiddleware`] rewrites such tags into their equivalent on the node, but only for the
+//! parameters that hold a block, so other arguments are passed through as sent.
+use futures::{future::Either, Future};
+use jsonrpc_core::{middleware, Call, FutureResponse, Middleware, Params, Request, Response};
+
+use crate::logging_middleware::Meta;
+
+/// Block tags not parsed by the zkSync types, with the tag they are equivalent to on the node.
+const BLOCK_TAG_ALIASES: [(&str, &str); 1] = [("safe", "finalized")];
+
+/// Methods taking a block parameter, with its position.
+const BLOCK_PARAMS: [(&str, usize); 15] = [
+    ("eth_getBalance", 1),
+    ("eth_getCode", 1),
+    ("eth_getTransactionCount", 1),
+    ("eth_getStorageAt", 2),
+    ("eth_getProof", 2),
+    ("eth_call", 1),
+    ("eth_estimateGas", 1),
+    ("eth_createAccessList", 1),
+    ("eth_feeHistory", 1),
+    ("eth_getBlockByNumber", 0),
+    ("eth_getBlockTransactionCountByNumber", 0),
+    ("eth_getTransactionByBlockNumberAndIndex", 0),
+    ("eth_getBlockReceipts", 0),
+    ("debug_traceBlockByNumber", 0),
+    ("debug_traceCall", 1),
+];
+
+/// Methods taking a filter object as their first parameter.
+const FILTER_METHODS: [&str; 2] = ["eth_getLogs", "eth_newFilter"];
+
+/// Fields of the filter objects holding a block tag.
+const FILTER_BLOCK_FIELDS: [&str; 2] = ["fromBlock", "toBlock"];
+
+/// Rewrites a block tag the zkSync types don't parse into its equivalent. Other values are left as is.
+fn normalize_block_tag(value: &mut serde_json::Value) {
+    if let serde_json::Value::String(tag) = value {
+        if let Some((_, alias)) = BLOCK_TAG_ALIASES.iter().find(|(name, _)| name == tag) {
+            *tag = alias.to_string();
+        }
+    }
+}
+
+/// Rewrites the block tags of the block parameter of `method`, or of the block fields of its filter
+/// object, so that every method taking a block parameter accepts them.
+pub fn normalize_block_tags(method: &str, params: &mut Params) {
+    let Params::Array(values) = params else {
+        return;
+    };
+
+    if let Some((_, position)) = BLOCK_PARAMS.iter().find(|(name, _)| *name == method) {
+        if let Some(value) = values.get_mut(*position) {
+            normalize_block_tag(value);
+        }
+    } else if FILTER_METHODS.contains(&method) {
+        if let Some(serde_json::Value::Object(fields)) = values.get_mut(0) {
+            FILTER_BLOCK_FIELDS
+                .iter()
+                .filter_map(|field| fields.get_mut(*field))
+                .for_each(normalize_block_tag);
+        }
+    }
+}
+
+/// Rewrites the block tags of a method call, see [`normalize_block_tags`].
+fn normalize_call(call: &mut Call) {
+    if let Call::MethodCall(method_call) = call {
+        normalize_block_tags(&method_call.method, &mut method_call.params);
+    }
+}
+
+/// Middleware rewriting the block tags of the incoming calls before their parameters are parsed.
+#[derive(Default)]
+pub struct BlockTagMiddleware;
+
+impl Middleware<Meta> for BlockTagMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = middleware::NoopCallFuture;
+
+    fn on_request<F, X>(&self, mut request: Request, meta: Meta, next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, Meta) -> X + Send,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        match &mut request {
+            Request::Single(call) => normalize_call(call),
+            Request::Batch(calls) => calls.iter_mut().for_each(normalize_call),
+        }
+
+        Either::Left(Box::pin(next(request, meta)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::{MetaIoHandler, Value};
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_block_tags_rewrites_safe_only_where_blocks_are_expected() {
+        let mut params = Params::Array(vec![
+            json!("0x36615cf349d7f6344891b1e7ca7c72883f5dc049"),
+            json!("safe"),
+        ]);
+        normalize_block_tags("eth_getBalance", &mut params);
+        assert_eq!(
+            Params::Array(vec![
+                json!("0x36615cf349d7f6344891b1e7ca7c72883f5dc049"),
+                json!("finalized"),
+            ]),
+            params
+        );
+
+        let mut params = Params::Array(vec![json!({"fromBlock": "safe", "toBlock": "latest"})]);
+        normalize_block_tags("eth_getLogs", &mut params);
+        assert_eq!(
+            Params::Array(vec![json!({"fromBlock": "finalized", "toBlock": "latest"})]),
+            params
+        );
+
+        // only the block parameter is rewritten
+        let mut params = Params::Array(vec![json!("safe"), json!("0x1"), json!("safe")]);
+        normalize_block_tags("eth_getStorageAt", &mut params);
+        assert_eq!(
+            Params::Array(vec![json!("safe"), json!("0x1"), json!("finalized")]),
+            params
+        );
+
+        // methods without a block parameter are left untouched
+        for method in ["eth_sign", "hardhat_setKnownAddress"] {
+            let mut params = Params::Array(vec![json!("0x01"), json!("safe")]);
+            normalize_block_tags(method, &mut params);
+            assert_eq!(Params::Array(vec![json!("0x01"), json!("safe")]), params);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_rewrites_the_block_tags_of_every_call() {
+        let mut io = MetaIoHandler::with_middleware(BlockTagMiddleware);
+        io.add_sync_method("eth_getCode", |params: Params| match params {
+            Params::Array(values) => Ok(values.get(1).cloned().unwrap_or(Value::Null)),
+            _ => Err(jsonrpc_core::Error::invalid_params("expected an array")),
+        });
+
+        let response = io
+            .handle_request(
+                r#"[{"jsonrpc":"2.0","method":"eth_getCode","params":["0x01","safe"],"id":1},
+                    {"jsonrpc":"2.0","method":"eth_getCode","params":["0x01","latest"],"id":2}]"#,
+                Meta::default(),
+            )
+            .await
+            .expect("no response");
+
+        assert_eq!(
+            r#"[{"jsonrpc":"2.0","result":"finalized","id":1},{"jsonrpc":"2.0","result":"latest","id":2}]"#,
+            response
+        );
+    }
+}
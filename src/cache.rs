@@ -2,11 +2,11 @@ use rustc_hash::FxHashMap;
 use serde::Serialize;
 use std::fs;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 use std::result::Result;
 use std::str::FromStr;
-use zksync_basic_types::H256;
+use zksync_basic_types::{H160, H256, U256};
 use zksync_types::api::{Block, Transaction, TransactionVariant};
 use zksync_types::Transaction as RawTransaction;
 
@@ -14,6 +14,16 @@ const CACHE_TYPE_BLOCKS_FULL: &str = "blocks_full";
 const CACHE_TYPE_BLOCKS_MIN: &str = "blocks_min";
 const CACHE_TYPE_BLOCK_RAW_TRANSACTIONS: &str = "block_raw_transactions";
 const CACHE_TYPE_TRANSACTIONS: &str = "transactions";
+const CACHE_TYPE_STORAGE: &str = "storage";
+const CACHE_TYPE_BYTECODES: &str = "bytecodes";
+const CACHE_TYPES: [&str; 6] = [
+    CACHE_TYPE_BLOCKS_FULL,
+    CACHE_TYPE_BLOCKS_MIN,
+    CACHE_TYPE_BLOCK_RAW_TRANSACTIONS,
+    CACHE_TYPE_TRANSACTIONS,
+    CACHE_TYPE_STORAGE,
+    CACHE_TYPE_BYTECODES,
+];
 
 /// Cache configuration. Can be one of:
 ///
@@ -40,6 +50,10 @@ pub(crate) struct Cache {
     blocks_min: FxHashMap<H256, Block<TransactionVariant>>,
     block_raw_transactions: FxHashMap<u64, Vec<RawTransaction>>,
     transactions: FxHashMap<H256, Transaction>,
+    /// Storage values keyed by the block number they were read at, so that the entries
+    /// cached for one fork block are never used when forking at a different block.
+    storage: FxHashMap<(u64, H160, U256), H256>,
+    bytecodes: FxHashMap<H256, Vec<u8>>,
 }
 
 impl Cache {
@@ -52,12 +66,7 @@ impl Cache {
 
         if let CacheConfig::Disk { dir, reset } = &config {
             if *reset {
-                for cache_type in [
-                    CACHE_TYPE_BLOCKS_FULL,
-                    CACHE_TYPE_BLOCKS_MIN,
-                    CACHE_TYPE_BLOCK_RAW_TRANSACTIONS,
-                    CACHE_TYPE_TRANSACTIONS,
-                ] {
+                for cache_type in CACHE_TYPES {
                     fs::remove_dir_all(Path::new(dir).join(cache_type)).unwrap_or_else(|err| {
                         log::warn!(
                             "failed removing directory {:?}: {:?}",
@@ -71,12 +80,7 @@ impl Cache {
                     .unwrap_or_else(|err| log::warn!("failed removing cache directory: {:?}", err));
             }
 
-            for cache_type in [
-                CACHE_TYPE_BLOCKS_FULL,
-                CACHE_TYPE_BLOCKS_MIN,
-                CACHE_TYPE_BLOCK_RAW_TRANSACTIONS,
-                CACHE_TYPE_TRANSACTIONS,
-            ] {
+            for cache_type in CACHE_TYPES {
                 fs::create_dir_all(Path::new(dir).join(cache_type)).unwrap_or_else(|err| {
                     panic!("failed creating directory {}: {:?}", cache_type, err)
                 });
@@ -89,6 +93,11 @@ impl Cache {
         cache
     }
 
+    /// Returns the configuration the cache was created with.
+    pub(crate) fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
     /// Returns the cached full/minimal block for the provided hash.
     pub(crate) fn get_block(
         &self,
@@ -186,73 +195,72 @@ impl Cache {
         self.transactions.insert(hash, transaction);
     }
 
+    /// Returns the cached storage value for the provided block number, address and index.
+    pub(crate) fn get_storage(&self, block_number: u64, address: H160, idx: U256) -> Option<&H256> {
+        if matches!(self.config, CacheConfig::None) {
+            return None;
+        }
+
+        self.storage.get(&(block_number, address, idx))
+    }
+
+    /// Cache a storage value for the provided block number, address and index.
+    pub(crate) fn insert_storage(
+        &mut self,
+        block_number: u64,
+        address: H160,
+        idx: U256,
+        value: H256,
+    ) {
+        if matches!(self.config, CacheConfig::None) {
+            return;
+        }
+
+        self.write_to_disk(
+            CACHE_TYPE_STORAGE,
+            format!("{}_{:#x}_{:x}", block_number, address, idx),
+            &value,
+        );
+        self.storage.insert((block_number, address, idx), value);
+    }
+
+    /// Returns the cached bytecode for the provided hash.
+    pub(crate) fn get_bytecode(&self, hash: &H256) -> Option<&Vec<u8>> {
+        if matches!(self.config, CacheConfig::None) {
+            return None;
+        }
+
+        self.bytecodes.get(hash)
+    }
+
+    /// Cache a bytecode for the provided hash.
+    pub(crate) fn insert_bytecode(&mut self, hash: H256, bytecode: Vec<u8>) {
+        if matches!(self.config, CacheConfig::None) {
+            return;
+        }
+
+        self.write_to_disk(
+            CACHE_TYPE_BYTECODES,
+            format!("{:#x}", hash),
+            &hex::encode(&bytecode),
+        );
+        self.bytecodes.insert(hash, bytecode);
+    }
+
     /// Reads the cache contents from the disk, if available.
+    /// Entries that cannot be read are removed, so that they are fetched again from the network.
     fn read_all_from_disk(&mut self, dir: &str) -> Result<(), String> {
-        for cache_type in [
-            CACHE_TYPE_BLOCKS_FULL,
-            CACHE_TYPE_BLOCKS_MIN,
-            CACHE_TYPE_BLOCK_RAW_TRANSACTIONS,
-            CACHE_TYPE_TRANSACTIONS,
-        ] {
+        for cache_type in CACHE_TYPES {
             let cache_dir = Path::new(dir).join(cache_type);
             let dir_listing = fs::read_dir(cache_dir.clone())
                 .map_err(|err| format!("failed reading dir '{:?}': {:?}", cache_dir, err))?
                 .flatten();
             for file in dir_listing {
-                let key = file
-                    .file_name()
-                    .to_str()
-                    .ok_or_else(|| String::from("failed converting filename to string"))?
-                    .to_string();
-
-                let cache_file = File::open(file.path()).map_err(|err| {
-                    format!("failed reading file: '{:?}': {:?}", file.path(), err)
-                })?;
-                let reader = BufReader::new(cache_file);
-                match cache_type {
-                    CACHE_TYPE_BLOCKS_FULL => {
-                        let key = H256::from_str(&key).map_err(|err| {
-                            format!("invalid key for cache file '{:?}': {:?}", key, err)
-                        })?;
-                        let block: Block<TransactionVariant> = serde_json::from_reader(reader)
-                            .map_err(|err| {
-                                format!("failed parsing json for cache file '{:?}': {:?}", key, err)
-                            })?;
-                        self.block_hashes.insert(block.number.as_u64(), block.hash);
-                        self.blocks_full.insert(key, block);
-                    }
-                    CACHE_TYPE_BLOCKS_MIN => {
-                        let key = H256::from_str(&key).map_err(|err| {
-                            format!("invalid key for cache file '{:?}': {:?}", key, err)
-                        })?;
-                        let block: Block<TransactionVariant> = serde_json::from_reader(reader)
-                            .map_err(|err| {
-                                format!("failed parsing json for cache file '{:?}': {:?}", key, err)
-                            })?;
-                        self.block_hashes.insert(block.number.as_u64(), block.hash);
-                        self.blocks_min.insert(key, block);
-                    }
-                    CACHE_TYPE_BLOCK_RAW_TRANSACTIONS => {
-                        let key = key.parse::<u64>().map_err(|err| {
-                            format!("invalid key for cache file '{:?}': {:?}", key, err)
-                        })?;
-                        let transactions: Vec<RawTransaction> = serde_json::from_reader(reader)
-                            .map_err(|err| {
-                                format!("failed parsing json for cache file '{:?}': {:?}", key, err)
-                            })?;
-                        self.block_raw_transactions.insert(key, transactions);
-                    }
-                    CACHE_TYPE_TRANSACTIONS => {
-                        let key = H256::from_str(&key).map_err(|err| {
-                            format!("invalid key for cache file '{:?}': {:?}", key, err)
-                        })?;
-                        let transaction: Transaction =
-                            serde_json::from_reader(reader).map_err(|err| {
-                                format!("failed parsing json for cache file '{:?}': {:?}", key, err)
-                            })?;
-                        self.transactions.insert(key, transaction);
-                    }
-                    _ => return Err(format!("invalid cache_type {}", cache_type)),
+                if let Err(err) = self.read_from_disk(cache_type, &file.path()) {
+                    log::warn!("removing unreadable cache file: {}", err);
+                    fs::remove_file(file.path()).unwrap_or_else(|err| {
+                        log::warn!("failed removing file '{:?}': {:?}", file.path(), err)
+                    });
                 }
             }
         }
@@ -260,20 +268,119 @@ impl Cache {
         Ok(())
     }
 
+    /// Reads a single cache entry from the disk.
+    fn read_from_disk(&mut self, cache_type: &str, path: &Path) -> Result<(), String> {
+        let key = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("failed converting filename '{:?}' to string", path))?
+            .to_string();
+
+        let cache_file = File::open(path)
+            .map_err(|err| format!("failed reading file: '{:?}': {:?}", path, err))?;
+        let reader = BufReader::new(cache_file);
+        match cache_type {
+            CACHE_TYPE_BLOCKS_FULL => {
+                let key = H256::from_str(&key)
+                    .map_err(|err| format!("invalid key for cache file '{:?}': {:?}", key, err))?;
+                let block: Block<TransactionVariant> =
+                    serde_json::from_reader(reader).map_err(|err| {
+                        format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                    })?;
+                self.block_hashes.insert(block.number.as_u64(), block.hash);
+                self.blocks_full.insert(key, block);
+            }
+            CACHE_TYPE_BLOCKS_MIN => {
+                let key = H256::from_str(&key)
+                    .map_err(|err| format!("invalid key for cache file '{:?}': {:?}", key, err))?;
+                let block: Block<TransactionVariant> =
+                    serde_json::from_reader(reader).map_err(|err| {
+                        format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                    })?;
+                self.block_hashes.insert(block.number.as_u64(), block.hash);
+                self.blocks_min.insert(key, block);
+            }
+            CACHE_TYPE_BLOCK_RAW_TRANSACTIONS => {
+                let key = key
+                    .parse::<u64>()
+                    .map_err(|err| format!("invalid key for cache file '{:?}': {:?}", key, err))?;
+                let transactions: Vec<RawTransaction> =
+                    serde_json::from_reader(reader).map_err(|err| {
+                        format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                    })?;
+                self.block_raw_transactions.insert(key, transactions);
+            }
+            CACHE_TYPE_TRANSACTIONS => {
+                let key = H256::from_str(&key)
+                    .map_err(|err| format!("invalid key for cache file '{:?}': {:?}", key, err))?;
+                let transaction: Transaction = serde_json::from_reader(reader).map_err(|err| {
+                    format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                })?;
+                self.transactions.insert(key, transaction);
+            }
+            CACHE_TYPE_STORAGE => {
+                let parsed_key = match key.split('_').collect::<Vec<_>>()[..] {
+                    [block_number, address, idx] => block_number
+                        .parse::<u64>()
+                        .ok()
+                        .zip(H160::from_str(address).ok())
+                        .zip(U256::from_str_radix(idx, 16).ok())
+                        .map(|((block_number, address), idx)| (block_number, address, idx)),
+                    _ => None,
+                };
+                let parsed_key =
+                    parsed_key.ok_or_else(|| format!("invalid key for cache file '{:?}'", key))?;
+                let value: H256 = serde_json::from_reader(reader).map_err(|err| {
+                    format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                })?;
+                self.storage.insert(parsed_key, value);
+            }
+            CACHE_TYPE_BYTECODES => {
+                let key = H256::from_str(&key)
+                    .map_err(|err| format!("invalid key for cache file '{:?}': {:?}", key, err))?;
+                let bytecode: String = serde_json::from_reader(reader).map_err(|err| {
+                    format!("failed parsing json for cache file '{:?}': {:?}", key, err)
+                })?;
+                let bytecode = hex::decode(bytecode).map_err(|err| {
+                    format!(
+                        "failed decoding bytecode for cache file '{:?}': {:?}",
+                        key, err
+                    )
+                })?;
+                self.bytecodes.insert(key, bytecode);
+            }
+            _ => return Err(format!("invalid cache_type {}", cache_type)),
+        }
+
+        Ok(())
+    }
+
     /// Writes the cache contents to disk, if supported.
     fn write_to_disk<T: Serialize>(&self, cache_type: &'static str, key: String, data: &T) {
         if let CacheConfig::Disk { dir, .. } = &self.config {
-            let file = Path::new(&dir).join(cache_type).join(key);
+            let file = Path::new(&dir).join(cache_type).join(&key);
+            // Write to a temporary file first, so that an interrupted write never leaves
+            // a partial entry behind under the real key.
+            let tmp_file = Path::new(&dir)
+                .join(cache_type)
+                .join(format!(".{}.tmp", key));
 
             log::debug!("writing cache {:?}", file);
-            match File::create(file.clone()) {
+            match File::create(tmp_file.clone()) {
                 Ok(cache_file) => {
-                    let writer = BufWriter::new(cache_file);
-                    if let Err(err) = serde_json::to_writer(writer, data) {
-                        log::error!("failed writing to cache '{:?}': {:?}", file, err);
+                    let mut writer = BufWriter::new(cache_file);
+                    let result = serde_json::to_writer(&mut writer, data)
+                        .map_err(|err| format!("{:?}", err))
+                        .and_then(|_| writer.flush().map_err(|err| format!("{:?}", err)))
+                        .and_then(|_| {
+                            fs::rename(&tmp_file, &file).map_err(|err| format!("{:?}", err))
+                        });
+                    if let Err(err) = result {
+                        log::error!("failed writing to cache '{:?}': {}", file, err);
+                        fs::remove_file(&tmp_file).unwrap_or_default();
                     }
                 }
-                Err(err) => log::error!("failed creating file: '{:?}': {:?}", file, err),
+                Err(err) => log::error!("failed creating file: '{:?}': {:?}", tmp_file, err),
             }
         }
     }
@@ -542,4 +649,67 @@ mod tests {
         );
         assert!(random_file_path.exists(), "random file was reset from disk");
     }
+
+    #[test]
+    fn test_cache_config_disk_preserves_storage_and_bytecodes() {
+        let cache_dir = TempDir::new("cache-test").expect("failed creating temporary dir");
+        let cache_dir_path = cache_dir
+            .path()
+            .to_str()
+            .expect("invalid dir name")
+            .to_string();
+        let mut cache = Cache::new(CacheConfig::Disk {
+            dir: cache_dir_path.clone(),
+            reset: true,
+        });
+
+        let address = H160::repeat_byte(0x1);
+        let idx = U256::from(0xabcd);
+        cache.insert_storage(8, address, idx, H256::repeat_byte(0x2));
+        cache.insert_bytecode(H256::repeat_byte(0x3), vec![0x1, 0x2, 0x3]);
+
+        let new_cache = Cache::new(CacheConfig::Disk {
+            dir: cache_dir_path,
+            reset: false,
+        });
+        assert_eq!(
+            Some(&H256::repeat_byte(0x2)),
+            new_cache.get_storage(8, address, idx)
+        );
+        assert_eq!(None, new_cache.get_storage(9, address, idx));
+        assert_eq!(
+            Some(&vec![0x1, 0x2, 0x3]),
+            new_cache.get_bytecode(&H256::repeat_byte(0x3))
+        );
+    }
+
+    #[test]
+    fn test_cache_config_disk_removes_corrupt_entries() {
+        let cache_dir = TempDir::new("cache-test").expect("failed creating temporary dir");
+        let cache_dir_path = cache_dir
+            .path()
+            .to_str()
+            .expect("invalid dir name")
+            .to_string();
+        let mut cache = Cache::new(CacheConfig::Disk {
+            dir: cache_dir_path.clone(),
+            reset: true,
+        });
+
+        cache.insert_transaction(H256::zero(), Default::default());
+        let corrupt_file = cache_dir
+            .path()
+            .join(CACHE_TYPE_TRANSACTIONS)
+            .join(format!("{:#x}", H256::repeat_byte(0x1)));
+        fs::write(&corrupt_file, "{\"partial\": ").expect("failed writing corrupt file");
+
+        let new_cache = Cache::new(CacheConfig::Disk {
+            dir: cache_dir_path,
+            reset: false,
+        });
+
+        assert!(new_cache.get_transaction(&H256::zero()).is_some());
+        assert!(new_cache.get_transaction(&H256::repeat_byte(0x1)).is_none());
+        assert!(!corrupt_file.exists(), "corrupt cache file was not removed");
+    }
 }
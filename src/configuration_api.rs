@@ -1,11 +1,16 @@
 // Built-in uses
-use std::sync::{Arc, RwLock};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 
 // External uses
+use bigdecimal::BigDecimal;
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
 
 // Workspace uses
+use zksync_basic_types::Address;
 
 // Local uses
 use crate::{
@@ -83,6 +88,37 @@ pub trait ConfigurationApiNamespaceT {
     /// The updated `resolve_hashes` value for the InMemoryNodeInner.
     #[rpc(name = "config_setResolveHashes", returns = "bool")]
     fn config_set_resolve_hashes(&self, value: bool) -> Result<bool>;
+
+    /// Set print_events for the InMemoryNodeInner
+    ///
+    /// # Parameters
+    /// - `value`: A bool to update print_events to
+    ///
+    /// # Returns
+    /// The updated `print_events` value for the InMemoryNodeInner.
+    #[rpc(name = "config_setPrintEvents", returns = "bool")]
+    fn config_set_print_events(&self, value: bool) -> Result<bool>;
+
+    /// Set print_storage_logs for the InMemoryNodeInner
+    ///
+    /// # Parameters
+    /// - `value`: A bool to update print_storage_logs to
+    ///
+    /// # Returns
+    /// The updated `print_storage_logs` value for the InMemoryNodeInner.
+    #[rpc(name = "config_setPrintStorageLogs", returns = "bool")]
+    fn config_set_print_storage_logs(&self, value: bool) -> Result<bool>;
+
+    /// Set the USD price of a token returned by `zks_getTokenPrice`
+    ///
+    /// # Parameters
+    /// - `token`: The address of the token, the zero address for ETH
+    /// - `price`: The price as a decimal string, e.g. "1834.25"
+    ///
+    /// # Returns
+    /// The updated price of the token.
+    #[rpc(name = "config_setTokenPrice", returns = "String")]
+    fn config_set_token_price(&self, token: Address, price: String) -> Result<String>;
 }
 
 impl<S: std::marker::Send + std::marker::Sync + 'static> ConfigurationApiNamespaceT
@@ -154,4 +190,26 @@ impl<S: std::marker::Send + std::marker::Sync + 'static> ConfigurationApiNamespa
         inner.resolve_hashes = value;
         Ok(inner.resolve_hashes)
     }
+
+    fn config_set_print_events(&self, value: bool) -> Result<bool> {
+        let mut inner = self.node.write().unwrap();
+        inner.print_events = value;
+        Ok(inner.print_events)
+    }
+
+    fn config_set_print_storage_logs(&self, value: bool) -> Result<bool> {
+        let mut inner = self.node.write().unwrap();
+        inner.print_storage_logs = value;
+        Ok(inner.print_storage_logs)
+    }
+
+    fn config_set_token_price(&self, token: Address, price: String) -> Result<String> {
+        let price = BigDecimal::from_str(&price).map_err(|err| {
+            jsonrpc_core::Error::invalid_params(format!("invalid price {:?}: {}", price, err))
+        })?;
+
+        let mut inner = self.node.write().unwrap();
+        inner.token_prices.insert(token, price);
+        Ok(inner.token_price(token).to_string())
+    }
 }
@@ -0,0 +1,628 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    fork::ForkSource,
+    node::{InMemoryNodeInner, MAX_TX_SIZE},
+    state_override::{validate_state_override, StateOverride},
+};
+use jsonrpc_core::{BoxFuture, Result};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use vm::{utils::ETH_CALL_GAS_LIMIT, vm::VmPartialExecutionResult, VmExecutionResult};
+use zksync_basic_types::{Address, Bytes, H256, U256};
+use zksync_core::api_server::web3::backend_jsonrpc::error::into_jsrpc_error;
+use zksync_types::{
+    api::BlockIdVariant,
+    l2::L2Tx,
+    transaction_request::CallRequest,
+    vm_trace::{Call, CallType, VmTrace},
+    zk_evm::zkevm_opcode_defs::FarCallOpcode,
+};
+use zksync_web3_decl::error::Web3Error;
+
+/// Call tree and VM execution statistics of a transaction, as printed with `--show-calls` and
+/// `--show-vm-details`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTrace {
+    /// The top-level calls made by the bootloader, each with its subcalls.
+    pub calls: Vec<Call>,
+    pub cycles_used: u32,
+    pub computational_gas_used: u32,
+    pub contracts_used: usize,
+    pub revert_reason: Option<String>,
+}
+
+impl TransactionTrace {
+    pub fn new(calls: Vec<Call>, result: &VmPartialExecutionResult) -> Self {
+        Self {
+            calls,
+            cycles_used: result.cycles_used,
+            computational_gas_used: result.computational_gas_used,
+            contracts_used: result.contracts_used,
+            revert_reason: result
+                .revert_reason
+                .as_ref()
+                .map(|revert_reason| revert_reason.to_string()),
+        }
+    }
+
+    /// Creates the trace of a call run like `eth_call`.
+    pub fn from_call(calls: Vec<Call>, result: &VmExecutionResult) -> Self {
+        Self {
+            calls,
+            cycles_used: result.cycles_used,
+            computational_gas_used: result.computational_gas_used,
+            contracts_used: result.contracts_used,
+            revert_reason: result
+                .revert_reason
+                .as_ref()
+                .map(|revert| revert.revert_reason.to_string()),
+        }
+    }
+}
+
+/// Name of the tracer producing the Geth `callTracer` output.
+const CALL_TRACER: &str = "callTracer";
+
+/// Returns true if the `callTracer` output is requested, or an error for unknown tracers.
+fn is_call_tracer(tracer: Option<&str>) -> Result<bool> {
+    match tracer {
+        None => Ok(false),
+        Some(CALL_TRACER) => Ok(true),
+        Some(tracer) => Err(jsonrpc_core::Error::invalid_params(format!(
+            "unsupported tracer '{}', only '{}' is available",
+            tracer, CALL_TRACER
+        ))),
+    }
+}
+
+/// Options of `debug_traceTransaction`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceOptions {
+    /// `callTracer` for the Geth call tracer output, the node trace otherwise.
+    pub tracer: Option<String>,
+}
+
+/// Options of `debug_traceCall`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceCallOptions {
+    /// `callTracer` for the Geth call tracer output, the node trace otherwise.
+    pub tracer: Option<String>,
+    /// Accounts to override for the duration of the call, like with `eth_call`.
+    pub state_overrides: Option<StateOverride>,
+}
+
+/// A call in the format of the Geth `callTracer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    pub r#type: String,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub gas: U256,
+    pub gas_used: U256,
+    pub input: Bytes,
+    pub output: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+impl From<&Call> for CallFrame {
+    fn from(call: &Call) -> Self {
+        let r#type = match call.r#type {
+            CallType::Call(FarCallOpcode::Delegate) => "DELEGATECALL",
+            CallType::Call(_) | CallType::NearCall => "CALL",
+            CallType::Create => "CREATE",
+        };
+        Self {
+            r#type: r#type.to_string(),
+            from: call.from,
+            to: call.to,
+            value: call.value,
+            gas: call.gas.into(),
+            gas_used: call.gas_used.into(),
+            input: Bytes(call.input.clone()),
+            output: Bytes(call.output.clone()),
+            error: call.revert_reason.clone().or_else(|| call.error.clone()),
+            calls: call.calls.iter().map(CallFrame::from).collect(),
+        }
+    }
+}
+
+/// Result of `debug_traceTransaction` and `debug_traceCall`, depending on the requested tracer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum TraceResult {
+    Trace(TransactionTrace),
+    CallTracer(CallFrame),
+}
+
+/// Implementation of DebugNamespace
+pub struct DebugNamespaceImpl<S> {
+    node: Arc<RwLock<InMemoryNodeInner<S>>>,
+}
+
+impl<S> DebugNamespaceImpl<S> {
+    /// Creates a new `Debug` instance with the given `node`.
+    pub fn new(node: Arc<RwLock<InMemoryNodeInner<S>>>) -> Self {
+        Self { node }
+    }
+}
+
+#[rpc]
+pub trait DebugNamespaceT {
+    /// Returns the call tree and the VM execution statistics of a transaction executed by the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the transaction
+    /// * `options` - `{"tracer": "callTracer"}` to get the trace in the format of the Geth
+    ///   `callTracer`, with the bootloader calls nested in a frame for the transaction
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that maybe resolves to the trace of the
+    /// transaction, or `None` if the transaction is unknown.
+    #[rpc(name = "debug_traceTransaction")]
+    fn trace_transaction(
+        &self,
+        hash: H256,
+        options: Option<TraceOptions>,
+    ) -> BoxFuture<Result<Option<TraceResult>>>;
+
+    /// Runs a call like `eth_call`, without changing the node state, and returns its call tree and
+    /// VM execution statistics. A call that reverts is still traced.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The call request
+    /// * `block` - The block to run the call on, the latest one by default. Older blocks are only
+    ///   available at or below the fork point.
+    /// * `options` - `{"tracer": "callTracer"}` to get the trace in the format of the Geth
+    ///   `callTracer`, and `stateOverrides` to override accounts for the duration of the call
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the trace of the call.
+    #[rpc(name = "debug_traceCall")]
+    fn trace_call(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+        options: Option<TraceCallOptions>,
+    ) -> BoxFuture<Result<TraceResult>>;
+}
+
+impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> DebugNamespaceT
+    for DebugNamespaceImpl<S>
+{
+    fn trace_transaction(
+        &self,
+        hash: H256,
+        options: Option<TraceOptions>,
+    ) -> BoxFuture<Result<Option<TraceResult>>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let call_tracer = is_call_tracer(options.unwrap_or_default().tracer.as_deref())?;
+
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let info = match inner.tx_results.get(&hash) {
+                Some(info) => info,
+                None => return Ok(None),
+            };
+            if !call_tracer {
+                return Ok(Some(TraceResult::Trace(TransactionTrace::new(
+                    info.result.call_traces.clone(),
+                    &info.result.result,
+                ))));
+            }
+
+            let gas_limit = info.tx.gas_limit();
+            Ok(Some(TraceResult::CallTracer(CallFrame {
+                r#type: String::from("CALL"),
+                from: info.tx.initiator_account(),
+                to: info.tx.recipient_account(),
+                value: info.tx.execute.value,
+                gas: gas_limit,
+                gas_used: gas_limit - info.result.gas_refunded,
+                input: Bytes(info.tx.execute.calldata.clone()),
+                output: Bytes::default(),
+                error: info
+                    .result
+                    .result
+                    .revert_reason
+                    .as_ref()
+                    .map(|revert_reason| revert_reason.to_string()),
+                calls: info
+                    .result
+                    .call_traces
+                    .iter()
+                    .map(CallFrame::from)
+                    .collect(),
+            })))
+        })
+    }
+
+    fn trace_call(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+        options: Option<TraceCallOptions>,
+    ) -> BoxFuture<Result<TraceResult>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let options = options.unwrap_or_default();
+            let call_tracer = is_call_tracer(options.tracer.as_deref())?;
+            let state_override = options.state_overrides.unwrap_or_default();
+            validate_state_override(&state_override)
+                .map_err(jsonrpc_core::Error::invalid_params)?;
+
+            let mut tx = L2Tx::from_request(request.into(), MAX_TX_SIZE)
+                .map_err(|err| into_jsrpc_error(Web3Error::SerializationError(err)))?;
+            tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
+
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let result = inner
+                .run_l2_call(tx.clone(), block, &state_override)
+                .map_err(jsonrpc_core::Error::invalid_params)?
+                .full_result;
+
+            let calls = match result.trace {
+                VmTrace::CallTrace(ref calls) => calls.clone(),
+                _ => vec![],
+            };
+            if !call_tracer {
+                return Ok(TraceResult::Trace(TransactionTrace::from_call(
+                    calls, &result,
+                )));
+            }
+
+            let output = match result.revert_reason {
+                Some(_) => vec![],
+                None => result
+                    .return_data
+                    .iter()
+                    .flat_map(|value| {
+                        let bytes: [u8; 32] = (*value).into();
+                        bytes.to_vec()
+                    })
+                    .collect(),
+            };
+            Ok(TraceResult::CallTracer(CallFrame {
+                r#type: String::from("CALL"),
+                from: tx.initiator_account(),
+                to: tx.recipient_account(),
+                value: tx.execute.value,
+                gas: tx.common_data.fee.gas_limit,
+                gas_used: result.gas_used.into(),
+                input: Bytes(tx.execute.calldata),
+                output: Bytes(output),
+                error: result
+                    .revert_reason
+                    .as_ref()
+                    .map(|revert| revert.revert_reason.to_string()),
+                calls: calls.iter().map(CallFrame::from).collect(),
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode, testing};
+    use zksync_basic_types::U64;
+    use zksync_core::api_server::web3::backend_jsonrpc::namespaces::eth::EthNamespaceT;
+    use zksync_types::{api::BlockNumber, L2_ETH_TOKEN_ADDRESS};
+
+    #[tokio::test]
+    async fn test_trace_transaction_returns_call_tree_and_vm_details() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        let tx_hash = H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
+
+        let trace = match debug
+            .trace_transaction(tx_hash, None)
+            .await
+            .expect("debug_traceTransaction failed")
+        {
+            Some(TraceResult::Trace(trace)) => trace,
+            other => panic!("unexpected trace: {:?}", other),
+        };
+
+        assert!(!trace.calls.is_empty());
+        assert!(trace.calls.iter().any(|call| !call.calls.is_empty()));
+        assert!(trace.cycles_used > 0);
+        assert!(trace.computational_gas_used > 0);
+        assert!(trace.contracts_used > 0);
+        assert_eq!(None, trace.revert_reason);
+
+        let json = serde_json::to_value(&trace).expect("failed serializing trace");
+        assert!(json["cyclesUsed"].is_number());
+        assert!(json["calls"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_trace_transaction_unknown_hash() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+
+        let trace = debug
+            .trace_transaction(H256::repeat_byte(0xab), None)
+            .await
+            .expect("debug_traceTransaction failed");
+
+        assert!(trace.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trace_transaction_with_call_tracer() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        let tx_hash = H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
+        let tx = node.get_inner().read().unwrap().tx_results[&tx_hash]
+            .tx
+            .clone();
+        let options = TraceOptions {
+            tracer: Some(String::from("callTracer")),
+        };
+
+        let frame = match debug
+            .trace_transaction(tx_hash, Some(options))
+            .await
+            .expect("debug_traceTransaction failed")
+        {
+            Some(TraceResult::CallTracer(frame)) => frame,
+            other => panic!("unexpected trace: {:?}", other),
+        };
+
+        assert_eq!("CALL", frame.r#type);
+        assert_eq!(tx.initiator_account(), frame.from);
+        assert_eq!(tx.recipient_account(), frame.to);
+        assert!(frame.gas_used > U256::zero() && frame.gas_used <= frame.gas);
+        assert_eq!(None, frame.error);
+        assert!(!frame.calls.is_empty());
+
+        let json = serde_json::to_value(&frame).expect("failed serializing frame");
+        for key in [
+            "type", "from", "to", "value", "gas", "gasUsed", "input", "output",
+        ] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    fn test_call_frame_maps_revert_reason_to_error() {
+        let call = Call {
+            r#type: CallType::Call(FarCallOpcode::Delegate),
+            to: Address::repeat_byte(0x01),
+            gas: 100,
+            gas_used: 40,
+            input: vec![0xa9, 0x05, 0x9c, 0xbb],
+            revert_reason: Some(String::from("not enough balance")),
+            calls: vec![Call::default()],
+            ..Default::default()
+        };
+
+        let frame = CallFrame::from(&call);
+
+        assert_eq!("DELEGATECALL", frame.r#type);
+        assert_eq!(U256::from(40), frame.gas_used);
+        assert_eq!(Some(String::from("not enough balance")), frame.error);
+        assert_eq!(1, frame.calls.len());
+        assert_eq!("0xa9059cbb", serde_json::to_value(&frame).unwrap()["input"]);
+    }
+
+    #[tokio::test]
+    async fn test_trace_transaction_rejects_unknown_tracer() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        let options = TraceOptions {
+            tracer: Some(String::from("prestateTracer")),
+        };
+
+        assert!(debug
+            .trace_transaction(H256::repeat_byte(0x01), Some(options))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_returns_nested_calls_without_changing_state() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        let from = Address::random();
+        let to = Address::random();
+        node.set_rich_account(from);
+        let balance = node.get_balance(from, None).await.unwrap();
+        let request = CallRequest {
+            from: Some(from),
+            to: Some(to),
+            value: Some(U256::from(1_000)),
+            ..Default::default()
+        };
+
+        let trace = match debug
+            .trace_call(request, None, None)
+            .await
+            .expect("debug_traceCall failed")
+        {
+            TraceResult::Trace(trace) => trace,
+            other => panic!("unexpected trace: {:?}", other),
+        };
+
+        assert!(trace.calls.iter().any(|call| !call.calls.is_empty()));
+        assert!(trace.cycles_used > 0);
+        assert_eq!(None, trace.revert_reason);
+        assert_eq!(balance, node.get_balance(from, None).await.unwrap());
+        assert_eq!(U256::zero(), node.get_balance(to, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_reports_revert_reason() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        // only the system contracts can move balances with `transferFromTo`
+        let mut data = ethabi::short_signature(
+            "transferFromTo",
+            &[
+                ethabi::ParamType::Address,
+                ethabi::ParamType::Address,
+                ethabi::ParamType::Uint(256),
+            ],
+        )
+        .to_vec();
+        data.extend(ethabi::encode(&[
+            ethabi::Token::Address(Address::random()),
+            ethabi::Token::Address(Address::random()),
+            ethabi::Token::Uint(U256::from(1_000)),
+        ]));
+        let request = CallRequest {
+            from: Some(Address::random()),
+            to: Some(L2_ETH_TOKEN_ADDRESS),
+            data: Some(Bytes(data)),
+            ..Default::default()
+        };
+
+        let trace = match debug
+            .trace_call(request, None, None)
+            .await
+            .expect("debug_traceCall failed")
+        {
+            TraceResult::Trace(trace) => trace,
+            other => panic!("unexpected trace: {:?}", other),
+        };
+
+        let revert_reason = trace.revert_reason.expect("the call didn't revert");
+        assert!(
+            revert_reason.contains("Only system contracts with special access"),
+            "unexpected revert reason: {}",
+            revert_reason
+        );
+        assert!(!trace.calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_rejects_unavailable_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        let result = debug
+            .trace_call(
+                CallRequest {
+                    to: Some(L2_ETH_TOKEN_ADDRESS),
+                    ..Default::default()
+                },
+                Some(BlockIdVariant::BlockNumber(
+                    BlockNumber::Number(U64::zero()),
+                )),
+                None,
+            )
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_with_call_tracer_and_state_overrides() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+        let from = Address::random();
+        let balance = U256::from(10u64.pow(18));
+        // L2EthToken keys the balances by the address as `uint256`
+        let mut data =
+            ethabi::short_signature("balanceOf", &[ethabi::ParamType::Uint(256)]).to_vec();
+        data.extend(ethabi::encode(&[ethabi::Token::Address(from)]));
+        let request = CallRequest {
+            from: Some(from),
+            to: Some(L2_ETH_TOKEN_ADDRESS),
+            data: Some(Bytes(data.clone())),
+            ..Default::default()
+        };
+        let options = |state_overrides| TraceCallOptions {
+            tracer: Some(String::from("callTracer")),
+            state_overrides,
+        };
+
+        let frame = |result| match result {
+            TraceResult::CallTracer(frame) => frame,
+            other => panic!("unexpected trace: {:?}", other),
+        };
+        let original = frame(
+            debug
+                .trace_call(request.clone(), None, Some(options(None)))
+                .await
+                .expect("debug_traceCall failed"),
+        );
+        let overridden = frame(
+            debug
+                .trace_call(
+                    request,
+                    None,
+                    Some(options(Some(StateOverride::from([(
+                        from,
+                        crate::state_override::AccountOverride {
+                            balance: Some(balance),
+                            ..Default::default()
+                        },
+                    )])))),
+                )
+                .await
+                .expect("debug_traceCall failed"),
+        );
+
+        assert_eq!(None, original.error);
+        assert_eq!(
+            Bytes(ethabi::encode(&[ethabi::Token::Uint(U256::zero())])),
+            original.output
+        );
+        assert_eq!(None, overridden.error);
+        assert_eq!(
+            Bytes(ethabi::encode(&[ethabi::Token::Uint(balance)])),
+            overridden.output
+        );
+        assert_eq!("CALL", overridden.r#type);
+        assert_eq!(from, overridden.from);
+        assert_eq!(L2_ETH_TOKEN_ADDRESS, overridden.to);
+        assert_eq!(Bytes(data), overridden.input);
+        assert!(overridden.gas_used > U256::zero() && overridden.gas_used <= overridden.gas);
+        // the overridden balance is not kept
+        assert_eq!(U256::zero(), node.get_balance(from, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_rejects_unknown_tracer() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let debug = DebugNamespaceImpl::new(node.get_inner());
+
+        let result = debug
+            .trace_call(
+                CallRequest {
+                    to: Some(L2_ETH_TOKEN_ADDRESS),
+                    ..Default::default()
+                },
+                None,
+                Some(TraceCallOptions {
+                    tracer: Some(String::from("prestateTracer")),
+                    ..Default::default()
+                }),
+            )
+            .await;
+
+        assert!(result.is_err());
+    }
+}
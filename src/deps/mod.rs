@@ -16,12 +16,13 @@ pub struct InMemoryStorage {
 
 impl InMemoryStorage {
     /// Constructs a storage that contains system smart contracts (with a given chain id).
+    /// Fails if the system contracts can't be loaded.
     pub fn with_system_contracts_and_chain_id(
         chain_id: L2ChainId,
         bytecode_hasher: impl Fn(&[u8]) -> H256,
         system_contracts_options: &crate::system_contracts::Options,
-    ) -> Self {
-        let contracts = crate::system_contracts::get_deployed_contracts(system_contracts_options);
+    ) -> Result<Self, String> {
+        let contracts = crate::system_contracts::get_deployed_contracts(system_contracts_options)?;
 
         let system_context_init_log = get_system_context_init_logs(chain_id);
 
@@ -39,10 +40,10 @@ impl InMemoryStorage {
             .into_iter()
             .map(|contract| (bytecode_hasher(&contract.bytecode), contract.bytecode))
             .collect();
-        Self {
+        Ok(Self {
             state,
             factory_deps,
-        }
+        })
     }
 
     /// Sets the storage `value` at the specified `key`.
@@ -11,15 +11,21 @@ use zksync_types::{
 };
 
 pub fn bytecode_from_slice(artifact_name: &str, contents: &[u8]) -> Vec<u8> {
-    let artifact: Value = serde_json::from_slice(contents).expect(artifact_name);
+    try_bytecode_from_slice(artifact_name, contents).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Like [`bytecode_from_slice`], but returns an error for artifacts that are not compiled in.
+pub fn try_bytecode_from_slice(artifact_name: &str, contents: &[u8]) -> Result<Vec<u8>, String> {
+    let artifact: Value = serde_json::from_slice(contents)
+        .map_err(|err| format!("Invalid artifact {:?}: {}", artifact_name, err))?;
     let bytecode = artifact["bytecode"]
         .as_str()
-        .unwrap_or_else(|| panic!("Bytecode not found in {:?}", artifact_name))
+        .ok_or_else(|| format!("Bytecode not found in {:?}", artifact_name))?
         .strip_prefix("0x")
-        .unwrap_or_else(|| panic!("Bytecode in {:?} is not hex", artifact_name));
+        .ok_or_else(|| format!("Bytecode in {:?} is not hex", artifact_name))?;
 
     hex::decode(bytecode)
-        .unwrap_or_else(|err| panic!("Can't decode bytecode in {:?}: {}", artifact_name, err))
+        .map_err(|err| format!("Can't decode bytecode in {:?}: {}", artifact_name, err))
 }
 
 pub static COMPILED_IN_SYSTEM_CONTRACTS: Lazy<Vec<DeployedContract>> = Lazy::new(|| {
@@ -0,0 +1,295 @@
+//! Hashing of EIP-712 typed data, as sent to `eth_signTypedData_v4`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+use serde_json::Value;
+use zksync_basic_types::{web3::signing::keccak256, H256, U256};
+
+/// A field of an EIP-712 struct type.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TypedDataField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// EIP-712 typed data, in the JSON format used by `eth_signTypedData_v4`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<TypedDataField>>,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+impl TypedData {
+    /// Returns the digest to sign: `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`.
+    pub fn hash(&self) -> Result<H256, String> {
+        let domain_separator = self.hash_struct("EIP712Domain", &self.domain)?;
+        let message_hash = self.hash_struct(&self.primary_type, &self.message)?;
+
+        let mut digest_input = vec![0x19, 0x01];
+        digest_input.extend_from_slice(domain_separator.as_bytes());
+        digest_input.extend_from_slice(message_hash.as_bytes());
+        Ok(H256(keccak256(&digest_input)))
+    }
+
+    /// Returns the type encoding of `primary_type`, followed by the encodings of all the struct
+    /// types it references, sorted by name.
+    pub fn encode_type(&self, primary_type: &str) -> Result<String, String> {
+        let mut dependencies = BTreeSet::new();
+        self.collect_dependencies(primary_type, &mut dependencies)?;
+        dependencies.remove(primary_type);
+
+        std::iter::once(primary_type)
+            .chain(dependencies.iter().map(String::as_str))
+            .map(|name| {
+                let fields = self.fields(name)?;
+                let fields = fields
+                    .iter()
+                    .map(|field| format!("{} {}", field.kind, field.name))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Ok(format!("{}({})", name, fields))
+            })
+            .collect()
+    }
+
+    fn fields(&self, struct_type: &str) -> Result<&Vec<TypedDataField>, String> {
+        self.types
+            .get(struct_type)
+            .ok_or_else(|| format!("unknown type '{}'", struct_type))
+    }
+
+    fn collect_dependencies(
+        &self,
+        struct_type: &str,
+        dependencies: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        if !dependencies.insert(struct_type.to_string()) {
+            return Ok(());
+        }
+        for field in self.fields(struct_type)? {
+            let base_type = field.kind.split('[').next().unwrap_or_default();
+            if self.types.contains_key(base_type) {
+                self.collect_dependencies(base_type, dependencies)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn hash_struct(&self, struct_type: &str, value: &Value) -> Result<H256, String> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| format!("expected an object for '{}'", struct_type))?;
+
+        let type_hash = keccak256(self.encode_type(struct_type)?.as_bytes());
+        let mut encoded = type_hash.to_vec();
+        for field in self.fields(struct_type)? {
+            let field_value = object.get(&field.name).unwrap_or(&Value::Null);
+            let encoded_field = self
+                .encode_value(&field.kind, field_value)
+                .map_err(|err| format!("{}.{}: {}", struct_type, field.name, err))?;
+            encoded.extend_from_slice(encoded_field.as_bytes());
+        }
+        Ok(H256(keccak256(&encoded)))
+    }
+
+    /// Encodes a single value into the 32 bytes used by `encodeData`.
+    fn encode_value(&self, kind: &str, value: &Value) -> Result<H256, String> {
+        if let Some(item_kind) = kind.strip_suffix(']') {
+            let item_kind = item_kind
+                .rfind('[')
+                .map(|index| &item_kind[..index])
+                .ok_or_else(|| format!("invalid array type '{}'", kind))?;
+            let items = value
+                .as_array()
+                .ok_or_else(|| format!("expected an array for '{}'", kind))?;
+            let mut encoded = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                encoded.extend_from_slice(self.encode_value(item_kind, item)?.as_bytes());
+            }
+            return Ok(H256(keccak256(&encoded)));
+        }
+
+        if self.types.contains_key(kind) {
+            return self.hash_struct(kind, value);
+        }
+
+        match kind {
+            "string" => {
+                let string = value.as_str().ok_or("expected a string")?;
+                Ok(H256(keccak256(string.as_bytes())))
+            }
+            "bytes" => Ok(H256(keccak256(&parse_hex_bytes(value)?))),
+            "bool" => {
+                let flag = value.as_bool().ok_or("expected a bool")?;
+                Ok(H256::from_low_u64_be(flag as u64))
+            }
+            "address" => {
+                let bytes = parse_hex_bytes(value)?;
+                if bytes.len() != 20 {
+                    return Err(format!("invalid address length {}", bytes.len()));
+                }
+                let mut padded = [0u8; 32];
+                padded[12..].copy_from_slice(&bytes);
+                Ok(H256(padded))
+            }
+            _ if kind.starts_with("uint") || kind.starts_with("int") => {
+                let mut bytes = [0u8; 32];
+                parse_integer(value, kind.starts_with("int"))?.to_big_endian(&mut bytes);
+                Ok(H256(bytes))
+            }
+            _ if kind.starts_with("bytes") => {
+                let bytes = parse_hex_bytes(value)?;
+                if bytes.len() > 32 {
+                    return Err(format!("value too long for '{}'", kind));
+                }
+                let mut padded = [0u8; 32];
+                padded[..bytes.len()].copy_from_slice(&bytes);
+                Ok(H256(padded))
+            }
+            _ => Err(format!("unsupported type '{}'", kind)),
+        }
+    }
+}
+
+fn parse_hex_bytes(value: &Value) -> Result<Vec<u8>, String> {
+    let string = value.as_str().ok_or("expected a hex string")?;
+    hex::decode(string.strip_prefix("0x").unwrap_or(string))
+        .map_err(|err| format!("invalid hex string '{}': {}", string, err))
+}
+
+/// Parses a JSON number, or a decimal or `0x`-prefixed hex string. Negative values of signed
+/// types are returned in two's complement.
+fn parse_integer(value: &Value, signed: bool) -> Result<U256, String> {
+    let string = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => string.clone(),
+        _ => return Err(String::from("expected a number")),
+    };
+    let (negative, digits) = match string.strip_prefix('-') {
+        Some(digits) if signed => (true, digits),
+        Some(_) => return Err(format!("negative value '{}' for unsigned type", string)),
+        None => (false, string.as_str()),
+    };
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex_digits) => U256::from_str_radix(hex_digits, 16).ok(),
+        None => U256::from_dec_str(digits).ok(),
+    }
+    .ok_or_else(|| format!("invalid number '{}'", string))?;
+
+    Ok(if negative {
+        U256::zero().overflowing_sub(magnitude).0
+    } else {
+        magnitude
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail_typed_data() -> TypedData {
+        serde_json::from_value(serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallet", "type": "address" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                "contents": "Hello, Bob!"
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hash_matches_eip712_example() {
+        let expected: H256 = "0xbe609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+            .parse()
+            .unwrap();
+
+        assert_eq!(expected, mail_typed_data().hash().unwrap());
+    }
+
+    #[test]
+    fn test_encode_type_includes_nested_and_array_types() {
+        let typed_data: TypedData = serde_json::from_value(serde_json::json!({
+            "types": {
+                "EIP712Domain": [],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallets", "type": "address[]" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person[]" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {},
+            "message": {
+                "from": { "name": "Cow", "wallets": [] },
+                "to": [{ "name": "Bob", "wallets": ["0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"] }],
+                "contents": "Hello, Bob!"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            "Mail(Person from,Person[] to,string contents)Person(string name,address[] wallets)",
+            typed_data.encode_type("Mail").unwrap()
+        );
+        assert!(typed_data.hash().is_ok());
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        assert_eq!(
+            U256::from(42),
+            parse_integer(&serde_json::json!(42), false).unwrap()
+        );
+        assert_eq!(
+            U256::from(42),
+            parse_integer(&serde_json::json!("0x2a"), false).unwrap()
+        );
+        assert_eq!(
+            U256::MAX,
+            parse_integer(&serde_json::json!("-1"), true).unwrap()
+        );
+        assert!(parse_integer(&serde_json::json!("-1"), false).is_err());
+    }
+
+    #[test]
+    fn test_hash_rejects_mismatching_values() {
+        let mut typed_data = mail_typed_data();
+        typed_data.message["to"]["wallet"] = serde_json::json!("0x1234");
+
+        assert!(typed_data.hash().is_err());
+    }
+}
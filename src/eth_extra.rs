@@ -0,0 +1,1434 @@
+use jsonrpc_core::{BoxFuture, Result};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use vm::utils::ETH_CALL_GAS_LIMIT;
+use zksync_basic_types::{
+    web3::types::{AccessList, AccessListItem},
+    Address, Bytes, Nonce, H160, H256, U256,
+};
+use zksync_core::api_server::web3::backend_jsonrpc::error::into_jsrpc_error;
+use zksync_state::ReadStorage;
+use zksync_types::{
+    api::{
+        BlockId, BlockIdVariant, BlockNumber, Transaction, TransactionReceipt, TransactionVariant,
+    },
+    fee::Fee,
+    get_code_key, get_nonce_key,
+    l2::L2Tx,
+    transaction_request::{CallRequest, TransactionRequest},
+    utils::{decompose_full_nonce, storage_key_for_eth_balance},
+    L2ChainId, PackedEthSignature, StorageKey, MAX_GAS_PER_PUBDATA_BYTE,
+};
+use zksync_utils::{h256_to_u256, u256_to_h256};
+use zksync_web3_decl::error::Web3Error;
+
+use crate::{
+    eip712::TypedData,
+    fork::ForkSource,
+    node::{revert_error, rich_wallet_private_key, InMemoryNode, InMemoryNodeInner, MAX_TX_SIZE},
+    proof::{AccountProof, StorageProof, StorageTree},
+    state_override::{validate_state_override, StateOverride},
+    utils::IntoBoxedFuture,
+};
+
+/// A transaction to be signed by one of the rich accounts. Missing fields are filled in by the
+/// node: the current nonce of the account, the estimated gas limit and the L2 gas price.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionArgs {
+    pub from: Address,
+    pub to: Option<Address>,
+    pub gas: Option<U256>,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub value: Option<U256>,
+    #[serde(alias = "input")]
+    pub data: Option<Bytes>,
+    pub nonce: Option<U256>,
+}
+
+/// Highest address of the system contracts, whose storage is left out of access lists.
+const MAX_SYSTEM_CONTRACT_ADDRESS: u64 = 0xffff;
+
+/// Result of `eth_createAccessList`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListWithGasUsed {
+    pub access_list: AccessList,
+    pub gas_used: U256,
+}
+
+/// `eth_` methods that are not part of the zkSync `EthNamespaceT`, but are expected by
+/// Ethereum tooling talking to the test node.
+#[rpc]
+pub trait EthTestNodeNamespaceT {
+    /// Returns the priority fee (tip) per gas to use on top of the base fee for EIP-1559 transactions.
+    ///
+    /// The bootloader only charges the base fee and refunds the rest, so the tip is never paid.
+    /// The current `eth_gasPrice` is returned, so that `maxFeePerGas = baseFee + tip` still covers
+    /// the base fee if the gas price is raised before the transaction is mined.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the priority fee in wei.
+    #[rpc(name = "eth_maxPriorityFeePerGas")]
+    fn max_priority_fee_per_gas(&self) -> BoxFuture<Result<U256>>;
+
+    /// Signs a message with the key of one of the rich accounts, prefixing it with
+    /// `"\x19Ethereum Signed Message:\n" + len(message)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The rich account to sign with
+    /// * `message` - The message to sign
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the 65-byte signature.
+    #[rpc(name = "eth_sign")]
+    fn sign(&self, address: Address, message: Bytes) -> BoxFuture<Result<Bytes>>;
+
+    /// Same as `eth_sign`, with the arguments in the order used by `personal_sign`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message to sign
+    /// * `address` - The rich account to sign with
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the 65-byte signature.
+    #[rpc(name = "personal_sign")]
+    fn personal_sign(&self, message: Bytes, address: Address) -> BoxFuture<Result<Bytes>>;
+
+    /// Signs EIP-712 typed data with the key of one of the rich accounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The rich account to sign with
+    /// * `typed_data` - The typed data (`types`, `primaryType`, `domain` and `message`), either as
+    ///   a JSON object or as a string containing it
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the 65-byte signature.
+    #[rpc(name = "eth_signTypedData_v4")]
+    fn sign_typed_data_v4(
+        &self,
+        address: Address,
+        typed_data: serde_json::Value,
+    ) -> BoxFuture<Result<Bytes>>;
+
+    /// Signs a transaction with the key of one of the rich accounts, without executing it.
+    /// The nonce, gas limit and gas price are filled in when missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The transaction to sign, sent `from` one of the rich accounts
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the raw EIP-712 (0x71)
+    /// transaction, as accepted by `eth_sendRawTransaction`.
+    #[rpc(name = "eth_signTransaction")]
+    fn sign_transaction(&self, request: TransactionArgs) -> BoxFuture<Result<Bytes>>;
+
+    /// Signs a transaction on behalf of one of the rich accounts (or sends it from an
+    /// impersonated account) and executes it. The nonce, gas limit and gas price are filled in
+    /// when missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The transaction to send, `from` one of the rich or impersonated accounts
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the transaction hash.
+    #[rpc(name = "eth_sendTransaction")]
+    fn send_transaction(&self, request: TransactionArgs) -> BoxFuture<Result<H256>>;
+
+    /// Executes a call without creating a transaction, optionally overriding the balance, nonce,
+    /// code or storage of some accounts for the duration of the call. Replaces the zkSync
+    /// `eth_call`, which does not accept the override object.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The call request
+    /// * `block` - The block to run the call on, the latest one by default
+    /// * `state_override` - The account fields to override, keyed by address
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the returned data.
+    #[rpc(name = "eth_call")]
+    fn call_with_state_override(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+        state_override: Option<StateOverride>,
+    ) -> BoxFuture<Result<Bytes>>;
+
+    /// Executes a call without creating a transaction, and returns the storage slots of the
+    /// contracts it read or wrote. Slots of the system contracts are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The call request
+    /// * `block` - The block to run the call on, the latest one by default
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the access list, and the
+    /// gas the VM reports the call used.
+    #[rpc(name = "eth_createAccessList")]
+    fn create_access_list(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+    ) -> BoxFuture<Result<AccessListWithGasUsed>>;
+
+    /// Returns the receipts of all the transactions of a block, in the order of the block.
+    /// Blocks at or below the fork point are read from the forked network.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The number, tag or hash of the block
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that maybe resolves to the receipts, or
+    /// `None` if the block is unknown.
+    #[rpc(name = "eth_getBlockReceipts")]
+    fn get_block_receipts(
+        &self,
+        block: BlockId,
+    ) -> BoxFuture<Result<Option<Vec<TransactionReceipt>>>>;
+
+    /// Returns the balance, nonce and code hash of an account and the values of the given storage
+    /// slots, with Merkle proofs against the state root of the block (see [`crate::proof`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The account to prove
+    /// * `keys` - The storage slots of the account to prove
+    /// * `block` - The block to prove the values at, only the latest block is supported
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `AccountProof`.
+    #[rpc(name = "eth_getProof")]
+    fn get_proof(
+        &self,
+        address: Address,
+        keys: Vec<H256>,
+        block: Option<BlockIdVariant>,
+    ) -> BoxFuture<Result<AccountProof>>;
+
+    /// Returns the transactions waiting in the mempool, in the order they will be mined. The
+    /// mempool is only used when automine is disabled (`--no-mining`).
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the pending transactions.
+    #[rpc(name = "eth_pendingTransactions")]
+    fn pending_transactions(&self) -> BoxFuture<Result<Vec<Transaction>>>;
+}
+
+/// Signs `message` as an Ethereum signed message with the key of the given rich account.
+fn sign_message(address: Address, message: &[u8]) -> Result<Bytes> {
+    let private_key = rich_wallet_private_key(&address).ok_or_else(|| {
+        jsonrpc_core::Error::invalid_params(format!("account not managed: {:?}", address))
+    })?;
+    PackedEthSignature::sign(&private_key, message)
+        .map(|signature| Bytes::from(signature.serialize_packed().to_vec()))
+        .map_err(|err| {
+            log::error!("failed signing message with {:?}: {}", address, err);
+            jsonrpc_core::Error::internal_error()
+        })
+}
+
+/// Signs the EIP-712 digest of `typed_data` with the key of the given rich account.
+fn sign_typed_data(address: Address, typed_data: serde_json::Value) -> Result<Bytes> {
+    let typed_data = match typed_data {
+        serde_json::Value::String(json) => serde_json::from_str::<TypedData>(&json),
+        value => serde_json::from_value::<TypedData>(value),
+    }
+    .map_err(|err| jsonrpc_core::Error::invalid_params(format!("invalid typed data: {}", err)))?;
+    let digest = typed_data.hash().map_err(|err| {
+        jsonrpc_core::Error::invalid_params(format!("invalid typed data: {}", err))
+    })?;
+
+    let private_key = rich_wallet_private_key(&address).ok_or_else(|| {
+        jsonrpc_core::Error::invalid_params(format!("account not managed: {:?}", address))
+    })?;
+    PackedEthSignature::sign_raw(&private_key, &digest)
+        .map(|signature| Bytes::from(signature.serialize_packed().to_vec()))
+        .map_err(|err| {
+            log::error!("failed signing typed data with {:?}: {}", address, err);
+            jsonrpc_core::Error::internal_error()
+        })
+}
+
+/// Proves the value stored at `key`. Values read from the forked network are not part of the
+/// local storage tree, so they are returned with an empty proof.
+fn storage_proof<S: ForkSource + std::fmt::Debug>(
+    inner: &InMemoryNodeInner<S>,
+    tree: &StorageTree,
+    key: StorageKey,
+) -> Result<StorageProof> {
+    let value = (&inner.fork_storage).read_value(&key);
+    let stored_locally = inner
+        .fork_storage
+        .inner
+        .read()
+        .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?
+        .raw_storage
+        .state
+        .contains_key(&key);
+
+    Ok(StorageProof {
+        key: *key.key(),
+        value,
+        proof: if !value.is_zero() && !stored_locally {
+            vec![]
+        } else {
+            tree.proof(key.hashed_key())
+        },
+    })
+}
+
+/// Builds the L2 transaction described by `request` and signs it with `private_key`, filling in
+/// the missing nonce, gas limit and gas price. Requests without `to` are rejected: on zkSync,
+/// contracts are deployed by calling the `ContractDeployer` with the bytecode as a factory
+/// dependency, which a plain Ethereum request can't describe.
+fn build_signed_transaction<S: ForkSource + std::fmt::Debug>(
+    inner: &mut InMemoryNodeInner<S>,
+    request: TransactionArgs,
+    private_key: &H256,
+) -> Result<L2Tx> {
+    let from = request.from;
+    let to = request.to.ok_or_else(|| {
+        jsonrpc_core::Error::invalid_params(
+            "contract creation not supported: `to` is required, deploy with an EIP-712 transaction instead",
+        )
+    })?;
+    let data = request.data.unwrap_or_default();
+    let value = request.value.unwrap_or_default();
+
+    let nonce = match request.nonce {
+        Some(nonce) => nonce,
+        None => {
+            let full_nonce = inner.fork_storage.read_value(&get_nonce_key(&from));
+            // transactions waiting in the mempool take the next nonces
+            decompose_full_nonce(h256_to_u256(full_nonce)).0 + inner.pending_transaction_count(from)
+        }
+    };
+    if nonce > U256::from(u32::MAX) {
+        return Err(jsonrpc_core::Error::invalid_params(format!(
+            "Nonce {} does not fit in 32 bits",
+            nonce
+        )));
+    }
+
+    let gas_limit = match request.gas {
+        Some(gas) => gas,
+        None => {
+            inner
+                .estimate_gas_impl(CallRequest {
+                    from: Some(from),
+                    to: Some(to),
+                    data: Some(data.clone()),
+                    value: Some(value),
+                    ..Default::default()
+                })?
+                .gas_limit
+        }
+    };
+    let fee = Fee {
+        gas_limit,
+        max_fee_per_gas: request
+            .max_fee_per_gas
+            .or(request.gas_price)
+            .unwrap_or_else(|| U256::from(inner.l2_gas_price)),
+        max_priority_fee_per_gas: request.max_priority_fee_per_gas.unwrap_or_default(),
+        gas_per_pubdata_limit: U256::from(MAX_GAS_PER_PUBDATA_BYTE),
+    };
+
+    L2Tx::new_signed(
+        to,
+        data.0,
+        Nonce(nonce.as_u32()),
+        fee,
+        value,
+        inner.fork_storage.chain_id,
+        private_key,
+        None,
+        Default::default(),
+    )
+    .map_err(|err| {
+        log::error!("failed signing transaction from {:?}: {}", from, err);
+        jsonrpc_core::Error::internal_error()
+    })
+}
+
+/// Encodes a signed transaction as a raw EIP-712 transaction, as accepted by `eth_sendRawTransaction`.
+fn encode_signed_transaction(tx: L2Tx, chain_id: L2ChainId) -> Result<Vec<u8>> {
+    let signature = PackedEthSignature::deserialize_packed(&tx.common_data.signature)
+        .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+    Ok(TransactionRequest::from(tx).get_signed_bytes(&signature, chain_id.0))
+}
+
+impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EthTestNodeNamespaceT
+    for InMemoryNode<S>
+{
+    fn max_priority_fee_per_gas(&self) -> BoxFuture<Result<U256>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(U256::from(inner.l2_gas_price))
+        })
+    }
+
+    fn sign(&self, address: Address, message: Bytes) -> BoxFuture<Result<Bytes>> {
+        sign_message(address, &message.0).into_boxed_future()
+    }
+
+    fn personal_sign(&self, message: Bytes, address: Address) -> BoxFuture<Result<Bytes>> {
+        sign_message(address, &message.0).into_boxed_future()
+    }
+
+    fn sign_typed_data_v4(
+        &self,
+        address: Address,
+        typed_data: serde_json::Value,
+    ) -> BoxFuture<Result<Bytes>> {
+        sign_typed_data(address, typed_data).into_boxed_future()
+    }
+
+    fn sign_transaction(&self, request: TransactionArgs) -> BoxFuture<Result<Bytes>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let private_key = rich_wallet_private_key(&request.from).ok_or_else(|| {
+                jsonrpc_core::Error::invalid_params(format!(
+                    "account not managed: {:?}",
+                    request.from
+                ))
+            })?;
+            let mut inner = inner
+                .write()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let chain_id = inner.fork_storage.chain_id;
+            let tx = build_signed_transaction(&mut *inner, request, &private_key)?;
+
+            encode_signed_transaction(tx, chain_id).map(Bytes::from)
+        })
+    }
+
+    fn send_transaction(&self, request: TransactionArgs) -> BoxFuture<Result<H256>> {
+        let node = self.clone();
+        Box::pin(async move {
+            let from = request.from;
+            let tx = {
+                let inner = node.get_inner();
+                let mut inner = inner
+                    .write()
+                    .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+                // impersonated accounts skip the signature validation, any key will do
+                let private_key = match rich_wallet_private_key(&from) {
+                    Some(private_key) => private_key,
+                    None if inner.impersonated_accounts.contains(&from) => H256::random(),
+                    None => {
+                        return Err(jsonrpc_core::Error::invalid_params(format!(
+                            "account {:?} is not managed by the node and is not impersonated",
+                            from
+                        )))
+                    }
+                };
+                let chain_id = inner.fork_storage.chain_id;
+                let mut tx = build_signed_transaction(&mut *inner, request, &private_key)?;
+
+                let raw = encode_signed_transaction(tx.clone(), chain_id)?;
+                let (_, hash) = TransactionRequest::from_bytes(&raw, chain_id.0)
+                    .map_err(|err| into_jsrpc_error(Web3Error::SerializationError(err)))?;
+                tx.set_input(raw, hash);
+                tx.common_data.initiator_address = from;
+                tx
+            };
+
+            let hash = tx.hash();
+            node.submit_tx(tx)?;
+            Ok(hash)
+        })
+    }
+
+    fn call_with_state_override(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+        state_override: Option<StateOverride>,
+    ) -> BoxFuture<Result<Bytes>> {
+        let state_override = state_override.unwrap_or_default();
+        if let Err(err) = validate_state_override(&state_override) {
+            return Err(jsonrpc_core::Error::invalid_params(err)).into_boxed_future();
+        }
+        InMemoryNode::call_with_state_override(self, request, block, state_override)
+    }
+
+    fn create_access_list(
+        &self,
+        request: CallRequest,
+        block: Option<BlockIdVariant>,
+    ) -> BoxFuture<Result<AccessListWithGasUsed>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let mut tx = L2Tx::from_request(request.into(), MAX_TX_SIZE)
+                .map_err(|err| into_jsrpc_error(Web3Error::SerializationError(err)))?;
+            tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
+
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let result = inner
+                .run_l2_call(tx, block, &StateOverride::default())
+                .map_err(jsonrpc_core::Error::invalid_params)?;
+            if let Some(revert) = &result.full_result.revert_reason {
+                return Err(revert_error(&revert.revert_reason));
+            }
+
+            let mut slots = BTreeMap::<Address, BTreeSet<H256>>::new();
+            for query in &result.full_result.storage_log_queries {
+                let address = query.log_query.address;
+                if address > H160::from_low_u64_be(MAX_SYSTEM_CONTRACT_ADDRESS) {
+                    slots
+                        .entry(address)
+                        .or_default()
+                        .insert(u256_to_h256(query.log_query.key));
+                }
+            }
+            let access_list = slots
+                .into_iter()
+                .map(|(address, storage_keys)| AccessListItem {
+                    address,
+                    storage_keys: storage_keys.into_iter().collect(),
+                })
+                .collect();
+
+            // the call runs without paying fees, so nothing is refunded and the gas spent by the
+            // VM is what the call used
+            Ok(AccessListWithGasUsed {
+                access_list,
+                gas_used: result.full_result.gas_used.into(),
+            })
+        })
+    }
+
+    fn get_block_receipts(
+        &self,
+        block: BlockId,
+    ) -> BoxFuture<Result<Option<Vec<TransactionReceipt>>>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let number = match &block {
+                BlockId::Hash(_) => None,
+                BlockId::Number(number) => Some(inner.resolve_block_number(*number)),
+            };
+            let fork_storage = inner
+                .fork_storage
+                .inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let fork = fork_storage.fork.as_ref();
+            let fork_error = |err: eyre::Report| {
+                log::error!("failed reading receipts from the fork: {:?}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            };
+
+            let local_block = match (&block, number) {
+                (BlockId::Hash(hash), _) => inner.blocks.get(hash),
+                (_, Some(number)) => inner
+                    .block_hashes
+                    .get(&number)
+                    .and_then(|hash| inner.blocks.get(hash)),
+                (_, None) => None,
+            };
+            let block = match (local_block, fork) {
+                (Some(local_block), _) => local_block.clone(),
+                // only the blocks up to the fork point are part of the local chain
+                (None, Some(fork)) => {
+                    let fork_block = match (block, number) {
+                        (BlockId::Hash(hash), _) => fork.fork_source.get_block_by_hash(hash, false),
+                        (_, Some(number)) if number <= fork.l2_miniblock => fork
+                            .fork_source
+                            .get_block_by_number(BlockNumber::Number(number.into()), false),
+                        _ => return Ok(None),
+                    }
+                    .map_err(fork_error)?;
+                    match fork_block {
+                        Some(fork_block) if fork_block.number.as_u64() <= fork.l2_miniblock => {
+                            fork_block
+                        }
+                        _ => return Ok(None),
+                    }
+                }
+                (None, None) => return Ok(None),
+            };
+
+            let mut receipts = Vec::with_capacity(block.transactions.len());
+            for transaction in &block.transactions {
+                let hash = match transaction {
+                    TransactionVariant::Full(transaction) => transaction.hash,
+                    TransactionVariant::Hash(hash) => *hash,
+                };
+                let receipt = match (inner.transaction_receipt(hash), fork) {
+                    (Some(receipt), _) => Some(receipt),
+                    (None, Some(fork)) => fork
+                        .fork_source
+                        .get_transaction_receipt(hash)
+                        .map_err(fork_error)?,
+                    (None, None) => None,
+                };
+                match receipt {
+                    Some(receipt) => receipts.push(receipt),
+                    None => {
+                        return Err(jsonrpc_core::Error::invalid_params(format!(
+                            "receipt of transaction {:?} not found",
+                            hash
+                        )))
+                    }
+                }
+            }
+            Ok(Some(receipts))
+        })
+    }
+
+    fn get_proof(
+        &self,
+        address: Address,
+        keys: Vec<H256>,
+        block: Option<BlockIdVariant>,
+    ) -> BoxFuture<Result<AccountProof>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            match inner.historical_fork_block(block) {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    return Err(jsonrpc_core::Error::invalid_params(format!(
+                        "proofs are only available for the latest block ({})",
+                        inner.current_miniblock
+                    )))
+                }
+                Err(err) => return Err(jsonrpc_core::Error::invalid_params(err)),
+            }
+            let tree = inner.storage_tree().map_err(|err| {
+                log::error!("failed building the storage tree: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })?;
+
+            let account_proof = vec![
+                storage_proof(&inner, &tree, storage_key_for_eth_balance(&address))?,
+                storage_proof(&inner, &tree, get_nonce_key(&address))?,
+                storage_proof(&inner, &tree, get_code_key(&address))?,
+            ];
+            let storage_proofs = keys
+                .into_iter()
+                .map(|key| storage_proof(&inner, &tree, StorageKey::new(address.into(), key)))
+                .collect::<Result<Vec<_>>>()?;
+            let (nonce, _) = decompose_full_nonce(h256_to_u256(account_proof[1].value));
+
+            Ok(AccountProof {
+                address,
+                balance: h256_to_u256(account_proof[0].value),
+                nonce,
+                code_hash: account_proof[2].value,
+                storage_hash: tree.root(),
+                account_proof,
+                storage_proof: storage_proofs,
+            })
+        })
+    }
+
+    fn pending_transactions(&self) -> BoxFuture<Result<Vec<Transaction>>> {
+        let inner = self.get_inner();
+        Box::pin(async move {
+            let inner = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(inner
+                .transaction_order
+                .sort(inner.mempool.clone())
+                .into_iter()
+                .map(Transaction::from)
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http_fork_source::HttpForkSource,
+        node::{RICH_WALLETS, TEST_NODE_NETWORK_ID},
+        proof::verify_proof,
+        state_override::AccountOverride,
+        testing::{self, ForkBlockConfig, MockServer},
+    };
+    use std::{collections::HashMap, str::FromStr};
+    use zksync_basic_types::{AccountTreeId, U64};
+    use zksync_core::api_server::web3::backend_jsonrpc::namespaces::eth::EthNamespaceT;
+    use zksync_types::{get_known_code_key, ECRECOVER_PRECOMPILE_ADDRESS, L2_ETH_TOKEN_ADDRESS};
+    use zksync_utils::bytecode::hash_bytecode;
+
+    fn mail_typed_data() -> serde_json::Value {
+        serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallets", "type": "address[]" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person[]" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 260,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": {
+                    "name": "Cow",
+                    "wallets": ["0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"]
+                },
+                "to": [{
+                    "name": "Bob",
+                    "wallets": ["0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"]
+                }],
+                "contents": "Hello, Bob!"
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_max_priority_fee_per_gas_is_accepted_by_execution() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let gas_price = node.gas_price().await.expect("gas_price");
+        let priority_fee = node
+            .max_priority_fee_per_gas()
+            .await
+            .expect("max_priority_fee_per_gas");
+        assert_eq!(gas_price, priority_fee);
+
+        let private_key = H256::random();
+        let from_account = PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.set_rich_account(from_account);
+        let mut tx = L2Tx::new_signed(
+            Address::random(),
+            vec![],
+            Nonce(0),
+            Fee {
+                gas_limit: U256::from(1_000_000),
+                max_fee_per_gas: gas_price + priority_fee,
+                max_priority_fee_per_gas: priority_fee,
+                gas_per_pubdata_limit: U256::from(20000),
+            },
+            U256::from(1),
+            L2ChainId(260),
+            &private_key,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+        tx.set_input(vec![], H256::repeat_byte(0x01));
+
+        node.apply_txs(vec![tx])
+            .expect("transaction priced with the suggested fees was rejected");
+
+        node.get_inner().write().unwrap().l2_gas_price = 500_000_000;
+        assert_eq!(
+            U256::from(500_000_000),
+            node.max_priority_fee_per_gas()
+                .await
+                .expect("max_priority_fee_per_gas")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_rich_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        let message = Bytes::from(b"hello era".to_vec());
+
+        let signature = node
+            .sign(address, message.clone())
+            .await
+            .expect("eth_sign failed");
+        let personal_signature = node
+            .personal_sign(message.clone(), address)
+            .await
+            .expect("personal_sign failed");
+
+        assert_eq!(65, signature.0.len());
+        assert_eq!(signature, personal_signature);
+        let recovered = PackedEthSignature::deserialize_packed(&signature.0)
+            .unwrap()
+            .signature_recover_signer(&message.0)
+            .unwrap();
+        assert_eq!(address, recovered);
+    }
+
+    #[tokio::test]
+    async fn test_sign_rejects_unmanaged_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let error = node
+            .sign(Address::random(), Bytes::from(b"hello era".to_vec()))
+            .await
+            .expect_err("signed with unmanaged account");
+
+        assert!(error.message.contains("account not managed"));
+    }
+
+    #[tokio::test]
+    async fn test_sign_typed_data_v4_recovers_with_ecrecover_on_node() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        let typed_data = mail_typed_data();
+
+        let signature = node
+            .sign_typed_data_v4(address, serde_json::Value::String(typed_data.to_string()))
+            .await
+            .expect("eth_signTypedData_v4 failed");
+        assert_eq!(65, signature.0.len());
+
+        let digest = serde_json::from_value::<TypedData>(typed_data)
+            .unwrap()
+            .hash()
+            .unwrap();
+        let v = match signature.0[64] {
+            v if v < 27 => v + 27,
+            v => v,
+        };
+        let mut data = digest.as_bytes().to_vec();
+        data.extend_from_slice(H256::from_low_u64_be(v as u64).as_bytes());
+        data.extend_from_slice(&signature.0[..64]);
+
+        let recovered = node
+            .call(
+                CallRequest {
+                    to: Some(ECRECOVER_PRECOMPILE_ADDRESS),
+                    data: Some(Bytes::from(data)),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .expect("ecrecover call failed");
+
+        assert_eq!(32, recovered.0.len());
+        assert_eq!(address, Address::from_slice(&recovered.0[12..]));
+    }
+
+    #[tokio::test]
+    async fn test_sign_typed_data_v4_rejects_unmanaged_account_and_invalid_data() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let error = node
+            .sign_typed_data_v4(Address::random(), mail_typed_data())
+            .await
+            .expect_err("signed with unmanaged account");
+        assert!(error.message.contains("account not managed"));
+
+        let address = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        let error = node
+            .sign_typed_data_v4(address, serde_json::json!({ "primaryType": "Mail" }))
+            .await
+            .expect_err("signed invalid typed data");
+        assert!(error.message.contains("invalid typed data"));
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction_round_trips_through_send_raw_transaction() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let from = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        let to = Address::random();
+        // the rich wallets are only funded when the node is started from the CLI
+        node.set_rich_account(from);
+
+        let raw = node
+            .sign_transaction(TransactionArgs {
+                from,
+                to: Some(to),
+                value: Some(U256::from(100)),
+                ..Default::default()
+            })
+            .await
+            .expect("eth_signTransaction failed");
+        assert_eq!(Some(&0x71), raw.0.first());
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(from, None).await.unwrap(),
+            "signing must not execute the transaction"
+        );
+
+        let (_, expected_hash) =
+            TransactionRequest::from_bytes(&raw.0, TEST_NODE_NETWORK_ID).unwrap();
+        let hash = node
+            .send_raw_transaction(raw)
+            .await
+            .expect("eth_sendRawTransaction failed");
+        assert_eq!(expected_hash, hash);
+
+        let tx = node
+            .get_transaction_by_hash(hash)
+            .await
+            .unwrap()
+            .expect("transaction not found");
+        assert_eq!(from, tx.from.unwrap());
+        assert_eq!(U256::from(100), node.get_balance(to, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_contract_creation_is_rejected() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let from = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        node.set_rich_account(from);
+        let deployment = || TransactionArgs {
+            from,
+            to: None,
+            data: Some(Bytes(vec![0; 64])),
+            ..Default::default()
+        };
+
+        let error = node
+            .sign_transaction(deployment())
+            .await
+            .expect_err("signed a contract creation");
+        assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, error.code);
+        assert!(error.message.contains("contract creation not supported"));
+
+        let error = node
+            .send_transaction(deployment())
+            .await
+            .expect_err("sent a contract creation");
+        assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, error.code);
+        assert!(error.message.contains("contract creation not supported"));
+        assert!(node.get_inner().read().unwrap().tx_results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction_rejects_unmanaged_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let error = node
+            .sign_transaction(TransactionArgs {
+                from: Address::random(),
+                to: Some(Address::random()),
+                ..Default::default()
+            })
+            .await
+            .expect_err("signed with unmanaged account");
+
+        assert!(error.message.contains("account not managed"));
+    }
+
+    #[tokio::test]
+    async fn test_send_transaction_from_rich_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let from = Address::from_str(RICH_WALLETS[1].0).unwrap();
+        let to = Address::random();
+        node.set_rich_account(from);
+        let transfer = |nonce| TransactionArgs {
+            from,
+            to: Some(to),
+            value: Some(U256::from(100)),
+            nonce,
+            ..Default::default()
+        };
+
+        let hash = node
+            .send_transaction(transfer(None))
+            .await
+            .expect("eth_sendTransaction failed");
+        node.send_transaction(transfer(None))
+            .await
+            .expect("second eth_sendTransaction failed");
+
+        let tx = node
+            .get_transaction_by_hash(hash)
+            .await
+            .unwrap()
+            .expect("transaction not found");
+        assert_eq!(from, tx.from.unwrap());
+        assert_eq!(U256::from(200), node.get_balance(to, None).await.unwrap());
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(from, None).await.unwrap()
+        );
+        assert!(node
+            .send_transaction(transfer(Some(U256::zero())))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_transaction_from_impersonated_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let from = Address::random();
+        let to = Address::random();
+        node.set_rich_account(from);
+        let transfer = TransactionArgs {
+            from,
+            to: Some(to),
+            value: Some(U256::from(100)),
+            ..Default::default()
+        };
+
+        let error = node
+            .send_transaction(transfer.clone())
+            .await
+            .expect_err("sent from an unmanaged account");
+        assert!(error.message.contains(&format!("{:?}", from)));
+
+        node.get_inner()
+            .write()
+            .unwrap()
+            .impersonated_accounts
+            .insert(from);
+        node.send_transaction(transfer)
+            .await
+            .expect("eth_sendTransaction from impersonated account failed");
+        assert_eq!(U256::from(100), node.get_balance(to, None).await.unwrap());
+    }
+
+    /// Returns the bytecode of the L2 ETH token, used as a contract with known storage layout.
+    fn eth_token_bytecode(node: &InMemoryNode<HttpForkSource>) -> Bytes {
+        let inner = node.get_inner();
+        let inner = inner.read().unwrap();
+        let mut storage = &inner.fork_storage;
+        let code_hash = storage.read_value(&get_code_key(&L2_ETH_TOKEN_ADDRESS));
+        Bytes(
+            storage
+                .load_factory_dep(code_hash)
+                .expect("missing L2 ETH token bytecode"),
+        )
+    }
+
+    fn call_request(to: Address, data: Vec<u8>) -> CallRequest {
+        CallRequest {
+            to: Some(to),
+            data: Some(Bytes(data)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_eth_call_with_balance_override_leaves_node_state_untouched() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(EthNamespaceT::to_delegate(node.clone()));
+        io.extend_with(EthTestNodeNamespaceT::to_delegate(node.clone()));
+        let account = Address::random();
+        // balanceOf(address)
+        let data = format!("0x70a08231{:0>64}", hex::encode(account.as_bytes()));
+
+        let response = io
+            .handle_request(&format!(
+                r#"{{"jsonrpc":"2.0","method":"eth_call","params":[{{"to":"{:#x}","data":"{}"}},"latest",{{"{:#x}":{{"balance":"0x2a"}}}}],"id":1}}"#,
+                L2_ETH_TOKEN_ADDRESS, data, account
+            ))
+            .await
+            .expect("no response");
+
+        assert!(
+            response.contains(&format!("\"result\":\"{:#x}\"", H256::from_low_u64_be(42))),
+            "unexpected response: {}",
+            response
+        );
+        assert_eq!(U256::zero(), node.get_balance(account, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_eth_call_with_code_override() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::random();
+        let state_override = StateOverride::from([(
+            address,
+            AccountOverride {
+                code: Some(eth_token_bytecode(&node)),
+                ..Default::default()
+            },
+        )]);
+
+        // decimals()
+        let result = EthTestNodeNamespaceT::call_with_state_override(
+            &node,
+            call_request(address, hex::decode("313ce567").unwrap()),
+            None,
+            Some(state_override),
+        )
+        .await
+        .expect("eth_call with code override failed");
+
+        assert_eq!(H256::from_low_u64_be(18).as_bytes(), &result.0[..]);
+        assert!(node.get_code(address, None).await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_eth_call_state_replaces_storage_and_state_diff_patches_it() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::random();
+        let total_supply_slot = H256::from_low_u64_be(1);
+        node.get_inner().write().unwrap().fork_storage.set_value(
+            StorageKey::new(AccountTreeId::new(address), total_supply_slot),
+            H256::from_low_u64_be(100),
+        );
+        let code = eth_token_bytecode(&node);
+        let unrelated_slot = HashMap::from([(H256::from_low_u64_be(7), H256::from_low_u64_be(1))]);
+        let total_supply = |account_override: AccountOverride| {
+            EthTestNodeNamespaceT::call_with_state_override(
+                &node,
+                // totalSupply()
+                call_request(address, hex::decode("18160ddd").unwrap()),
+                None,
+                Some(StateOverride::from([(
+                    address,
+                    AccountOverride {
+                        code: Some(code.clone()),
+                        ..account_override
+                    },
+                )])),
+            )
+        };
+
+        let patched = total_supply(AccountOverride {
+            state_diff: Some(unrelated_slot.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(H256::from_low_u64_be(100).as_bytes(), &patched.0[..]);
+
+        let replaced = total_supply(AccountOverride {
+            state: Some(unrelated_slot),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(H256::zero().as_bytes(), &replaced.0[..]);
+    }
+
+    #[tokio::test]
+    async fn test_eth_call_rejects_invalid_state_override() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::random();
+        let state_override = StateOverride::from([(
+            address,
+            AccountOverride {
+                state: Some(HashMap::new()),
+                state_diff: Some(HashMap::new()),
+                ..Default::default()
+            },
+        )]);
+
+        let error = EthTestNodeNamespaceT::call_with_state_override(
+            &node,
+            call_request(L2_ETH_TOKEN_ADDRESS, vec![]),
+            None,
+            Some(state_override),
+        )
+        .await
+        .expect_err("accepted both state and stateDiff");
+
+        assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, error.code);
+    }
+
+    #[tokio::test]
+    async fn test_create_access_list_contains_user_contract_slots() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::random();
+        let code = eth_token_bytecode(&node);
+        let code_hash = hash_bytecode(&code.0);
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            inner.fork_storage.store_factory_dep(code_hash, code.0);
+            inner
+                .fork_storage
+                .set_value(get_known_code_key(&code_hash), H256::from_low_u64_be(1));
+            inner
+                .fork_storage
+                .set_value(get_code_key(&address), code_hash);
+        }
+
+        // totalSupply()
+        let result = node
+            .create_access_list(
+                call_request(address, hex::decode("18160ddd").unwrap()),
+                None,
+            )
+            .await
+            .expect("eth_createAccessList failed");
+
+        assert_eq!(1, result.access_list.len());
+        assert_eq!(address, result.access_list[0].address);
+        assert_eq!(
+            vec![H256::from_low_u64_be(1)],
+            result.access_list[0].storage_keys
+        );
+        assert!(result.gas_used > U256::zero());
+        assert!(result.gas_used < U256::from(ETH_CALL_GAS_LIMIT));
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json["accessList"][0]["storageKeys"].is_array());
+        assert!(json["gasUsed"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_get_block_receipts_matches_transaction_receipts() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        let block_hash = crate::testing::apply_tx(&node, tx_hash);
+        let expected = node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .expect("failed fetching receipt")
+            .expect("no receipt");
+
+        for block in [
+            BlockId::Hash(block_hash),
+            BlockId::Number(BlockNumber::Latest),
+        ] {
+            let receipts = node
+                .get_block_receipts(block)
+                .await
+                .expect("eth_getBlockReceipts failed")
+                .expect("block not found");
+            assert_eq!(
+                serde_json::to_value(vec![&expected]).unwrap(),
+                serde_json::to_value(receipts).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_block_receipts_returns_none_for_unknown_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        for block in [
+            BlockId::Hash(H256::repeat_byte(0xab)),
+            BlockId::Number(BlockNumber::Number(U64::from(42))),
+        ] {
+            let receipts = node
+                .get_block_receipts(block)
+                .await
+                .expect("eth_getBlockReceipts failed");
+            assert!(receipts.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_block_receipts_only_forwards_blocks_up_to_the_fork() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockByNumber",
+                "params": ["0x8", false],
+            }),
+            testing::BlockResponseBuilder::new()
+                .set_hash(H256::repeat_byte(0x08))
+                .set_number(8)
+                .build(),
+        );
+        let after_fork_hash = H256::repeat_byte(0x0c);
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockByHash",
+                "params": [format!("{:#x}", after_fork_hash), false],
+            }),
+            testing::BlockResponseBuilder::new()
+                .set_hash(after_fork_hash)
+                .set_number(12)
+                .build(),
+        );
+        let node = testing::forked_node(&mock_server).await;
+
+        let receipts = node
+            .get_block_receipts(BlockId::Number(BlockNumber::Number(U64::from(8))))
+            .await
+            .expect("eth_getBlockReceipts failed");
+        assert_eq!(Some(0), receipts.map(|receipts| receipts.len()));
+
+        // local blocks that don't exist yet, and blocks of the forked network after the fork
+        for block in [
+            BlockId::Number(BlockNumber::Number(U64::from(11))),
+            BlockId::Hash(after_fork_hash),
+        ] {
+            let receipts = node
+                .get_block_receipts(block)
+                .await
+                .expect("eth_getBlockReceipts failed");
+            assert!(receipts.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_verifies_against_block_state_root() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = Address::repeat_byte(0x01);
+        let slot = H256::from_low_u64_be(3);
+        node.set_rich_account(address);
+        node.get_inner().write().unwrap().fork_storage.set_value(
+            StorageKey::new(address.into(), slot),
+            H256::from_low_u64_be(7),
+        );
+        crate::testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        let block = node
+            .get_block_by_number(BlockNumber::Latest, false)
+            .await
+            .expect("failed fetching block")
+            .expect("no block");
+        let proof = node
+            .get_proof(address, vec![slot, H256::from_low_u64_be(4)], None)
+            .await
+            .expect("eth_getProof failed");
+
+        assert_eq!(block.state_root, proof.storage_hash);
+        assert_eq!(U256::from(10u128.pow(30)), proof.balance);
+        assert_eq!(U256::zero(), proof.nonce);
+        for (key, account_proof) in [
+            storage_key_for_eth_balance(&address),
+            get_nonce_key(&address),
+            get_code_key(&address),
+        ]
+        .into_iter()
+        .zip(&proof.account_proof)
+        {
+            assert!(verify_proof(
+                proof.storage_hash,
+                key.hashed_key(),
+                account_proof.value,
+                &account_proof.proof
+            ));
+        }
+        assert_eq!(
+            vec![H256::from_low_u64_be(7), H256::zero()],
+            proof
+                .storage_proof
+                .iter()
+                .map(|storage_proof| storage_proof.value)
+                .collect::<Vec<_>>()
+        );
+        for storage_proof in &proof.storage_proof {
+            let key = StorageKey::new(address.into(), storage_proof.key);
+            assert!(verify_proof(
+                proof.storage_hash,
+                key.hashed_key(),
+                storage_proof.value,
+                &storage_proof.proof
+            ));
+        }
+
+        let error = node
+            .get_proof(
+                address,
+                vec![],
+                Some(BlockIdVariant::BlockNumber(BlockNumber::Number(U64::from(
+                    0,
+                )))),
+            )
+            .await
+            .expect_err("proved a historical block");
+        assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, error.code);
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_returns_fork_values_with_empty_proof() {
+        let address = Address::repeat_byte(0x01);
+        let value = H256::repeat_byte(0x03);
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getStorageAt",
+                "params": [
+                    format!("{address:#x}"),
+                    "0x2",
+                    "0xa",
+                ],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("{value:#x}"),
+            }),
+        );
+        let node = testing::forked_node(&mock_server).await;
+        // the account itself is local, only the slot is read from the fork
+        let slot = H256::from_low_u64_be(2);
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            inner.fork_storage.set_value(
+                storage_key_for_eth_balance(&address),
+                H256::from_low_u64_be(100),
+            );
+            inner
+                .fork_storage
+                .set_value(get_nonce_key(&address), H256::zero());
+            inner
+                .fork_storage
+                .set_value(get_code_key(&address), H256::zero());
+        }
+
+        let proof = node
+            .get_proof(address, vec![slot], None)
+            .await
+            .expect("eth_getProof failed");
+
+        assert_eq!(U256::from(100), proof.balance);
+        assert!(verify_proof(
+            proof.storage_hash,
+            storage_key_for_eth_balance(&address).hashed_key(),
+            H256::from_low_u64_be(100),
+            &proof.account_proof[0].proof
+        ));
+        assert_eq!(value, proof.storage_proof[0].value);
+        assert!(proof.storage_proof[0].proof.is_empty());
+    }
+}
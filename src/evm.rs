@@ -1,8 +1,18 @@
-use std::sync::{Arc, RwLock};
-
-use crate::{fork::ForkSource, node::InMemoryNodeInner, utils::mine_empty_blocks};
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::{
+    fork::ForkSource,
+    node::{InMemoryNode, InMemoryNodeInner},
+    state::SerializableState,
+    utils::mine_empty_blocks,
+};
 use jsonrpc_core::{BoxFuture, Result};
 use jsonrpc_derive::rpc;
+use zksync_basic_types::{H256, U64};
 use zksync_core::api_server::web3::backend_jsonrpc::error::into_jsrpc_error;
 use zksync_web3_decl::error::Web3Error;
 
@@ -20,26 +30,32 @@ impl<S> EvmNamespaceImpl<S> {
 
 #[rpc]
 pub trait EvmNamespaceT {
-    /// Increase the current timestamp for the node
+    /// Increase the current timestamp for the node. The jump is applied to the next block,
+    /// and every following block is produced relative to it.
     ///
     /// # Parameters
     /// - `time_delta`: The number of seconds to increase time by
     ///
     /// # Returns
-    /// The applied time delta to `current_timestamp` value for the InMemoryNodeInner.
+    /// The total number of seconds the clock was moved forward by, across all the calls.
     #[rpc(name = "evm_increaseTime")]
     fn increase_time(&self, time_delta_seconds: u64) -> BoxFuture<Result<u64>>;
 
-    /// Force a single block to be mined.
+    /// Force one or more blocks to be mined.
+    ///
+    /// Mines the transactions waiting in the mempool first, all in a single block, then empty
+    /// blocks (containing zero transactions) until `count` blocks were mined.
     ///
-    /// Will mine an empty block (containing zero transactions)
+    /// # Parameters
+    /// - `count`: The number of blocks to mine, defaults to 1
     ///
     /// # Returns
-    /// The string "0x0".
+    /// The hash of the latest mined block.
     #[rpc(name = "evm_mine")]
-    fn evm_mine(&self) -> BoxFuture<Result<String>>;
+    fn evm_mine(&self, count: Option<U64>) -> BoxFuture<Result<H256>>;
 
-    /// Set the current timestamp for the node. The timestamp must be in future.
+    /// Set the timestamp of the next block. The timestamp must not be earlier than the current one.
+    /// Blocks sealed after the next one resume the normal timestamp increments.
     ///
     /// # Parameters
     /// - `timestamp`: The timestamp to set the time to
@@ -60,6 +76,74 @@ pub trait EvmNamespaceT {
     /// The difference between the `current_timestamp` and the new timestamp for the InMemoryNodeInner.
     #[rpc(name = "evm_setTime")]
     fn set_time(&self, time: u64) -> BoxFuture<Result<i128>>;
+
+    /// Enable or disable interval mining. When enabled, the mempool is mined every `interval`
+    /// milliseconds, or an empty block is sealed if it is empty. This comes in addition to the
+    /// blocks produced by transactions when automine is enabled.
+    ///
+    /// # Parameters
+    /// - `interval`: The time between blocks in milliseconds, or `0` to disable interval mining
+    ///
+    /// # Returns
+    /// `true` if the operation was successful.
+    #[rpc(name = "evm_setIntervalMining")]
+    fn set_interval_mining(&self, interval: u64) -> BoxFuture<Result<bool>>;
+
+    /// Enable or disable automine, as Anvil does. Enabling it stops interval mining, as automine
+    /// and interval mining are exclusive mining modes in Anvil.
+    ///
+    /// # Parameters
+    /// - `enabled`: Whether transactions are mined as soon as they are received
+    #[rpc(name = "anvil_setAutomine")]
+    fn anvil_set_automine(&self, enabled: bool) -> BoxFuture<Result<()>>;
+
+    /// Switch to interval mining, as Anvil does: automine is disabled and the mempool is mined
+    /// every `interval` milliseconds. With `0`, interval mining is stopped and automine is left as is.
+    ///
+    /// # Parameters
+    /// - `interval`: The time between blocks in milliseconds, or `0` to disable interval mining
+    #[rpc(name = "anvil_setIntervalMining")]
+    fn anvil_set_interval_mining(&self, interval: u64) -> BoxFuture<Result<()>>;
+
+    /// Snapshot the state of the blockchain at the current block.
+    ///
+    /// # Returns
+    /// The id of the snapshot, to be passed to `evm_revert`.
+    #[rpc(name = "evm_snapshot")]
+    fn snapshot(&self) -> BoxFuture<Result<U64>>;
+
+    /// Revert the state of the blockchain to a previous snapshot. The snapshots taken after it
+    /// can't be used anymore. The snapshot itself and the earlier ones are kept, so the state can
+    /// be reverted to them again.
+    ///
+    /// # Parameters
+    /// - `snapshot_id`: The id returned by `evm_snapshot`
+    ///
+    /// # Returns
+    /// `true` if the state was reverted, `false` if the snapshot doesn't exist.
+    #[rpc(name = "evm_revert")]
+    fn revert_snapshot(&self, snapshot_id: U64) -> BoxFuture<Result<bool>>;
+
+    /// Write the state of the node (accounts, storage, blocks, transactions, tokens and clock) to a
+    /// JSON file, that `anvil_loadState` can restore after a restart.
+    ///
+    /// # Parameters
+    /// - `path`: The file to write, replaced if it exists
+    ///
+    /// # Returns
+    /// `true` once the file is written.
+    #[rpc(name = "anvil_dumpState")]
+    fn dump_state(&self, path: String) -> BoxFuture<Result<bool>>;
+
+    /// Replace the state of the node with the one written by `anvil_dumpState`.
+    ///
+    /// # Parameters
+    /// - `path`: The file written by `anvil_dumpState`
+    ///
+    /// # Returns
+    /// `true` once the state is loaded.
+    #[rpc(name = "anvil_loadState")]
+    fn load_state(&self, path: String) -> BoxFuture<Result<bool>>;
 }
 
 impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EvmNamespaceT
@@ -69,16 +153,14 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EvmNamespaceT
         let inner = Arc::clone(&self.node);
 
         Box::pin(async move {
-            if time_delta_seconds == 0 {
-                return Ok(time_delta_seconds);
-            }
-
             let time_delta = time_delta_seconds.saturating_mul(1000);
             match inner.write() {
                 Ok(mut inner_guard) => {
                     inner_guard.current_timestamp =
                         inner_guard.current_timestamp.saturating_add(time_delta);
-                    Ok(time_delta_seconds)
+                    inner_guard.time_offset =
+                        inner_guard.time_offset.saturating_add(time_delta_seconds);
+                    Ok(inner_guard.time_offset)
                 }
                 Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
             }
@@ -92,7 +174,10 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EvmNamespaceT
             match inner.write() {
                 Ok(mut inner_guard) => {
                     if timestamp < inner_guard.current_timestamp {
-                        Err(into_jsrpc_error(Web3Error::InternalError))
+                        Err(jsonrpc_core::Error::invalid_params(format!(
+                            "timestamp ({}) must be greater than or equal to the current timestamp ({})",
+                            timestamp, inner_guard.current_timestamp
+                        )))
                     } else {
                         inner_guard.current_timestamp = timestamp;
                         Ok(timestamp)
@@ -119,24 +204,245 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EvmNamespaceT
         })
     }
 
-    fn evm_mine(&self) -> BoxFuture<Result<String>> {
-        let inner = Arc::clone(&self.node);
+    fn evm_mine(&self, count: Option<U64>) -> BoxFuture<Result<H256>> {
+        let node = InMemoryNode::from_inner(Arc::clone(&self.node));
         Box::pin(async move {
-            match inner.write() {
+            let count = count.unwrap_or(U64::from(1));
+            if count.is_zero() {
+                return Err(jsonrpc_core::Error::invalid_params(
+                    "Number of blocks must be greater than 0".to_string(),
+                ));
+            }
+            let mined = node.mine_mempool().map_err(|err| {
+                log::error!("failed mining the mempool: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })?;
+
+            match node.get_inner().write() {
                 Ok(mut inner) => {
-                    mine_empty_blocks(&mut inner, 1, 1000);
+                    if count.as_u64() > mined {
+                        mine_empty_blocks(&mut inner, count.as_u64() - mined, 1000).map_err(
+                            |err| {
+                                log::error!("failed mining empty blocks: {}", err);
+                                into_jsrpc_error(Web3Error::InternalError)
+                            },
+                        )?;
+                    }
                     log::info!("👷 Mined block #{}", inner.current_miniblock);
-                    Ok("0x0".to_string())
+                    inner
+                        .block_hashes
+                        .get(&inner.current_miniblock)
+                        .copied()
+                        .ok_or_else(|| into_jsrpc_error(Web3Error::InternalError))
                 }
                 Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
             }
         })
     }
+
+    fn set_interval_mining(&self, interval: u64) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    replace_interval_mining(&inner, &mut inner_guard, interval);
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn anvil_set_automine(&self, enabled: bool) -> BoxFuture<Result<()>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    if enabled && inner_guard.interval_mining.is_some() {
+                        replace_interval_mining(&inner, &mut inner_guard, 0);
+                    }
+                    inner_guard.automine = enabled;
+                    log::info!(
+                        "👷 Automine {}",
+                        if enabled { "enabled" } else { "disabled" }
+                    );
+                    Ok(())
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn anvil_set_interval_mining(&self, interval: u64) -> BoxFuture<Result<()>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    if interval != 0 {
+                        inner_guard.automine = false;
+                    }
+                    replace_interval_mining(&inner, &mut inner_guard, interval);
+                    Ok(())
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn snapshot(&self) -> BoxFuture<Result<U64>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    let snapshot = inner_guard.snapshot().map_err(|err| {
+                        log::error!("failed creating snapshot: {}", err);
+                        into_jsrpc_error(Web3Error::InternalError)
+                    })?;
+                    inner_guard.last_snapshot_id += U64::one();
+                    let snapshot_id = inner_guard.last_snapshot_id;
+                    inner_guard.snapshots.insert(snapshot_id, snapshot);
+                    log::info!("👷 Created snapshot {}", snapshot_id);
+                    Ok(snapshot_id)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn revert_snapshot(&self, snapshot_id: U64) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    let snapshot = match inner_guard.snapshots.get(&snapshot_id) {
+                        Some(snapshot) => snapshot.clone(),
+                        None => {
+                            log::warn!("Snapshot {} doesn't exist", snapshot_id);
+                            return Ok(false);
+                        }
+                    };
+                    // drop the snapshots taken after the requested one, which stays valid
+                    inner_guard.snapshots.retain(|id, _| *id <= snapshot_id);
+                    inner_guard.restore_snapshot(snapshot).map_err(|err| {
+                        log::error!("failed restoring snapshot: {}", err);
+                        into_jsrpc_error(Web3Error::InternalError)
+                    })?;
+                    log::info!("👷 Reverted to snapshot {}", snapshot_id);
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn dump_state(&self, path: String) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let state = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?
+                .dump_state()
+                .map_err(|err| {
+                    log::error!("failed dumping state: {}", err);
+                    into_jsrpc_error(Web3Error::InternalError)
+                })?;
+            state
+                .to_file(Path::new(&path))
+                .map_err(jsonrpc_core::Error::invalid_params)?;
+            log::info!("👷 Dumped state to {}", path);
+            Ok(true)
+        })
+    }
+
+    fn load_state(&self, path: String) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let state = SerializableState::from_file(Path::new(&path))
+                .map_err(jsonrpc_core::Error::invalid_params)?;
+            inner
+                .write()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?
+                .load_state(state)
+                .map_err(|err| {
+                    log::error!("failed loading state: {}", err);
+                    into_jsrpc_error(Web3Error::InternalError)
+                })?;
+            log::info!("👷 Loaded state from {}", path);
+            Ok(true)
+        })
+    }
+}
+
+/// Stops the running interval mining task, if any, and starts a new one unless `interval_ms` is 0.
+/// `inner` is the locked `node`.
+fn replace_interval_mining<S: Send + Sync + 'static + ForkSource + std::fmt::Debug>(
+    node: &Arc<RwLock<InMemoryNodeInner<S>>>,
+    inner: &mut InMemoryNodeInner<S>,
+    interval_ms: u64,
+) {
+    if let Some(task) = inner.interval_mining.take() {
+        task.abort();
+    }
+    if interval_ms == 0 {
+        log::info!("👷 Interval mining disabled");
+    } else {
+        inner.interval_mining = Some(spawn_interval_mining(Arc::clone(node), interval_ms));
+        log::info!("👷 Interval mining enabled, every {}ms", interval_ms);
+    }
+}
+
+/// Spawns a task mining the mempool every `interval_ms`, or sealing an empty block if it is empty.
+/// Blocks are sealed under the same lock used for transactions, and each empty block moves the
+/// clock forward by `interval_ms`.
+pub fn spawn_interval_mining<S: Send + Sync + 'static + ForkSource + std::fmt::Debug>(
+    node: Arc<RwLock<InMemoryNodeInner<S>>>,
+    interval_ms: u64,
+) -> tokio::task::JoinHandle<()> {
+    let node = InMemoryNode::from_inner(node);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(interval_ms));
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match node.mine_mempool() {
+                Ok(0) => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    log::error!(
+                        "failed mining the mempool, stopping interval mining: {}",
+                        err
+                    );
+                    return;
+                }
+            }
+            match node.get_inner().write() {
+                Ok(mut inner) => {
+                    if let Err(err) = mine_empty_blocks(&mut inner, 1, interval_ms) {
+                        log::error!("failed mining a block, stopping interval mining: {}", err);
+                        return;
+                    }
+                    log::info!("👷 Mined block #{}", inner.current_miniblock);
+                }
+                Err(_) => {
+                    log::error!("failed acquiring node lock, stopping interval mining");
+                    return;
+                }
+            }
+        }
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode};
+    use crate::{
+        eth_extra::{EthTestNodeNamespaceT, TransactionArgs},
+        http_fork_source::HttpForkSource,
+        node::InMemoryNode,
+        testing,
+    };
+    use std::str::FromStr;
+    use zksync_basic_types::U256;
     use zksync_core::api_server::web3::backend_jsonrpc::namespaces::eth::EthNamespaceT;
 
     use super::*;
@@ -152,7 +458,6 @@ mod tests {
             .read()
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
-        let expected_response = increase_value_seconds;
 
         let actual_response = evm
             .increase_time(increase_value_seconds)
@@ -164,7 +469,10 @@ mod tests {
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
 
-        assert_eq!(expected_response, actual_response, "erroneous response");
+        assert_eq!(
+            increase_value_seconds, actual_response,
+            "erroneous response"
+        );
         assert_eq!(
             increase_value_seconds.saturating_mul(1000u64),
             timestamp_after.saturating_sub(timestamp_before),
@@ -184,7 +492,6 @@ mod tests {
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
         assert_ne!(0, timestamp_before, "initial timestamp must be non zero",);
-        let expected_response = increase_value_seconds;
 
         let actual_response = evm
             .increase_time(increase_value_seconds)
@@ -196,7 +503,10 @@ mod tests {
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
 
-        assert_eq!(expected_response, actual_response, "erroneous response");
+        assert_eq!(
+            increase_value_seconds, actual_response,
+            "erroneous response"
+        );
         assert_eq!(
             u64::MAX,
             timestamp_after,
@@ -215,7 +525,6 @@ mod tests {
             .read()
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
-        let expected_response = increase_value_seconds;
 
         let actual_response = evm
             .increase_time(increase_value_seconds)
@@ -227,7 +536,10 @@ mod tests {
             .map(|inner| inner.current_timestamp)
             .expect("failed reading timestamp");
 
-        assert_eq!(expected_response, actual_response, "erroneous response");
+        assert_eq!(
+            increase_value_seconds, actual_response,
+            "erroneous response"
+        );
         assert_eq!(
             increase_value_seconds.saturating_mul(1000u64),
             timestamp_after.saturating_sub(timestamp_before),
@@ -235,6 +547,58 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_increase_time_returns_total_offset_and_applies_to_following_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let first_offset = evm.increase_time(10).await.expect("failed increasing time");
+        let second_offset = evm.increase_time(5).await.expect("failed increasing time");
+        let first_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let second_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        assert_eq!(10, first_offset);
+        assert_eq!(15, second_offset);
+        let first_block = node
+            .get_block_by_hash(first_block_hash, false)
+            .await
+            .expect("failed fetching block")
+            .expect("block was not found");
+        let second_block = node
+            .get_block_by_hash(second_block_hash, false)
+            .await
+            .expect("failed fetching block")
+            .expect("block was not found");
+        assert_eq!(U256::from(1000 + 15_000), first_block.timestamp);
+        assert_eq!(U256::from(1000 + 15_000 + 1), second_block.timestamp);
+    }
+
+    #[tokio::test]
+    async fn test_increase_time_rejects_negative_values() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(EvmNamespaceImpl::new(node.get_inner()).to_delegate());
+
+        let response = io
+            .handle_request(
+                r#"{"jsonrpc":"2.0","method":"evm_increaseTime","params":[-10],"id":1}"#,
+            )
+            .await
+            .expect("no response");
+
+        assert!(
+            response.contains("\"error\""),
+            "unexpected response: {}",
+            response
+        );
+        let timestamp = node
+            .get_inner()
+            .read()
+            .map(|inner| inner.current_timestamp)
+            .expect("failed reading timestamp");
+        assert_eq!(1000, timestamp);
+    }
+
     #[tokio::test]
     async fn test_set_next_block_timestamp_future() {
         let node = InMemoryNode::<HttpForkSource>::default();
@@ -287,7 +651,35 @@ mod tests {
 
         let result = evm.set_next_block_timestamp(timestamp_before).await;
 
-        assert!(result.is_err(), "expected an error for timestamp in past");
+        match result {
+            Err(err) => assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, err.code),
+            Ok(_) => panic!("expected an error for timestamp in past"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_next_block_timestamp_applies_to_next_block_only() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        evm.set_next_block_timestamp(10_000)
+            .await
+            .expect("failed setting timestamp");
+        let first_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let second_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        let first_block = node
+            .get_block_by_hash(first_block_hash, false)
+            .await
+            .expect("failed fetching block")
+            .expect("block was not found");
+        let second_block = node
+            .get_block_by_hash(second_block_hash, false)
+            .await
+            .expect("failed fetching block")
+            .expect("block was not found");
+        assert_eq!(U256::from(10_000), first_block.timestamp);
+        assert_eq!(U256::from(10_001), second_block.timestamp);
     }
 
     #[tokio::test]
@@ -456,8 +848,7 @@ mod tests {
             .await
             .unwrap()
             .expect("block exists");
-        let result = evm.evm_mine().await.expect("evm_mine");
-        assert_eq!(&result, "0x0");
+        let result = evm.evm_mine(None).await.expect("evm_mine");
 
         let current_block = node
             .get_block_by_number(zksync_types::api::BlockNumber::Latest, false)
@@ -465,11 +856,11 @@ mod tests {
             .unwrap()
             .expect("block exists");
 
+        assert_eq!(current_block.hash, result);
         assert_eq!(start_block.number + 1, current_block.number);
         assert_eq!(start_block.timestamp + 1000, current_block.timestamp);
 
-        let result = evm.evm_mine().await.expect("evm_mine");
-        assert_eq!(&result, "0x0");
+        let result = evm.evm_mine(None).await.expect("evm_mine");
 
         let current_block = node
             .get_block_by_number(zksync_types::api::BlockNumber::Latest, false)
@@ -477,7 +868,439 @@ mod tests {
             .unwrap()
             .expect("block exists");
 
+        assert_eq!(current_block.hash, result);
         assert_eq!(start_block.number + 2, current_block.number);
         assert_eq!(start_block.timestamp + 2000, current_block.timestamp);
     }
+
+    #[tokio::test]
+    async fn test_evm_mine_with_count() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let result = evm.evm_mine(Some(U64::from(3))).await.expect("evm_mine");
+
+        let mut latest_hash = H256::zero();
+        for number in 1..=3u64 {
+            let block = node
+                .get_block_by_number(zksync_types::api::BlockNumber::Number(number.into()), false)
+                .await
+                .unwrap()
+                .expect("block exists");
+            assert_eq!(U64::from(number), block.number);
+            latest_hash = block.hash;
+        }
+        assert_eq!(latest_hash, result);
+        assert!(evm.evm_mine(Some(U64::zero())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_evm_mine_links_parent_hashes() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let tx_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        evm.evm_mine(Some(U64::from(2))).await.expect("evm_mine");
+
+        let mut parent_hash = H256::zero();
+        for number in 1..=3u64 {
+            let block = node
+                .get_block_by_number(zksync_types::api::BlockNumber::Number(number.into()), false)
+                .await
+                .unwrap()
+                .expect("block exists");
+            assert_eq!(parent_hash, block.parent_hash);
+            parent_hash = block.hash;
+        }
+        let first_block = node
+            .get_block_by_number(zksync_types::api::BlockNumber::Number(1.into()), false)
+            .await
+            .unwrap()
+            .expect("block exists");
+        assert_eq!(tx_block_hash, first_block.hash);
+    }
+
+    #[tokio::test]
+    async fn test_evm_mine_mines_the_mempool_when_automine_is_disabled() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let from = zksync_basic_types::Address::from_str(crate::node::RICH_WALLETS[0].0).unwrap();
+        node.set_rich_account(from);
+        node.get_inner().write().unwrap().automine = false;
+
+        let mut hashes = vec![];
+        for _ in 0..2 {
+            let hash = node
+                .send_transaction(TransactionArgs {
+                    from,
+                    to: Some(zksync_basic_types::Address::random()),
+                    value: Some(U256::from(100)),
+                    ..Default::default()
+                })
+                .await
+                .expect("eth_sendTransaction failed");
+            hashes.push(hash);
+        }
+        assert_eq!(0, node.get_inner().read().unwrap().current_miniblock);
+        assert!(node
+            .get_transaction_receipt(hashes[0])
+            .await
+            .unwrap()
+            .is_none());
+        let pending = node.pending_transactions().await.unwrap();
+        assert_eq!(hashes, pending.iter().map(|tx| tx.hash).collect::<Vec<_>>());
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(
+                from,
+                Some(zksync_types::api::BlockIdVariant::BlockNumber(
+                    zksync_types::api::BlockNumber::Pending
+                ))
+            )
+            .await
+            .unwrap()
+        );
+
+        let mined_hash = evm.evm_mine(None).await.expect("evm_mine");
+        let block = node
+            .get_block_by_number(zksync_types::api::BlockNumber::Latest, false)
+            .await
+            .unwrap()
+            .expect("block exists");
+        assert_eq!(U64::from(1), block.number);
+        assert_eq!(block.hash, mined_hash);
+        assert_eq!(2, block.transactions.len());
+        for (index, hash) in hashes.into_iter().enumerate() {
+            let receipt = node
+                .get_transaction_receipt(hash)
+                .await
+                .unwrap()
+                .expect("receipt not found");
+            assert_eq!(Some(U64::from(1)), receipt.block_number);
+            let tx = node
+                .get_transaction_by_block_hash_and_index(block.hash, U64::from(index))
+                .await
+                .unwrap()
+                .expect("transaction not found");
+            assert_eq!(hash, tx.hash);
+            assert_eq!(Some(block.hash), tx.block_hash);
+            let tx = node
+                .get_transaction_by_hash(hash)
+                .await
+                .unwrap()
+                .expect("transaction not found");
+            assert_eq!(Some(U64::from(index)), tx.transaction_index);
+            assert_eq!(Some(block.hash), tx.block_hash);
+        }
+        assert!(node.pending_transactions().await.unwrap().is_empty());
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(from, None).await.unwrap()
+        );
+    }
+
+    // the clock is paused and moves forward only when every task is idle, so the intervals elapse
+    // deterministically during the sleeps
+    #[tokio::test(start_paused = true)]
+    async fn test_set_interval_mining_seals_blocks_until_disabled() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let current_miniblock = || {
+            node.get_inner()
+                .read()
+                .map(|inner| inner.current_miniblock)
+                .expect("failed reading block number")
+        };
+
+        assert!(evm.set_interval_mining(10).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mined_before_disable = current_miniblock();
+        assert!(mined_before_disable > 0, "no blocks were mined");
+
+        assert!(evm.set_interval_mining(0).await.unwrap());
+        let block_after_disable = current_miniblock();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(block_after_disable, current_miniblock());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_anvil_mining_modes_are_exclusive() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let current_miniblock = || {
+            node.get_inner()
+                .read()
+                .map(|inner| inner.current_miniblock)
+                .expect("failed reading block number")
+        };
+
+        evm.anvil_set_interval_mining(10).await.unwrap();
+        {
+            let inner = node.get_inner();
+            let inner = inner.read().unwrap();
+            assert!(!inner.automine);
+            assert!(inner.interval_mining.is_some());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(current_miniblock() > 0, "no blocks were mined");
+
+        evm.anvil_set_automine(true).await.unwrap();
+        {
+            let inner = node.get_inner();
+            let inner = inner.read().unwrap();
+            assert!(inner.automine);
+            assert!(inner.interval_mining.is_none());
+        }
+        let block_after_automine = current_miniblock();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(block_after_automine, current_miniblock());
+
+        evm.anvil_set_automine(false).await.unwrap();
+        assert!(!node.get_inner().read().unwrap().automine);
+    }
+
+    #[tokio::test]
+    async fn test_revert_restores_state_at_snapshot() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let account = zksync_basic_types::Address::random();
+        node.set_rich_account(account);
+        let balance_before = node.get_balance(account, None).await.unwrap();
+
+        let snapshot_id = evm.snapshot().await.expect("evm_snapshot");
+        assert_eq!(U64::from(1), snapshot_id);
+
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        evm.increase_time(100).await.expect("evm_increaseTime");
+        evm.evm_mine(Some(U64::from(2))).await.expect("evm_mine");
+        node.get_inner().write().unwrap().fork_storage.set_value(
+            zksync_types::utils::storage_key_for_eth_balance(&account),
+            H256::zero(),
+        );
+        assert_eq!(U256::zero(), node.get_balance(account, None).await.unwrap());
+
+        assert!(evm.revert_snapshot(snapshot_id).await.expect("evm_revert"));
+
+        assert_eq!(U64::zero(), node.get_block_number().await.unwrap());
+        assert_eq!(
+            balance_before,
+            node.get_balance(account, None).await.unwrap()
+        );
+        assert_eq!(0, node.get_inner().read().unwrap().time_offset);
+        assert!(node
+            .get_transaction_by_hash(H256::repeat_byte(0x01))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_revert_drops_transactions_queued_after_snapshot() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let from = zksync_basic_types::Address::from_str(crate::node::RICH_WALLETS[0].0).unwrap();
+        node.set_rich_account(from);
+        node.get_inner().write().unwrap().automine = false;
+        let pending = Some(zksync_types::api::BlockIdVariant::BlockNumber(
+            zksync_types::api::BlockNumber::Pending,
+        ));
+
+        let snapshot_id = evm.snapshot().await.expect("evm_snapshot");
+        let hash = node
+            .send_transaction(TransactionArgs {
+                from,
+                to: Some(zksync_basic_types::Address::random()),
+                value: Some(U256::from(100)),
+                ..Default::default()
+            })
+            .await
+            .expect("eth_sendTransaction failed");
+        assert_eq!(
+            U256::one(),
+            node.get_transaction_count(from, pending.clone())
+                .await
+                .unwrap()
+        );
+
+        assert!(evm.revert_snapshot(snapshot_id).await.expect("evm_revert"));
+
+        assert!(node.pending_transactions().await.unwrap().is_empty());
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(from, pending).await.unwrap()
+        );
+        evm.evm_mine(None).await.expect("evm_mine");
+        let block = node
+            .get_block_by_number(zksync_types::api::BlockNumber::Latest, false)
+            .await
+            .unwrap()
+            .expect("block exists");
+        assert!(block.transactions.is_empty());
+        assert!(node.get_transaction_receipt(hash).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_revert_invalidates_later_snapshots() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let first = evm.snapshot().await.expect("evm_snapshot");
+        evm.evm_mine(None).await.expect("evm_mine");
+        let second = evm.snapshot().await.expect("evm_snapshot");
+        assert!(second > first);
+
+        assert!(evm.revert_snapshot(first).await.expect("evm_revert"));
+        assert!(!evm.revert_snapshot(second).await.expect("evm_revert"));
+        assert!(!evm.revert_snapshot(U64::zero()).await.expect("evm_revert"));
+    }
+
+    #[tokio::test]
+    async fn test_revert_keeps_the_reverted_snapshot() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let snapshot_id = evm.snapshot().await.expect("evm_snapshot");
+        for _ in 0..2 {
+            evm.evm_mine(None).await.expect("evm_mine");
+            assert_eq!(U64::one(), node.get_block_number().await.unwrap());
+
+            assert!(evm.revert_snapshot(snapshot_id).await.expect("evm_revert"));
+            assert_eq!(U64::zero(), node.get_block_number().await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revert_keeps_earlier_snapshots_and_never_reuses_ids() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let first = evm.snapshot().await.expect("evm_snapshot");
+        evm.evm_mine(None).await.expect("evm_mine");
+        let second = evm.snapshot().await.expect("evm_snapshot");
+        evm.evm_mine(None).await.expect("evm_mine");
+        let third = evm.snapshot().await.expect("evm_snapshot");
+        evm.evm_mine(None).await.expect("evm_mine");
+
+        assert!(evm.revert_snapshot(second).await.expect("evm_revert"));
+        assert_eq!(U64::from(1), node.get_block_number().await.unwrap());
+        assert!(!evm.revert_snapshot(third).await.expect("evm_revert"));
+
+        let fourth = evm.snapshot().await.expect("evm_snapshot");
+        assert_eq!(U64::from(4), fourth);
+        evm.evm_mine(None).await.expect("evm_mine");
+
+        assert!(evm.revert_snapshot(first).await.expect("evm_revert"));
+        assert_eq!(U64::zero(), node.get_block_number().await.unwrap());
+        assert!(!evm.revert_snapshot(fourth).await.expect("evm_revert"));
+    }
+
+    #[tokio::test]
+    async fn test_load_state_restores_dumped_state_in_a_fresh_node() {
+        let dir = tempdir::TempDir::new("state").expect("failed creating temporary dir");
+        let path = dir.path().join("state.json").display().to_string();
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+        let account = zksync_basic_types::Address::random();
+        node.set_rich_account(account);
+        let block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        evm.increase_time(100).await.expect("evm_increaseTime");
+        let storage_key = zksync_types::StorageKey::new(
+            zksync_types::AccountTreeId::new(account),
+            H256::repeat_byte(0x02),
+        );
+        node.get_inner()
+            .write()
+            .unwrap()
+            .fork_storage
+            .set_value(storage_key, H256::repeat_byte(0x03));
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            inner.known_tokens.insert(account);
+            inner
+                .token_prices
+                .insert(account, bigdecimal::BigDecimal::from_str("1.5").unwrap());
+            inner.next_priority_op_id = 5;
+        }
+
+        assert!(evm.dump_state(path.clone()).await.expect("anvil_dumpState"));
+
+        let fresh_node = InMemoryNode::<HttpForkSource>::default();
+        let fresh_evm = EvmNamespaceImpl::new(fresh_node.get_inner());
+        let snapshot_id = fresh_evm.snapshot().await.expect("evm_snapshot");
+        assert!(fresh_evm
+            .load_state(path.clone())
+            .await
+            .expect("anvil_loadState"));
+
+        assert_eq!(
+            node.get_balance(account, None).await.unwrap(),
+            fresh_node.get_balance(account, None).await.unwrap()
+        );
+        assert_eq!(
+            node.get_storage(account, U256::from_big_endian(&[0x02; 32]), None)
+                .await
+                .unwrap(),
+            fresh_node
+                .get_storage(account, U256::from_big_endian(&[0x02; 32]), None)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            node.get_block_number().await.unwrap(),
+            fresh_node.get_block_number().await.unwrap()
+        );
+        assert!(fresh_node
+            .get_block_by_hash(block_hash, false)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            node.get_transaction_receipt(H256::repeat_byte(0x01))
+                .await
+                .unwrap()
+                .map(|receipt| (receipt.block_number, receipt.status, receipt.gas_used)),
+            fresh_node
+                .get_transaction_receipt(H256::repeat_byte(0x01))
+                .await
+                .unwrap()
+                .map(|receipt| (receipt.block_number, receipt.status, receipt.gas_used))
+        );
+        assert!(!fresh_evm
+            .revert_snapshot(snapshot_id)
+            .await
+            .expect("evm_revert"));
+        let inner = node.get_inner();
+        let fresh_inner = fresh_node.get_inner();
+        let (inner, fresh_inner) = (inner.read().unwrap(), fresh_inner.read().unwrap());
+        assert_eq!(inner.current_timestamp, fresh_inner.current_timestamp);
+        assert_eq!(inner.time_offset, fresh_inner.time_offset);
+        assert_eq!(inner.known_tokens, fresh_inner.known_tokens);
+        assert_eq!(inner.token_prices, fresh_inner.token_prices);
+        assert_eq!(5, fresh_inner.next_priority_op_id);
+        assert_eq!(
+            inner
+                .tx_results
+                .keys()
+                .collect::<std::collections::HashSet<_>>(),
+            fresh_inner
+                .tx_results
+                .keys()
+                .collect::<std::collections::HashSet<_>>()
+        );
+        assert_eq!(
+            inner.dump_state().unwrap(),
+            fresh_inner.dump_state().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_state_rejects_missing_file() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let evm = EvmNamespaceImpl::new(node.get_inner());
+
+        let result = evm.load_state("/nonexistent/state.json".to_string()).await;
+
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,427 @@
+//! Log filters and the registry of filters installed with the `eth_new*Filter` methods.
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use zksync_basic_types::{H160, H256, U256};
+use zksync_types::api::{BlockNumber, Log};
+use zksync_web3_decl::types::{Filter, FilterChanges};
+
+use crate::utils::resolve_block_number;
+
+/// Maximum number of indexed topics an event can carry.
+const MAX_TOPICS: usize = 4;
+
+/// A log filter matching on the block range, emitting addresses and topics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    /// First block (inclusive) to return logs from.
+    pub from_block: BlockNumber,
+    /// Last block (inclusive) to return logs from.
+    pub to_block: BlockNumber,
+    /// Contract addresses the log must originate from. Empty matches any address.
+    pub addresses: Vec<H160>,
+    /// Topic sets for every position. `None` (or an empty set) matches any topic.
+    pub topics: [Option<HashSet<H256>>; MAX_TOPICS],
+    /// The single block to return logs from (EIP-234). Takes precedence over the block range.
+    pub block_hash: Option<H256>,
+}
+
+impl LogFilter {
+    /// Creates a new [LogFilter] from the JSON-RPC `filter` object.
+    ///
+    /// Missing `fromBlock`/`toBlock` default to `latest`, and are ignored if `blockHash` is set.
+    pub fn new(filter: Filter) -> Self {
+        let addresses = filter.address.map(|address| address.0).unwrap_or_default();
+
+        let mut topics: [Option<HashSet<H256>>; MAX_TOPICS] = Default::default();
+        for (position, topic_set) in filter
+            .topics
+            .unwrap_or_default()
+            .into_iter()
+            .take(MAX_TOPICS)
+            .enumerate()
+        {
+            topics[position] = topic_set.map(|set| set.0.into_iter().collect());
+        }
+
+        LogFilter {
+            from_block: filter.from_block.unwrap_or(BlockNumber::Latest),
+            to_block: filter.to_block.unwrap_or(BlockNumber::Latest),
+            addresses,
+            topics,
+            block_hash: filter.block_hash,
+        }
+    }
+
+    /// Returns true if the given block number falls in the filter's range,
+    /// with block tags resolved against `latest_block`.
+    pub fn matches_block(&self, block_number: u64, latest_block: u64) -> bool {
+        resolve_block_number(self.from_block, latest_block) <= block_number
+            && block_number <= resolve_block_number(self.to_block, latest_block)
+    }
+
+    /// Returns true if the log satisfies the block range, address and topic constraints.
+    pub fn matches(&self, log: &Log, latest_block: u64) -> bool {
+        match self.block_hash {
+            Some(block_hash) if log.block_hash != Some(block_hash) => return false,
+            Some(_) => {}
+            None => {
+                let block_number = log.block_number.unwrap_or_default().as_u64();
+                if !self.matches_block(block_number, latest_block) {
+                    return false;
+                }
+            }
+        }
+
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+
+        self.topics
+            .iter()
+            .enumerate()
+            .all(|(position, topic_set)| match topic_set {
+                Some(set) if !set.is_empty() => log
+                    .topics
+                    .get(position)
+                    .map_or(false, |topic| set.contains(topic)),
+                _ => true,
+            })
+    }
+}
+
+/// Filters that were not polled for this long are uninstalled.
+pub const FILTER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The kinds of filters that can be installed with the `eth_new*Filter` methods,
+/// together with the updates collected since the last poll.
+#[derive(Debug, Clone)]
+pub enum FilterType {
+    /// Collects the logs matching the filter.
+    Log {
+        filter: LogFilter,
+        updates: Vec<Log>,
+    },
+    /// Collects the hashes of the new blocks.
+    Block { updates: Vec<H256> },
+    /// Collects the hashes of the new transactions.
+    PendingTransaction { updates: Vec<H256> },
+}
+
+#[derive(Debug, Clone)]
+struct InstalledFilter {
+    filter_type: FilterType,
+    last_polled: Instant,
+}
+
+/// Registry of the filters installed through the `eth_new*Filter` methods.
+///
+/// All the filter kinds share the same id space. New logs, blocks and transactions are pushed
+/// into every matching filter and drained when the filter is polled with `eth_getFilterChanges`.
+/// Filters that are not polled within [FILTER_TIMEOUT] are removed, so abandoned filters don't
+/// keep accumulating updates.
+#[derive(Debug, Default, Clone)]
+pub struct EthFilters {
+    id_counter: U256,
+    filters: HashMap<U256, InstalledFilter>,
+}
+
+impl EthFilters {
+    /// Installs a new log filter and returns its id.
+    pub fn add_log_filter(&mut self, filter: Filter) -> U256 {
+        self.add_filter(FilterType::Log {
+            filter: LogFilter::new(filter),
+            updates: vec![],
+        })
+    }
+
+    /// Installs a new block filter and returns its id.
+    pub fn add_block_filter(&mut self) -> U256 {
+        self.add_filter(FilterType::Block { updates: vec![] })
+    }
+
+    /// Installs a new pending transaction filter and returns its id.
+    pub fn add_pending_transaction_filter(&mut self) -> U256 {
+        self.add_filter(FilterType::PendingTransaction { updates: vec![] })
+    }
+
+    fn add_filter(&mut self, filter_type: FilterType) -> U256 {
+        self.remove_expired_filters();
+
+        self.id_counter += U256::one();
+        self.filters.insert(
+            self.id_counter,
+            InstalledFilter {
+                filter_type,
+                last_polled: Instant::now(),
+            },
+        );
+        self.id_counter
+    }
+
+    /// Uninstalls the filter with the given id. Returns false if no such filter exists.
+    pub fn remove_filter(&mut self, id: U256) -> bool {
+        self.filters.remove(&id).is_some()
+    }
+
+    /// Returns the changes of the given filter since the last poll, or `None` if
+    /// the filter does not exist. Log filters return logs, the other filters return hashes.
+    pub fn get_new_changes(&mut self, id: U256) -> Option<FilterChanges> {
+        self.remove_expired_filters();
+
+        let filter = self.filters.get_mut(&id)?;
+        filter.last_polled = Instant::now();
+        let changes = match &mut filter.filter_type {
+            FilterType::Log { updates, .. } => FilterChanges::Logs(std::mem::take(updates)),
+            FilterType::Block { updates } | FilterType::PendingTransaction { updates } => {
+                FilterChanges::Hashes(std::mem::take(updates))
+            }
+        };
+        Some(changes)
+    }
+
+    /// Returns the log filter with the given id, or `None` if the filter does not exist or is not
+    /// a log filter. Like polling, this keeps the filter from expiring.
+    pub fn get_log_filter(&mut self, id: U256) -> Option<LogFilter> {
+        self.remove_expired_filters();
+
+        let filter = self.filters.get_mut(&id)?;
+        filter.last_polled = Instant::now();
+        match &filter.filter_type {
+            FilterType::Log { filter, .. } => Some(filter.clone()),
+            _ => None,
+        }
+    }
+
+    /// Notifies the installed filters about a log produced in the latest block.
+    pub fn notify_new_log(&mut self, log: &Log, latest_block: u64) {
+        for installed in self.filters.values_mut() {
+            if let FilterType::Log { filter, updates } = &mut installed.filter_type {
+                if filter.matches(log, latest_block) {
+                    updates.push(log.clone());
+                }
+            }
+        }
+    }
+
+    /// Notifies the installed filters about a new block.
+    pub fn notify_new_block(&mut self, hash: H256) {
+        for filter in self.filters.values_mut() {
+            if let FilterType::Block { updates } = &mut filter.filter_type {
+                updates.push(hash);
+            }
+        }
+    }
+
+    /// Notifies the installed filters about a new transaction.
+    pub fn notify_new_pending_transaction(&mut self, hash: H256) {
+        for filter in self.filters.values_mut() {
+            if let FilterType::PendingTransaction { updates } = &mut filter.filter_type {
+                updates.push(hash);
+            }
+        }
+    }
+
+    fn remove_expired_filters(&mut self) {
+        self.filters
+            .retain(|_, filter| filter.last_polled.elapsed() < FILTER_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zksync_basic_types::U64;
+    use zksync_web3_decl::types::ValueOrArray;
+
+    fn log_at(block_number: u64, address: H160, topics: Vec<H256>) -> Log {
+        Log {
+            address,
+            topics,
+            data: Default::default(),
+            block_hash: Some(H256::repeat_byte(0xab)),
+            block_number: Some(U64::from(block_number)),
+            l1_batch_number: None,
+            transaction_hash: Some(H256::repeat_byte(0xcd)),
+            transaction_index: Some(U64::zero()),
+            log_index: Some(Default::default()),
+            transaction_log_index: Some(Default::default()),
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_matches_latest_block_only() {
+        let filter = LogFilter::new(Filter::default());
+
+        assert!(filter.matches(&log_at(5, H160::random(), vec![]), 5));
+        assert!(!filter.matches(&log_at(4, H160::random(), vec![]), 5));
+    }
+
+    #[test]
+    fn test_earliest_to_latest_range() {
+        let filter = LogFilter::new(Filter {
+            from_block: Some(BlockNumber::Earliest),
+            to_block: Some(BlockNumber::Latest),
+            ..Default::default()
+        });
+
+        assert!(filter.matches(&log_at(0, H160::random(), vec![]), 5));
+        assert!(filter.matches(&log_at(5, H160::random(), vec![]), 5));
+        assert!(!filter.matches(&log_at(6, H160::random(), vec![]), 5));
+    }
+
+    #[test]
+    fn test_block_hash_filter_ignores_the_block_range() {
+        let filter = LogFilter::new(Filter {
+            from_block: Some(BlockNumber::Latest),
+            block_hash: Some(H256::repeat_byte(0xab)),
+            ..Default::default()
+        });
+        let mut other_block_log = log_at(1, H160::random(), vec![]);
+        other_block_log.block_hash = Some(H256::repeat_byte(0xcd));
+
+        assert!(filter.matches(&log_at(1, H160::random(), vec![]), 5));
+        assert!(!filter.matches(&other_block_log, 1));
+    }
+
+    #[test]
+    fn test_address_filter_accepts_any_listed_address() {
+        let address_a = H160::repeat_byte(0x1);
+        let address_b = H160::repeat_byte(0x2);
+        let filter = LogFilter::new(Filter {
+            address: Some(ValueOrArray(vec![address_a, address_b])),
+            ..Default::default()
+        });
+
+        assert!(filter.matches(&log_at(1, address_a, vec![]), 1));
+        assert!(filter.matches(&log_at(1, address_b, vec![]), 1));
+        assert!(!filter.matches(&log_at(1, H160::repeat_byte(0x3), vec![]), 1));
+    }
+
+    #[test]
+    fn test_topic_filter_with_wildcards_and_or_sets() {
+        let topic_a = H256::repeat_byte(0xa);
+        let topic_b = H256::repeat_byte(0xb);
+        let topic_c = H256::repeat_byte(0xc);
+        let filter = LogFilter::new(Filter {
+            topics: Some(vec![None, Some(ValueOrArray(vec![topic_b, topic_c]))]),
+            ..Default::default()
+        });
+
+        assert!(filter.matches(&log_at(1, H160::random(), vec![topic_a, topic_b]), 1));
+        assert!(filter.matches(&log_at(1, H160::random(), vec![topic_b, topic_c]), 1));
+        assert!(!filter.matches(&log_at(1, H160::random(), vec![topic_b, topic_a]), 1));
+        assert!(!filter.matches(&log_at(1, H160::random(), vec![topic_a]), 1));
+    }
+
+    #[test]
+    fn test_empty_topic_sets_match_everything() {
+        let filter = LogFilter::new(Filter {
+            topics: Some(vec![Some(ValueOrArray(vec![]))]),
+            ..Default::default()
+        });
+        assert!(filter.matches(&log_at(1, H160::random(), vec![]), 1));
+
+        let filter = LogFilter::new(Filter {
+            topics: Some(vec![]),
+            ..Default::default()
+        });
+        assert!(filter.matches(&log_at(1, H160::random(), vec![H256::random()]), 1));
+    }
+
+    #[test]
+    fn test_filter_ids_are_monotonic() {
+        let mut filters = EthFilters::default();
+
+        let first = filters.add_log_filter(Filter::default());
+        let second = filters.add_log_filter(Filter::default());
+        assert!(filters.remove_filter(second));
+        let third = filters.add_log_filter(Filter::default());
+
+        assert_eq!(U256::from(1), first);
+        assert_eq!(U256::from(2), second);
+        assert_eq!(U256::from(3), third);
+    }
+
+    #[test]
+    fn test_get_new_changes_drains_updates() {
+        let mut filters = EthFilters::default();
+        let address = H160::repeat_byte(0x1);
+        let id = filters.add_log_filter(Filter {
+            address: Some(ValueOrArray(vec![address])),
+            ..Default::default()
+        });
+
+        filters.notify_new_log(&log_at(1, address, vec![]), 1);
+        filters.notify_new_log(&log_at(1, H160::repeat_byte(0x2), vec![]), 1);
+
+        match filters.get_new_changes(id) {
+            Some(FilterChanges::Logs(logs)) => assert_eq!(vec![log_at(1, address, vec![])], logs),
+            _ => panic!("expected logs"),
+        }
+        match filters.get_new_changes(id) {
+            Some(FilterChanges::Logs(logs)) => assert!(logs.is_empty()),
+            _ => panic!("expected logs"),
+        }
+    }
+
+    #[test]
+    fn test_removed_filter_has_no_changes() {
+        let mut filters = EthFilters::default();
+        let id = filters.add_log_filter(Filter::default());
+
+        assert!(filters.remove_filter(id));
+        assert!(!filters.remove_filter(id));
+        assert!(filters.get_new_changes(id).is_none());
+    }
+
+    #[test]
+    fn test_unpolled_filters_expire() {
+        let mut filters = EthFilters::default();
+        let expired = filters.add_log_filter(Filter::default());
+        let active = filters.add_log_filter(Filter::default());
+        filters
+            .filters
+            .get_mut(&expired)
+            .expect("filter not installed")
+            .last_polled = Instant::now() - FILTER_TIMEOUT;
+
+        assert!(filters.get_new_changes(expired).is_none());
+        assert!(filters.get_new_changes(active).is_some());
+    }
+
+    #[test]
+    fn test_block_and_pending_transaction_filters_return_hashes() {
+        let mut filters = EthFilters::default();
+        let log_filter = filters.add_log_filter(Filter::default());
+        let block_filter = filters.add_block_filter();
+        let tx_filter = filters.add_pending_transaction_filter();
+        let block_hash = H256::repeat_byte(0x1);
+        let tx_hash = H256::repeat_byte(0x2);
+
+        filters.notify_new_pending_transaction(tx_hash);
+        filters.notify_new_block(block_hash);
+        filters.notify_new_log(&log_at(1, H160::random(), vec![]), 1);
+
+        match filters.get_new_changes(block_filter) {
+            Some(FilterChanges::Hashes(hashes)) => assert_eq!(vec![block_hash], hashes),
+            _ => panic!("expected hashes"),
+        }
+        match filters.get_new_changes(tx_filter) {
+            Some(FilterChanges::Hashes(hashes)) => assert_eq!(vec![tx_hash], hashes),
+            _ => panic!("expected hashes"),
+        }
+        match filters.get_new_changes(log_filter) {
+            Some(FilterChanges::Logs(logs)) => assert_eq!(1, logs.len()),
+            _ => panic!("expected logs"),
+        }
+        match filters.get_new_changes(block_filter) {
+            Some(FilterChanges::Hashes(hashes)) => assert!(hashes.is_empty()),
+            _ => panic!("expected hashes"),
+        }
+    }
+}
@@ -4,17 +4,20 @@
 //! And ForkDetails - that parses network address and fork height from arguments.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryInto,
     future::Future,
     sync::{Arc, RwLock},
 };
 
+use futures::future::BoxFuture;
 use tokio::runtime::Builder;
 use zksync_basic_types::{Address, L1BatchNumber, L2ChainId, MiniblockNumber, H256, U256, U64};
 
 use zksync_types::{
-    api::{Block, BlockIdVariant, BlockNumber, Transaction, TransactionVariant},
+    api::{
+        Block, BlockIdVariant, BlockNumber, Transaction, TransactionReceipt, TransactionVariant,
+    },
     l2::L2Tx,
     StorageKey,
 };
@@ -27,7 +30,7 @@ use zksync_web3_decl::{jsonrpsee::http_client::HttpClientBuilder, namespaces::Zk
 
 use crate::{cache::CacheConfig, node::TEST_NODE_NETWORK_ID};
 use crate::{deps::InMemoryStorage, http_fork_source::HttpForkSource};
-use crate::{deps::ReadStorage as RS, system_contracts};
+use crate::{deps::ReadStorage as RS, proof::StorageTree, system_contracts};
 
 pub fn block_on<F: Future + Send + 'static>(future: F) -> F::Output
 where
@@ -64,32 +67,64 @@ pub struct ForkStorageInner<S> {
     // If set - it hold the necessary information on where to fetch the data.
     // If not set - it will simply read from underlying storage.
     pub fork: Option<ForkDetails<S>>,
+    // Merkle tree over the local storage, brought up to date by `update_storage_tree`.
+    pub storage_tree: StorageTree,
+    // Keys written to the local storage since the tree was last updated.
+    pub modified_keys: HashSet<StorageKey>,
+}
+
+impl<S> ForkStorageInner<S> {
+    /// Replaces the local storage. The storage tree is rebuilt the next time it is updated.
+    pub fn replace_raw_storage(&mut self, raw_storage: InMemoryStorage) {
+        self.raw_storage = raw_storage;
+        self.storage_tree = Default::default();
+        self.modified_keys = self.raw_storage.state.keys().copied().collect();
+    }
+
+    /// Rehashes the storage tree for the keys written since the last update, and returns it.
+    pub fn update_storage_tree(&mut self) -> &StorageTree {
+        for key in std::mem::take(&mut self.modified_keys) {
+            let value = self
+                .raw_storage
+                .state
+                .get(&key)
+                .copied()
+                .unwrap_or_default();
+            self.storage_tree.update(key.hashed_key(), value);
+        }
+        &self.storage_tree
+    }
 }
 
 impl<S: ForkSource> ForkStorage<S> {
+    /// Creates the storage on top of the `fork`, with the given system contracts deployed.
+    /// Fails if the system contracts can't be loaded.
     pub fn new(
         fork: Option<ForkDetails<S>>,
         system_contracts_options: &system_contracts::Options,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let chain_id = fork
             .as_ref()
             .and_then(|d| d.overwrite_chain_id)
             .unwrap_or(L2ChainId(TEST_NODE_NETWORK_ID));
         log::info!("Starting network with chain id: {:?}", chain_id);
 
-        ForkStorage {
+        let raw_storage = InMemoryStorage::with_system_contracts_and_chain_id(
+            chain_id,
+            hash_bytecode,
+            system_contracts_options,
+        )?;
+        Ok(ForkStorage {
             inner: Arc::new(RwLock::new(ForkStorageInner {
-                raw_storage: InMemoryStorage::with_system_contracts_and_chain_id(
-                    chain_id,
-                    hash_bytecode,
-                    system_contracts_options,
-                ),
+                modified_keys: raw_storage.state.keys().copied().collect(),
+                raw_storage,
                 value_read_cache: Default::default(),
                 fork,
                 factory_dep_cache: Default::default(),
+                storage_tree: Default::default(),
             })),
             chain_id,
-        }
+        })
     }
 
     fn read_value_internal(&self, key: &StorageKey) -> zksync_types::StorageValue {
@@ -97,7 +132,8 @@ impl<S: ForkSource> ForkStorage<S> {
         let local_storage = mutator.raw_storage.read_value(key);
 
         if let Some(fork) = &mutator.fork {
-            if !H256::is_zero(&local_storage) {
+            // values written locally (even zeroes) take precedence over the fork
+            if !H256::is_zero(&local_storage) || mutator.raw_storage.state.contains_key(key) {
                 return local_storage;
             }
 
@@ -175,9 +211,52 @@ impl<S: std::fmt::Debug + ForkSource> ReadStorage for &ForkStorage<S> {
     }
 }
 
+/// Read-only view of the forked network state as of a block at or below the fork point.
+/// Local changes are ignored, only bytecodes are shared with the fork storage.
+#[derive(Debug)]
+pub struct ForkStorageAtBlock<'a, S> {
+    storage: &'a ForkStorage<S>,
+    miniblock: u64,
+}
+
+impl<'a, S> ForkStorageAtBlock<'a, S> {
+    pub fn new(storage: &'a ForkStorage<S>, miniblock: u64) -> Self {
+        Self { storage, miniblock }
+    }
+}
+
+impl<'a, S: std::fmt::Debug + ForkSource> ReadStorage for ForkStorageAtBlock<'a, S> {
+    fn read_value(&mut self, key: &StorageKey) -> zksync_types::StorageValue {
+        let mutator = self.storage.inner.read().unwrap();
+        match &mutator.fork {
+            Some(fork) => fork
+                .fork_source
+                .get_storage_at(
+                    *key.account().address(),
+                    h256_to_u256(*key.key()),
+                    Some(BlockIdVariant::BlockNumber(BlockNumber::Number(U64::from(
+                        self.miniblock,
+                    )))),
+                )
+                .unwrap(),
+            None => H256::zero(),
+        }
+    }
+
+    fn is_write_initial(&mut self, key: &StorageKey) -> bool {
+        let mut mutator = self.storage.inner.write().unwrap();
+        mutator.raw_storage.is_write_initial(key)
+    }
+
+    fn load_factory_dep(&mut self, hash: H256) -> Option<Vec<u8>> {
+        self.storage.load_factory_dep_internal(hash)
+    }
+}
+
 impl<S> ForkStorage<S> {
     pub fn set_value(&mut self, key: StorageKey, value: zksync_types::StorageValue) {
         let mut mutator = self.inner.write().unwrap();
+        mutator.modified_keys.insert(key);
         mutator.raw_storage.set_value(key, value)
     }
     pub fn store_factory_dep(&mut self, hash: H256, bytecode: Vec<u8>) {
@@ -203,6 +282,9 @@ pub trait ForkSource {
     /// Returns the transaction for a given hash.
     fn get_transaction_by_hash(&self, hash: H256) -> eyre::Result<Option<Transaction>>;
 
+    /// Returns the receipt of the transaction with the given hash.
+    fn get_transaction_receipt(&self, hash: H256) -> eyre::Result<Option<TransactionReceipt>>;
+
     /// Gets all transactions that belong to a given miniblock.
     fn get_raw_block_transactions(
         &self,
@@ -231,6 +313,45 @@ pub trait ForkSource {
         &self,
         block_number: zksync_types::api::BlockNumber,
     ) -> eyre::Result<Option<U256>>;
+
+    /// Returns information about a transaction by block hash and transaction index position.
+    fn get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: zksync_basic_types::web3::types::Index,
+    ) -> eyre::Result<Option<Transaction>>;
+
+    /// Returns information about a transaction by block number and transaction index position.
+    fn get_transaction_by_block_number_and_index(
+        &self,
+        block_number: zksync_types::api::BlockNumber,
+        index: zksync_basic_types::web3::types::Index,
+    ) -> eyre::Result<Option<Transaction>>;
+
+    /// Returns the balances of all the tokens held by an account, keyed by token address.
+    fn get_all_account_balances(&self, address: Address) -> eyre::Result<HashMap<Address, U256>>;
+
+    /// Returns a page of the tokens confirmed on the network, sorted by symbol.
+    fn get_confirmed_tokens(
+        &self,
+        from: u32,
+        limit: u8,
+    ) -> eyre::Result<Vec<zksync_web3_decl::types::Token>>;
+
+    /// Returns the USD price of a token.
+    fn get_token_price(&self, token: Address) -> eyre::Result<bigdecimal::BigDecimal>;
+
+    /// Returns the zkSync specific details of a block.
+    fn get_block_details(
+        &self,
+        miniblock: MiniblockNumber,
+    ) -> eyre::Result<Option<zksync_types::api::BlockDetails>>;
+
+    /// Returns the zkSync specific details of a transaction.
+    fn get_transaction_details(
+        &self,
+        hash: H256,
+    ) -> eyre::Result<Option<zksync_types::api::TransactionDetails>>;
 }
 
 /// Holds the information about the original chain.
@@ -250,6 +371,34 @@ pub struct ForkDetails<S> {
     pub l1_gas_price: u64,
 }
 
+/// Fork sources that can be created from the URL of a network, used by `hardhat_reset` to fork
+/// another network.
+pub trait ForkFactory: ForkSource + Sized {
+    /// Forks the network at `url`, at `block_number` or at its latest block. `current` is the
+    /// source of the fork being replaced, if any, whose settings are reused.
+    fn create_fork(
+        url: &str,
+        block_number: Option<u64>,
+        current: Option<&Self>,
+    ) -> BoxFuture<'static, Result<ForkDetails<Self>, String>>;
+}
+
+impl ForkFactory for HttpForkSource {
+    fn create_fork(
+        url: &str,
+        block_number: Option<u64>,
+        current: Option<&Self>,
+    ) -> BoxFuture<'static, Result<ForkDetails<Self>, String>> {
+        let cache_config = match current.map(|current| current.cache_config_for(url)) {
+            Some(Ok(cache_config)) => cache_config,
+            Some(Err(err)) => return Box::pin(async move { Err(err) }),
+            None => CacheConfig::Memory,
+        };
+        let url = url.to_owned();
+        Box::pin(async move { ForkDetails::from_network(&url, block_number, cache_config).await })
+    }
+}
+
 impl ForkDetails<HttpForkSource> {
     pub async fn from_url_and_miniblock_and_chain(
         url: &str,
@@ -257,28 +406,28 @@ impl ForkDetails<HttpForkSource> {
         miniblock: u64,
         chain_id: Option<L2ChainId>,
         cache_config: CacheConfig,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let block_details = client
             .get_block_details(MiniblockNumber(miniblock as u32))
             .await
-            .unwrap()
-            .unwrap_or_else(|| panic!("Could not find block {:?} in {:?}", miniblock, url));
+            .map_err(|err| format!("failed fetching block {} from {}: {}", miniblock, url, err))?
+            .ok_or_else(|| format!("Could not find block {:?} in {:?}", miniblock, url))?;
 
         let root_hash = block_details
             .base
             .root_hash
-            .unwrap_or_else(|| panic!("fork block #{} missing root hash", miniblock));
+            .ok_or_else(|| format!("fork block #{} missing root hash", miniblock))?;
         let block = client
             .get_block_by_hash(root_hash, true)
             .await
             .ok()
             .flatten()
-            .unwrap_or_else(|| {
-                panic!(
+            .ok_or_else(|| {
+                format!(
                     "Could not find block #{:?} ({:#x}) in {:?}",
                     miniblock, root_hash, url
                 )
-            });
+            })?;
         let l1_batch_number = block_details.l1_batch_number;
 
         log::info!(
@@ -286,7 +435,7 @@ impl ForkDetails<HttpForkSource> {
             url, l1_batch_number, miniblock, block_details.base.timestamp, block_details.base.l1_gas_price,
         );
 
-        ForkDetails {
+        Ok(ForkDetails {
             fork_source: HttpForkSource::new(url.to_owned(), cache_config),
             l1_block: l1_batch_number,
             l2_block: block,
@@ -295,26 +444,47 @@ impl ForkDetails<HttpForkSource> {
             l2_miniblock_hash: root_hash,
             overwrite_chain_id: chain_id,
             l1_gas_price: block_details.base.l1_gas_price,
-        }
+        })
     }
     /// Create a fork from a given network at a given height.
-    pub async fn from_network(fork: &str, fork_at: Option<u64>, cache_config: CacheConfig) -> Self {
-        let (url, client) = Self::fork_to_url_and_client(fork);
+    pub async fn from_network(
+        fork: &str,
+        fork_at: Option<u64>,
+        cache_config: CacheConfig,
+    ) -> Result<Self, String> {
+        let (url, client) = Self::fork_to_url_and_client(fork)?;
         let l2_miniblock = if let Some(fork_at) = fork_at {
             fork_at
         } else {
-            client.get_block_number().await.unwrap().as_u64()
+            client
+                .get_block_number()
+                .await
+                .map_err(|err| format!("failed fetching the latest block from {}: {}", url, err))?
+                .as_u64()
         };
         Self::from_url_and_miniblock_and_chain(url, client, l2_miniblock, None, cache_config).await
     }
 
     /// Create a fork from a given network, at a height BEFORE a transaction.
     /// This will allow us to apply this transaction locally on top of this fork.
-    pub async fn from_network_tx(fork: &str, tx: H256, cache_config: CacheConfig) -> Self {
-        let (url, client) = Self::fork_to_url_and_client(fork);
-        let tx_details = client.get_transaction_by_hash(tx).await.unwrap().unwrap();
+    pub async fn from_network_tx(
+        fork: &str,
+        tx: H256,
+        cache_config: CacheConfig,
+    ) -> Result<Self, String> {
+        let (url, client) = Self::fork_to_url_and_client(fork)?;
+        let tx_details = client
+            .get_transaction_by_hash(tx)
+            .await
+            .map_err(|err| format!("failed fetching transaction {:?} from {}: {}", tx, url, err))?
+            .ok_or_else(|| format!("Could not find transaction {:?} in {:?}", tx, url))?;
         let overwrite_chain_id = Some(L2ChainId(tx_details.chain_id.as_u32() as u16));
-        let miniblock_number = MiniblockNumber(tx_details.block_number.unwrap().as_u32());
+        let miniblock_number = MiniblockNumber(
+            tx_details
+                .block_number
+                .ok_or_else(|| format!("transaction {:?} is not in a block yet", tx))?
+                .as_u32(),
+        );
         // We have to sync to the one-miniblock before the one where transaction is.
         let l2_miniblock = miniblock_number.saturating_sub(1) as u64;
 
@@ -331,18 +501,23 @@ impl ForkDetails<HttpForkSource> {
 
 impl<S: ForkSource> ForkDetails<S> {
     /// Return URL and HTTP client for a given fork name.
-    pub fn fork_to_url_and_client(fork: &str) -> (&str, HttpClient) {
-        let url = match fork {
-            "mainnet" => "https://mainnet.era.zksync.io:443",
-            "testnet" => "https://testnet.era.zksync.dev:443",
-            _ => fork,
-        };
+    pub fn fork_to_url_and_client(fork: &str) -> Result<(&str, HttpClient), String> {
+        let url = Self::fork_to_url(fork);
 
         let client = HttpClientBuilder::default()
             .build(url)
-            .expect("Unable to create a client for fork");
+            .map_err(|err| format!("Unable to create a client for fork {}: {}", url, err))?;
+
+        Ok((url, client))
+    }
 
-        (url, client)
+    /// Return the URL of a given fork name.
+    pub fn fork_to_url(fork: &str) -> &str {
+        match fork {
+            "mainnet" => "https://mainnet.era.zksync.io:443",
+            "testnet" => "https://testnet.era.zksync.dev:443",
+            _ => fork,
+        }
     }
 
     /// Returns transactions that are in the same L2 miniblock as replay_tx, but were executed before it.
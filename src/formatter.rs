@@ -1,19 +1,33 @@
 //! Helper methods to display transaction data in more human readable way.
-use crate::{node::ShowCalls, resolver};
+use crate::{
+    node::{FormatterOutput, ShowCalls},
+    resolver,
+};
 
 use colored::Colorize;
-use serde::Deserialize;
-use std::collections::HashMap;
+use ethabi::Contract;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
 
 use crate::fork::block_on;
-use zksync_basic_types::H160;
+use zksync_basic_types::{H160, H256, U256};
 
 use vm::vm::VmPartialExecutionResult;
-use zksync_types::{vm_trace::Call, StorageLogQuery, StorageLogQueryType, VmEvent};
+use zksync_types::{
+    l2_to_l1_log::L2ToL1Log, vm_trace::Call, StorageLogQuery, StorageLogQueryType, VmEvent,
+};
 
 use lazy_static::lazy_static;
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+/// Gas used by a single call above which it is highlighted in the printed call traces.
+pub const DEFAULT_GAS_HIGHLIGHT_THRESHOLD: u32 = 100_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum ContractType {
     System,
     Precompile,
@@ -26,141 +40,698 @@ pub struct KnownAddress {
     address: H160,
     name: String,
     contract_type: ContractType,
+    /// Path to the ABI JSON file of the contract, used to decode the call parameters.
+    /// Relative paths are resolved against the `--abi-dir` (if set).
+    #[serde(default)]
+    abi_path: Option<String>,
+}
+
+impl KnownAddress {
+    /// Returns the entry in the `[address, name, contract_type, abi_path]` format of the address map files.
+    fn to_json(&self) -> serde_json::Value {
+        let mut entry = serde_json::json!([
+            format!("{:#x}", self.address),
+            self.name,
+            self.contract_type
+        ]);
+        if let (Some(abi_path), Some(entry)) = (&self.abi_path, entry.as_array_mut()) {
+            entry.push(serde_json::Value::from(abi_path.clone()));
+        }
+        entry
+    }
 }
 
 lazy_static! {
     /// Loads the known contact addresses from the JSON file.
-    static ref KNOWN_ADDRESSES: HashMap<H160, KnownAddress> = {
+    /// Additional addresses can be merged in at runtime with [load_address_map_from_file].
+    static ref KNOWN_ADDRESSES: RwLock<HashMap<H160, KnownAddress>> = {
         let json_value = serde_json::from_slice(include_bytes!("data/address_map.json")).unwrap();
         let pairs: Vec<KnownAddress> = serde_json::from_value(json_value).unwrap();
 
-        pairs
-            .into_iter()
-            .map(|entry| (entry.address, entry))
-            .collect()
+        RwLock::new(
+            pairs
+                .into_iter()
+                .map(|entry| (entry.address, entry))
+                .collect(),
+        )
     };
 }
 
-fn address_to_human_readable(address: H160) -> Option<String> {
-    KNOWN_ADDRESSES
-        .get(&address)
-        .map(|known_address| match known_address.contract_type {
-            ContractType::System => known_address.name.to_string(),
-            ContractType::Precompile => format!("{}", known_address.name.dimmed()),
-            ContractType::Popular => format!("{}", known_address.name.green()),
-            ContractType::Unknown => known_address.name.to_string(),
+/// Returns true if the user asked for plain output via the `NO_COLOR` environment variable
+/// (set to any non-empty value, see https://no-color.org).
+pub fn no_color_requested() -> bool {
+    env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty())
+}
+
+/// Enables or disables the colors used by every print helper in this module.
+/// When disabled, all the `colored` styling calls (`.blue()`, `.on_red()`, `.dimmed()`, ...) become no-ops.
+pub fn set_colors_enabled(enabled: bool) {
+    colored::control::set_override(enabled);
+}
+
+lazy_static! {
+    /// Directory with user supplied ABI files, named after the contract address (`0x<address>.json`).
+    static ref ABI_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+    /// ABIs that were already looked up, including the addresses without one.
+    static ref ABIS: RwLock<HashMap<H160, Option<Contract>>> = RwLock::new(HashMap::new());
+    /// The user supplied address map file, where the addresses added at runtime can be persisted.
+    static ref ADDRESS_MAP_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Sets the directory that is searched for the ABI files used to decode the call parameters.
+pub fn set_abi_dir(path: PathBuf) {
+    if let Ok(mut abi_dir) = ABI_DIR.write() {
+        *abi_dir = Some(path);
+    }
+    if let Ok(mut abis) = ABIS.write() {
+        abis.clear();
+    }
+}
+
+/// Forgets the directory set with [set_abi_dir], and the ABIs read from it.
+#[cfg(test)]
+pub fn clear_abi_dir() {
+    if let Ok(mut abi_dir) = ABI_DIR.write() {
+        *abi_dir = None;
+    }
+    if let Ok(mut abis) = ABIS.write() {
+        abis.clear();
+    }
+}
+
+/// Returns the path of the ABI file for the given address (if any) - either the one
+/// from the known addresses, or `0x<address>.json` in the ABI directory.
+fn abi_path(address: &H160) -> Option<PathBuf> {
+    let abi_dir = ABI_DIR.read().ok().and_then(|abi_dir| abi_dir.clone());
+    match known_address(address).and_then(|known_address| known_address.abi_path) {
+        Some(path) => match &abi_dir {
+            Some(abi_dir) => Some(abi_dir.join(path)),
+            None => Some(PathBuf::from(path)),
+        },
+        None => abi_dir.map(|abi_dir| abi_dir.join(format!("{:#x}.json", address))),
+    }
+}
+
+/// Loads the ABI of the contract at the given address. Missing or invalid files are ignored.
+fn contract_abi(address: &H160) -> Option<Contract> {
+    if let Some(abi) = ABIS.read().ok().and_then(|abis| abis.get(address).cloned()) {
+        return abi;
+    }
+
+    let abi = abi_path(address)
+        .and_then(|path| fs::File::open(path).ok())
+        .and_then(|file| Contract::load(file).ok());
+    if let Ok(mut abis) = ABIS.write() {
+        abis.insert(*address, abi.clone());
+    }
+    abi
+}
+
+/// Decodes the parameters of the calldata using the ABI, returning one `name: value` entry per parameter.
+/// Falls back to the raw hex of the parameters if the function is unknown or the decoding fails.
+fn decode_call_params(abi: &Contract, input: &[u8]) -> Vec<String> {
+    if input.len() < 4 {
+        return vec![];
+    }
+    let (selector, params) = input.split_at(4);
+
+    abi.functions()
+        .find(|function| function.short_signature() == selector)
+        .and_then(|function| {
+            function.decode_input(params).ok().map(|tokens| {
+                function
+                    .inputs
+                    .iter()
+                    .zip(tokens)
+                    .map(|(param, token)| format!("{}: {}", param.name, token))
+                    .collect()
+            })
+        })
+        .unwrap_or_else(|| {
+            if params.is_empty() {
+                vec![]
+            } else {
+                vec![format!("0x{}", hex::encode(params))]
+            }
         })
 }
 
-/// Pretty-prints event object
-/// if skip_resolve is false, will try to contact openchain to resolve the topic hashes.
-pub fn print_event(event: &VmEvent, resolve_hashes: bool) {
-    let event = event.clone();
+/// Maximum number of bytes of undecoded return data printed under a call.
+const MAX_PRINTED_OUTPUT_BYTES: usize = 64;
+
+/// Decodes the return data of a call using the ABI (if any), returning one `name: value` entry per
+/// output (the type is used for unnamed outputs). Falls back to the raw hex of the return data,
+/// truncated to `MAX_PRINTED_OUTPUT_BYTES`, if the function is unknown or the decoding fails.
+fn decode_call_output(abi: Option<&Contract>, input: &[u8], output: &[u8]) -> Vec<String> {
+    if output.is_empty() {
+        return vec![];
+    }
+
+    abi.filter(|_| input.len() >= 4)
+        .and_then(|abi| {
+            abi.functions()
+                .find(|function| function.short_signature() == input[..4])
+        })
+        .and_then(|function| {
+            function.decode_output(output).ok().map(|tokens| {
+                function
+                    .outputs
+                    .iter()
+                    .zip(tokens)
+                    .map(|(param, token)| {
+                        if param.name.is_empty() {
+                            format!("{}: {}", param.kind, token)
+                        } else {
+                            format!("{}: {}", param.name, token)
+                        }
+                    })
+                    .collect()
+            })
+        })
+        .unwrap_or_else(|| {
+            if output.len() > MAX_PRINTED_OUTPUT_BYTES {
+                vec![format!(
+                    "0x{}... ({} bytes)",
+                    hex::encode(&output[..MAX_PRINTED_OUTPUT_BYTES]),
+                    output.len()
+                )]
+            } else {
+                vec![format!("0x{}", hex::encode(output))]
+            }
+        })
+}
+
+/// Returns the known address entry for the given address (if any).
+fn known_address(address: &H160) -> Option<KnownAddress> {
+    KNOWN_ADDRESSES
+        .read()
+        .ok()
+        .and_then(|known_addresses| known_addresses.get(address).cloned())
+}
+
+/// Merges the known addresses from a user supplied JSON file (same format as `data/address_map.json`)
+/// into the addresses used by the formatter. User entries override the built-in ones.
+///
+/// Returns the number of loaded entries.
+pub fn load_address_map_from_file(path: &Path) -> Result<usize, String> {
+    let contents = fs::read(path)
+        .map_err(|err| format!("failed reading address map '{:?}': {:?}", path, err))?;
+    let entries: Vec<KnownAddress> = serde_json::from_slice(&contents)
+        .map_err(|err| format!("failed parsing address map '{:?}': {:?}", path, err))?;
+
+    let mut known_addresses = KNOWN_ADDRESSES
+        .write()
+        .map_err(|err| format!("failed acquiring lock for known addresses: {:?}", err))?;
+    let count = entries.len();
+    for entry in entries {
+        known_addresses.insert(entry.address, entry);
+    }
+    if let Ok(mut address_map_path) = ADDRESS_MAP_PATH.write() {
+        *address_map_path = Some(path.to_path_buf());
+    }
+
+    Ok(count)
+}
+
+/// Returns the address map file loaded with [load_address_map_from_file] (if any).
+pub fn address_map_path() -> Option<PathBuf> {
+    ADDRESS_MAP_PATH.read().ok().and_then(|path| path.clone())
+}
+
+/// Forgets the address map file loaded with [load_address_map_from_file].
+#[cfg(test)]
+pub fn clear_address_map_path() {
+    if let Ok(mut address_map_path) = ADDRESS_MAP_PATH.write() {
+        *address_map_path = None;
+    }
+}
+
+/// Adds a known address used by the formatter, overriding any existing entry for it.
+/// If `persist_to` is set, the entry is also written to that address map file (replacing the
+/// entry of the same address, if any), so that it is loaded again on the next start.
+pub fn add_known_address(
+    address: H160,
+    name: String,
+    contract_type: ContractType,
+    persist_to: Option<&Path>,
+) -> Result<(), String> {
+    let entry = KnownAddress {
+        address,
+        name,
+        contract_type,
+        abi_path: None,
+    };
+
+    if let Some(path) = persist_to {
+        let contents = fs::read(path)
+            .map_err(|err| format!("failed reading address map '{:?}': {:?}", path, err))?;
+        let mut entries: Vec<KnownAddress> = serde_json::from_slice(&contents)
+            .map_err(|err| format!("failed parsing address map '{:?}': {:?}", path, err))?;
+        entries.retain(|existing| existing.address != address);
+        entries.push(entry.clone());
+
+        let json = serde_json::Value::Array(entries.iter().map(KnownAddress::to_json).collect());
+        let contents = serde_json::to_string_pretty(&json)
+            .map_err(|err| format!("failed serializing address map: {:?}", err))?;
+        fs::write(path, contents)
+            .map_err(|err| format!("failed writing address map '{:?}': {:?}", path, err))?;
+    }
+
+    KNOWN_ADDRESSES
+        .write()
+        .map_err(|err| format!("failed acquiring lock for known addresses: {:?}", err))?
+        .insert(address, entry);
+    if let Ok(mut abis) = ABIS.write() {
+        abis.remove(&address);
+    }
+
+    Ok(())
+}
+
+/// Returns the plain (uncolored) name of a known address.
+fn address_name(address: H160) -> Option<String> {
+    known_address(&address).map(|known_address| known_address.name)
+}
+
+fn address_to_human_readable(address: H160) -> Option<String> {
+    known_address(&address).map(|known_address| match known_address.contract_type {
+        ContractType::System => known_address.name.to_string(),
+        ContractType::Precompile => format!("{}", known_address.name.dimmed()),
+        ContractType::Popular => format!("{}", known_address.name.green()),
+        ContractType::Unknown => known_address.name.to_string(),
+    })
+}
+
+/// Structured representation of an event, used for the `json` output format.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EventOutput {
+    pub address: H160,
+    pub address_name: Option<String>,
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+/// Structured representation of a call (and its subcalls), used for the `json` output format.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CallOutput {
+    pub r#type: String,
+    pub to: H160,
+    pub to_name: Option<String>,
+    pub function_signature: String,
+    /// Human readable function name resolved from the selector, when available.
+    pub resolved_name: Option<String>,
+    /// Amount of wei transferred with the call.
+    pub value: U256,
+    pub gas: u32,
+    /// Gas actually consumed by the call, including its subcalls.
+    pub gas_used: u32,
+    pub revert_reason: Option<String>,
+    pub error: Option<String>,
+    pub calls: Vec<CallOutput>,
+    /// Number of subcalls left out of `calls` because they are deeper than the maximum call depth.
+    pub hidden_calls: usize,
+}
+
+/// Structured representation of a storage log, used for the `json` output format.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StorageLogOutput {
+    pub log_type: String,
+    pub address: H160,
+    pub address_name: Option<String>,
+    pub key: U256,
+    pub read_value: U256,
+    pub written_value: Option<U256>,
+}
+
+/// Structured representation of an L2→L1 log, used for the `json` output format.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct L2ToL1LogOutput {
+    /// Always `L2ToL1`, to tell the log apart from the storage logs.
+    pub log_type: String,
+    pub sender: H160,
+    pub sender_name: Option<String>,
+    pub is_service: bool,
+    pub key: H256,
+    pub value: H256,
+}
+
+/// Logs the given value as a single line of JSON.
+fn log_json<T: Serialize>(value: &T) {
+    match serde_json::to_string(value) {
+        Ok(json) => log::info!("{}", json),
+        Err(err) => log::error!("failed serializing output to json: {:?}", err),
+    }
+}
+
+/// Returns the resolved name, or `None` if the lookup failed so that the raw hash is printed
+/// instead. Failures are only logged, the output must never depend on the network being up.
+fn resolved_or_log(selector: &str, result: eyre::Result<Option<String>>) -> Option<String> {
+    result.unwrap_or_else(|err| {
+        log::debug!("failed resolving selector {}: {:?}", selector, err);
+        None
+    })
+}
+
+/// Resolves the event topics to human readable names (if `resolve_hashes` is set).
+fn resolve_topics(event: VmEvent, resolve_hashes: bool) -> Vec<String> {
     block_on(async move {
         let mut tt: Vec<String> = vec![];
         if !resolve_hashes {
             tt = event.indexed_topics.iter().map(|t| t.to_string()).collect();
         } else {
             for topic in event.indexed_topics {
-                let selector = resolver::decode_event_selector(&format!(
-                    "0x{}",
-                    hex::encode(topic.as_bytes())
-                ))
-                .await
-                .unwrap();
-                tt.push(selector.unwrap_or(format!("{:?}", topic)));
+                let selector = format!("0x{}", hex::encode(topic.as_bytes()));
+                let resolved = resolver::decode_event_selector(&selector).await;
+                tt.push(resolved_or_log(&selector, resolved).unwrap_or(format!("{:?}", topic)));
             }
         }
+        tt
+    })
+}
 
-        log::info!(
+/// Pretty-prints event object
+/// if skip_resolve is false, will try to contact openchain to resolve the topic hashes.
+pub fn print_event(event: &VmEvent, resolve_hashes: bool, output: &FormatterOutput) {
+    let tt = resolve_topics(event.clone(), resolve_hashes);
+
+    match output {
+        FormatterOutput::Text => log::info!(
             "{} {}",
             address_to_human_readable(event.address)
                 .map(|x| format!("{:42}", x.blue()))
                 .unwrap_or(format!("{:42}", format!("{:?}", event.address).blue())),
             tt.join(", ")
-        );
-    });
+        ),
+        FormatterOutput::Json => log_json(&EventOutput {
+            address: event.address,
+            address_name: address_name(event.address),
+            topics: tt,
+            data: format!("0x{}", hex::encode(&event.value)),
+        }),
+    }
 }
 
-/// Pretty-prints contents of a 'call' - including subcalls.
-/// If skip_resolve is false, will try to contact openchain to resolve the ABI names.
-pub fn print_call(call: &Call, padding: usize, show_calls: &ShowCalls, resolve_hashes: bool) {
-    let contract_type = KNOWN_ADDRESSES
-        .get(&call.to)
-        .cloned()
+fn contract_type_of(address: &H160) -> ContractType {
+    known_address(address)
         .map(|known_address| known_address.contract_type)
-        .unwrap_or(ContractType::Unknown);
+        .unwrap_or(ContractType::Unknown)
+}
 
-    let should_print = match (&contract_type, &show_calls) {
-        (_, ShowCalls::All) => true,
+fn should_print_call(contract_type: &ContractType, show_calls: &ShowCalls) -> bool {
+    match (contract_type, show_calls) {
+        (_, ShowCalls::All) | (_, ShowCalls::Flat) => true,
+        // failing calls are selected by `should_print`, regardless of the contract type
+        (_, ShowCalls::Errors) => true,
         (_, ShowCalls::None) => false,
         // now we're left only with 'user' and 'system'
         (ContractType::Unknown, _) => true,
         (ContractType::Popular, _) => true,
-        (ContractType::Precompile, _) => false,
+        // Precompiles are only shown with `All` (and `Flat`), as they would clutter the other views.
+        (ContractType::Precompile, ShowCalls::User)
+        | (ContractType::Precompile, ShowCalls::System) => false,
         // Now we're left with System
         (ContractType::System, ShowCalls::User) => false,
         (ContractType::System, ShowCalls::System) => true,
+    }
+}
+
+/// Returns whether the call itself reverted or failed.
+fn call_failed(call: &Call) -> bool {
+    call.revert_reason.is_some() || call.error.is_some()
+}
+
+/// Returns whether the call, or any call below it, reverted or failed.
+fn contains_failure(call: &Call) -> bool {
+    call_failed(call) || call.calls.iter().any(contains_failure)
+}
+
+/// Returns whether `show_calls` selects the call. With `ShowCalls::Errors` only the failing calls
+/// and their ancestors are selected.
+fn should_print(call: &Call, show_calls: &ShowCalls) -> bool {
+    if *show_calls == ShowCalls::Errors {
+        return contains_failure(call);
+    }
+    should_print_call(&contract_type_of(&call.to), show_calls)
+}
+
+/// Returns the function selector of the call (or the full input if it is shorter than a selector),
+/// and whether the returned value is a proper 4-byte selector.
+fn call_selector(call: &Call) -> (String, bool) {
+    if call.input.len() >= 4 {
+        (hex::encode(&call.input.as_slice()[..4]), true)
+    } else {
+        (format!("0x{}", hex::encode(call.input.as_slice())), false)
+    }
+}
+
+/// Builds the structured representation of a call at `depth`. Calls filtered out by `show_calls`
+/// are skipped, but their subcalls are still included (attached to the closest printed parent).
+/// Subcalls deeper than `max_depth` are left out and only counted in `hidden_calls`.
+///
+/// Returns the printed calls, and the number of hidden calls not attached to any of them yet.
+fn call_to_output(
+    call: &Call,
+    depth: usize,
+    show_calls: &ShowCalls,
+    max_depth: Option<usize>,
+    resolve_hashes: bool,
+) -> (Vec<CallOutput>, usize) {
+    let (subcalls, hidden_calls) = if max_depth.map_or(false, |max_depth| depth >= max_depth) {
+        (vec![], count_printed_subcalls(call, show_calls))
+    } else {
+        call.calls
+            .iter()
+            .fold((vec![], 0), |(mut subcalls, hidden_calls), subcall| {
+                let (outputs, hidden) =
+                    call_to_output(subcall, depth + 1, show_calls, max_depth, resolve_hashes);
+                subcalls.extend(outputs);
+                (subcalls, hidden_calls + hidden)
+            })
     };
-    if should_print {
-        let function_signature = if call.input.len() >= 4 {
-            let sig = call.input.as_slice()[..4]
-                .iter()
-                .map(|byte| format!("{:02x}", byte))
-                .collect::<Vec<_>>()
-                .join("");
 
+    if !should_print(call, show_calls) {
+        return (subcalls, hidden_calls);
+    }
+    let contract_type = contract_type_of(&call.to);
+
+    let (sig, is_selector) = call_selector(call);
+    let resolved_name =
+        if is_selector && contract_type != ContractType::Precompile && resolve_hashes {
+            let selector = sig.clone();
+            block_on(async move {
+                let resolved = resolver::decode_function_selector(&selector).await;
+                resolved_or_log(&selector, resolved)
+            })
+        } else {
+            None
+        };
+    let function_signature = if is_selector {
+        format!("0x{}", sig)
+    } else {
+        sig
+    };
+
+    let output = CallOutput {
+        r#type: format!("{:?}", call.r#type),
+        to: call.to,
+        to_name: address_name(call.to),
+        function_signature,
+        resolved_name,
+        value: call.value,
+        gas: call.gas,
+        gas_used: call.gas_used,
+        revert_reason: call.revert_reason.clone(),
+        error: call.error.clone(),
+        calls: subcalls,
+        hidden_calls,
+    };
+    (vec![output], 0)
+}
+
+/// Formats a wei amount as a human readable ETH value with 6 decimals, e.g. `0.500000 ETH`.
+/// Amounts too small to show up with 6 decimals are formatted in gwei (e.g. `2.500000 gwei`), or in
+/// wei below 1 gwei.
+fn format_eth_value(value: U256) -> String {
+    let wei_per_gwei = U256::exp10(9);
+    if value < wei_per_gwei {
+        return format!("{} wei", value);
+    }
+    if value < U256::exp10(12) {
+        let decimals = (value % wei_per_gwei) / U256::exp10(3);
+        return format!("{}.{:06} gwei", value / wei_per_gwei, decimals.as_u64());
+    }
+    let wei_per_eth = U256::exp10(18);
+    let decimals = (value % wei_per_eth) / U256::exp10(12);
+    format!("{}.{:06} ETH", value / wei_per_eth, decimals.as_u64())
+}
+
+/// Builds the text line printed for a single call (without its subcalls). The gas used is
+/// highlighted when it exceeds `gas_highlight_threshold`.
+fn format_call_line(
+    call: &Call,
+    prefix: &str,
+    function_signature: &str,
+    gas_highlight_threshold: u32,
+) -> String {
+    let gas_used = format!("[gas used: {}]", call.gas_used);
+    format!(
+        "{}{:?} {} {}{} {} {} {} {}",
+        prefix,
+        call.r#type,
+        address_to_human_readable(call.to)
+            .map(|x| format!("{:<52}", x))
+            .unwrap_or(format!("{:<52}", format!("{:?}", call.to).bold())),
+        function_signature,
+        if call.value.is_zero() {
+            String::default()
+        } else {
+            format!(" {}", format_eth_value(call.value))
+        },
+        if call.gas_used > gas_highlight_threshold {
+            gas_used.yellow().bold()
+        } else {
+            gas_used.cyan()
+        },
+        call.revert_reason
+            .as_ref()
+            .map(|s| format!("Revert: {}", s))
+            .unwrap_or_default(),
+        call.error
+            .as_ref()
+            .map(|s| format!("Error: {}", s))
+            .unwrap_or_default(),
+        call.gas
+    )
+}
+
+/// Returns the number of calls below `call` (at any depth) that `show_calls` would print.
+fn count_printed_subcalls(call: &Call, show_calls: &ShowCalls) -> usize {
+    call.calls
+        .iter()
+        .map(|subcall| {
+            let printed = should_print(subcall, show_calls);
+            usize::from(printed) + count_printed_subcalls(subcall, show_calls)
+        })
+        .sum()
+}
+
+/// Pretty-prints contents of a 'call' - including subcalls.
+/// If skip_resolve is false, will try to contact openchain to resolve the ABI names.
+/// With `ShowCalls::Flat`, subcalls are not indented - each line is prefixed with its depth instead.
+/// Subcalls deeper than `max_depth` are replaced by a single line with their count.
+/// Calls that used more than `gas_highlight_threshold` gas have it highlighted.
+/// With `FormatterOutput::Json`, the whole call tree is printed as a single line with a JSON array,
+/// where the subcalls deeper than `max_depth` are only counted in `hidden_calls`.
+pub fn print_call(
+    call: &Call,
+    padding: usize,
+    show_calls: &ShowCalls,
+    max_depth: Option<usize>,
+    gas_highlight_threshold: u32,
+    resolve_hashes: bool,
+    output: &FormatterOutput,
+) {
+    if *output == FormatterOutput::Json {
+        let (calls, _) = call_to_output(call, padding / 2, show_calls, max_depth, resolve_hashes);
+        log_json(&calls);
+        return;
+    }
+
+    let contract_type = contract_type_of(&call.to);
+
+    if should_print(call, show_calls) {
+        let (sig, is_selector) = call_selector(call);
+        let function_signature = if is_selector {
             if contract_type == ContractType::Precompile || !resolve_hashes {
                 format!("{:>16}", sig)
             } else {
                 block_on(async move {
-                    let fetch = resolver::decode_function_selector(&sig).await.unwrap();
-                    fetch.unwrap_or(format!("{:>16}", format!("0x{}", sig).dimmed()))
+                    let fetch = resolver::decode_function_selector(&sig).await;
+                    resolved_or_log(&sig, fetch)
+                        .unwrap_or(format!("{:>16}", format!("0x{}", sig).dimmed()))
                 })
             }
         } else {
-            format!(
-                "0x{}",
-                call.input
-                    .as_slice()
-                    .iter()
-                    .map(|byte| format!("{:02x}", byte))
-                    .collect::<Vec<_>>()
-                    .join("")
-            )
+            sig
         };
 
-        let pretty_print = format!(
-            "{}{:?} {} {} {} {} {}",
-            " ".repeat(padding),
-            call.r#type,
-            address_to_human_readable(call.to)
-                .map(|x| format!("{:<52}", x))
-                .unwrap_or(format!("{:<52}", format!("{:?}", call.to).bold())),
-            function_signature,
-            call.revert_reason
-                .as_ref()
-                .map(|s| format!("Revert: {}", s))
-                .unwrap_or_default(),
-            call.error
-                .as_ref()
-                .map(|s| format!("Error: {}", s))
-                .unwrap_or_default(),
-            call.gas
-        );
+        let prefix = if *show_calls == ShowCalls::Flat {
+            format!("[{}] ", padding / 2)
+        } else {
+            " ".repeat(padding)
+        };
+
+        let pretty_print =
+            format_call_line(call, &prefix, &function_signature, gas_highlight_threshold);
 
         if call.revert_reason.as_ref().is_some() || call.error.as_ref().is_some() {
             log::info!("{}", pretty_print.on_red());
         } else {
             log::info!("{}", pretty_print);
         }
+
+        let abi = contract_abi(&call.to);
+        if let Some(abi) = &abi {
+            for param in decode_call_params(abi, &call.input) {
+                log::info!("{}  {}", prefix, param.dimmed());
+            }
+        }
+        if call.revert_reason.is_none() && call.error.is_none() {
+            let outputs = decode_call_output(abi.as_ref(), &call.input, &call.output);
+            if !outputs.is_empty() {
+                log::info!(
+                    "{}  {}",
+                    prefix,
+                    format!("returns {}", outputs.join(", ")).dimmed()
+                );
+            }
+        }
+    }
+
+    let depth = padding / 2;
+    if max_depth.map_or(false, |max_depth| depth >= max_depth) {
+        let hidden = count_printed_subcalls(call, show_calls);
+        if hidden > 0 {
+            let prefix = if *show_calls == ShowCalls::Flat {
+                format!("[{}] ", depth + 1)
+            } else {
+                " ".repeat(padding + 2)
+            };
+            log::info!(
+                "{}{}",
+                prefix,
+                format!("... {} more subcalls hidden ...", hidden).dimmed()
+            );
+        }
+        return;
     }
     for subcall in &call.calls {
-        print_call(subcall, padding + 2, show_calls, resolve_hashes);
+        print_call(
+            subcall,
+            padding + 2,
+            show_calls,
+            max_depth,
+            gas_highlight_threshold,
+            resolve_hashes,
+            output,
+        );
     }
 }
 
-pub fn print_logs(log_query: &StorageLogQuery) {
+pub fn print_logs(log_query: &StorageLogQuery, output: &FormatterOutput) {
+    if *output == FormatterOutput::Json {
+        log_json(&StorageLogOutput {
+            log_type: format!("{:?}", log_query.log_type),
+            address: log_query.log_query.address,
+            address_name: address_name(log_query.log_query.address),
+            key: log_query.log_query.key,
+            read_value: log_query.log_query.read_value,
+            written_value: (log_query.log_type != StorageLogQueryType::Read)
+                .then_some(log_query.log_query.written_value),
+        });
+        return;
+    }
+
     let separator = "─".repeat(82);
     log::info!("{:<15} {:?}", "Type:", log_query.log_type);
     log::info!(
@@ -187,6 +758,33 @@ pub fn print_logs(log_query: &StorageLogQuery) {
     log::info!("{}", separator);
 }
 
+/// Prints a log sent to L1, among the storage logs. It is annotated as an L2 → L1 log, as it is
+/// not a storage access.
+pub fn print_l2_to_l1_log(log: &L2ToL1Log, output: &FormatterOutput) {
+    if *output == FormatterOutput::Json {
+        log_json(&L2ToL1LogOutput {
+            log_type: String::from("L2ToL1"),
+            sender: log.sender,
+            sender_name: address_name(log.sender),
+            is_service: log.is_service,
+            key: log.key,
+            value: log.value,
+        });
+        return;
+    }
+
+    let separator = "─".repeat(82);
+    log::info!("{:<15} {}", "Type:", "L2 → L1 Log".cyan());
+    log::info!(
+        "{:<15} {}",
+        "Sender:",
+        address_to_human_readable(log.sender).unwrap_or(format!("{}", log.sender))
+    );
+    log::info!("{:<15} {:?}", "Key:", log.key);
+    log::info!("{:<15} {:?}", "Value:", log.value);
+    log::info!("{}", separator);
+}
+
 pub fn print_vm_details(result: &VmPartialExecutionResult) {
     log::info!("");
     log::info!("┌──────────────────────────┐");
@@ -207,3 +805,515 @@ pub fn print_vm_details(result: &VmPartialExecutionResult) {
 
     log::info!("════════════════════════════");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_call_output_json_roundtrip() {
+        let output = CallOutput {
+            r#type: "Call(Normal)".to_string(),
+            to: H160::repeat_byte(0x01),
+            to_name: Some("L2EthToken".to_string()),
+            function_signature: "0xa9059cbb".to_string(),
+            resolved_name: Some("transfer(address,uint256)".to_string()),
+            value: U256::exp10(17),
+            gas: 1_000,
+            gas_used: 600,
+            revert_reason: Some("not enough balance".to_string()),
+            error: None,
+            calls: vec![CallOutput {
+                r#type: "Call(Mimic)".to_string(),
+                to: H160::repeat_byte(0x02),
+                to_name: None,
+                function_signature: "0x".to_string(),
+                resolved_name: None,
+                value: U256::zero(),
+                gas: 500,
+                gas_used: 500,
+                revert_reason: None,
+                error: Some("out of gas".to_string()),
+                calls: vec![],
+                hidden_calls: 3,
+            }],
+            hidden_calls: 0,
+        };
+
+        let json = serde_json::to_string(&output).expect("failed serializing");
+        let actual: CallOutput = serde_json::from_str(&json).expect("failed deserializing");
+
+        assert_eq!(output, actual);
+    }
+
+    #[test]
+    fn test_event_and_storage_log_output_json_roundtrip() {
+        let event = EventOutput {
+            address: H160::repeat_byte(0x01),
+            address_name: None,
+            topics: vec![format!("{:?}", zksync_basic_types::H256::repeat_byte(0x02))],
+            data: "0x1234".to_string(),
+        };
+        let json = serde_json::to_string(&event).expect("failed serializing");
+        assert_eq!(
+            event,
+            serde_json::from_str::<EventOutput>(&json).expect("failed deserializing")
+        );
+
+        let storage_log = StorageLogOutput {
+            log_type: "Read".to_string(),
+            address: H160::repeat_byte(0x03),
+            address_name: Some("NonceHolder".to_string()),
+            key: U256::from(1),
+            read_value: U256::from(2),
+            written_value: None,
+        };
+        let json = serde_json::to_string(&storage_log).expect("failed serializing");
+        assert_eq!(
+            storage_log,
+            serde_json::from_str::<StorageLogOutput>(&json).expect("failed deserializing")
+        );
+    }
+
+    #[test]
+    fn test_call_to_output_skips_filtered_calls_but_keeps_subcalls() {
+        let user_contract = H160::repeat_byte(0xab);
+        // Nonce Holder
+        let system_contract = H160::from_low_u64_be(0x8003);
+        let call = Call {
+            to: system_contract,
+            calls: vec![Call {
+                to: user_contract,
+                input: vec![0xa9, 0x05, 0x9c, 0xbb, 0x00],
+                gas: 100,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let (output, _) = call_to_output(&call, 0, &ShowCalls::User, None, false);
+
+        assert_eq!(1, output.len());
+        assert_eq!(user_contract, output[0].to);
+        assert_eq!("0xa9059cbb", output[0].function_signature);
+        assert_eq!(None, output[0].resolved_name);
+        assert_eq!(100, output[0].gas);
+        assert!(output[0].calls.is_empty());
+    }
+
+    #[test]
+    fn test_call_to_output_hides_calls_deeper_than_max_depth() {
+        let user_contract = H160::repeat_byte(0xab);
+        // Nonce Holder
+        let system_contract = H160::from_low_u64_be(0x8003);
+        let leaf = || Call {
+            to: user_contract,
+            ..Default::default()
+        };
+        let call = Call {
+            to: user_contract,
+            calls: vec![
+                Call {
+                    to: user_contract,
+                    calls: vec![leaf(), leaf()],
+                    ..Default::default()
+                },
+                Call {
+                    to: system_contract,
+                    calls: vec![leaf()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (output, hidden) = call_to_output(&call, 0, &ShowCalls::User, Some(1), false);
+
+        assert_eq!(0, hidden);
+        assert_eq!(1, output.len());
+        assert_eq!(1, output[0].calls.len());
+        assert_eq!(2, output[0].calls[0].hidden_calls);
+        assert!(output[0].calls[0].calls.is_empty());
+        // the call hidden below the filtered system call is attached to its closest printed parent
+        assert_eq!(1, output[0].hidden_calls);
+
+        let (output, _) = call_to_output(&call, 0, &ShowCalls::User, Some(0), false);
+        assert_eq!(4, output[0].hidden_calls);
+        assert!(output[0].calls.is_empty());
+
+        let (output, _) = call_to_output(&call, 0, &ShowCalls::All, None, false);
+        assert_eq!(2, output[0].calls.len());
+        assert_eq!(0, output[0].calls[0].hidden_calls);
+    }
+
+    #[test]
+    fn test_count_printed_subcalls_skips_filtered_calls() {
+        let user_contract = H160::repeat_byte(0xab);
+        // Nonce Holder
+        let system_contract = H160::from_low_u64_be(0x8003);
+        let call = Call {
+            to: user_contract,
+            calls: vec![
+                Call {
+                    to: system_contract,
+                    calls: vec![Call {
+                        to: user_contract,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                Call {
+                    to: user_contract,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(2, count_printed_subcalls(&call, &ShowCalls::User));
+        assert_eq!(3, count_printed_subcalls(&call, &ShowCalls::All));
+        assert_eq!(0, count_printed_subcalls(&call, &ShowCalls::None));
+    }
+
+    #[test]
+    fn test_show_calls_errors_keeps_only_the_failure_path() {
+        let parent = H160::repeat_byte(0x01);
+        let failing = H160::repeat_byte(0x02);
+        let call = Call {
+            to: parent,
+            calls: vec![
+                Call {
+                    to: H160::repeat_byte(0x03),
+                    calls: vec![Call {
+                        to: H160::repeat_byte(0x04),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                Call {
+                    // Nonce Holder, system contracts are shown too
+                    to: H160::from_low_u64_be(0x8003),
+                    calls: vec![Call {
+                        to: failing,
+                        revert_reason: Some("boom".to_string()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (output, _) = call_to_output(&call, 0, &ShowCalls::Errors, None, false);
+
+        assert_eq!(1, output.len());
+        assert_eq!(parent, output[0].to);
+        assert_eq!(1, output[0].calls.len());
+        assert_eq!(H160::from_low_u64_be(0x8003), output[0].calls[0].to);
+        assert_eq!(failing, output[0].calls[0].calls[0].to);
+        assert_eq!(2, count_printed_subcalls(&call, &ShowCalls::Errors));
+        assert_eq!(
+            0,
+            count_printed_subcalls(&call.calls[0], &ShowCalls::Errors)
+        );
+    }
+
+    #[test]
+    fn test_load_address_map_from_file_overrides_built_in_entries() {
+        let _settings = testing::GLOBAL_SETTINGS_LOCK.blocking_lock();
+        let dir = tempdir::TempDir::new("address-map").expect("failed creating temporary dir");
+        let path = dir.path().join("address_map.json");
+        let user_address = H160::repeat_byte(0xcd);
+        let bootloader = H160::from_low_u64_be(0x8001);
+        let built_in = known_address(&bootloader).expect("bootloader is not a known address");
+        fs::write(
+            &path,
+            serde_json::json!([
+                [format!("{:#x}", user_address), "MyToken", "Popular"],
+                [format!("{:#x}", bootloader), "MyBootloader", "System"],
+            ])
+            .to_string(),
+        )
+        .expect("failed writing address map");
+
+        let loaded = load_address_map_from_file(&path).expect("failed loading address map");
+
+        assert_eq!(2, loaded);
+        assert_eq!(Some("MyToken".to_string()), address_name(user_address));
+        assert_eq!(ContractType::Popular, contract_type_of(&user_address));
+        assert_eq!(Some("MyBootloader".to_string()), address_name(bootloader));
+        assert_eq!(ContractType::System, contract_type_of(&bootloader));
+
+        // restore the built-in entry for the other tests
+        add_known_address(bootloader, built_in.name, built_in.contract_type, None)
+            .expect("failed restoring the built-in entry");
+    }
+
+    #[test]
+    fn test_load_address_map_from_file_rejects_invalid_file() {
+        let dir = tempdir::TempDir::new("address-map").expect("failed creating temporary dir");
+        let path = dir.path().join("address_map.json");
+        fs::write(&path, "{ not json").expect("failed writing address map");
+
+        assert!(load_address_map_from_file(&path).is_err());
+        assert!(load_address_map_from_file(&dir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_add_known_address_persists_to_address_map() {
+        let _settings = testing::GLOBAL_SETTINGS_LOCK.blocking_lock();
+        let dir = tempdir::TempDir::new("address-map").expect("failed creating temporary dir");
+        let path = dir.path().join("address_map.json");
+        let existing = H160::repeat_byte(0xce);
+        let added = H160::repeat_byte(0xcf);
+        fs::write(
+            &path,
+            serde_json::json!([
+                [
+                    format!("{:#x}", existing),
+                    "Existing",
+                    "Popular",
+                    "existing.json"
+                ],
+                [format!("{:#x}", added), "Outdated", "Unknown"],
+            ])
+            .to_string(),
+        )
+        .expect("failed writing address map");
+
+        add_known_address(added, "MyVault".to_string(), ContractType::Popular, None)
+            .expect("failed adding known address");
+        assert_eq!(Some("MyVault".to_string()), address_name(added));
+        assert_eq!(ContractType::Popular, contract_type_of(&added));
+        let untouched = fs::read_to_string(&path).expect("failed reading address map");
+        assert!(untouched.contains("Outdated"));
+
+        add_known_address(
+            added,
+            "MyVault".to_string(),
+            ContractType::Popular,
+            Some(&path),
+        )
+        .expect("failed persisting known address");
+        let persisted: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!([
+                [
+                    format!("{:#x}", existing),
+                    "Existing",
+                    "Popular",
+                    "existing.json"
+                ],
+                [format!("{:#x}", added), "MyVault", "Popular"],
+            ]),
+            persisted
+        );
+    }
+
+    #[test]
+    fn test_format_call_line_shows_non_zero_value() {
+        let call = Call {
+            to: H160::repeat_byte(0xab),
+            value: U256::exp10(17) * 5,
+            gas: 100,
+            ..Default::default()
+        };
+
+        let line = format_call_line(&call, "", "0xa9059cbb", DEFAULT_GAS_HIGHLIGHT_THRESHOLD);
+
+        assert!(line.contains("0xa9059cbb 0.500000 ETH "));
+    }
+
+    #[test]
+    fn test_format_call_line_omits_zero_value() {
+        let call = Call {
+            to: H160::repeat_byte(0xab),
+            gas: 100,
+            ..Default::default()
+        };
+
+        let line = format_call_line(&call, "", "0xa9059cbb", DEFAULT_GAS_HIGHLIGHT_THRESHOLD);
+
+        assert!(!line.contains("ETH"));
+    }
+
+    #[test]
+    fn test_format_call_line_highlights_gas_above_threshold() {
+        let call = Call {
+            to: H160::repeat_byte(0xab),
+            gas: 500_000,
+            gas_used: 200_000,
+            ..Default::default()
+        };
+
+        let highlighted = format_call_line(&call, "", "0xa9059cbb", 100_000);
+        let plain = format_call_line(&call, "", "0xa9059cbb", 300_000);
+
+        assert!(highlighted.contains(&"[gas used: 200000]".yellow().bold().to_string()));
+        assert!(plain.contains(&"[gas used: 200000]".cyan().to_string()));
+    }
+
+    #[test]
+    fn test_format_eth_value() {
+        assert_eq!("0.500000 ETH", format_eth_value(U256::exp10(17) * 5));
+        assert_eq!(
+            "12.000001 ETH",
+            format_eth_value(U256::exp10(19) + U256::exp10(18) * 2 + U256::exp10(12))
+        );
+        assert_eq!("0.000001 ETH", format_eth_value(U256::exp10(12)));
+        assert_eq!(
+            "2.500000 gwei",
+            format_eth_value(U256::from(2_500_000_000u64))
+        );
+        assert_eq!("999.999999 gwei", format_eth_value(U256::exp10(12) - 1));
+        assert_eq!("1 wei", format_eth_value(U256::from(1)));
+        assert_eq!("250000000 wei", format_eth_value(U256::from(250_000_000)));
+    }
+
+    #[test]
+    fn test_should_print_call_shows_precompiles_only_for_all_and_flat() {
+        assert!(should_print_call(
+            &ContractType::Precompile,
+            &ShowCalls::All
+        ));
+        assert!(should_print_call(
+            &ContractType::Precompile,
+            &ShowCalls::Flat
+        ));
+        assert!(!should_print_call(
+            &ContractType::Precompile,
+            &ShowCalls::User
+        ));
+        assert!(!should_print_call(
+            &ContractType::Precompile,
+            &ShowCalls::System
+        ));
+        assert!(!should_print_call(
+            &ContractType::Precompile,
+            &ShowCalls::None
+        ));
+    }
+
+    fn transfer_abi() -> Contract {
+        let abi = serde_json::json!([{
+            "type": "function",
+            "name": "transfer",
+            "inputs": [
+                { "name": "to", "type": "address" },
+                { "name": "amount", "type": "uint256" }
+            ],
+            "outputs": [{ "name": "", "type": "bool" }],
+            "stateMutability": "nonpayable"
+        }]);
+        serde_json::from_value(abi).expect("invalid abi")
+    }
+
+    #[test]
+    fn test_decode_call_params() {
+        let abi = transfer_abi();
+        let mut input = vec![0xa9, 0x05, 0x9c, 0xbb];
+        input.extend(ethabi::encode(&[
+            ethabi::Token::Address(H160::repeat_byte(0x01)),
+            ethabi::Token::Uint(U256::from(100)),
+        ]));
+
+        let params = decode_call_params(&abi, &input);
+
+        assert_eq!(
+            vec![
+                format!("to: {}", hex::encode(H160::repeat_byte(0x01))),
+                "amount: 64".to_string()
+            ],
+            params
+        );
+    }
+
+    #[test]
+    fn test_decode_call_params_falls_back_to_raw_hex() {
+        let abi = transfer_abi();
+
+        // Truncated parameters.
+        let params = decode_call_params(&abi, &[0xa9, 0x05, 0x9c, 0xbb, 0x12, 0x34]);
+        assert_eq!(vec!["0x1234".to_string()], params);
+
+        // Unknown selector without parameters.
+        assert!(decode_call_params(&abi, &[0x01, 0x02, 0x03, 0x04]).is_empty());
+    }
+
+    #[test]
+    fn test_decode_call_output() {
+        let abi = transfer_abi();
+        let input = [0xa9, 0x05, 0x9c, 0xbb];
+        let output = ethabi::encode(&[ethabi::Token::Bool(true)]);
+
+        assert_eq!(
+            vec!["bool: true".to_string()],
+            decode_call_output(Some(&abi), &input, &output)
+        );
+        assert_eq!(
+            vec![format!("0x{}", hex::encode(&output))],
+            decode_call_output(None, &input, &output)
+        );
+        assert_eq!(
+            vec![format!("0x{}... (96 bytes)", "00".repeat(64))],
+            decode_call_output(None, &input, &[0u8; 96])
+        );
+        assert!(decode_call_output(Some(&abi), &input, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_contract_abi_is_loaded_from_abi_dir() {
+        let _settings = testing::GLOBAL_SETTINGS_LOCK.blocking_lock();
+        let dir = tempdir::TempDir::new("abi-dir").expect("failed creating temporary dir");
+        let address = H160::repeat_byte(0xef);
+        fs::write(
+            dir.path().join(format!("{:#x}.json", address)),
+            serde_json::to_string(&serde_json::json!([{
+                "type": "function",
+                "name": "transfer",
+                "inputs": [
+                    { "name": "to", "type": "address" },
+                    { "name": "amount", "type": "uint256" }
+                ],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            }]))
+            .unwrap(),
+        )
+        .expect("failed writing abi");
+
+        set_abi_dir(dir.path().to_path_buf());
+
+        assert_eq!(
+            Some(transfer_abi().functions().count()),
+            contract_abi(&address).map(|abi| abi.functions().count())
+        );
+        assert!(contract_abi(&H160::repeat_byte(0xee)).is_none());
+
+        clear_abi_dir();
+        assert!(contract_abi(&address).is_none());
+    }
+
+    #[test]
+    fn test_print_event_falls_back_to_raw_topics_when_resolver_fails() {
+        let _settings = testing::GLOBAL_SETTINGS_LOCK.blocking_lock();
+        // nothing listens on this port, so every lookup fails
+        resolver::set_resolver_url("http://127.0.0.1:1/api/v1/signatures".to_string());
+        let topic = zksync_basic_types::H256::repeat_byte(0x9a);
+        let event = VmEvent {
+            address: H160::repeat_byte(0x01),
+            indexed_topics: vec![topic],
+            value: vec![0x12, 0x34],
+            ..Default::default()
+        };
+
+        let topics = resolve_topics(event.clone(), true);
+        print_event(&event, true, &FormatterOutput::Text);
+        print_event(&event, true, &FormatterOutput::Json);
+        resolver::set_resolver_url(String::new());
+
+        assert_eq!(vec![format!("{:?}", topic)], topics);
+    }
+}
@@ -0,0 +1,174 @@
+//! Genesis file passed with `--genesis`, used to pre-fund accounts and pre-deploy contracts.
+//!
+//! The format is a subset of the `alloc` section of geth genesis files, adapted for zkSync:
+//! ```json
+//! {
+//!   "alloc": {
+//!     "0x36615Cf349d7F6344891B1e7CA7C72883F5dc049": {
+//!       "balance": "0xde0b6b3a7640000",
+//!       "nonce": "0x1",
+//!       "code": "0x...",
+//!       "storage": { "0x00...00": "0x00...01" }
+//!     }
+//!   }
+//! }
+//! ```
+//! Only `alloc` and the `balance` of each account are required. The other top-level fields of
+//! geth genesis files (`config`, `gasLimit`, ...) are ignored.
+
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use serde::Deserialize;
+use zksync_basic_types::{Address, Bytes, H256, U256};
+use zksync_types::{
+    get_code_key, get_known_code_key, get_nonce_key,
+    utils::{nonces_to_full_nonce, storage_key_for_eth_balance},
+    StorageKey,
+};
+use zksync_utils::{bytecode::hash_bytecode, u256_to_h256};
+
+use crate::{fork::ForkStorage, utils::validate_bytecode};
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct Genesis {
+    pub alloc: HashMap<Address, GenesisAccount>,
+}
+
+/// State of a single account at genesis.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GenesisAccount {
+    pub balance: U256,
+    /// The deployment nonce if the account has `code`, the transaction nonce otherwise.
+    pub nonce: Option<U256>,
+    /// zkSync bytecode of the contract deployed at the address.
+    pub code: Option<Bytes>,
+    pub storage: Option<HashMap<H256, H256>>,
+}
+
+impl Genesis {
+    /// Reads and validates the genesis file at `path`.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("failed opening genesis file {:?}: {}", path, err))?;
+        let genesis: Genesis = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| format!("failed parsing genesis file {:?}: {}", path, err))?;
+        genesis.validate()?;
+        Ok(genesis)
+    }
+
+    /// Checks that every account can be written: nonces must fit in 32 bits and `code` must be a
+    /// valid zkSync bytecode.
+    pub fn validate(&self) -> Result<(), String> {
+        for (address, account) in &self.alloc {
+            if matches!(account.nonce, Some(nonce) if nonce > U256::from(u32::MAX)) {
+                return Err(format!("{:?}: `nonce` must fit in 32 bits", address));
+            }
+            if let Some(code) = &account.code {
+                validate_bytecode(&code.0)
+                    .map_err(|err| format!("{:?}: invalid `code`: {}", address, err))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the accounts directly to `storage`. Contracts are stored the same way as with
+    /// `hardhat_setCode`, so no deployment transaction is executed and no event is emitted.
+    pub fn apply<S>(&self, storage: &mut ForkStorage<S>) {
+        for (address, account) in &self.alloc {
+            storage.set_value(
+                storage_key_for_eth_balance(address),
+                u256_to_h256(account.balance),
+            );
+            if let Some(nonce) = account.nonce {
+                let full_nonce = if account.code.is_some() {
+                    nonces_to_full_nonce(U256::zero(), nonce)
+                } else {
+                    nonces_to_full_nonce(nonce, U256::zero())
+                };
+                storage.set_value(get_nonce_key(address), u256_to_h256(full_nonce));
+            }
+            if let Some(code) = &account.code {
+                let code_hash = hash_bytecode(&code.0);
+                storage.store_factory_dep(code_hash, code.0.clone());
+                storage.set_value(get_known_code_key(&code_hash), u256_to_h256(U256::one()));
+                storage.set_value(get_code_key(address), code_hash);
+            }
+            for (slot, value) in account.storage.iter().flatten() {
+                storage.set_value(StorageKey::new((*address).into(), *slot), *value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http_fork_source::HttpForkSource, system_contracts};
+    use zksync_state::ReadStorage;
+
+    #[test]
+    fn test_genesis_requires_balance_and_valid_code() {
+        let address = "0x36615cf349d7f6344891b1e7ca7c72883f5dc049";
+        let parse = |account: serde_json::Value| {
+            serde_json::from_value::<Genesis>(serde_json::json!({
+                "config": { "chainId": 260 },
+                "alloc": { address: account },
+            }))
+        };
+
+        assert!(parse(serde_json::json!({ "nonce": "0x1" })).is_err());
+        assert!(parse(serde_json::json!({ "balance": "0x1", "unknown": "0x1" })).is_err());
+        let genesis = parse(serde_json::json!({ "balance": "0x1", "code": "0x00" })).unwrap();
+        assert!(genesis.validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_writes_the_accounts_to_storage() {
+        let mut storage =
+            ForkStorage::<HttpForkSource>::new(None, &system_contracts::Options::BuiltIn);
+        let wallet = Address::repeat_byte(0x01);
+        let contract = Address::repeat_byte(0x02);
+        let code = vec![0u8; 32];
+        let slot = H256::from_low_u64_be(1);
+        let genesis = Genesis {
+            alloc: HashMap::from([
+                (
+                    wallet,
+                    GenesisAccount {
+                        balance: U256::from(100),
+                        nonce: Some(U256::from(3)),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    contract,
+                    GenesisAccount {
+                        code: Some(Bytes(code.clone())),
+                        storage: Some(HashMap::from([(slot, H256::repeat_byte(0xab))])),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+        };
+        genesis.validate().expect("invalid genesis");
+        genesis.apply(&mut storage);
+
+        let mut reader = &storage;
+        assert_eq!(
+            u256_to_h256(U256::from(100)),
+            reader.read_value(&storage_key_for_eth_balance(&wallet))
+        );
+        assert_eq!(
+            u256_to_h256(U256::from(3)),
+            reader.read_value(&get_nonce_key(&wallet))
+        );
+        let code_hash = hash_bytecode(&code);
+        assert_eq!(code_hash, reader.read_value(&get_code_key(&contract)));
+        assert_eq!(Some(code), reader.load_factory_dep(code_hash));
+        assert_eq!(
+            H256::repeat_byte(0xab),
+            reader.read_value(&StorageKey::new(contract.into(), slot))
+        );
+    }
+}
@@ -1,18 +1,149 @@
 use std::sync::{Arc, RwLock};
 
-use crate::{fork::ForkSource, node::InMemoryNodeInner, utils::mine_empty_blocks};
+use crate::{
+    fork::{ForkFactory, ForkSource},
+    formatter::{self, ContractType},
+    node::{InMemoryNode, InMemoryNodeInner, L2ToL1Message},
+    utils::{mine_empty_blocks, validate_bytecode, HexQuantity},
+};
 use jsonrpc_core::{BoxFuture, Result};
 use jsonrpc_derive::rpc;
-use zksync_basic_types::{Address, U256, U64};
+use serde::Deserialize;
+use zksync_basic_types::{web3::signing::keccak256, Address, Bytes, PriorityOpId, H256, U256, U64};
 use zksync_core::api_server::web3::backend_jsonrpc::error::into_jsrpc_error;
 use zksync_state::ReadStorage;
 use zksync_types::{
-    get_nonce_key,
+    get_code_key, get_known_code_key, get_nonce_key,
+    l1::L1TxCommonData,
     utils::{decompose_full_nonce, nonces_to_full_nonce, storage_key_for_eth_balance},
+    AccountTreeId, Execute, ExecuteTransactionCommon, StorageKey, Transaction,
+    REQUIRED_L1_TO_L2_GAS_PER_PUBDATA_BYTE,
 };
-use zksync_utils::{h256_to_u256, u256_to_h256};
+use zksync_utils::{bytecode::hash_bytecode, h256_to_u256, u256_to_h256};
 use zksync_web3_decl::error::Web3Error;
 
+/// Parameters of `hardhat_reset`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResetRequest {
+    /// The network to fork, a local node is started when missing.
+    pub forking: Option<ForkingRequest>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ForkingRequest {
+    /// URL of the network, or `mainnet` / `testnet`.
+    pub json_rpc_url: String,
+    /// Block to fork at, as a number like hardhat - default: the latest block of the network.
+    pub block_number: Option<u64>,
+}
+
+/// Parameters of `hardhat_simulateL1ToL2Tx`, named after the arguments of
+/// `Mailbox.requestL2Transaction` on L1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct L1ToL2TxRequest {
+    /// Sender of the transaction on L1, used as is (contract senders are not aliased).
+    pub from: Address,
+    /// Contract called on L2, or the recipient of the deposit.
+    pub contract_l2: Address,
+    /// Value passed with the L2 call - default: 0.
+    pub l2_value: Option<U256>,
+    /// Calldata of the L2 call - default: empty.
+    pub calldata: Option<Bytes>,
+    /// Gas limit of the L2 call - default: `DEFAULT_L1_TO_L2_GAS_LIMIT`.
+    pub l2_gas_limit: Option<U256>,
+    /// Gas per pubdata byte - default: the value required by the bootloader for L1 transactions.
+    pub l2_gas_per_pubdata_byte_limit: Option<U256>,
+    /// Bytecodes of the contracts deployed by the L2 call.
+    pub factory_deps: Option<Vec<Bytes>>,
+    /// Receives the unspent gas on L2 - default: `from`.
+    pub refund_recipient: Option<Address>,
+    /// ETH minted to `from` on L2 before the call - default: `l2Value` plus the maximum fee.
+    pub mint_value: Option<U256>,
+}
+
+/// Gas limit of `hardhat_simulateL1ToL2Tx` transactions that don't set `l2GasLimit`.
+pub const DEFAULT_L1_TO_L2_GAS_LIMIT: u64 = 10_000_000;
+
+impl L1ToL2TxRequest {
+    /// Creates the L1 transaction of the priority operation `serial_id`, received at
+    /// `received_timestamp_ms` on the node clock. Its hash is derived from the serial id and the
+    /// parameters, so the same requests always get the same hashes. Fails if the minted value
+    /// overflows.
+    pub fn into_transaction(
+        self,
+        serial_id: u64,
+        gas_price: U256,
+        received_timestamp_ms: u64,
+    ) -> std::result::Result<Transaction, String> {
+        let l2_value = self.l2_value.unwrap_or_default();
+        let gas_limit = self
+            .l2_gas_limit
+            .unwrap_or_else(|| U256::from(DEFAULT_L1_TO_L2_GAS_LIMIT));
+        let gas_per_pubdata_limit = self
+            .l2_gas_per_pubdata_byte_limit
+            .unwrap_or_else(|| U256::from(REQUIRED_L1_TO_L2_GAS_PER_PUBDATA_BYTE));
+        let refund_recipient = self.refund_recipient.unwrap_or(self.from);
+        let to_mint = match self.mint_value {
+            Some(mint_value) => mint_value,
+            None => gas_limit
+                .checked_mul(gas_price)
+                .and_then(|fee| fee.checked_add(l2_value))
+                .ok_or_else(|| {
+                    format!(
+                        "the value to mint overflows: l2Value {} plus l2GasLimit {} at gas price {}",
+                        l2_value, gas_limit, gas_price
+                    )
+                })?,
+        };
+        let calldata = self.calldata.unwrap_or_default().0;
+
+        let mut preimage = serial_id.to_be_bytes().to_vec();
+        preimage.extend_from_slice(self.from.as_bytes());
+        preimage.extend_from_slice(self.contract_l2.as_bytes());
+        preimage.extend_from_slice(refund_recipient.as_bytes());
+        for value in [
+            l2_value,
+            gas_limit,
+            gas_per_pubdata_limit,
+            to_mint,
+            gas_price,
+        ] {
+            preimage.extend_from_slice(u256_to_h256(value).as_bytes());
+        }
+        preimage.extend_from_slice(&calldata);
+        let factory_deps = self
+            .factory_deps
+            .map(|deps| deps.into_iter().map(|dep| dep.0).collect::<Vec<_>>());
+        for dep in factory_deps.iter().flatten() {
+            preimage.extend_from_slice(hash_bytecode(dep).as_bytes());
+        }
+
+        Ok(Transaction {
+            common_data: ExecuteTransactionCommon::L1(L1TxCommonData {
+                sender: self.from,
+                serial_id: PriorityOpId(serial_id),
+                max_fee_per_gas: gas_price,
+                gas_limit,
+                gas_per_pubdata_limit,
+                canonical_tx_hash: H256(keccak256(&preimage)),
+                to_mint,
+                refund_recipient,
+                ..Default::default()
+            }),
+            execute: Execute {
+                contract_address: self.contract_l2,
+                calldata,
+                value: l2_value,
+                factory_deps,
+            },
+            received_timestamp_ms,
+        })
+    }
+}
+
 /// Implementation of HardhatNamespaceImpl
 pub struct HardhatNamespaceImpl<S> {
     node: Arc<RwLock<InMemoryNodeInner<S>>>,
@@ -32,26 +163,28 @@ pub trait HardhatNamespaceT {
     /// # Arguments
     ///
     /// * `address` - The `Address` whose balance will be edited
-    /// * `balance` - The new balance to set for the given address, in wei
+    /// * `balance` - The new balance to set for the given address, in wei, as a hex string with or without the `0x` prefix
     ///
     /// # Returns
     ///
     /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
     #[rpc(name = "hardhat_setBalance")]
-    fn set_balance(&self, address: Address, balance: U256) -> BoxFuture<Result<bool>>;
+    fn set_balance(&self, address: Address, balance: HexQuantity) -> BoxFuture<Result<bool>>;
 
-    /// Modifies an account's nonce by overwriting it.
+    /// Modifies an account's nonce by overwriting it. The nonce can't be moved backwards, as that
+    /// would make already executed transactions valid again.
     ///
     /// # Arguments
     ///
     /// * `address` - The `Address` whose nonce is to be changed
-    /// * `nonce` - The new nonce
+    /// * `nonce` - The new nonce, as a hex string with or without the `0x` prefix. Must fit in 32 bits
+    ///   and be greater than or equal to the current nonce
     ///
     /// # Returns
     ///
     /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
-    #[rpc(name = "hardhat_setNonce")]
-    fn set_nonce(&self, address: Address, balance: U256) -> BoxFuture<Result<bool>>;
+    #[rpc(name = "hardhat_setNonce", alias("anvil_setNonce"))]
+    fn set_nonce(&self, address: Address, nonce: HexQuantity) -> BoxFuture<Result<bool>>;
 
     /// Sometimes you may want to advance the latest block number of the network by a large number of blocks.
     /// One way to do this would be to call the evm_mine RPC method multiple times, but this is too slow if you want to mine thousands of blocks.
@@ -71,17 +204,169 @@ pub trait HardhatNamespaceT {
         num_blocks: Option<U64>,
         interval: Option<U64>,
     ) -> BoxFuture<Result<bool>>;
+
+    /// Hardhat Network allows you to send transactions impersonating specific account and contract addresses.
+    /// The signatures of the transactions sent from such an address are not verified.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to impersonate
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_impersonateAccount")]
+    fn impersonate_account(&self, address: Address) -> BoxFuture<Result<bool>>;
+
+    /// Use this method to stop impersonating an account after having previously used `hardhat_impersonateAccount`.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to stop impersonating
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` that is `true` if the account was being impersonated.
+    #[rpc(name = "hardhat_stopImpersonatingAccount")]
+    fn stop_impersonating_account(&self, address: Address) -> BoxFuture<Result<bool>>;
+
+    /// Modifies the bytecode stored at an account's address. The storage of the account is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address where the given code should be stored
+    /// * `code` - The zkSync bytecode to store, an odd number of 32-byte words. Empty code clears the account's code
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_setCode")]
+    fn set_code(&self, address: Address, code: Bytes) -> BoxFuture<Result<bool>>;
+
+    /// Writes a single storage slot of an account.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the account
+    /// * `slot` - The 32-byte storage slot to write
+    /// * `value` - The 32-byte value to store
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_setStorageAt", alias("anvil_setStorageAt"))]
+    fn set_storage_at(
+        &self,
+        address: Address,
+        slot: Bytes,
+        value: Bytes,
+    ) -> BoxFuture<Result<bool>>;
+
+    /// Names an address in the printed call traces, overriding any existing name.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to name
+    /// * `name` - The name printed instead of the address
+    /// * `contract_type` - One of `System`, `Precompile`, `Popular` or `Unknown`
+    /// * `persist` - If true, the entry is also saved to the `--address-map` file - default: false
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_addKnownAddress")]
+    fn add_known_address(
+        &self,
+        address: Address,
+        name: String,
+        contract_type: ContractType,
+        persist: Option<bool>,
+    ) -> BoxFuture<Result<bool>>;
+
+    /// Sets the L2 gas price, returned by `eth_gasPrice` and used for fee estimation and to
+    /// validate the following transactions. Both the reported and the fair L2 gas prices are
+    /// set to the new value.
+    ///
+    /// # Arguments
+    ///
+    /// * `gas_price` - The new gas price, in wei, as a hex string with or without the `0x` prefix.
+    ///   Must be greater than 0 and fit in 64 bits
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_setMinGasPrice", alias("anvil_setMinGasPrice"))]
+    fn set_min_gas_price(&self, gas_price: HexQuantity) -> BoxFuture<Result<bool>>;
+
+    /// Discards the whole state of the node and starts again from scratch, optionally forking
+    /// another network. All the snapshots are invalidated.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - `{ "forking": { "jsonRpcUrl": ..., "blockNumber": ... } }` to fork a network,
+    ///   a local node is started when `forking` (or the whole request) is missing
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "hardhat_reset", alias("anvil_reset"))]
+    fn reset(&self, request: Option<ResetRequest>) -> BoxFuture<Result<bool>>;
+
+    /// Simulates a call to `Mailbox.requestL2Transaction` on L1, like a deposit: the L1
+    /// transaction is added to the priority queue, and executed by the bootloader with the next
+    /// block (right away with automine).
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The L1 sender and the arguments of `requestL2Transaction`
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the hash of the L2 transaction.
+    #[rpc(name = "hardhat_simulateL1ToL2Tx")]
+    fn simulate_l1_to_l2_tx(&self, request: L1ToL2TxRequest) -> BoxFuture<Result<H256>>;
+
+    /// Returns the messages sent to L1 with `L1Messenger.sendToL1` in a range of blocks, whose
+    /// proofs are returned by `zks_getL2ToL1LogProof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_block` - The first block of the range
+    /// * `to_block` - The last block of the range, included
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the messages, in execution order.
+    #[rpc(name = "hardhat_getL2ToL1Messages")]
+    fn get_l2_to_l1_messages(
+        &self,
+        from_block: U64,
+        to_block: U64,
+    ) -> BoxFuture<Result<Vec<L2ToL1Message>>>;
+}
+
+/// Converts a storage slot or value argument into a `H256`, requiring exactly 32 bytes.
+fn storage_word(name: &str, bytes: &Bytes) -> Result<H256> {
+    if bytes.0.len() != 32 {
+        return Err(jsonrpc_core::Error::invalid_params(format!(
+            "{} must be a 32-byte hex string, got {} bytes",
+            name,
+            bytes.0.len()
+        )));
+    }
+    Ok(H256::from_slice(&bytes.0))
 }
 
-impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> HardhatNamespaceT
+impl<S: Send + Sync + 'static + ForkFactory + std::fmt::Debug> HardhatNamespaceT
     for HardhatNamespaceImpl<S>
 {
     fn set_balance(
         &self,
         address: Address,
-        balance: U256,
+        balance: HexQuantity,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
         let inner = Arc::clone(&self.node);
+        let balance = balance.0;
 
         Box::pin(async move {
             match inner.write() {
@@ -108,31 +393,32 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> HardhatNamespaceT
     fn set_nonce(
         &self,
         address: Address,
-        nonce: U256,
+        nonce: HexQuantity,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
         let inner = Arc::clone(&self.node);
+        let nonce = nonce.0;
         Box::pin(async move {
+            if nonce > U256::from(u32::MAX) {
+                return Err(jsonrpc_core::Error::invalid_params(format!(
+                    "Nonce {} does not fit in 32 bits",
+                    nonce
+                )));
+            }
             match inner.write() {
                 Ok(mut inner_guard) => {
                     let nonce_key = get_nonce_key(&address);
                     let full_nonce = inner_guard.fork_storage.read_value(&nonce_key);
-                    let (mut account_nonce, mut deployment_nonce) =
+                    let (account_nonce, deployment_nonce) =
                         decompose_full_nonce(h256_to_u256(full_nonce));
-                    if account_nonce >= nonce {
+                    if nonce < account_nonce {
                         return Err(jsonrpc_core::Error::invalid_params(format!(
                             "Account Nonce is already set to a higher value ({}, requested {})",
                             account_nonce, nonce
                         )));
                     }
-                    account_nonce = nonce;
-                    if deployment_nonce >= nonce {
-                        return Err(jsonrpc_core::Error::invalid_params(format!(
-                            "Deployment Nonce is already set to a higher value ({}, requested {})",
-                            deployment_nonce, nonce
-                        )));
-                    }
-                    deployment_nonce = nonce;
-                    let enforced_full_nonce = nonces_to_full_nonce(account_nonce, deployment_nonce);
+                    // keep the deployment nonce from going backwards, so that CREATE addresses aren't reused
+                    let enforced_full_nonce =
+                        nonces_to_full_nonce(nonce, deployment_nonce.max(nonce));
                     log::info!(
                         "👷 Nonces for address {:?} have been set to {}",
                         address,
@@ -166,7 +452,11 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> HardhatNamespaceT
                             "Number of blocks must be greater than 0".to_string(),
                         ));
                     }
-                    mine_empty_blocks(&mut inner, num_blocks.as_u64(), interval_ms.as_u64());
+                    mine_empty_blocks(&mut inner, num_blocks.as_u64(), interval_ms.as_u64())
+                        .map_err(|err| {
+                            log::error!("failed mining empty blocks: {}", err);
+                            into_jsrpc_error(Web3Error::InternalError)
+                        })?;
                     log::info!("👷 Mined {} blocks", num_blocks);
                     Ok(true)
                 }
@@ -174,15 +464,277 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> HardhatNamespaceT
             }
         })
     }
+
+    fn impersonate_account(&self, address: Address) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner) => {
+                    if inner.impersonated_accounts.insert(address) {
+                        log::info!("🕵️ Account {:?} has been impersonated", address);
+                    }
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn stop_impersonating_account(&self, address: Address) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            match inner.write() {
+                Ok(mut inner) => {
+                    let was_impersonated = inner.impersonated_accounts.remove(&address);
+                    if was_impersonated {
+                        log::info!("🕵️ Stopped impersonating account {:?}", address);
+                    }
+                    Ok(was_impersonated)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn set_code(&self, address: Address, code: Bytes) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let code = code.0;
+            if !code.is_empty() {
+                validate_bytecode(&code).map_err(jsonrpc_core::Error::invalid_params)?;
+            }
+            match inner.write() {
+                Ok(mut inner) => {
+                    let code_key = get_code_key(&address);
+                    let code_hash = if code.is_empty() {
+                        H256::zero()
+                    } else {
+                        hash_bytecode(&code)
+                    };
+                    let current_miniblock = inner.current_miniblock;
+                    inner
+                        .record_code_changes([(&code_key, &code_hash)], current_miniblock)
+                        .map_err(|err| {
+                            log::error!("failed recording code change: {}", err);
+                            into_jsrpc_error(Web3Error::InternalError)
+                        })?;
+
+                    if code.is_empty() {
+                        inner.fork_storage.set_value(code_key, H256::zero());
+                        log::info!("👷 Code for address {:?} has been cleared", address);
+                        return Ok(true);
+                    }
+
+                    inner.fork_storage.store_factory_dep(code_hash, code);
+                    inner
+                        .fork_storage
+                        .set_value(get_known_code_key(&code_hash), u256_to_h256(U256::one()));
+                    inner.fork_storage.set_value(code_key, code_hash);
+                    log::info!(
+                        "👷 Code for address {:?} has been set to {:?}",
+                        address,
+                        code_hash
+                    );
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn set_storage_at(
+        &self,
+        address: Address,
+        slot: Bytes,
+        value: Bytes,
+    ) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let slot = storage_word("slot", &slot)?;
+            let value = storage_word("value", &value)?;
+            match inner.write() {
+                Ok(mut inner) => {
+                    let key = StorageKey::new(AccountTreeId::new(address), slot);
+                    inner.fork_storage.set_value(key, value);
+                    log::info!(
+                        "👷 Storage slot {:?} of address {:?} has been set to {:?}",
+                        slot,
+                        address,
+                        value
+                    );
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn add_known_address(
+        &self,
+        address: Address,
+        name: String,
+        contract_type: ContractType,
+        persist: Option<bool>,
+    ) -> BoxFuture<Result<bool>> {
+        Box::pin(async move {
+            let persist_to = if persist.unwrap_or(false) {
+                let path = formatter::address_map_path().ok_or_else(|| {
+                    jsonrpc_core::Error::invalid_params(
+                        "no address map to persist to, start the node with --address-map",
+                    )
+                })?;
+                Some(path)
+            } else {
+                None
+            };
+
+            formatter::add_known_address(
+                address,
+                name.clone(),
+                contract_type,
+                persist_to.as_deref(),
+            )
+            .map_err(|err| {
+                log::error!("failed adding known address {:?}: {}", address, err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })?;
+            log::info!("👷 Address {:?} is now known as {}", address, name);
+            Ok(true)
+        })
+    }
+
+    fn set_min_gas_price(&self, gas_price: HexQuantity) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let gas_price = gas_price.0;
+            if gas_price.is_zero() || gas_price > U256::from(u64::MAX) {
+                return Err(jsonrpc_core::Error::invalid_params(format!(
+                    "gas price must be greater than 0 and fit in 64 bits, got {}",
+                    gas_price
+                )));
+            }
+            match inner.write() {
+                Ok(mut inner) => {
+                    inner.l2_gas_price = gas_price.as_u64();
+                    inner.fair_l2_gas_price = gas_price.as_u64();
+                    log::info!("👷 L2 gas price has been set to {} wei", gas_price);
+                    Ok(true)
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    fn reset(&self, request: Option<ResetRequest>) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let forking = request.unwrap_or_default().forking;
+            let fork = match forking {
+                None => None,
+                Some(forking) => {
+                    let create_fork = {
+                        let inner = inner
+                            .read()
+                            .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+                        let fork_storage = inner
+                            .fork_storage
+                            .inner
+                            .read()
+                            .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+                        S::create_fork(
+                            &forking.json_rpc_url,
+                            forking.block_number,
+                            fork_storage.fork.as_ref().map(|fork| &fork.fork_source),
+                        )
+                    };
+                    let fork = create_fork.await.map_err(|err| {
+                        jsonrpc_core::Error::invalid_params(format!(
+                            "failed forking {}: {}",
+                            forking.json_rpc_url, err
+                        ))
+                    })?;
+                    Some(fork)
+                }
+            };
+
+            let mut inner = inner
+                .write()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            inner.reset(fork).map_err(|err| {
+                log::error!("failed resetting the node: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })?;
+            log::info!("👷 Node has been reset");
+            Ok(true)
+        })
+    }
+
+    fn simulate_l1_to_l2_tx(&self, request: L1ToL2TxRequest) -> BoxFuture<Result<H256>> {
+        let inner = Arc::clone(&self.node);
+        let node = InMemoryNode::from_inner(Arc::clone(&self.node));
+        Box::pin(async move {
+            let tx = {
+                let mut inner = inner
+                    .write()
+                    .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+                let tx = request
+                    .into_transaction(
+                        inner.next_priority_op_id,
+                        U256::from(inner.l2_gas_price),
+                        inner.current_timestamp.saturating_mul(1000),
+                    )
+                    .map_err(jsonrpc_core::Error::invalid_params)?;
+                inner.next_priority_op_id += 1;
+                tx
+            };
+            let tx_hash = tx.hash();
+            node.submit_priority_tx(tx).map_err(|err| {
+                into_jsrpc_error(Web3Error::SubmitTransactionError(
+                    format!("Execution error: {}", err),
+                    tx_hash.as_bytes().to_vec(),
+                ))
+            })?;
+            Ok(tx_hash)
+        })
+    }
+
+    fn get_l2_to_l1_messages(
+        &self,
+        from_block: U64,
+        to_block: U64,
+    ) -> BoxFuture<Result<Vec<L2ToL1Message>>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            if from_block > to_block {
+                return Err(jsonrpc_core::Error::invalid_params(format!(
+                    "fromBlock {} is after toBlock {}",
+                    from_block, to_block
+                )));
+            }
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(reader.l2_to_l1_messages(from_block.as_u64(), to_block.as_u64()))
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode};
+    use crate::{
+        deps::system_contracts::bytecode_from_slice,
+        http_fork_source::HttpForkSource,
+        node::{DEFAULT_SENDER_BALANCE, RICH_WALLETS},
+        testing::{self, ForkBlockConfig, MockServer},
+    };
     use std::str::FromStr;
+    use zksync_basic_types::{MiniblockNumber, Nonce};
     use zksync_core::api_server::web3::backend_jsonrpc::namespaces::eth::EthNamespaceT;
-    use zksync_types::api::BlockNumber;
+    use zksync_types::{
+        api::BlockNumber, fee::Fee, l2::L2Tx, transaction_request::CallRequest, L2ChainId,
+        PackedEthSignature,
+    };
 
     #[tokio::test]
     async fn test_set_balance() {
@@ -193,7 +745,7 @@ mod tests {
         let balance_before = node.get_balance(address, None).await.unwrap();
 
         let result = hardhat
-            .set_balance(address, U256::from(1337))
+            .set_balance(address, U256::from(1337).into())
             .await
             .unwrap();
         assert!(result);
@@ -203,6 +755,97 @@ mod tests {
         assert_ne!(balance_before, balance_after);
     }
 
+    #[tokio::test]
+    async fn test_set_balance_funds_transfer() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let private_key = H256::random();
+        let from_account = PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        let to_account = Address::random();
+
+        let result = hardhat
+            .set_balance(from_account, U256::from(10u128.pow(18)).into())
+            .await
+            .unwrap();
+        assert!(result);
+
+        let mut tx = L2Tx::new_signed(
+            to_account,
+            vec![],
+            Nonce(0),
+            Fee {
+                gas_limit: U256::from(1_000_000),
+                max_fee_per_gas: U256::from(250_000_000),
+                max_priority_fee_per_gas: U256::from(250_000_000),
+                gas_per_pubdata_limit: U256::from(20000),
+            },
+            U256::from(1337),
+            L2ChainId(260),
+            &private_key,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+        tx.set_input(vec![], H256::repeat_byte(0x01));
+        node.apply_txs(vec![tx])
+            .expect("failed applying transfer from funded account");
+
+        let balance = node.get_balance(to_account, None).await.unwrap();
+        assert_eq!(balance, U256::from(1337));
+    }
+
+    #[tokio::test]
+    async fn test_set_balance_accepts_bare_hex_and_pays_for_gas() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(HardhatNamespaceImpl::new(node.get_inner()).to_delegate());
+        let private_key = H256::random();
+        let from_account = PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        let huge_balance = U256::from(10u128.pow(30));
+
+        let response = io
+            .handle_request(&format!(
+                r#"{{"jsonrpc":"2.0","method":"hardhat_setBalance","params":["{:#x}","{:x}"],"id":1}}"#,
+                from_account, huge_balance
+            ))
+            .await
+            .expect("no response");
+        assert!(
+            response.contains("\"result\":true"),
+            "unexpected response: {}",
+            response
+        );
+        assert_eq!(
+            huge_balance,
+            node.get_balance(from_account, None).await.unwrap()
+        );
+
+        let mut tx = L2Tx::new_signed(
+            Address::random(),
+            vec![],
+            Nonce(0),
+            Fee {
+                gas_limit: U256::from(1_000_000),
+                max_fee_per_gas: U256::from(250_000_000),
+                max_priority_fee_per_gas: U256::from(250_000_000),
+                gas_per_pubdata_limit: U256::from(20000),
+            },
+            U256::zero(),
+            L2ChainId(260),
+            &private_key,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+        tx.set_input(vec![], H256::repeat_byte(0x01));
+        node.apply_txs(vec![tx])
+            .expect("failed applying tx paid by the funded account");
+
+        assert!(node.get_balance(from_account, None).await.unwrap() < huge_balance);
+    }
+
     #[tokio::test]
     async fn test_set_nonce() {
         let address = Address::from_str("0x36615Cf349d7F6344891B1e7CA7C72883F5dc049").unwrap();
@@ -211,16 +854,117 @@ mod tests {
 
         let nonce_before = node.get_transaction_count(address, None).await.unwrap();
 
-        let result = hardhat.set_nonce(address, U256::from(1337)).await.unwrap();
+        let result = hardhat
+            .set_nonce(address, U256::from(1337).into())
+            .await
+            .unwrap();
         assert!(result);
 
         let nonce_after = node.get_transaction_count(address, None).await.unwrap();
         assert_eq!(nonce_after, U256::from(1337));
         assert_ne!(nonce_before, nonce_after);
 
+        // setting the same nonce again is a no-op
+        let result = hardhat
+            .set_nonce(address, U256::from(1337).into())
+            .await
+            .unwrap();
+        assert!(result);
+
         // setting nonce lower than the current one should fail
-        let result = hardhat.set_nonce(address, U256::from(1336)).await;
+        let result = hardhat.set_nonce(address, U256::from(1336).into()).await;
         assert!(result.is_err());
+        let nonce_after = node.get_transaction_count(address, None).await.unwrap();
+        assert_eq!(nonce_after, U256::from(1337));
+    }
+
+    #[tokio::test]
+    async fn test_set_nonce_applies_to_impersonated_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let impersonated = Address::random();
+        node.set_rich_account(impersonated);
+        let build_tx = |nonce: u32| {
+            let mut tx = L2Tx::new_signed(
+                Address::random(),
+                vec![],
+                Nonce(nonce),
+                Fee {
+                    gas_limit: U256::from(1_000_000),
+                    max_fee_per_gas: U256::from(250_000_000),
+                    max_priority_fee_per_gas: U256::from(250_000_000),
+                    gas_per_pubdata_limit: U256::from(20000),
+                },
+                U256::from(1),
+                L2ChainId(260),
+                &H256::random(),
+                None,
+                Default::default(),
+            )
+            .unwrap();
+            tx.set_input(vec![], H256::from_low_u64_be(nonce as u64 + 1));
+            tx.common_data.initiator_address = impersonated;
+            tx
+        };
+
+        assert!(hardhat.impersonate_account(impersonated).await.unwrap());
+        assert!(hardhat
+            .set_nonce(impersonated, U256::from(5).into())
+            .await
+            .unwrap());
+
+        assert!(node.apply_txs(vec![build_tx(0)]).is_err());
+        node.apply_txs(vec![build_tx(5)])
+            .expect("failed applying tx with the overridden nonce");
+        assert_eq!(
+            U256::from(6),
+            node.get_transaction_count(impersonated, None)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_nonce_rejects_overflowing_nonce() {
+        let address = Address::random();
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+
+        let result = hardhat
+            .set_nonce(address, (U256::from(u32::MAX) + 1).into())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(address, None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anvil_set_nonce_accepts_bare_hex() {
+        let address = Address::random();
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(HardhatNamespaceImpl::new(node.get_inner()).to_delegate());
+
+        let response = io
+            .handle_request(&format!(
+                r#"{{"jsonrpc":"2.0","method":"anvil_setNonce","params":["{:#x}","2a"],"id":1}}"#,
+                address
+            ))
+            .await
+            .expect("no response");
+
+        assert!(
+            response.contains("\"result\":true"),
+            "unexpected response: {}",
+            response
+        );
+        assert_eq!(
+            U256::from(42),
+            node.get_transaction_count(address, None).await.unwrap()
+        );
     }
 
     #[tokio::test]
@@ -299,4 +1043,664 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_impersonate_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let to_impersonate =
+            Address::from_str("0xd8da6bf26964af9d7eed9e03e53415d37aa96045").unwrap();
+        node.set_rich_account(to_impersonate);
+
+        // The transaction is signed by a random key, so its signature doesn't match the sender.
+        let mut tx = L2Tx::new_signed(
+            Address::random(),
+            vec![],
+            Nonce(0),
+            Fee {
+                gas_limit: U256::from(1_000_000),
+                max_fee_per_gas: U256::from(250_000_000),
+                max_priority_fee_per_gas: U256::from(250_000_000),
+                gas_per_pubdata_limit: U256::from(20000),
+            },
+            U256::from(1),
+            L2ChainId(260),
+            &H256::random(),
+            None,
+            Default::default(),
+        )
+        .unwrap();
+        tx.set_input(vec![], H256::repeat_byte(0x01));
+        tx.common_data.initiator_address = to_impersonate;
+
+        assert!(node.apply_txs(vec![tx.clone()]).is_err());
+
+        let result = hardhat
+            .impersonate_account(to_impersonate)
+            .await
+            .expect("impersonate_account");
+        assert!(result);
+        node.apply_txs(vec![tx])
+            .expect("failed applying impersonated tx");
+
+        let nonce = node
+            .get_transaction_count(to_impersonate, None)
+            .await
+            .unwrap();
+        assert_eq!(U256::from(1), nonce);
+    }
+
+    #[tokio::test]
+    async fn test_impersonated_account_transfers_value() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let whale = Address::random();
+        let recipient = Address::random();
+        node.set_rich_account(whale);
+        let build_transfer = |nonce: u32, input_hash: H256| {
+            // signed by a random key, the signature doesn't match the whale
+            let mut tx = L2Tx::new_signed(
+                recipient,
+                vec![],
+                Nonce(nonce),
+                Fee {
+                    gas_limit: U256::from(1_000_000),
+                    max_fee_per_gas: U256::from(250_000_000),
+                    max_priority_fee_per_gas: U256::from(250_000_000),
+                    gas_per_pubdata_limit: U256::from(20000),
+                },
+                U256::from(1000),
+                L2ChainId(260),
+                &H256::random(),
+                None,
+                Default::default(),
+            )
+            .unwrap();
+            tx.set_input(vec![], input_hash);
+            tx.common_data.initiator_address = whale;
+            tx
+        };
+        let whale_balance_before = node.get_balance(whale, None).await.unwrap();
+
+        assert!(hardhat.impersonate_account(whale).await.unwrap());
+        node.apply_txs(vec![build_transfer(0, H256::repeat_byte(0x01))])
+            .expect("failed applying impersonated transfer");
+
+        assert_eq!(
+            U256::from(1000),
+            node.get_balance(recipient, None).await.unwrap()
+        );
+        assert!(node.get_balance(whale, None).await.unwrap() < whale_balance_before - 1000);
+
+        assert!(hardhat.stop_impersonating_account(whale).await.unwrap());
+        assert!(node
+            .apply_txs(vec![build_transfer(1, H256::repeat_byte(0x02))])
+            .is_err());
+        assert_eq!(
+            U256::from(1000),
+            node.get_balance(recipient, None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_impersonating_account() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let address = Address::random();
+
+        assert!(hardhat.impersonate_account(address).await.unwrap());
+        assert!(hardhat.stop_impersonating_account(address).await.unwrap());
+        assert!(!hardhat.stop_impersonating_account(address).await.unwrap());
+        assert!(!node
+            .get_inner()
+            .read()
+            .unwrap()
+            .impersonated_accounts
+            .contains(&address));
+    }
+
+    #[tokio::test]
+    async fn test_set_code() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let address = Address::random();
+        let slot = StorageKey::new(AccountTreeId::new(address), H256::zero());
+        node.get_inner()
+            .write()
+            .unwrap()
+            .fork_storage
+            .set_value(slot, H256::repeat_byte(0x11));
+        let code = vec![0x42; 32 * 3];
+
+        let result = hardhat
+            .set_code(address, Bytes::from(code.clone()))
+            .await
+            .expect("set_code");
+        assert!(result);
+
+        let actual_code = node.get_code(address, None).await.unwrap();
+        assert_eq!(code, actual_code.0);
+        let storage = node.get_storage(address, U256::zero(), None).await.unwrap();
+        assert_eq!(H256::repeat_byte(0x11), storage);
+    }
+
+    #[tokio::test]
+    async fn test_set_code_is_executed_by_calls() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let address = Address::random();
+        let code = bytecode_from_slice(
+            "L2EthToken",
+            include_bytes!("deps/contracts/L2EthToken.json"),
+        );
+        let decimals_call = CallRequest {
+            to: Some(address),
+            // decimals()
+            data: Some(vec![0x31, 0x3c, 0xe5, 0x67].into()),
+            ..Default::default()
+        };
+
+        assert!(hardhat
+            .set_code(address, Bytes::from(code))
+            .await
+            .expect("set_code"));
+        let result = node
+            .call(decimals_call, None)
+            .await
+            .expect("failed calling the injected code");
+        assert_eq!(u256_to_h256(U256::from(18)).as_bytes(), result.0.as_slice());
+
+        assert!(hardhat
+            .set_code(address, Bytes::default())
+            .await
+            .expect("set_code"));
+        assert!(node.get_code(address, None).await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_code_rejects_invalid_bytecode() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let address = Address::random();
+
+        for code in [vec![0x42; 31], vec![0x42; 64]] {
+            let result = hardhat.set_code(address, Bytes::from(code.clone())).await;
+            assert!(result.is_err(), "accepted code of length {}", code.len());
+        }
+        assert!(node.get_code(address, None).await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_storage_at_is_read_by_get_storage_and_calls() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let address = Address::random();
+        let code = bytecode_from_slice(
+            "L2EthToken",
+            include_bytes!("deps/contracts/L2EthToken.json"),
+        );
+        assert!(hardhat
+            .set_code(address, Bytes::from(code))
+            .await
+            .expect("set_code"));
+
+        // `totalSupply` is stored in slot 1, after the `balance` mapping
+        let slot = u256_to_h256(U256::one());
+        let value = u256_to_h256(U256::from(42));
+        assert!(hardhat
+            .set_storage_at(
+                address,
+                Bytes::from(slot.as_bytes().to_vec()),
+                Bytes::from(value.as_bytes().to_vec())
+            )
+            .await
+            .expect("set_storage_at"));
+
+        let storage = node.get_storage(address, U256::one(), None).await.unwrap();
+        assert_eq!(value, storage);
+        let total_supply_call = CallRequest {
+            to: Some(address),
+            // totalSupply()
+            data: Some(vec![0x18, 0x16, 0x0d, 0xdd].into()),
+            ..Default::default()
+        };
+        let result = node
+            .call(total_supply_call, None)
+            .await
+            .expect("failed calling totalSupply");
+        assert_eq!(value.as_bytes(), result.0.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_set_storage_at_rejects_malformed_words() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(hardhat.to_delegate());
+
+        let response = io
+            .handle_request(
+                r#"{"jsonrpc":"2.0","method":"anvil_setStorageAt","params":["0x36615Cf349d7F6344891B1e7CA7C72883F5dc049","0x01","0x0000000000000000000000000000000000000000000000000000000000000001"],"id":1}"#,
+            )
+            .await
+            .unwrap();
+        assert!(
+            response.contains("slot must be a 32-byte hex string, got 1 bytes"),
+            "{}",
+            response
+        );
+
+        let result = hardhat
+            .set_storage_at(
+                Address::random(),
+                Bytes::from(vec![0; 32]),
+                Bytes::from(vec![0; 33]),
+            )
+            .await
+            .expect_err("accepted a 33-byte value");
+        assert!(result
+            .message
+            .contains("value must be a 32-byte hex string"));
+    }
+
+    #[tokio::test]
+    async fn test_add_known_address_requires_address_map_to_persist() {
+        let _settings = testing::GLOBAL_SETTINGS_LOCK.lock().await;
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(hardhat.to_delegate());
+        let address = Address::repeat_byte(0xd1);
+
+        let response = io
+            .handle_request(&format!(
+                r#"{{"jsonrpc":"2.0","method":"hardhat_addKnownAddress","params":["{:#x}","MyToken","Popular"],"id":1}}"#,
+                address
+            ))
+            .await
+            .unwrap();
+        assert!(response.contains(r#""result":true"#), "{}", response);
+
+        formatter::clear_address_map_path();
+        let error = hardhat
+            .add_known_address(
+                address,
+                "MyVault".to_string(),
+                ContractType::Popular,
+                Some(true),
+            )
+            .await
+            .expect_err("persisted without an address map");
+        assert_eq!(jsonrpc_core::ErrorCode::InvalidParams, error.code);
+
+        let dir = tempdir::TempDir::new("address-map").expect("failed creating temporary dir");
+        let path = dir.path().join("address_map.json");
+        std::fs::write(&path, "[]").expect("failed writing address map");
+        formatter::load_address_map_from_file(&path).expect("failed loading address map");
+        let persisted = hardhat
+            .add_known_address(
+                address,
+                "MyVault".to_string(),
+                ContractType::Popular,
+                Some(true),
+            )
+            .await;
+        formatter::clear_address_map_path();
+
+        assert!(persisted.expect("failed persisting known address"));
+        let persisted: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!([[format!("{:#x}", address), "MyVault", "Popular"]]),
+            persisted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_min_gas_price_updates_gas_price_and_estimates() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let request = CallRequest {
+            from: Some(Address::repeat_byte(0x01)),
+            to: Some(Address::repeat_byte(0x02)),
+            value: Some(U256::zero()),
+            ..Default::default()
+        };
+
+        for gas_price in [500_000_000u64, 100_000_000] {
+            assert!(hardhat
+                .set_min_gas_price(HexQuantity(U256::from(gas_price)))
+                .await
+                .expect("hardhat_setMinGasPrice failed"));
+
+            let fee = node
+                .get_inner()
+                .read()
+                .unwrap()
+                .estimate_gas_impl(request.clone())
+                .expect("failed estimating fee");
+            assert_eq!(U256::from(gas_price), node.gas_price().await.unwrap());
+            assert_eq!(U256::from(gas_price), fee.max_fee_per_gas);
+        }
+
+        assert!(hardhat
+            .set_min_gas_price(HexQuantity(U256::zero()))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_discards_the_state_and_can_fork() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let rich_wallet = Address::from_str(RICH_WALLETS[0].0).unwrap();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            let snapshot = inner.snapshot().expect("failed taking snapshot");
+            inner.snapshots.insert(U64::one(), snapshot);
+            inner.automine = false;
+        }
+        hardhat
+            .simulate_l1_to_l2_tx(deposit_request(rich_wallet, Address::repeat_byte(0x02)))
+            .await
+            .expect("hardhat_simulateL1ToL2Tx failed");
+
+        assert!(hardhat.reset(None).await.expect("hardhat_reset failed"));
+        {
+            let inner = node.get_inner();
+            let inner = inner.read().unwrap();
+            assert_eq!(0, inner.current_miniblock);
+            assert!(inner.tx_results.is_empty());
+            assert!(inner.snapshots.is_empty());
+            assert!(inner.priority_queue.is_empty());
+            assert_eq!(0, inner.next_priority_op_id);
+        }
+        assert_eq!(
+            U256::from(DEFAULT_SENDER_BALANCE),
+            node.get_balance(rich_wallet, None).await.unwrap()
+        );
+
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let request = ResetRequest {
+            forking: Some(ForkingRequest {
+                json_rpc_url: mock_server.url(),
+                block_number: None,
+            }),
+        };
+        assert!(hardhat
+            .reset(Some(request))
+            .await
+            .expect("hardhat_reset with forking failed"));
+        let inner = node.get_inner();
+        let inner = inner.read().unwrap();
+        assert_eq!(10, inner.current_miniblock);
+        assert!(inner.blocks.contains_key(&H256::repeat_byte(0xab)));
+    }
+
+    #[tokio::test]
+    async fn test_reset_applies_the_genesis_again() {
+        let address = Address::repeat_byte(0x01);
+        let genesis = crate::genesis::Genesis {
+            alloc: std::collections::HashMap::from([(
+                address,
+                crate::genesis::GenesisAccount {
+                    balance: U256::from(1000),
+                    ..Default::default()
+                },
+            )]),
+        };
+        let node = InMemoryNode::<HttpForkSource>::new(
+            None,
+            &crate::node::NodeConfig {
+                genesis: Some(genesis),
+                ..Default::default()
+            },
+        );
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        assert_eq!(
+            U256::from(1000),
+            node.get_balance(address, None).await.unwrap()
+        );
+        hardhat
+            .set_balance(address, HexQuantity(U256::from(5)))
+            .await
+            .expect("hardhat_setBalance failed");
+
+        assert!(hardhat.reset(None).await.expect("hardhat_reset failed"));
+
+        assert_eq!(
+            U256::from(1000),
+            node.get_balance(address, None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_rejects_unreachable_network() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        let result = hardhat
+            .reset(Some(ResetRequest {
+                forking: Some(ForkingRequest {
+                    json_rpc_url: String::from("http://127.0.0.1:1"),
+                    block_number: None,
+                }),
+            }))
+            .await;
+
+        assert_eq!(
+            jsonrpc_core::ErrorCode::InvalidParams,
+            result.expect_err("reset didn't fail").code
+        );
+        assert_eq!(1, node.get_inner().read().unwrap().current_miniblock);
+    }
+
+    #[tokio::test]
+    async fn test_get_l2_to_l1_messages_in_block_range() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let first = testing::send_l1_message(&node, b"first");
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let second = testing::send_l1_message(&node, b"second");
+
+        let messages = hardhat
+            .get_l2_to_l1_messages(U64::from(1), U64::from(3))
+            .await
+            .expect("hardhat_getL2ToL1Messages failed");
+
+        assert_eq!(2, messages.len());
+        assert_eq!(first, messages[0].transaction_hash);
+        assert_eq!(U64::from(1), messages[0].block_number);
+        assert_eq!(Bytes(b"first".to_vec()), messages[0].message);
+        assert_eq!(H256(keccak256(b"first")), messages[0].hash);
+        assert_eq!(second, messages[1].transaction_hash);
+        assert_eq!(U64::from(3), messages[1].block_number);
+        assert_eq!(
+            1,
+            hardhat
+                .get_l2_to_l1_messages(U64::from(2), U64::from(100))
+                .await
+                .expect("hardhat_getL2ToL1Messages failed")
+                .len()
+        );
+        assert!(hardhat
+            .get_l2_to_l1_messages(U64::from(3), U64::from(1))
+            .await
+            .is_err());
+    }
+
+    fn deposit_request(from: Address, to: Address) -> L1ToL2TxRequest {
+        L1ToL2TxRequest {
+            from,
+            contract_l2: to,
+            l2_value: Some(U256::from(1000)),
+            calldata: None,
+            l2_gas_limit: None,
+            l2_gas_per_pubdata_byte_limit: None,
+            factory_deps: None,
+            refund_recipient: None,
+            mint_value: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulate_l1_to_l2_tx_executes_the_deposit() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        let from = Address::repeat_byte(0x01);
+        let to = Address::repeat_byte(0x02);
+
+        let tx_hash = hardhat
+            .simulate_l1_to_l2_tx(deposit_request(from, to))
+            .await
+            .expect("hardhat_simulateL1ToL2Tx failed");
+
+        assert_eq!(U256::from(1000), node.get_balance(to, None).await.unwrap());
+        let receipt = node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .unwrap()
+            .expect("receipt not found");
+        assert_eq!(Some(U64::from(1)), receipt.status);
+        assert_eq!(Some(U64::from(1)), receipt.block_number);
+        let tx = node
+            .get_transaction_by_hash(tx_hash)
+            .await
+            .unwrap()
+            .expect("transaction not found");
+        assert_eq!(Some(from), tx.from);
+        assert_eq!(Some(U64::from(255)), tx.transaction_type);
+        assert_eq!(U256::zero(), tx.nonce);
+
+        let details = node
+            .get_inner()
+            .read()
+            .unwrap()
+            .block_details(MiniblockNumber(1))
+            .expect("failed getting block details")
+            .expect("no block details");
+        assert_eq!(1, details.base.l1_tx_count);
+        assert_eq!(0, details.base.l2_tx_count);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_l1_to_l2_tx_hashes_are_deterministic() {
+        let from = Address::repeat_byte(0x01);
+        let to = Address::repeat_byte(0x02);
+        let mut hashes = vec![];
+        for _ in 0..2 {
+            let node = InMemoryNode::<HttpForkSource>::default();
+            let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+            let first = hardhat
+                .simulate_l1_to_l2_tx(deposit_request(from, to))
+                .await
+                .expect("hardhat_simulateL1ToL2Tx failed");
+            let second = hardhat
+                .simulate_l1_to_l2_tx(deposit_request(from, to))
+                .await
+                .expect("hardhat_simulateL1ToL2Tx failed");
+            assert_ne!(first, second);
+            hashes.push((first, second));
+        }
+
+        assert_eq!(hashes[0], hashes[1]);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_l1_to_l2_tx_rejects_overflowing_mint_value() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+
+        let result = hardhat
+            .simulate_l1_to_l2_tx(L1ToL2TxRequest {
+                l2_gas_limit: Some(U256::MAX),
+                ..deposit_request(Address::repeat_byte(0x01), Address::repeat_byte(0x02))
+            })
+            .await;
+
+        assert_eq!(
+            jsonrpc_core::ErrorCode::InvalidParams,
+            result.expect_err("overflow not rejected").code
+        );
+        assert_eq!(0, node.get_inner().read().unwrap().next_priority_op_id);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_l1_to_l2_tx_is_received_at_the_node_time() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        node.get_inner().write().unwrap().automine = false;
+        let timestamp = node.get_inner().read().unwrap().current_timestamp;
+
+        hardhat
+            .simulate_l1_to_l2_tx(deposit_request(
+                Address::repeat_byte(0x01),
+                Address::repeat_byte(0x02),
+            ))
+            .await
+            .expect("hardhat_simulateL1ToL2Tx failed");
+
+        assert_eq!(
+            timestamp * 1000,
+            node.get_inner().read().unwrap().priority_queue[0].received_timestamp_ms
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restores_the_priority_queue() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        node.get_inner().write().unwrap().automine = false;
+        let snapshot = node.get_inner().read().unwrap().snapshot().unwrap();
+
+        hardhat
+            .simulate_l1_to_l2_tx(deposit_request(
+                Address::repeat_byte(0x01),
+                Address::repeat_byte(0x02),
+            ))
+            .await
+            .expect("hardhat_simulateL1ToL2Tx failed");
+        node.get_inner()
+            .write()
+            .unwrap()
+            .restore_snapshot(snapshot)
+            .expect("failed restoring snapshot");
+
+        let inner = node.get_inner();
+        let inner = inner.read().unwrap();
+        assert!(inner.priority_queue.is_empty());
+        assert_eq!(0, inner.next_priority_op_id);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_l1_to_l2_tx_waits_for_the_next_block_without_automine() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+        node.get_inner().write().unwrap().automine = false;
+        let to = Address::repeat_byte(0x02);
+
+        let tx_hash = hardhat
+            .simulate_l1_to_l2_tx(deposit_request(Address::repeat_byte(0x01), to))
+            .await
+            .expect("hardhat_simulateL1ToL2Tx failed");
+        assert_eq!(U256::zero(), node.get_balance(to, None).await.unwrap());
+        assert_eq!(1, node.get_inner().read().unwrap().priority_queue.len());
+
+        assert_eq!(1, node.mine_mempool().expect("failed mining"));
+
+        assert!(node.get_inner().read().unwrap().priority_queue.is_empty());
+        assert_eq!(U256::from(1000), node.get_balance(to, None).await.unwrap());
+        assert!(node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .unwrap()
+            .is_some());
+    }
 }
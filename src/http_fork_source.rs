@@ -2,6 +2,7 @@ use std::sync::RwLock;
 
 use eyre::Context;
 use zksync_basic_types::{H256, U256};
+use zksync_types::api::{BlockIdVariant, BlockNumber};
 use zksync_web3_decl::{
     jsonrpsee::http_client::{HttpClient, HttpClientBuilder},
     namespaces::{EthNamespaceClient, ZksNamespaceClient},
@@ -9,7 +10,7 @@ use zksync_web3_decl::{
 
 use crate::{
     cache::{Cache, CacheConfig},
-    fork::{block_on, ForkSource},
+    fork::{block_on, ForkDetails, ForkSource},
 };
 
 #[derive(Debug)]
@@ -29,6 +30,25 @@ impl HttpForkSource {
         }
     }
 
+    /// Returns the cache config of a fork of `url` replacing this one: the same config, with the
+    /// disk cache cleared when forking another network, as its entries aren't keyed by URL.
+    pub fn cache_config_for(&self, url: &str) -> Result<CacheConfig, String> {
+        let same_network = ForkDetails::<HttpForkSource>::fork_to_url(url) == self.fork_url;
+        let cache_config = self
+            .cache
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on cache: {:?}", err))?
+            .config()
+            .clone();
+        Ok(match cache_config {
+            CacheConfig::Disk { dir, .. } => CacheConfig::Disk {
+                dir,
+                reset: !same_network,
+            },
+            cache_config => cache_config,
+        })
+    }
+
     pub fn create_client(&self) -> HttpClient {
         HttpClientBuilder::default()
             .build(self.fork_url.clone())
@@ -43,8 +63,35 @@ impl ForkSource for HttpForkSource {
         idx: zksync_basic_types::U256,
         block: Option<zksync_types::api::BlockIdVariant>,
     ) -> eyre::Result<zksync_basic_types::H256> {
+        // Only the values read at a specific block number can be cached.
+        let maybe_number = match block {
+            Some(BlockIdVariant::BlockNumber(BlockNumber::Number(number))) => Some(number.as_u64()),
+            _ => None,
+        };
+
+        if let Some(value) = maybe_number.and_then(|number| {
+            self.cache
+                .read()
+                .ok()
+                .and_then(|guard| guard.get_storage(number, address, idx).cloned())
+        }) {
+            log::debug!("using cached storage for {address:?} at {idx}");
+            return Ok(value);
+        }
+
         let client = self.create_client();
         block_on(async move { client.get_storage_at(address, idx, block).await })
+            .map(|value| {
+                if let Some(number) = maybe_number {
+                    self.cache
+                        .write()
+                        .map(|mut guard| guard.insert_storage(number, address, idx, value))
+                        .unwrap_or_else(|err| {
+                            log::warn!("failed writing to cache for 'get_storage_at': {:?}", err)
+                        });
+                }
+                value
+            })
             .wrap_err("fork http client failed")
     }
 
@@ -52,8 +99,31 @@ impl ForkSource for HttpForkSource {
         &self,
         hash: zksync_basic_types::H256,
     ) -> eyre::Result<Option<Vec<u8>>> {
+        if let Ok(Some(bytecode)) = self
+            .cache
+            .read()
+            .map(|guard| guard.get_bytecode(&hash).cloned())
+        {
+            log::debug!("using cached bytecode for {hash}");
+            return Ok(Some(bytecode));
+        }
+
         let client = self.create_client();
         block_on(async move { client.get_bytecode_by_hash(hash).await })
+            .map(|maybe_bytecode| {
+                if let Some(bytecode) = &maybe_bytecode {
+                    self.cache
+                        .write()
+                        .map(|mut guard| guard.insert_bytecode(hash, bytecode.clone()))
+                        .unwrap_or_else(|err| {
+                            log::warn!(
+                                "failed writing to cache for 'get_bytecode_by_hash': {:?}",
+                                err
+                            )
+                        });
+                }
+                maybe_bytecode
+            })
             .wrap_err("fork http client failed")
     }
 
@@ -220,6 +290,96 @@ impl ForkSource for HttpForkSource {
         })
         .wrap_err("fork http client failed")
     }
+
+    /// Returns information about a transaction by block hash and transaction index position.
+    fn get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: zksync_basic_types::web3::types::Index,
+    ) -> eyre::Result<Option<zksync_types::api::Transaction>> {
+        let client = self.create_client();
+        block_on(async move {
+            client
+                .get_transaction_by_block_hash_and_index(block_hash, index)
+                .await
+        })
+        .wrap_err("fork http client failed")
+    }
+
+    fn get_transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> eyre::Result<Option<zksync_types::api::TransactionReceipt>> {
+        let client = self.create_client();
+        block_on(async move { client.get_transaction_receipt(hash).await })
+            .wrap_err("fork http client failed")
+    }
+
+    /// Returns information about a transaction by block number and transaction index position.
+    fn get_transaction_by_block_number_and_index(
+        &self,
+        block_number: zksync_types::api::BlockNumber,
+        index: zksync_basic_types::web3::types::Index,
+    ) -> eyre::Result<Option<zksync_types::api::Transaction>> {
+        let client = self.create_client();
+        block_on(async move {
+            client
+                .get_transaction_by_block_number_and_index(block_number, index)
+                .await
+        })
+        .wrap_err("fork http client failed")
+    }
+
+    /// Returns the balances of all the tokens held by an account, on the latest block of the network.
+    fn get_all_account_balances(
+        &self,
+        address: zksync_basic_types::Address,
+    ) -> eyre::Result<std::collections::HashMap<zksync_basic_types::Address, U256>> {
+        let client = self.create_client();
+        block_on(async move { client.get_all_account_balances(address).await })
+            .wrap_err("fork http client failed")
+    }
+
+    /// Returns a page of the tokens confirmed on the network.
+    fn get_confirmed_tokens(
+        &self,
+        from: u32,
+        limit: u8,
+    ) -> eyre::Result<Vec<zksync_web3_decl::types::Token>> {
+        let client = self.create_client();
+        block_on(async move { client.get_confirmed_tokens(from, limit).await })
+            .wrap_err("fork http client failed")
+    }
+
+    /// Returns the USD price of a token.
+    fn get_token_price(
+        &self,
+        token: zksync_basic_types::Address,
+    ) -> eyre::Result<bigdecimal::BigDecimal> {
+        let client = self.create_client();
+        block_on(async move { client.get_token_price(token).await })
+            .wrap_err("fork http client failed")
+    }
+
+    /// Returns the zkSync specific details of a block.
+    fn get_block_details(
+        &self,
+        miniblock: zksync_basic_types::MiniblockNumber,
+    ) -> eyre::Result<Option<zksync_types::api::BlockDetails>> {
+        let client = self.create_client();
+        block_on(async move { client.get_block_details(miniblock).await })
+            .wrap_err("fork http client failed")
+    }
+
+    /// Returns the zkSync specific details of a transaction.
+    fn get_transaction_details(
+        &self,
+        hash: zksync_basic_types::H256,
+    ) -> eyre::Result<Option<zksync_types::api::TransactionDetails>> {
+        let client = self.create_client();
+        block_on(async move { client.get_transaction_details(hash).await })
+            .wrap_err("fork http client failed")
+    }
 }
 
 #[cfg(test)]
@@ -466,4 +626,80 @@ mod tests {
             .expect("no transaction");
         assert_eq!(input_tx_hash, actual_transaction.hash);
     }
+
+    #[test]
+    fn test_get_storage_at_for_block_number_is_cached() {
+        let input_address = zksync_basic_types::Address::repeat_byte(0x01);
+        let input_idx = U256::from(2);
+        let input_block = Some(BlockIdVariant::BlockNumber(BlockNumber::Number(U64::from(
+            8,
+        ))));
+        let value = H256::repeat_byte(0x03);
+
+        let mock_server = testing::MockServer::run();
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getStorageAt",
+                "params": [
+                    format!("{input_address:#x}"),
+                    "0x2",
+                    "0x8",
+                ],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("{value:#x}"),
+            }),
+        );
+
+        let fork_source = HttpForkSource::new(mock_server.url(), CacheConfig::Memory);
+
+        let actual_value = fork_source
+            .get_storage_at(input_address, input_idx, input_block)
+            .expect("failed fetching storage");
+        assert_eq!(value, actual_value);
+
+        let actual_value = fork_source
+            .get_storage_at(input_address, input_idx, input_block)
+            .expect("failed fetching cached storage");
+        assert_eq!(value, actual_value);
+    }
+
+    #[test]
+    fn test_get_bytecode_by_hash_is_cached() {
+        let input_hash = H256::repeat_byte(0x01);
+        let bytecode = vec![0x1u8, 0x2, 0x3, 0x4];
+
+        let mock_server = testing::MockServer::run();
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getBytecodeByHash",
+                "params": [
+                    format!("{input_hash:#x}"),
+                ],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": bytecode,
+            }),
+        );
+
+        let fork_source = HttpForkSource::new(mock_server.url(), CacheConfig::Memory);
+
+        let actual_bytecode = fork_source
+            .get_bytecode_by_hash(input_hash)
+            .expect("failed fetching bytecode");
+        assert_eq!(Some(bytecode.clone()), actual_bytecode);
+
+        let actual_bytecode = fork_source
+            .get_bytecode_by_hash(input_hash)
+            .expect("failed fetching cached bytecode");
+        assert_eq!(Some(bytecode), actual_bytecode);
+    }
 }
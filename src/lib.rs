@@ -44,12 +44,22 @@
 pub mod bootloader_debug;
 pub mod configuration_api;
 pub mod console_log;
+pub mod debug;
 pub mod deps;
+pub mod eip712;
+pub mod eth_extra;
+pub mod filters;
 pub mod fork;
 pub mod formatter;
+pub mod genesis;
 pub mod http_fork_source;
+pub mod logging_middleware;
 pub mod node;
+pub mod proof;
+pub mod pubsub;
 pub mod resolver;
+pub mod state;
+pub mod state_override;
 pub mod system_contracts;
 pub mod utils;
 pub mod zks;
@@ -3,27 +3,124 @@ use futures::Future;
 use futures::{future::Either, FutureExt};
 use itertools::Itertools;
 use jsonrpc_core::{
-    middleware, Call, FutureResponse, Metadata, Middleware, Params, Request, Response,
+    middleware, Call, Failure, FutureResponse, Id, Metadata, MethodCall, Middleware, Output,
+    Params, Request, Response, Version,
 };
+use jsonrpc_pubsub::{PubSubMetadata, Session};
 use log::LevelFilter;
+use std::sync::Arc;
+
+/// Per-request metadata. Carries the pub-sub session for WebSocket connections.
+#[derive(Clone, Default)]
+pub struct Meta {
+    session: Option<Arc<Session>>,
+}
+
+impl Meta {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self {
+            session: Some(session),
+        }
+    }
+}
 
-#[derive(Clone, Debug, Default)]
-pub struct Meta();
 impl Metadata for Meta {}
 
+impl PubSubMetadata for Meta {
+    fn session(&self) -> Option<Arc<Session>> {
+        self.session.clone()
+    }
+}
+
+/// Default maximum number of calls in a single batch request.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1000;
+
 pub struct LoggingMiddleware {
     log_level_filter: LevelFilter,
+    max_batch_size: usize,
 }
 
 impl LoggingMiddleware {
-    pub fn new(log_level_filter: LevelFilter) -> Self {
-        Self { log_level_filter }
+    pub fn new(log_level_filter: LevelFilter, max_batch_size: usize) -> Self {
+        Self {
+            log_level_filter,
+            max_batch_size,
+        }
+    }
+
+    /// Checks the size of a batch request: it can't be empty, nor have more than `max_batch_size` calls.
+    fn validate_batch(&self, calls: &[Call]) -> Result<(), String> {
+        if calls.is_empty() {
+            return Err(String::from("empty batch request"));
+        }
+        if calls.len() > self.max_batch_size {
+            return Err(format!(
+                "batch of {} requests exceeds the maximum batch size of {}",
+                calls.len(),
+                self.max_batch_size
+            ));
+        }
+        Ok(())
+    }
+
+    fn log_method_call(&self, method_call: &MethodCall) {
+        match self.log_level_filter {
+            LevelFilter::Trace => {
+                let full_params = match &method_call.params {
+                    Params::Array(values) => {
+                        if values.is_empty() {
+                            String::default()
+                        } else {
+                            format!("with [{}]", values.iter().join(", "))
+                        }
+                    }
+                    _ => String::default(),
+                };
+
+                log::trace!("{} was called {}", method_call.method.cyan(), full_params);
+            }
+            _ => {
+                // Generate truncated params for requests with massive payloads
+                let truncated_params = match &method_call.params {
+                    Params::Array(values) => {
+                        if values.is_empty() {
+                            String::default()
+                        } else {
+                            format!(
+                                "with [{}]",
+                                values
+                                    .iter()
+                                    .map(|s| {
+                                        let s_str = s.to_string();
+                                        if s_str.len() > 70 {
+                                            format!("{:.67}...", s_str)
+                                        } else {
+                                            s_str
+                                        }
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join(", ")
+                            )
+                        }
+                    }
+                    _ => String::default(),
+                };
+
+                log::info!(
+                    "{} was called {}",
+                    method_call.method.cyan(),
+                    truncated_params
+                );
+            }
+        }
     }
 }
 
 /// Logging Middleware for all in-bound requests
 /// Logs out incoming requests and their parameters
 /// Useful for debugging applications that are pointed at this service
+/// Batch requests are rejected as a whole if they exceed the maximum batch size, otherwise every call
+/// is dispatched and answered separately, in the same order.
 impl Middleware<Meta> for LoggingMiddleware {
     type Future = FutureResponse;
     type CallFuture = middleware::NoopCallFuture;
@@ -33,57 +130,33 @@ impl Middleware<Meta> for LoggingMiddleware {
         F: FnOnce(Request, Meta) -> X + Send,
         X: Future<Output = Option<Response>> + Send + 'static,
     {
-        if let Request::Single(Call::MethodCall(method_call)) = &request {
-            match self.log_level_filter {
-                LevelFilter::Trace => {
-                    let full_params = match &method_call.params {
-                        Params::Array(values) => {
-                            if values.is_empty() {
-                                String::default()
-                            } else {
-                                format!("with [{}]", values.iter().join(", "))
-                            }
-                        }
-                        _ => String::default(),
-                    };
-
-                    log::trace!("{} was called {}", method_call.method.cyan(), full_params);
+        match &request {
+            Request::Single(Call::MethodCall(method_call)) => self.log_method_call(method_call),
+            Request::Batch(calls) => {
+                if let Err(message) = self.validate_batch(calls) {
+                    log::warn!("Rejected batch request: {}", message);
+                    let failure = Output::Failure(Failure {
+                        jsonrpc: Some(Version::V2),
+                        error: jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::InvalidRequest,
+                            message,
+                            data: None,
+                        },
+                        id: Id::Null,
+                    });
+                    return Either::Left(Box::pin(futures::future::ready(Some(Response::Single(
+                        failure,
+                    )))));
                 }
-                _ => {
-                    // Generate truncated params for requests with massive payloads
-                    let truncated_params = match &method_call.params {
-                        Params::Array(values) => {
-                            if values.is_empty() {
-                                String::default()
-                            } else {
-                                format!(
-                                    "with [{}]",
-                                    values
-                                        .iter()
-                                        .map(|s| {
-                                            let s_str = s.to_string();
-                                            if s_str.len() > 70 {
-                                                format!("{:.67}...", s_str)
-                                            } else {
-                                                s_str
-                                            }
-                                        })
-                                        .collect::<Vec<String>>()
-                                        .join(", ")
-                                )
-                            }
-                        }
-                        _ => String::default(),
-                    };
-
-                    log::info!(
-                        "{} was called {}",
-                        method_call.method.cyan(),
-                        truncated_params
-                    );
+                log::info!("Batch of {} requests", calls.len());
+                for call in calls {
+                    if let Call::MethodCall(method_call) = call {
+                        self.log_method_call(method_call);
+                    }
                 }
             }
-        };
+            _ => {}
+        }
 
         Either::Left(Box::pin(next(request, meta).map(move |res| {
             log::trace!("API response => {:?}", res);
@@ -91,3 +164,77 @@ impl Middleware<Meta> for LoggingMiddleware {
         })))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::{MetaIoHandler, Value};
+
+    fn io_handler(max_batch_size: usize) -> MetaIoHandler<Meta, LoggingMiddleware> {
+        let mut io = MetaIoHandler::with_middleware(LoggingMiddleware::new(
+            LevelFilter::Info,
+            max_batch_size,
+        ));
+        io.add_sync_method("echo", |params: Params| match params {
+            Params::Array(values) => Ok(values.into_iter().next().unwrap_or(Value::Null)),
+            _ => Err(jsonrpc_core::Error::invalid_params("expected an array")),
+        });
+        io
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_answered_in_order_with_individual_errors() {
+        let io = io_handler(DEFAULT_MAX_BATCH_SIZE);
+
+        let response = io
+            .handle_request(
+                r#"[
+                    {"jsonrpc":"2.0","method":"echo","params":[1],"id":7},
+                    {"jsonrpc":"2.0","method":"missing","params":[],"id":"b"},
+                    {"jsonrpc":"2.0","method":"echo","params":[3],"id":5}
+                ]"#,
+                Meta::default(),
+            )
+            .await
+            .expect("no response");
+        let response: Value = serde_json::from_str(&response).unwrap();
+
+        assert_eq!(
+            serde_json::json!([
+                {"jsonrpc":"2.0","result":1,"id":7},
+                {"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":"b"},
+                {"jsonrpc":"2.0","result":3,"id":5},
+            ]),
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_over_the_maximum_size_is_rejected() {
+        let io = io_handler(2);
+        let call = r#"{"jsonrpc":"2.0","method":"echo","params":[1],"id":1}"#;
+
+        for request in [format!("[{call},{call},{call}]"), String::from("[]")] {
+            let response = io
+                .handle_request(&request, Meta::default())
+                .await
+                .expect("no response");
+            let response: Value = serde_json::from_str(&response).unwrap();
+            assert_eq!(-32600, response["error"]["code"], "{}", response);
+            assert_eq!(Value::Null, response["id"]);
+        }
+
+        let response = io
+            .handle_request(&format!("[{call},{call}]"), Meta::default())
+            .await
+            .expect("no response");
+        assert_eq!(
+            2,
+            serde_json::from_str::<Value>(&response)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len()
+        );
+    }
+}
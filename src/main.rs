@@ -1,30 +1,49 @@
 use crate::cache::CacheConfig;
 use crate::hardhat::{HardhatNamespaceImpl, HardhatNamespaceT};
-use crate::node::{ShowGasDetails, ShowStorageLogs, ShowVMDetails};
+use crate::node::{
+    FeeConfig, FormatterOutput, NodeConfig, ShowGasDetails, ShowStorageLogs, ShowVMDetails,
+    TransactionOrder,
+};
+use anyhow::anyhow;
+use block_tags::BlockTagMiddleware;
 use clap::{Parser, Subcommand, ValueEnum};
 use configuration_api::ConfigurationApiNamespaceT;
+use debug::{DebugNamespaceImpl, DebugNamespaceT};
+use eth_extra::EthTestNodeNamespaceT;
 use evm::{EvmNamespaceImpl, EvmNamespaceT};
-use fork::{ForkDetails, ForkSource};
-use logging_middleware::LoggingMiddleware;
+use fork::{ForkDetails, ForkFactory};
+use genesis::Genesis;
+use logging_middleware::{LoggingMiddleware, Meta};
 use node::ShowCalls;
+use pubsub::{EthPubSubNamespaceImpl, EthPubSubNamespaceT};
 use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, TermLogger, TerminalMode, WriteLogger,
 };
-use zks::ZkMockNamespaceImpl;
+use zks::{ZkMockNamespaceImpl, ZksTestNodeNamespaceT};
 
+mod block_tags;
 mod bootloader_debug;
 mod cache;
 mod configuration_api;
 mod console_log;
+mod debug;
 mod deps;
+mod eip712;
+mod eth_extra;
 mod evm;
+mod filters;
 mod fork;
 mod formatter;
+mod genesis;
 mod hardhat;
 mod http_fork_source;
 mod logging_middleware;
 mod node;
+mod proof;
+mod pubsub;
 mod resolver;
+mod state;
+mod state_override;
 mod system_contracts;
 mod testing;
 mod utils;
@@ -37,7 +56,9 @@ use std::{
     env,
     fs::File,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
     str::FromStr,
+    sync::Arc,
 };
 
 use tracing::Level;
@@ -49,83 +70,61 @@ use futures::{
     FutureExt,
 };
 use jsonrpc_core::MetaIoHandler;
+use jsonrpc_pubsub::Session;
 use zksync_basic_types::{L2ChainId, H160, H256};
 
-use crate::{configuration_api::ConfigurationApiNamespace, node::TEST_NODE_NETWORK_ID};
+use crate::{
+    configuration_api::ConfigurationApiNamespace,
+    node::{L2_GAS_PRICE, RICH_WALLETS, TEST_NODE_NETWORK_ID},
+};
 use zksync_core::api_server::web3::backend_jsonrpc::namespaces::{
     eth::EthNamespaceT, net::NetNamespaceT, zks::ZksNamespaceT,
 };
 
-/// List of wallets (address, private key) that we seed with tokens at start.
-pub const RICH_WALLETS: [(&str, &str); 10] = [
-    (
-        "0x36615Cf349d7F6344891B1e7CA7C72883F5dc049",
-        "0x7726827caac94a7f9e1b160f7ea819f172f7b6f9d2a97f992c38edeab82d4110",
-    ),
-    (
-        "0xa61464658AfeAf65CccaaFD3a512b69A83B77618",
-        "0xac1e735be8536c6534bb4f17f06f6afc73b2b5ba84ac2cfb12f7461b20c0bbe3",
-    ),
-    (
-        "0x0D43eB5B8a47bA8900d84AA36656c92024e9772e",
-        "0xd293c684d884d56f8d6abd64fc76757d3664904e309a0645baf8522ab6366d9e",
-    ),
-    (
-        "0xA13c10C0D5bd6f79041B9835c63f91de35A15883",
-        "0x850683b40d4a740aa6e745f889a6fdc8327be76e122f5aba645a5b02d0248db8",
-    ),
-    (
-        "0x8002cD98Cfb563492A6fB3E7C8243b7B9Ad4cc92",
-        "0xf12e28c0eb1ef4ff90478f6805b68d63737b7f33abfa091601140805da450d93",
-    ),
-    (
-        "0x4F9133D1d3F50011A6859807C837bdCB31Aaab13",
-        "0xe667e57a9b8aaa6709e51ff7d093f1c5b73b63f9987e4ab4aa9a5c699e024ee8",
-    ),
-    (
-        "0xbd29A1B981925B94eEc5c4F1125AF02a2Ec4d1cA",
-        "0x28a574ab2de8a00364d5dd4b07c4f2f574ef7fcc2a86a197f65abaec836d1959",
-    ),
-    (
-        "0xedB6F5B4aab3dD95C7806Af42881FF12BE7e9daa",
-        "0x74d8b3a188f7260f67698eb44da07397a298df5427df681ef68c45b34b61f998",
-    ),
-    (
-        "0xe706e60ab5Dc512C36A4646D719b889F398cbBcB",
-        "0xbe79721778b48bcc679b78edac0ce48306a8578186ffcb9f2ee455ae6efeace1",
-    ),
-    (
-        "0xE90E12261CCb0F3F7976Ae611A29e84a6A85f424",
-        "0x3eb15da85647edd9a1159a4a13b9e7c56877c4eb33f614546d4db06a51868b1c",
-    ),
-];
-
-#[allow(clippy::too_many_arguments)]
-async fn build_json_http<
-    S: std::marker::Sync + std::marker::Send + 'static + ForkSource + std::fmt::Debug,
+/// Middlewares of the RPC server: requests are logged as sent, then their block tags are rewritten.
+type RpcMiddleware = (LoggingMiddleware, BlockTagMiddleware);
+
+/// Builds the handler with every namespace served by the node.
+/// A separate instance is created for each transport, all sharing the same node state.
+fn build_io_handler<
+    S: std::marker::Sync + std::marker::Send + 'static + ForkFactory + std::fmt::Debug,
 >(
-    addr: SocketAddr,
     log_level_filter: LevelFilter,
-    node: InMemoryNode<S>,
-    net: NetNamespace,
-    config_api: ConfigurationApiNamespace<S>,
-    evm: EvmNamespaceImpl<S>,
-    zks: ZkMockNamespaceImpl<S>,
-    hardhat: HardhatNamespaceImpl<S>,
+    max_batch_size: usize,
+    node: &InMemoryNode<S>,
+) -> MetaIoHandler<Meta, RpcMiddleware> {
+    let net = NetNamespace::new(L2ChainId(TEST_NODE_NETWORK_ID));
+    let config_api = ConfigurationApiNamespace::new(node.get_inner());
+    let evm = EvmNamespaceImpl::new(node.get_inner());
+    let zks = ZkMockNamespaceImpl::new(node.get_inner());
+    let zks_test = ZkMockNamespaceImpl::new(node.get_inner());
+    let hardhat = HardhatNamespaceImpl::new(node.get_inner());
+    let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+    let debug = DebugNamespaceImpl::new(node.get_inner());
+
+    let mut io = MetaIoHandler::with_middleware((
+        LoggingMiddleware::new(log_level_filter, max_batch_size),
+        BlockTagMiddleware,
+    ));
+    io.extend_with(EthNamespaceT::to_delegate(node.clone()));
+    io.extend_with(EthTestNodeNamespaceT::to_delegate(node.clone()));
+    io.extend_with(net.to_delegate());
+    io.extend_with(config_api.to_delegate());
+    io.extend_with(evm.to_delegate());
+    io.extend_with(ZksNamespaceT::to_delegate(zks));
+    io.extend_with(ZksTestNodeNamespaceT::to_delegate(zks_test));
+    io.extend_with(hardhat.to_delegate());
+    io.extend_with(pubsub.to_delegate());
+    io.extend_with(debug.to_delegate());
+    io
+}
+
+async fn build_json_http(
+    addr: SocketAddr,
+    io_handler: MetaIoHandler<Meta, RpcMiddleware>,
 ) -> tokio::task::JoinHandle<()> {
     let (sender, recv) = oneshot::channel::<()>();
 
-    let io_handler = {
-        let mut io = MetaIoHandler::with_middleware(LoggingMiddleware::new(log_level_filter));
-        io.extend_with(node.to_delegate());
-        io.extend_with(net.to_delegate());
-        io.extend_with(config_api.to_delegate());
-        io.extend_with(evm.to_delegate());
-        io.extend_with(zks.to_delegate());
-        io.extend_with(hardhat.to_delegate());
-        io
-    };
-
     std::thread::spawn(move || {
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
@@ -146,6 +145,37 @@ async fn build_json_http<
     tokio::spawn(recv.map(drop))
 }
 
+async fn build_json_ws(
+    addr: SocketAddr,
+    io_handler: MetaIoHandler<Meta, RpcMiddleware>,
+) -> tokio::task::JoinHandle<()> {
+    let (sender, recv) = oneshot::channel::<()>();
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+
+        // Every connection gets its own session, so its subscriptions are dropped on disconnect.
+        let server = jsonrpc_ws_server::ServerBuilder::with_meta_extractor(
+            io_handler,
+            |context: &jsonrpc_ws_server::RequestContext| {
+                Meta::new(Arc::new(Session::new(context.sender())))
+            },
+        )
+        .event_loop_executor(runtime.handle().clone())
+        .start(&addr)
+        .unwrap();
+
+        let _ = server.wait();
+        let _ = sender;
+    });
+
+    tokio::spawn(recv.map(drop))
+}
+
 /// Log filter level for the node.
 #[derive(Debug, Clone, ValueEnum)]
 enum LogLevel {
@@ -184,9 +214,54 @@ struct Cli {
     #[arg(long, default_value = "8011")]
     /// Port to listen on - default: 8011
     port: u16,
+    #[arg(long, default_value = "8012")]
+    /// Port to listen on for WebSocket connections - default: 8012
+    ws_port: u16,
+    #[arg(long, default_value_t = logging_middleware::DEFAULT_MAX_BATCH_SIZE)]
+    /// Maximum number of requests in a single JSON-RPC batch - default: 1000
+    max_batch_size: usize,
+    #[arg(long, default_value_t = L2_GAS_PRICE)]
+    /// L2 gas price in wei, returned by `eth_gasPrice` - default: 250000000
+    l2_gas_price: u64,
+    #[arg(long)]
+    /// L2 gas price in wei charged by the VM and used for fee estimation - default: the `--l2-gas-price`
+    fair_l2_gas_price: Option<u64>,
+    #[arg(long)]
+    /// Gas per pubdata byte used for fee estimation - default: derived from the L1 and fair L2 gas prices
+    gas_per_pubdata: Option<u64>,
+    #[arg(long)]
+    /// Mine a block every given number of milliseconds, in addition to the blocks mined for each transaction
+    block_time: Option<u64>,
+    #[arg(long)]
+    /// Keep the received transactions in the mempool until a block is mined with `evm_mine` (or `--block-time`)
+    no_mining: bool,
+    #[arg(long, default_value = "fees")]
+    /// Order in which the mempool is mined
+    order: TransactionOrder,
+    #[arg(long)]
+    /// Return the token prices of the forked network from `zks_getTokenPrice`, instead of the static ones
+    fork_token_prices: bool,
+    #[arg(long)]
+    /// JSON file with the balances, nonces, code and storage of the accounts at genesis
+    genesis: Option<PathBuf>,
     #[arg(long, default_value = "none")]
     /// Show call debug information
     show_calls: ShowCalls,
+    #[arg(long)]
+    /// Maximum depth of the printed call traces. Deeper subcalls are replaced by a single line with their count.
+    max_call_depth: Option<usize>,
+    #[arg(long, default_value_t = formatter::DEFAULT_GAS_HIGHLIGHT_THRESHOLD)]
+    /// Gas used by a single call above which it is highlighted in the printed call traces.
+    gas_highlight_threshold: u32,
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    /// Print the events emitted by the transactions - default: true
+    print_events: bool,
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    /// Print the storage logs selected by `--show-storage-logs` - default: true
+    print_storage_logs: bool,
+    #[arg(long)]
+    /// Don't print call traces, events and storage logs, overriding the options above.
+    quiet: bool,
     #[arg(long, default_value = "none")]
     /// Show storage log information
     show_storage_logs: ShowStorageLogs,
@@ -198,15 +273,53 @@ struct Cli {
     /// Show Gas details information
     show_gas_details: ShowGasDetails,
 
+    #[arg(long, default_value = "text")]
+    /// Format of the printed call traces, events and storage logs
+    output_format: FormatterOutput,
+
     #[arg(long)]
     /// If true, the tool will try to contact openchain to resolve the ABI & topic names.
     /// It will make debug log more readable, but will decrease the performance.
     resolve_hashes: bool,
 
+    #[arg(long, visible_aliases = ["offline", "no-resolve"])]
+    /// Never contact the selector database, even if `--resolve-hashes` is set. Only the local signatures are resolved. Useful in CI without network access.
+    resolver_offline: bool,
+
+    #[arg(long)]
+    /// URL of the selector database used by `--resolve-hashes`, overriding the `RESOLVER_URL` environment variable.
+    resolver_url: Option<String>,
+
+    #[arg(long, default_value_t = resolver::DEFAULT_CACHE_CAPACITY)]
+    /// Maximum number of selectors resolved with `--resolve-hashes` that are kept in memory - default: 10000
+    resolver_cache_size: usize,
+
     #[arg(long)]
     /// If true, will load the locally compiled system contracts (useful when doing changes to system contracts or bootloader)
     dev_use_local_contracts: bool,
 
+    /// Path to a checkout of the system contracts repository to load the compiled bootloaders and
+    /// default account from, like `--dev-use-local-contracts` but without ZKSYNC_HOME.
+    #[arg(
+        long,
+        conflicts_with_all = ["dev_use_local_contracts", "bootloader_path"]
+    )]
+    dev_system_contracts_dir: Option<PathBuf>,
+
+    /// Path to a compiled bootloader (.zbin) to use instead of the built-in ones.
+    /// Must be used together with `--default-account-path`.
+    #[arg(
+        long,
+        requires = "default_account_path",
+        conflicts_with = "dev_use_local_contracts"
+    )]
+    bootloader_path: Option<PathBuf>,
+
+    /// Path to a compiled default account (.zbin) to use instead of the built-in one.
+    /// Must be used together with `--bootloader-path`.
+    #[arg(long, requires = "bootloader_path")]
+    default_account_path: Option<PathBuf>,
+
     /// Log filter level - default: info
     #[arg(long, default_value = "info")]
     log: LogLevel,
@@ -224,8 +337,18 @@ struct Cli {
     reset_cache: bool,
 
     /// Cache directory location for `disk` cache - default: ".cache"
-    #[arg(long, default_value = ".cache")]
+    #[arg(long, visible_alias = "fork-cache-dir", default_value = ".cache")]
     cache_dir: String,
+
+    /// Path to a JSON file with additional known contract addresses (same format as `src/data/address_map.json`).
+    /// The entries override the built-in ones when printing calls, events and storage logs.
+    #[arg(long)]
+    address_map: Option<PathBuf>,
+
+    /// Directory with contract ABI files (named `0x<address>.json`), used to decode the parameters of printed calls.
+    /// Relative `abi_path` entries of the address map are resolved against it.
+    #[arg(long)]
+    abi_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -272,6 +395,12 @@ struct ReplayArgs {
 async fn main() -> anyhow::Result<()> {
     let opt = Cli::parse();
 
+    // Decide once, before anything gets printed, whether the output should be colored.
+    let colors_enabled = !formatter::no_color_requested();
+    if !colors_enabled {
+        formatter::set_colors_enabled(false);
+    }
+
     let log_level_filter = LevelFilter::from(opt.log);
     let log_config = ConfigBuilder::new()
         .add_filter_allow_str("era_test_node")
@@ -281,7 +410,11 @@ async fn main() -> anyhow::Result<()> {
             log_level_filter,
             log_config.clone(),
             TerminalMode::Mixed,
-            ColorChoice::Auto,
+            if colors_enabled {
+                ColorChoice::Auto
+            } else {
+                ColorChoice::Never
+            },
         ),
         WriteLogger::new(
             log_level_filter,
@@ -296,6 +429,27 @@ async fn main() -> anyhow::Result<()> {
             log::info!("+++++ Reading local contracts from {:?} +++++", path);
         }
     }
+    if let Some(path) = &opt.address_map {
+        let count = formatter::load_address_map_from_file(path).map_err(|err| anyhow!(err))?;
+        log::info!("Loaded {} known addresses from {:?}", count, path);
+    }
+    if opt.resolver_offline {
+        log::info!("Resolver is offline, selectors won't be resolved");
+        resolver::set_enabled(false);
+    }
+    if let Some(url) = opt.resolver_url {
+        resolver::set_resolver_url(url);
+    }
+    resolver::set_cache_capacity(opt.resolver_cache_size);
+    if let Some(path) = env::var_os("RESOLVER_SIGNATURES") {
+        let count = resolver::load_signatures_from_file(&PathBuf::from(&path))
+            .map_err(|err| anyhow!(err))?;
+        log::info!("Loaded {} signatures from {:?}", count, path);
+    }
+    if let Some(path) = &opt.abi_dir {
+        formatter::set_abi_dir(path.clone());
+    }
+
     let cache_config = match opt.cache {
         CacheType::None => CacheConfig::None,
         CacheType::Memory => CacheConfig::Memory,
@@ -316,12 +470,16 @@ async fn main() -> anyhow::Result<()> {
 
     let fork_details = match &opt.command {
         Command::Run => None,
-        Command::Fork(fork) => {
-            Some(ForkDetails::from_network(&fork.network, fork.fork_at, cache_config).await)
-        }
-        Command::ReplayTx(replay_tx) => {
-            Some(ForkDetails::from_network_tx(&replay_tx.network, replay_tx.tx, cache_config).await)
-        }
+        Command::Fork(fork) => Some(
+            ForkDetails::from_network(&fork.network, fork.fork_at, cache_config)
+                .await
+                .map_err(|err| anyhow!(err))?,
+        ),
+        Command::ReplayTx(replay_tx) => Some(
+            ForkDetails::from_network_tx(&replay_tx.network, replay_tx.tx, cache_config)
+                .await
+                .map_err(|err| anyhow!(err))?,
+        ),
     };
 
     // If we're replaying the transaction, we need to sync to the previous block
@@ -335,21 +493,88 @@ async fn main() -> anyhow::Result<()> {
     } else {
         vec![]
     };
-    let system_contracts_options = if opt.dev_use_local_contracts {
-        system_contracts::Options::Local
-    } else {
-        system_contracts::Options::BuiltIn
+    let system_contracts_options = match (opt.bootloader_path, opt.default_account_path) {
+        (Some(bootloader_path), Some(default_account_path)) => {
+            log::info!(
+                "+++++ Using bootloader {:?} and default account {:?} +++++",
+                bootloader_path,
+                default_account_path
+            );
+            system_contracts::Options::Custom {
+                bootloader_path,
+                default_account_path,
+            }
+        }
+        _ if opt.dev_use_local_contracts => system_contracts::Options::Local,
+        _ => match opt.dev_system_contracts_dir {
+            Some(root) => {
+                log::info!("+++++ Reading local contracts from {:?} +++++", root);
+                system_contracts::Options::LocalFrom(root)
+            }
+            None => system_contracts::Options::BuiltIn,
+        },
     };
 
-    let node = InMemoryNode::new(
+    if opt.l2_gas_price == 0 {
+        return Err(anyhow!("--l2-gas-price must be greater than 0"));
+    }
+    if opt.fair_l2_gas_price == Some(0) {
+        return Err(anyhow!("--fair-l2-gas-price must be greater than 0"));
+    }
+    if opt.gas_per_pubdata == Some(0) {
+        return Err(anyhow!("--gas-per-pubdata must be greater than 0"));
+    }
+    let fee_config = FeeConfig {
+        l2_gas_price: opt.l2_gas_price,
+        fair_l2_gas_price: opt.fair_l2_gas_price.unwrap_or(opt.l2_gas_price),
+        gas_per_pubdata: opt.gas_per_pubdata,
+    };
+    if opt.block_time == Some(0) {
+        return Err(anyhow!("--block-time must be greater than 0"));
+    }
+    let genesis = match &opt.genesis {
+        Some(path) => {
+            let genesis = Genesis::from_file(path).map_err(|err| anyhow!(err))?;
+            log::info!(
+                "Loaded {} genesis accounts from {:?}",
+                genesis.alloc.len(),
+                path
+            );
+            Some(genesis)
+        }
+        None => None,
+    };
+    let (show_calls, print_events, print_storage_logs) = if opt.quiet {
+        (ShowCalls::None, false, false)
+    } else {
+        (opt.show_calls, opt.print_events, opt.print_storage_logs)
+    };
+    let node = InMemoryNode::try_new(
         fork_details,
-        opt.show_calls,
-        opt.show_storage_logs,
-        opt.show_vm_details,
-        opt.show_gas_details,
-        opt.resolve_hashes,
-        &system_contracts_options,
-    );
+        &NodeConfig {
+            show_calls,
+            max_call_depth: opt.max_call_depth,
+            gas_highlight_threshold: opt.gas_highlight_threshold,
+            print_events,
+            print_storage_logs,
+            show_storage_logs: opt.show_storage_logs,
+            show_vm_details: opt.show_vm_details,
+            show_gas_details: opt.show_gas_details,
+            formatter_output: opt.output_format,
+            resolve_hashes: opt.resolve_hashes,
+            system_contracts_options,
+            fee_config,
+            genesis,
+        },
+    )
+    .map_err(|err| anyhow!("failed starting the node: {}", err))?;
+    {
+        let inner = node.get_inner();
+        let mut inner = inner.write().unwrap();
+        inner.automine = !opt.no_mining;
+        inner.transaction_order = opt.order;
+        inner.forward_token_prices = opt.fork_token_prices;
+    }
 
     if !transactions_to_replay.is_empty() {
         let _ = node.apply_txs(transactions_to_replay);
@@ -366,29 +591,37 @@ async fn main() -> anyhow::Result<()> {
         log::info!("");
     }
 
-    let net = NetNamespace::new(L2ChainId(TEST_NODE_NETWORK_ID));
-    let config_api = ConfigurationApiNamespace::new(node.get_inner());
-    let evm = EvmNamespaceImpl::new(node.get_inner());
-    let zks = ZkMockNamespaceImpl::new(node.get_inner());
-    let hardhat = HardhatNamespaceImpl::new(node.get_inner());
-
-    let threads = build_json_http(
+    let http_thread = build_json_http(
         SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), opt.port),
-        log_level_filter,
-        node,
-        net,
-        config_api,
-        evm,
-        zks,
-        hardhat,
+        build_io_handler(log_level_filter, opt.max_batch_size, &node),
     )
     .await;
+    let ws_thread = build_json_ws(
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), opt.ws_port),
+        build_io_handler(log_level_filter, opt.max_batch_size, &node),
+    )
+    .await;
+
+    if let Some(block_time) = opt.block_time {
+        let inner = node.get_inner();
+        let task = evm::spawn_interval_mining(Arc::clone(&inner), block_time);
+        inner.write().unwrap().interval_mining = Some(task);
+        log::info!("👷 Interval mining enabled, every {}ms", block_time);
+    }
 
     log::info!("========================================");
     log::info!("  Node is ready at 127.0.0.1:{}", opt.port);
+    log::info!("  WebSocket is ready at 127.0.0.1:{}", opt.ws_port);
     log::info!("========================================");
 
-    future::select_all(vec![threads]).await.0.unwrap();
+    future::select_all(vec![http_thread, ws_thread])
+        .await
+        .0
+        .unwrap();
+
+    if let Some(task) = node.get_inner().write().unwrap().interval_mining.take() {
+        task.abort();
+    }
 
     Ok(())
 }
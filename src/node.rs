@@ -2,13 +2,22 @@
 use crate::{
     bootloader_debug::BootloaderDebug,
     console_log::ConsoleLogHandler,
-    fork::{ForkDetails, ForkSource, ForkStorage},
-    formatter,
+    deps::InMemoryStorage,
+    filters::{EthFilters, LogFilter},
+    fork::{ForkDetails, ForkSource, ForkStorage, ForkStorageAtBlock},
+    formatter::{self, DEFAULT_GAS_HIGHLIGHT_THRESHOLD},
+    genesis::Genesis,
+    proof::StorageTree,
+    pubsub::EthSubscriptions,
+    state::{SerializableState, SerializableTransaction, StorageEntry, STATE_VERSION},
+    state_override::{OverriddenStorage, StateOverride},
     system_contracts::{self, SystemContracts},
     utils::{
-        adjust_l1_gas_price_for_tx, derive_gas_estimation_overhead, to_human_size, IntoBoxedFuture,
+        adjust_l1_gas_price_for_tx, derive_gas_estimation_overhead, l2_to_l1_logs_merkle_proof,
+        resolve_block_number, to_human_size, IntoBoxedFuture,
     },
 };
+use bigdecimal::BigDecimal;
 use clap::Parser;
 use colored::Colorize;
 use core::fmt::Display;
@@ -16,25 +25,25 @@ use futures::FutureExt;
 use jsonrpc_core::BoxFuture;
 use std::{
     cmp::{self},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     str::FromStr,
     sync::{Arc, RwLock},
 };
 
 use vm::{
     utils::{BLOCK_GAS_LIMIT, ETH_CALL_GAS_LIMIT},
-    vm::VmTxExecutionResult,
+    vm::{VmExecutionLogs, VmPartialExecutionResult, VmTxExecutionResult},
     vm_with_bootloader::{
         derive_base_fee_and_gas_per_pubdata, init_vm_inner, push_transaction_to_bootloader_memory,
         BlockContext, BlockContextMode, BootloaderJobType, DerivedBlockContext, TxExecutionMode,
         BLOCK_OVERHEAD_PUBDATA,
     },
     HistoryDisabled, HistoryEnabled, HistoryMode, OracleTools, TxRevertReason, VmBlockResult,
-    VmInstance,
+    VmInstance, VmRevertReason,
 };
 use zksync_basic_types::{
     web3::{self, signing::keccak256},
-    AccountTreeId, Bytes, H160, H256, U256, U64,
+    AccountTreeId, Bytes, L1BatchNumber, MiniblockNumber, H160, H256, U256, U64,
 };
 use zksync_contracts::BaseSystemContracts;
 use zksync_core::api_server::web3::backend_jsonrpc::{
@@ -42,7 +51,10 @@ use zksync_core::api_server::web3::backend_jsonrpc::{
 };
 use zksync_state::{ReadStorage, StorageView, WriteStorage};
 use zksync_types::{
-    api::{Block, Log, TransactionReceipt, TransactionVariant},
+    api::{
+        Block, BlockDetails, BlockDetailsBase, BlockId, BlockIdVariant, BlockNumber, BlockStatus,
+        L2ToL1LogProof, Log, TransactionReceipt, TransactionVariant,
+    },
     fee::Fee,
     get_code_key, get_nonce_key,
     l2::L2Tx,
@@ -56,16 +68,18 @@ use zksync_types::{
     zk_evm::{
         block_properties::BlockProperties, zkevm_opcode_defs::system_params::MAX_PUBDATA_PER_BLOCK,
     },
-    StorageKey, StorageLogQueryType, Transaction, ACCOUNT_CODE_STORAGE_ADDRESS,
-    L2_ETH_TOKEN_ADDRESS, MAX_GAS_PER_PUBDATA_BYTE, MAX_L2_TX_GAS_LIMIT,
+    ExecuteTransactionCommon, L2ChainId, StorageKey, StorageLogQueryType, Transaction, VmEvent,
+    ACCOUNT_CODE_STORAGE_ADDRESS, L1_MESSENGER_ADDRESS, L2_ETH_TOKEN_ADDRESS,
+    MAX_GAS_PER_PUBDATA_BYTE, MAX_L2_TX_GAS_LIMIT, PRIORITY_OPERATION_L2_TX_TYPE,
 };
 use zksync_utils::{
+    be_words_to_bytes,
     bytecode::{compress_bytecode, hash_bytecode},
     h256_to_account_address, h256_to_u256, h256_to_u64, u256_to_h256,
 };
 use zksync_web3_decl::{
     error::Web3Error,
-    types::{FeeHistory, Filter, FilterChanges},
+    types::{FeeHistory, Filter, FilterChanges, Token},
 };
 
 /// Max possible size of an ABI encoded tx (in bytes).
@@ -76,7 +90,7 @@ pub const NON_FORK_FIRST_BLOCK_TIMESTAMP: u64 = 1_000;
 pub const TEST_NODE_NETWORK_ID: u16 = 260;
 /// L1 Gas Price.
 pub const L1_GAS_PRICE: u64 = 50_000_000_000;
-/// L2 Gas Price (0.25 gwei).
+/// Default L2 Gas Price (0.25 gwei), see `InMemoryNodeInner::l2_gas_price`.
 pub const L2_GAS_PRICE: u64 = 250_000_000;
 /// L1 Gas Price Scale Factor for gas estimation.
 pub const ESTIMATE_GAS_L1_GAS_PRICE_SCALE_FACTOR: f64 = 1.2;
@@ -86,27 +100,301 @@ pub const ESTIMATE_GAS_PUBLISH_BYTE_OVERHEAD: u32 = 100;
 pub const ESTIMATE_GAS_ACCEPTABLE_OVERESTIMATION: u32 = 1_000;
 /// The factor by which to scale the gasLimit.
 pub const ESTIMATE_GAS_SCALE_FACTOR: f32 = 1.3;
+/// Balance of the zero address during calls and gas estimations, see [`fund_default_sender`].
+pub const DEFAULT_SENDER_BALANCE: u128 = 10u128.pow(30);
+/// USD price of ETH returned by `zks_getTokenPrice`, unless set with `config_setTokenPrice`.
+pub const DEFAULT_ETH_PRICE: u64 = 1_500;
+
+/// List of wallets (address, private key) that we seed with tokens at start.
+pub const RICH_WALLETS: [(&str, &str); 10] = [
+    (
+        "0x36615Cf349d7F6344891B1e7CA7C72883F5dc049",
+        "0x7726827caac94a7f9e1b160f7ea819f172f7b6f9d2a97f992c38edeab82d4110",
+    ),
+    (
+        "0xa61464658AfeAf65CccaaFD3a512b69A83B77618",
+        "0xac1e735be8536c6534bb4f17f06f6afc73b2b5ba84ac2cfb12f7461b20c0bbe3",
+    ),
+    (
+        "0x0D43eB5B8a47bA8900d84AA36656c92024e9772e",
+        "0xd293c684d884d56f8d6abd64fc76757d3664904e309a0645baf8522ab6366d9e",
+    ),
+    (
+        "0xA13c10C0D5bd6f79041B9835c63f91de35A15883",
+        "0x850683b40d4a740aa6e745f889a6fdc8327be76e122f5aba645a5b02d0248db8",
+    ),
+    (
+        "0x8002cD98Cfb563492A6fB3E7C8243b7B9Ad4cc92",
+        "0xf12e28c0eb1ef4ff90478f6805b68d63737b7f33abfa091601140805da450d93",
+    ),
+    (
+        "0x4F9133D1d3F50011A6859807C837bdCB31Aaab13",
+        "0xe667e57a9b8aaa6709e51ff7d093f1c5b73b63f9987e4ab4aa9a5c699e024ee8",
+    ),
+    (
+        "0xbd29A1B981925B94eEc5c4F1125AF02a2Ec4d1cA",
+        "0x28a574ab2de8a00364d5dd4b07c4f2f574ef7fcc2a86a197f65abaec836d1959",
+    ),
+    (
+        "0xedB6F5B4aab3dD95C7806Af42881FF12BE7e9daa",
+        "0x74d8b3a188f7260f67698eb44da07397a298df5427df681ef68c45b34b61f998",
+    ),
+    (
+        "0xe706e60ab5Dc512C36A4646D719b889F398cbBcB",
+        "0xbe79721778b48bcc679b78edac0ce48306a8578186ffcb9f2ee455ae6efeace1",
+    ),
+    (
+        "0xE90E12261CCb0F3F7976Ae611A29e84a6A85f424",
+        "0x3eb15da85647edd9a1159a4a13b9e7c56877c4eb33f614546d4db06a51868b1c",
+    ),
+];
 
 pub fn compute_hash(block_number: u32, tx_hash: H256) -> H256 {
     let digest = [&block_number.to_be_bytes()[..], tx_hash.as_bytes()].concat();
     H256(keccak256(&digest))
 }
 
+/// Returns the private key of the given address, if it is one of the [`RICH_WALLETS`].
+pub fn rich_wallet_private_key(address: &H160) -> Option<H256> {
+    RICH_WALLETS
+        .iter()
+        .find(|(wallet_address, _)| H160::from_str(wallet_address).ok().as_ref() == Some(address))
+        .and_then(|(_, private_key)| H256::from_str(private_key).ok())
+}
+
 /// Information about the executed transaction.
+#[derive(Clone)]
 pub struct TxExecutionInfo {
-    pub tx: L2Tx,
+    // The L2 transaction, or the L1 transaction of a simulated priority operation.
+    pub tx: Transaction,
     // Batch number where transaction was executed.
     pub batch_number: u32,
     pub miniblock_number: u64,
+    // L2 gas price when the transaction was executed, its fee is the gas used at this price.
+    pub gas_price: u64,
+    // Contract deployed by the transaction, if any.
+    pub contract_address: Option<H160>,
     pub result: VmTxExecutionResult,
 }
 
+impl TxExecutionInfo {
+    /// Returns the gas used by the transaction: its gas limit, less the refunded gas.
+    pub fn gas_used(&self) -> U256 {
+        self.tx.gas_limit() - self.result.gas_refunded
+    }
+}
+
+impl From<&TxExecutionInfo> for SerializableTransaction {
+    fn from(info: &TxExecutionInfo) -> Self {
+        Self {
+            tx: info.tx.clone(),
+            batch_number: info.batch_number,
+            miniblock_number: info.miniblock_number,
+            gas_price: info.gas_price,
+            success: info.result.status == TxExecutionStatus::Success,
+            revert_reason: info
+                .result
+                .result
+                .revert_reason
+                .as_ref()
+                .map(|revert_reason| revert_reason.to_string()),
+            gas_refunded: info.result.gas_refunded,
+            contract_address: info.contract_address,
+            events: info.result.result.logs.events.clone(),
+            l2_to_l1_logs: info.result.result.logs.l2_to_l1_logs.clone(),
+            call_traces: info.result.call_traces.clone(),
+            contracts_used: info.result.result.contracts_used,
+            cycles_used: info.result.result.cycles_used,
+            computational_gas_used: info.result.result.computational_gas_used,
+        }
+    }
+}
+
+/// Rebuilds the execution info from a state file. The storage logs are not part of it.
+impl From<SerializableTransaction> for TxExecutionInfo {
+    fn from(tx: SerializableTransaction) -> Self {
+        Self {
+            tx: tx.tx,
+            batch_number: tx.batch_number,
+            miniblock_number: tx.miniblock_number,
+            gas_price: tx.gas_price,
+            contract_address: tx.contract_address,
+            result: VmTxExecutionResult {
+                status: if tx.success {
+                    TxExecutionStatus::Success
+                } else {
+                    TxExecutionStatus::Failure
+                },
+                result: VmPartialExecutionResult {
+                    logs: VmExecutionLogs {
+                        events: tx.events,
+                        l2_to_l1_logs: tx.l2_to_l1_logs,
+                        ..Default::default()
+                    },
+                    revert_reason: tx.revert_reason.map(|msg| {
+                        TxRevertReason::TxReverted(VmRevertReason::General { msg, data: vec![] })
+                    }),
+                    contracts_used: tx.contracts_used,
+                    cycles_used: tx.cycles_used,
+                    computational_gas_used: tx.computational_gas_used,
+                },
+                call_traces: tx.call_traces,
+                gas_refunded: tx.gas_refunded,
+                operator_suggested_refund: 0,
+            },
+        }
+    }
+}
+
+/// Fee parameters the node starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeConfig {
+    /// Gas price returned by `eth_gasPrice` and `eth_feeHistory`, and reported as the effective
+    /// gas price of the executed transactions.
+    pub l2_gas_price: u64,
+    /// Price of the L2 gas the VM charges the transactions with. The base fee returned by
+    /// `zks_estimateFee` is derived from it and the L1 gas price.
+    pub fair_l2_gas_price: u64,
+    /// Gas charged per byte of pubdata by `eth_estimateGas` and `zks_estimateFee`, instead of
+    /// the value derived from the L1 and fair L2 gas prices.
+    pub gas_per_pubdata: Option<u64>,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            l2_gas_price: L2_GAS_PRICE,
+            fair_l2_gas_price: L2_GAS_PRICE,
+            gas_per_pubdata: None,
+        }
+    }
+}
+
+/// Settings the node is started with, set with the command line options of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeConfig {
+    pub show_calls: ShowCalls,
+    pub max_call_depth: Option<usize>,
+    pub gas_highlight_threshold: u32,
+    pub print_events: bool,
+    pub print_storage_logs: bool,
+    pub show_storage_logs: ShowStorageLogs,
+    pub show_vm_details: ShowVMDetails,
+    pub show_gas_details: ShowGasDetails,
+    pub formatter_output: FormatterOutput,
+    pub resolve_hashes: bool,
+    pub system_contracts_options: system_contracts::Options,
+    pub fee_config: FeeConfig,
+    /// Accounts written to the storage when the node starts, and again on `hardhat_reset`.
+    pub genesis: Option<Genesis>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            show_calls: ShowCalls::None,
+            max_call_depth: None,
+            gas_highlight_threshold: DEFAULT_GAS_HIGHLIGHT_THRESHOLD,
+            print_events: true,
+            print_storage_logs: true,
+            show_storage_logs: ShowStorageLogs::None,
+            show_vm_details: ShowVMDetails::None,
+            show_gas_details: ShowGasDetails::None,
+            formatter_output: FormatterOutput::Text,
+            resolve_hashes: false,
+            system_contracts_options: system_contracts::Options::BuiltIn,
+            fee_config: FeeConfig::default(),
+            genesis: None,
+        }
+    }
+}
+
+/// Fee estimated by `zks_estimateFee`, together with the components of its gas limit.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FeeBreakdown {
+    #[serde(flatten)]
+    pub fee: Fee,
+    /// Gas needed to execute the transaction body, scaled up by [`ESTIMATE_GAS_SCALE_FACTOR`].
+    pub tx_body_gas: U256,
+    /// Gas charged for publishing the factory dependencies the network doesn't know yet.
+    pub pubdata_cost: U256,
+    /// Gas charged for the batch resources the transaction takes up (bootloader memory,
+    /// transaction slots and encoding).
+    pub overhead: U256,
+}
+
+/// Message sent to L1 with `L1Messenger.sendToL1`, returned by `hardhat_getL2ToL1Messages`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L2ToL1Message {
+    pub block_number: U64,
+    pub l1_batch_number: U64,
+    pub transaction_hash: H256,
+    /// Index of the log among the L2→L1 logs of the transaction, as passed to
+    /// `zks_getL2ToL1LogProof`.
+    pub l2_to_l1_log_index: U64,
+    /// Contract that called `sendToL1`.
+    pub sender: H160,
+    /// Hash of the message, the value of the L2→L1 log.
+    pub hash: H256,
+    pub message: Bytes,
+}
+
+/// Order in which the transactions waiting in the mempool are mined.
+#[derive(Debug, Clone, Copy, clap::ValueEnum, PartialEq, Eq)]
+pub enum TransactionOrder {
+    /// In the order they were received.
+    Fifo,
+    /// Highest `maxFeePerGas` first, keeping the nonce order of the transactions of each sender.
+    Fees,
+}
+
+impl TransactionOrder {
+    /// Returns `txs`, in the order they were received, in the order to mine them.
+    pub fn sort(&self, txs: Vec<L2Tx>) -> Vec<L2Tx> {
+        match self {
+            TransactionOrder::Fifo => txs,
+            TransactionOrder::Fees => {
+                // transactions of each sender, by nonce, with senders in order of arrival
+                let mut queues: Vec<VecDeque<L2Tx>> = vec![];
+                for tx in txs {
+                    match queues
+                        .iter_mut()
+                        .find(|queue| queue[0].initiator_account() == tx.initiator_account())
+                    {
+                        Some(queue) => queue.push_back(tx),
+                        None => queues.push(VecDeque::from([tx])),
+                    }
+                }
+                for queue in &mut queues {
+                    queue.make_contiguous().sort_by_key(|tx| tx.nonce());
+                }
+
+                let mut sorted = vec![];
+                while let Some(queue) = queues
+                    .iter_mut()
+                    .filter(|queue| !queue.is_empty())
+                    .rev()
+                    .max_by_key(|queue| queue[0].common_data.fee.max_fee_per_gas)
+                {
+                    sorted.extend(queue.pop_front());
+                }
+                sorted
+            }
+        }
+    }
+}
+
 #[derive(Debug, clap::Parser, Clone, clap::ValueEnum, PartialEq, Eq)]
 pub enum ShowCalls {
     None,
     User,
     System,
     All,
+    /// Same calls as `All`, but printed one per line prefixed with the call depth.
+    Flat,
+    /// Only the calls that reverted or failed, together with the calls leading to them.
+    Errors,
 }
 
 impl FromStr for ShowCalls {
@@ -118,8 +406,10 @@ impl FromStr for ShowCalls {
             "user" => Ok(ShowCalls::User),
             "system" => Ok(ShowCalls::System),
             "all" => Ok(ShowCalls::All),
+            "flat" => Ok(ShowCalls::Flat),
+            "errors" => Ok(ShowCalls::Errors),
             _ => Err(format!(
-                "Unknown ShowCalls value {} - expected one of none|user|system|all.",
+                "Unknown ShowCalls value {} - expected one of none|user|system|all|flat|errors.",
                 s
             )),
         }
@@ -217,6 +507,91 @@ impl Display for ShowGasDetails {
     }
 }
 
+#[derive(Debug, Parser, Clone, clap::ValueEnum, PartialEq, Eq)]
+pub enum FormatterOutput {
+    Text,
+    Json,
+}
+
+impl FromStr for FormatterOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "text" => Ok(FormatterOutput::Text),
+            "json" => Ok(FormatterOutput::Json),
+            _ => Err(format!(
+                "Unknown FormatterOutput value {} - expected one of text|json.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for FormatterOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Reasons a transaction is rejected before being executed. They are rendered as geth does,
+/// since tooling like hardhat classifies the failures by matching on these messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionValidationError {
+    /// The nonce was already used by the account.
+    NonceTooLow {
+        address: H160,
+        tx_nonce: U256,
+        account_nonce: U256,
+    },
+    /// The nonce leaves a gap after the nonces used by the account and its pending transactions.
+    NonceTooHigh {
+        address: H160,
+        tx_nonce: U256,
+        expected_nonce: U256,
+    },
+    /// The account can't pay for `gas_limit * max_fee_per_gas + value`.
+    InsufficientFunds {
+        address: H160,
+        balance: U256,
+        cost: U256,
+    },
+}
+
+impl Display for TransactionValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            TransactionValidationError::NonceTooLow {
+                address,
+                tx_nonce,
+                account_nonce,
+            } => write!(
+                f,
+                "nonce too low: address {:?}, tx: {} state: {}",
+                address, tx_nonce, account_nonce
+            ),
+            TransactionValidationError::NonceTooHigh {
+                address,
+                tx_nonce,
+                expected_nonce,
+            } => write!(
+                f,
+                "nonce too high: address {:?}, tx: {} state: {}",
+                address, tx_nonce, expected_nonce
+            ),
+            TransactionValidationError::InsufficientFunds {
+                address,
+                balance,
+                cost,
+            } => write!(
+                f,
+                "insufficient funds for gas * price + value: address {:?} have {} want {}",
+                address, balance, cost
+            ),
+        }
+    }
+}
+
 /// Helper struct for InMemoryNode.
 /// S - is the Source of the Fork.
 pub struct InMemoryNodeInner<S> {
@@ -225,27 +600,116 @@ pub struct InMemoryNodeInner<S> {
     pub current_batch: u32,
     /// The latest miniblock number.
     pub current_miniblock: u64,
+    /// Total time (in seconds) the clock was moved forward with `evm_increaseTime`.
+    pub time_offset: u64,
     pub l1_gas_price: u64,
+    /// The L2 gas price returned by `eth_gasPrice`, see [`FeeConfig::l2_gas_price`].
+    pub l2_gas_price: u64,
+    /// The L2 gas price used by the VM and fee estimation, see [`FeeConfig::fair_l2_gas_price`].
+    pub fair_l2_gas_price: u64,
+    /// Fixed gas per pubdata byte for fee estimation, see [`FeeConfig::gas_per_pubdata`].
+    pub gas_per_pubdata: Option<u64>,
     // Map from transaction to details about the exeuction
     pub tx_results: HashMap<H256, TxExecutionInfo>,
     // Map from block hash to information about the block.
     pub blocks: HashMap<H256, Block<TransactionVariant>>,
     // Map from block number to a block hash.
     pub block_hashes: HashMap<u64, H256>,
+    // L1 gas price and fair L2 gas price each local block was sealed with, by block number.
+    pub block_gas_prices: HashMap<u64, (u64, u64)>,
     // Underlying storage
     pub fork_storage: ForkStorage<S>,
     // Debug level information.
     pub show_calls: ShowCalls,
+    // Maximum depth of the printed call traces, deeper subcalls are summarized.
+    pub max_call_depth: Option<usize>,
+    // Gas used by a call above which it is highlighted in the printed call traces.
+    pub gas_highlight_threshold: u32,
+    // If false - events emitted by transactions are not printed.
+    pub print_events: bool,
+    // If false - storage logs are not printed, whatever `show_storage_logs` is set to.
+    pub print_storage_logs: bool,
     // Displays storage logs.
     pub show_storage_logs: ShowStorageLogs,
     // Displays VM details.
     pub show_vm_details: ShowVMDetails,
     // Gas details information.
     pub show_gas_details: ShowGasDetails,
+    // Format of the printed calls, events and storage logs.
+    pub formatter_output: FormatterOutput,
     // If true - will contact openchain to resolve the ABI to function names.
     pub resolve_hashes: bool,
     pub console_log_handler: ConsoleLogHandler,
     pub system_contracts: SystemContracts,
+    // Where the system contracts are loaded from, used when reloading them.
+    pub system_contracts_options: system_contracts::Options,
+    // Accounts written to the storage at genesis (`--genesis`), written again on reset.
+    pub genesis: Option<Genesis>,
+    // Filters installed with `eth_newFilter` and polled with `eth_getFilterChanges`.
+    pub filters: EthFilters,
+    // Accounts whose transactions are executed without verifying the signature.
+    pub impersonated_accounts: HashSet<H160>,
+    // Code hashes set locally for every address, with the block they were set at (in block order).
+    pub code_history: HashMap<H160, Vec<(u64, H256)>>,
+    // Transactions received with `eth_sendRawTransaction` that are not in a block yet, with their sender.
+    pub pending_transactions: HashMap<H256, H160>,
+    // If false, the received transactions wait in the mempool until a block is mined (`--no-mining`).
+    pub automine: bool,
+    // Transactions waiting to be mined when automine is disabled, in the order they were received.
+    pub mempool: Vec<L2Tx>,
+    // L1 transactions added with `hardhat_simulateL1ToL2Tx`, mined before the mempool.
+    pub priority_queue: Vec<Transaction>,
+    // Serial id of the next L1 transaction added to the priority queue.
+    pub next_priority_op_id: u64,
+    // Order in which the mempool is mined, set with `--order`.
+    pub transaction_order: TransactionOrder,
+    // Subscriptions created with `eth_subscribe` over WebSocket.
+    pub subscriptions: EthSubscriptions,
+    // Background task sealing blocks at a fixed interval, set with `evm_setIntervalMining`.
+    pub interval_mining: Option<tokio::task::JoinHandle<()>>,
+    // Snapshots taken with `evm_snapshot`, keyed by their id. Ids are never reused.
+    pub snapshots: BTreeMap<U64, StateSnapshot>,
+    // Id of the last snapshot taken.
+    pub last_snapshot_id: U64,
+    // Contracts that emitted an ERC20 `Transfer` event, whose balances `zks_getAllAccountBalances` returns.
+    pub known_tokens: HashSet<H160>,
+    // Tokens added with `register_token`, returned by `zks_getConfirmedTokens`.
+    pub registered_tokens: Vec<Token>,
+    // USD prices returned by `zks_getTokenPrice`, set with `config_setTokenPrice`.
+    pub token_prices: HashMap<H160, BigDecimal>,
+    // If true, the prices not set locally are read from the forked network (`--fork-token-prices`).
+    pub forward_token_prices: bool,
+}
+
+/// Copy of the node state taken by `evm_snapshot`, that `evm_revert` restores.
+/// Data read from the fork is not part of it, as it doesn't change.
+#[derive(Clone)]
+pub struct StateSnapshot {
+    current_timestamp: u64,
+    current_batch: u32,
+    current_miniblock: u64,
+    time_offset: u64,
+    l1_gas_price: u64,
+    l2_gas_price: u64,
+    fair_l2_gas_price: u64,
+    gas_per_pubdata: Option<u64>,
+    tx_results: HashMap<H256, TxExecutionInfo>,
+    blocks: HashMap<H256, Block<TransactionVariant>>,
+    block_hashes: HashMap<u64, H256>,
+    block_gas_prices: HashMap<u64, (u64, u64)>,
+    impersonated_accounts: HashSet<H160>,
+    code_history: HashMap<H160, Vec<(u64, H256)>>,
+    pending_transactions: HashMap<H256, H160>,
+    mempool: Vec<L2Tx>,
+    priority_queue: Vec<Transaction>,
+    next_priority_op_id: u64,
+    filters: EthFilters,
+    known_tokens: HashSet<H160>,
+    registered_tokens: Vec<Token>,
+    token_prices: HashMap<H160, BigDecimal>,
+    raw_storage: InMemoryStorage,
+    storage_tree: StorageTree,
+    modified_keys: HashSet<StorageKey>,
 }
 
 type L2TxResult = (
@@ -256,2417 +720,5170 @@ type L2TxResult = (
 );
 
 impl<S: std::fmt::Debug + ForkSource> InMemoryNodeInner<S> {
-    pub fn create_block_context(&self) -> BlockContext {
-        BlockContext {
-            block_number: self.current_batch,
-            block_timestamp: self.current_timestamp,
+    /// Takes a snapshot of the current state: blocks, transactions (mined and pending), filters,
+    /// tokens and local storage.
+    pub fn snapshot(&self) -> Result<StateSnapshot, String> {
+        let storage = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?;
+
+        Ok(StateSnapshot {
+            current_timestamp: self.current_timestamp,
+            current_batch: self.current_batch,
+            current_miniblock: self.current_miniblock,
+            time_offset: self.time_offset,
             l1_gas_price: self.l1_gas_price,
-            fair_l2_gas_price: L2_GAS_PRICE,
-            operator_address: H160::zero(),
-        }
+            l2_gas_price: self.l2_gas_price,
+            fair_l2_gas_price: self.fair_l2_gas_price,
+            gas_per_pubdata: self.gas_per_pubdata,
+            tx_results: self.tx_results.clone(),
+            blocks: self.blocks.clone(),
+            block_hashes: self.block_hashes.clone(),
+            block_gas_prices: self.block_gas_prices.clone(),
+            impersonated_accounts: self.impersonated_accounts.clone(),
+            code_history: self.code_history.clone(),
+            pending_transactions: self.pending_transactions.clone(),
+            mempool: self.mempool.clone(),
+            priority_queue: self.priority_queue.clone(),
+            next_priority_op_id: self.next_priority_op_id,
+            filters: self.filters.clone(),
+            known_tokens: self.known_tokens.clone(),
+            registered_tokens: self.registered_tokens.clone(),
+            token_prices: self.token_prices.clone(),
+            raw_storage: storage.raw_storage.clone(),
+            storage_tree: storage.storage_tree.clone(),
+            modified_keys: storage.modified_keys.clone(),
+        })
     }
 
-    pub fn create_block_properties(contracts: &BaseSystemContracts) -> BlockProperties {
-        BlockProperties {
-            default_aa_code_hash: h256_to_u256(contracts.default_aa.hash),
-            zkporter_is_available: false,
-        }
+    /// Restores the state captured by [`InMemoryNodeInner::snapshot`].
+    pub fn restore_snapshot(&mut self, snapshot: StateSnapshot) -> Result<(), String> {
+        let mut storage = self
+            .fork_storage
+            .inner
+            .write()
+            .map_err(|err| format!("failed acquiring write lock on storage: {:?}", err))?;
+        storage.raw_storage = snapshot.raw_storage;
+        storage.storage_tree = snapshot.storage_tree;
+        storage.modified_keys = snapshot.modified_keys;
+        drop(storage);
+
+        self.current_timestamp = snapshot.current_timestamp;
+        self.current_batch = snapshot.current_batch;
+        self.current_miniblock = snapshot.current_miniblock;
+        self.time_offset = snapshot.time_offset;
+        self.l1_gas_price = snapshot.l1_gas_price;
+        self.l2_gas_price = snapshot.l2_gas_price;
+        self.fair_l2_gas_price = snapshot.fair_l2_gas_price;
+        self.gas_per_pubdata = snapshot.gas_per_pubdata;
+        self.tx_results = snapshot.tx_results;
+        self.blocks = snapshot.blocks;
+        self.block_hashes = snapshot.block_hashes;
+        self.block_gas_prices = snapshot.block_gas_prices;
+        self.impersonated_accounts = snapshot.impersonated_accounts;
+        self.code_history = snapshot.code_history;
+        self.pending_transactions = snapshot.pending_transactions;
+        self.mempool = snapshot.mempool;
+        self.priority_queue = snapshot.priority_queue;
+        self.next_priority_op_id = snapshot.next_priority_op_id;
+        self.filters = snapshot.filters;
+        self.known_tokens = snapshot.known_tokens;
+        self.registered_tokens = snapshot.registered_tokens;
+        self.token_prices = snapshot.token_prices;
+        Ok(())
     }
 
-    /// Estimates the gas required for a given call request.
-    ///
-    /// # Arguments
-    ///
-    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` with a `Fee` representing the estimated gas related data.
-    pub fn estimate_gas_impl(
-        &self,
-        req: zksync_types::transaction_request::CallRequest,
-    ) -> jsonrpc_core::Result<Fee> {
-        let mut l2_tx = match L2Tx::from_request(req.into(), MAX_TX_SIZE) {
-            Ok(tx) => tx,
-            Err(e) => {
-                let error = Web3Error::SerializationError(e);
-                return Err(into_jsrpc_error(error));
-            }
-        };
-
-        let tx: Transaction = l2_tx.clone().into();
-        let fair_l2_gas_price = L2_GAS_PRICE;
+    /// Returns the state written by `anvil_dumpState`: the local storage, blocks, transactions,
+    /// tokens and clock. On a forked node, the data read from the fork is not included.
+    pub fn dump_state(&self) -> Result<SerializableState, String> {
+        let storage = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?;
 
-        // Calculate Adjusted L1 Price
-        let l1_gas_price = {
-            let current_l1_gas_price =
-                ((self.l1_gas_price as f64) * ESTIMATE_GAS_L1_GAS_PRICE_SCALE_FACTOR) as u64;
+        let mut storage_entries = storage
+            .raw_storage
+            .state
+            .iter()
+            .map(|(key, value)| StorageEntry {
+                address: *key.address(),
+                key: *key.key(),
+                value: *value,
+            })
+            .collect::<Vec<_>>();
+        storage_entries.sort_by_key(|entry| (entry.address, entry.key));
+        let mut blocks = self.blocks.values().cloned().collect::<Vec<_>>();
+        blocks.sort_by_key(|block| block.number);
+        let mut transactions = self
+            .tx_results
+            .values()
+            .map(SerializableTransaction::from)
+            .collect::<Vec<_>>();
+        transactions.sort_by_key(|tx| (tx.miniblock_number, tx.tx.hash()));
+
+        Ok(SerializableState {
+            version: STATE_VERSION,
+            current_timestamp: self.current_timestamp,
+            current_batch: self.current_batch,
+            current_miniblock: self.current_miniblock,
+            time_offset: self.time_offset,
+            l1_gas_price: self.l1_gas_price,
+            l2_gas_price: self.l2_gas_price,
+            fair_l2_gas_price: self.fair_l2_gas_price,
+            gas_per_pubdata: self.gas_per_pubdata,
+            storage: storage_entries,
+            factory_deps: storage
+                .raw_storage
+                .factory_deps
+                .iter()
+                .map(|(hash, bytecode)| (*hash, Bytes(bytecode.clone())))
+                .collect(),
+            blocks,
+            block_hashes: self.block_hashes.clone().into_iter().collect(),
+            block_gas_prices: self.block_gas_prices.clone().into_iter().collect(),
+            impersonated_accounts: self.impersonated_accounts.iter().copied().collect(),
+            code_history: self.code_history.clone().into_iter().collect(),
+            transactions,
+            mempool: self.mempool.clone(),
+            priority_queue: self.priority_queue.clone(),
+            next_priority_op_id: self.next_priority_op_id,
+            known_tokens: self.known_tokens.iter().copied().collect(),
+            registered_tokens: self.registered_tokens.clone(),
+            token_prices: self
+                .token_prices
+                .iter()
+                .map(|(token, price)| (*token, price.to_string()))
+                .collect(),
+        })
+    }
 
-            // In order for execution to pass smoothly, we need to ensure that block's required gasPerPubdata will be
-            // <= to the one in the transaction itself.
-            adjust_l1_gas_price_for_tx(
-                current_l1_gas_price,
-                L2_GAS_PRICE,
-                tx.gas_per_pubdata_byte_limit(),
-            )
-        };
+    /// Replaces the local storage, blocks, transactions, tokens and clock with the ones from
+    /// `state`. The snapshots, the filters and the values cached from the fork are dropped.
+    /// The version of the state is checked when it is read, see [`SerializableState::from_file`].
+    pub fn load_state(&mut self, state: SerializableState) -> Result<(), String> {
+        let token_prices = state
+            .token_prices
+            .into_iter()
+            .map(|(token, price)| {
+                BigDecimal::from_str(&price)
+                    .map(|price| (token, price))
+                    .map_err(|err| {
+                        format!("invalid price {:?} of token {:?}: {}", price, token, err)
+                    })
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
 
-        let (base_fee, gas_per_pubdata_byte) =
-            derive_base_fee_and_gas_per_pubdata(l1_gas_price, fair_l2_gas_price);
+        let mut storage = self
+            .fork_storage
+            .inner
+            .write()
+            .map_err(|err| format!("failed acquiring write lock on storage: {:?}", err))?;
+        storage.replace_raw_storage(InMemoryStorage {
+            state: state
+                .storage
+                .into_iter()
+                .map(|entry| {
+                    (
+                        StorageKey::new(entry.address.into(), entry.key),
+                        entry.value,
+                    )
+                })
+                .collect(),
+            factory_deps: state
+                .factory_deps
+                .into_iter()
+                .map(|(hash, bytecode)| (hash, bytecode.0))
+                .collect(),
+        });
+        storage.value_read_cache.clear();
+        storage.factory_dep_cache.clear();
+        drop(storage);
+
+        self.current_timestamp = state.current_timestamp;
+        self.current_batch = state.current_batch;
+        self.current_miniblock = state.current_miniblock;
+        self.time_offset = state.time_offset;
+        self.l1_gas_price = state.l1_gas_price;
+        self.l2_gas_price = state.l2_gas_price;
+        self.fair_l2_gas_price = state.fair_l2_gas_price;
+        self.gas_per_pubdata = state.gas_per_pubdata;
+        self.tx_results = state
+            .transactions
+            .into_iter()
+            .map(|tx| (tx.tx.hash(), TxExecutionInfo::from(tx)))
+            .collect();
+        self.blocks = state
+            .blocks
+            .into_iter()
+            .map(|block| (block.hash, block))
+            .collect();
+        self.block_hashes = state.block_hashes.into_iter().collect();
+        self.block_gas_prices = state.block_gas_prices.into_iter().collect();
+        self.impersonated_accounts = state.impersonated_accounts.into_iter().collect();
+        self.code_history = state.code_history.into_iter().collect();
+        self.pending_transactions = state
+            .mempool
+            .iter()
+            .map(|tx| (tx.hash(), tx.initiator_account()))
+            .collect();
+        self.mempool = state.mempool;
+        self.priority_queue = state.priority_queue;
+        self.next_priority_op_id = state.next_priority_op_id;
+        self.known_tokens = state.known_tokens.into_iter().collect();
+        self.registered_tokens = state.registered_tokens;
+        self.token_prices = token_prices;
+        self.snapshots.clear();
+        self.filters = Default::default();
+        Ok(())
+    }
 
-        // Properly format signature
-        if l2_tx.common_data.signature.is_empty() {
-            l2_tx.common_data.signature = vec![0u8; 65];
-            l2_tx.common_data.signature[64] = 27;
+    /// Returns the current settings of the node, as they were changed with the `config_*`
+    /// methods.
+    pub fn config(&self) -> NodeConfig {
+        NodeConfig {
+            show_calls: self.show_calls.clone(),
+            max_call_depth: self.max_call_depth,
+            gas_highlight_threshold: self.gas_highlight_threshold,
+            print_events: self.print_events,
+            print_storage_logs: self.print_storage_logs,
+            show_storage_logs: self.show_storage_logs.clone(),
+            show_vm_details: self.show_vm_details.clone(),
+            show_gas_details: self.show_gas_details.clone(),
+            formatter_output: self.formatter_output.clone(),
+            resolve_hashes: self.resolve_hashes,
+            system_contracts_options: self.system_contracts_options.clone(),
+            genesis: self.genesis.clone(),
+            fee_config: FeeConfig {
+                l2_gas_price: self.l2_gas_price,
+                fair_l2_gas_price: self.fair_l2_gas_price,
+                gas_per_pubdata: self.gas_per_pubdata,
+            },
         }
+    }
 
-        l2_tx.common_data.fee.gas_per_pubdata_limit = MAX_GAS_PER_PUBDATA_BYTE.into();
-        l2_tx.common_data.fee.max_fee_per_gas = base_fee.into();
-        l2_tx.common_data.fee.max_priority_fee_per_gas = base_fee.into();
+    /// Discards the whole state and starts again from the genesis (or the `fork`) block, as a
+    /// freshly started node with the same settings. The snapshots are dropped, and the rich
+    /// wallets are funded again. The mining mode and the subscriptions are kept.
+    pub fn reset(&mut self, fork: Option<ForkDetails<S>>) -> Result<(), String> {
+        let node = InMemoryNode::try_new(fork, &self.config())?;
+        let mut inner = Arc::try_unwrap(node.inner)
+            .map_err(|_| String::from("node is still in use"))?
+            .into_inner()
+            .map_err(|err| format!("failed acquiring lock: {:?}", err))?;
+
+        for (address, _) in RICH_WALLETS {
+            let address = H160::from_str(address).map_err(|err| err.to_string())?;
+            inner.fork_storage.set_value(
+                storage_key_for_eth_balance(&address),
+                u256_to_h256(U256::from(DEFAULT_SENDER_BALANCE)),
+            );
+        }
+        inner.automine = self.automine;
+        inner.transaction_order = self.transaction_order;
+        inner.token_prices = std::mem::take(&mut self.token_prices);
+        inner.forward_token_prices = self.forward_token_prices;
+        inner.subscriptions = std::mem::take(&mut self.subscriptions);
+        inner.interval_mining = self.interval_mining.take();
+
+        *self = inner;
+        Ok(())
+    }
 
-        let mut storage_view = StorageView::new(&self.fork_storage);
+    /// Runs L2 'eth call' method - that doesn't commit to a block.
+    /// The `state_override` is only visible to this call and never reaches the node storage.
+    /// Blocks older than the latest one can only be used at or below the fork point.
+    pub fn run_l2_call(
+        &self,
+        l2_tx: L2Tx,
+        block: Option<BlockIdVariant>,
+        state_override: &StateOverride,
+    ) -> Result<VmBlockResult, String> {
+        match self.historical_fork_block(block)? {
+            None => self.execute_l2_call(
+                OverriddenStorage::new(&self.fork_storage, state_override),
+                l2_tx,
+            ),
+            Some(miniblock) => self.execute_l2_call(
+                OverriddenStorage::new(
+                    ForkStorageAtBlock::new(&self.fork_storage, miniblock),
+                    state_override,
+                ),
+                l2_tx,
+            ),
+        }
+    }
 
-        // Calculate gas_for_bytecodes_pubdata
-        let pubdata_for_factory_deps = l2_tx
-            .execute
-            .factory_deps
-            .as_deref()
-            .unwrap_or_default()
-            .iter()
-            .map(|bytecode| {
-                if storage_view.is_bytecode_known(&hash_bytecode(bytecode)) {
-                    return 0;
-                }
+    /// Executes the call in the playground bootloader on top of `storage`.
+    fn execute_l2_call<T: ReadStorage + std::fmt::Debug>(
+        &self,
+        storage: T,
+        l2_tx: L2Tx,
+    ) -> Result<VmBlockResult, String> {
+        let execution_mode = TxExecutionMode::EthCall {
+            missed_storage_invocation_limit: 1000000,
+        };
 
-                let length = if let Ok(compressed) = compress_bytecode(bytecode) {
-                    compressed.len()
-                } else {
-                    bytecode.len()
-                };
-                length as u32 + ESTIMATE_GAS_PUBLISH_BYTE_OVERHEAD
-            })
-            .sum::<u32>();
+        let mut storage_view = StorageView::new(storage);
+        fund_default_sender(&mut storage_view, &l2_tx);
 
-        if pubdata_for_factory_deps > MAX_PUBDATA_PER_BLOCK {
-            return Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
-                "exceeds limit for published pubdata".into(),
-                Default::default(),
-            )));
-        }
+        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryEnabled);
 
-        let gas_for_bytecodes_pubdata: u32 =
-            pubdata_for_factory_deps * (gas_per_pubdata_byte as u32);
+        let bootloader_code = &self.system_contracts.contacts_for_l2_call();
 
         let block_context = self.create_block_context();
+        let block_properties = Self::create_block_properties(bootloader_code);
 
-        // We are using binary search to find the minimal values of gas_limit under which the transaction succeeds
-        let mut lower_bound = 0;
-        let mut upper_bound = MAX_L2_TX_GAS_LIMIT as u32;
+        // init vm
+        let mut vm = init_vm_inner(
+            &mut oracle_tools,
+            BlockContextMode::NewBlock(block_context.into(), Default::default()),
+            &block_properties,
+            BLOCK_GAS_LIMIT,
+            bootloader_code,
+            execution_mode,
+        );
 
-        while lower_bound + ESTIMATE_GAS_ACCEPTABLE_OVERESTIMATION < upper_bound {
-            let mid = (lower_bound + upper_bound) / 2;
-            let try_gas_limit = gas_for_bytecodes_pubdata + mid;
+        let tx: Transaction = l2_tx.into();
 
-            let estimate_gas_result = InMemoryNodeInner::estimate_gas_step(
-                l2_tx.clone(),
-                gas_per_pubdata_byte,
-                try_gas_limit,
-                l1_gas_price,
-                base_fee,
-                block_context,
-                &self.fork_storage,
-                self.system_contracts.contracts_for_fee_estimate(),
-            );
+        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
 
-            if estimate_gas_result.is_err() {
-                lower_bound = mid + 1;
-            } else {
-                upper_bound = mid;
-            }
+        let vm_block_result =
+            vm.execute_till_block_end_with_call_tracer(BootloaderJobType::TransactionExecution);
+
+        if let Some(revert_reason) = &vm_block_result.full_result.revert_reason {
+            log::info!("Call {} {:?}", "FAILED".red(), revert_reason.revert_reason);
+        } else {
+            log::info!("Call {}", "SUCCESS".green());
         }
+        if let VmTrace::CallTrace(call_trace) = &vm_block_result.full_result.trace {
+            log::info!("=== Console Logs: ");
+            for call in call_trace {
+                self.console_log_handler.handle_call_recurive(call);
+            }
 
-        let tx_body_gas_limit = cmp::min(
-            MAX_L2_TX_GAS_LIMIT as u32,
-            (upper_bound as f32 * ESTIMATE_GAS_SCALE_FACTOR) as u32,
-        );
-        let suggested_gas_limit = tx_body_gas_limit + gas_for_bytecodes_pubdata;
-
-        let estimate_gas_result = InMemoryNodeInner::estimate_gas_step(
-            l2_tx.clone(),
-            gas_per_pubdata_byte,
-            suggested_gas_limit,
-            l1_gas_price,
-            base_fee,
-            block_context,
-            &self.fork_storage,
-            self.system_contracts.contracts_for_fee_estimate(),
-        );
-
-        let overhead: u32 = derive_gas_estimation_overhead(
-            suggested_gas_limit,
-            gas_per_pubdata_byte as u32,
-            tx.encoding_len(),
-        );
-
-        match estimate_gas_result {
-            Err(tx_revert_reason) => {
-                log::info!("{}", format!("Unable to estimate gas for the request with our suggested gas limit of {}. The transaction is most likely unexecutable. Breakdown of estimation:", suggested_gas_limit + overhead).red());
-                log::info!(
-                    "{}",
-                    format!(
-                        "\tEstimated transaction body gas cost: {}",
-                        tx_body_gas_limit
-                    )
-                    .red()
-                );
-                log::info!(
-                    "{}",
-                    format!("\tGas for pubdata: {}", gas_for_bytecodes_pubdata).red()
-                );
-                log::info!("{}", format!("\tOverhead: {}", overhead).red());
-                let message = tx_revert_reason.to_string();
-                let pretty_message = format!(
-                    "execution reverted{}{}",
-                    if message.is_empty() { "" } else { ": " },
-                    message
+            log::info!("=== Call traces:");
+            for call in call_trace {
+                formatter::print_call(
+                    call,
+                    0,
+                    &self.show_calls,
+                    self.max_call_depth,
+                    self.gas_highlight_threshold,
+                    self.resolve_hashes,
+                    &self.formatter_output,
                 );
-                let data = match tx_revert_reason {
-                    TxRevertReason::EthCall(vm_revert_reason) => vm_revert_reason.encoded_data(),
-                    TxRevertReason::TxReverted(vm_revert_reason) => vm_revert_reason.encoded_data(),
-                    _ => vec![],
-                };
-                log::info!("{}", pretty_message.on_red());
-                Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
-                    pretty_message,
-                    data,
-                )))
-            }
-            Ok(_) => {
-                let full_gas_limit = match tx_body_gas_limit
-                    .overflowing_add(gas_for_bytecodes_pubdata + overhead)
-                {
-                    (value, false) => value,
-                    (_, true) => {
-                        log::info!("{}", "Overflow when calculating gas estimation. We've exceeded the block gas limit by summing the following values:".red());
-                        log::info!(
-                            "{}",
-                            format!(
-                                "\tEstimated transaction body gas cost: {}",
-                                tx_body_gas_limit
-                            )
-                            .red()
-                        );
-                        log::info!(
-                            "{}",
-                            format!("\tGas for pubdata: {}", gas_for_bytecodes_pubdata).red()
-                        );
-                        log::info!("{}", format!("\tOverhead: {}", overhead).red());
-                        return Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
-                            "exceeds block gas limit".into(),
-                            Default::default(),
-                        )));
-                    }
-                };
-
-                let fee = Fee {
-                    max_fee_per_gas: base_fee.into(),
-                    max_priority_fee_per_gas: 0u32.into(),
-                    gas_limit: full_gas_limit.into(),
-                    gas_per_pubdata_limit: gas_per_pubdata_byte.into(),
-                };
-                Ok(fee)
             }
         }
+
+        Ok(vm_block_result)
     }
 
-    /// Runs fee estimation against a sandbox vm with the given gas_limit.
-    #[allow(clippy::too_many_arguments)]
-    fn estimate_gas_step(
-        mut l2_tx: L2Tx,
-        gas_per_pubdata_byte: u64,
-        tx_gas_limit: u32,
-        l1_gas_price: u64,
-        base_fee: u64,
-        mut block_context: BlockContext,
-        fork_storage: &ForkStorage<S>,
-        bootloader_code: &BaseSystemContracts,
-    ) -> Result<VmBlockResult, TxRevertReason> {
-        let tx: Transaction = l2_tx.clone().into();
-        let l1_gas_price =
-            adjust_l1_gas_price_for_tx(l1_gas_price, L2_GAS_PRICE, tx.gas_per_pubdata_byte_limit());
+    /// Checks that the nonce of `tx` follows the nonces used by the sender and its pending
+    /// transactions, and that the sender can pay for it. Nonces above the expected one are only
+    /// rejected with automine, as the mempool may receive the missing transactions later.
+    pub fn validate_tx(&self, tx: &L2Tx) -> Result<(), TransactionValidationError> {
+        let mut storage = &self.fork_storage;
+        let address = tx.initiator_account();
+
+        let full_nonce = storage.read_value(&get_nonce_key(&address));
+        let (account_nonce, _) = decompose_full_nonce(h256_to_u256(full_nonce));
+        let tx_nonce = U256::from(tx.nonce().0);
+        if tx_nonce < account_nonce {
+            return Err(TransactionValidationError::NonceTooLow {
+                address,
+                tx_nonce,
+                account_nonce,
+            });
+        }
+        let expected_nonce = account_nonce + self.pending_transaction_count(address);
+        if self.automine && tx_nonce > expected_nonce {
+            return Err(TransactionValidationError::NonceTooHigh {
+                address,
+                tx_nonce,
+                expected_nonce,
+            });
+        }
 
-        // Set gas_limit for transaction
-        let gas_limit_with_overhead = tx_gas_limit
-            + derive_gas_estimation_overhead(
-                tx_gas_limit,
-                gas_per_pubdata_byte as u32,
-                tx.encoding_len(),
+        // the fee is charged to the paymaster, if any
+        let mut cost = tx.execute.value;
+        if tx.payer() == address {
+            cost = cost.saturating_add(
+                tx.common_data
+                    .fee
+                    .gas_limit
+                    .saturating_mul(tx.common_data.fee.max_fee_per_gas),
             );
-        l2_tx.common_data.fee.gas_limit = gas_limit_with_overhead.into();
-
-        let mut storage_view = StorageView::new(fork_storage);
-
-        // The nonce needs to be updated
-        let nonce = l2_tx.nonce();
-        let nonce_key = get_nonce_key(&l2_tx.initiator_account());
-        let full_nonce = storage_view.read_value(&nonce_key);
-        let (_, deployment_nonce) = decompose_full_nonce(h256_to_u256(full_nonce));
-        let enforced_full_nonce = nonces_to_full_nonce(U256::from(nonce.0), deployment_nonce);
-        storage_view.set_value(nonce_key, u256_to_h256(enforced_full_nonce));
+        }
+        let balance = h256_to_u256(storage.read_value(&storage_key_for_eth_balance(&address)));
+        if balance < cost {
+            return Err(TransactionValidationError::InsufficientFunds {
+                address,
+                balance,
+                cost,
+            });
+        }
+        Ok(())
+    }
 
-        // We need to explicitly put enough balance into the account of the users
-        let payer = l2_tx.payer();
-        let balance_key = storage_key_for_eth_balance(&payer);
-        let mut current_balance = h256_to_u256(storage_view.read_value(&balance_key));
-        let added_balance = l2_tx.common_data.fee.gas_limit * l2_tx.common_data.fee.max_fee_per_gas;
-        current_balance += added_balance;
-        storage_view.set_value(balance_key, u256_to_h256(current_balance));
+    /// Returns the number of transactions sent by `address` that are not in a block yet.
+    pub fn pending_transaction_count(&self, address: H160) -> usize {
+        self.pending_transactions
+            .values()
+            .filter(|sender| **sender == address)
+            .count()
+    }
 
-        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryDisabled);
+    /// Records the code hashes among the storage `writes` as set at `miniblock`, so that the code
+    /// of the accounts can be queried at older blocks. Must be called before the writes are applied.
+    pub fn record_code_changes<'a>(
+        &mut self,
+        writes: impl IntoIterator<Item = (&'a StorageKey, &'a H256)>,
+        miniblock: u64,
+    ) -> Result<(), String> {
+        let fork_storage = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        for (key, code_hash) in writes {
+            if *key.account().address() != ACCOUNT_CODE_STORAGE_ADDRESS {
+                continue;
+            }
+            let history = self
+                .code_history
+                .entry(h256_to_account_address(key.key()))
+                .or_default();
+            // keep the code the account had before, unless it comes from the fork
+            if history.is_empty() {
+                if let Some(previous_code_hash) = fork_storage.raw_storage.state.get(key) {
+                    history.push((0, *previous_code_hash));
+                }
+            }
+            history.push((miniblock, *code_hash));
+        }
+        Ok(())
+    }
 
-        block_context.l1_gas_price = l1_gas_price;
-        let derived_block_context = DerivedBlockContext {
-            context: block_context,
-            base_fee,
+    /// Returns the code hash of `address` at the given block. Blocks at or below the fork point are
+    /// read from the fork, later ones from the code changes recorded by the node.
+    pub fn code_hash_at(
+        &self,
+        address: H160,
+        block: Option<BlockIdVariant>,
+    ) -> Result<H256, String> {
+        let code_key = get_code_key(&address);
+        let number = match block.map(BlockId::from) {
+            Some(BlockId::Number(number)) => self.resolve_block_number(number),
+            Some(BlockId::Hash(hash)) => self
+                .blocks
+                .get(&hash)
+                .map(|block| block.number.as_u64())
+                .ok_or_else(|| format!("block {:?} not found", hash))?,
+            None => self.current_miniblock,
         };
+        if number > self.current_miniblock {
+            return Err(format!("block {} not found", number));
+        }
+        if number == self.current_miniblock {
+            return Ok((&self.fork_storage).read_value(&code_key));
+        }
 
-        let block_properties = InMemoryNodeInner::<S>::create_block_properties(bootloader_code);
+        let fork_miniblock = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|e| format!("Failed to acquire read lock: {}", e))?
+            .fork
+            .as_ref()
+            .map(|fork| fork.l2_miniblock);
+        match fork_miniblock {
+            Some(fork_miniblock) if number <= fork_miniblock => {
+                return Ok(ForkStorageAtBlock::new(&self.fork_storage, number).read_value(&code_key))
+            }
+            _ => {}
+        }
 
-        let execution_mode = TxExecutionMode::EstimateFee {
-            missed_storage_invocation_limit: 1000000,
+        let history = match self.code_history.get(&address) {
+            Some(history) => history,
+            None => return Ok((&self.fork_storage).read_value(&code_key)),
         };
+        Ok(
+            match history
+                .iter()
+                .rev()
+                .find(|(miniblock, _)| *miniblock <= number)
+            {
+                Some((_, code_hash)) => *code_hash,
+                // the code was set after the block, and nothing was there before it locally
+                None => match fork_miniblock {
+                    Some(fork_miniblock) => {
+                        ForkStorageAtBlock::new(&self.fork_storage, fork_miniblock)
+                            .read_value(&code_key)
+                    }
+                    None => H256::zero(),
+                },
+            },
+        )
+    }
 
-        // init vm
-        let mut vm = init_vm_inner(
-            &mut oracle_tools,
-            BlockContextMode::OverrideCurrent(derived_block_context),
-            &block_properties,
-            BLOCK_GAS_LIMIT,
-            bootloader_code,
-            execution_mode,
-        );
-
-        let tx: Transaction = l2_tx.into();
+    /// Returns the Merkle tree over the storage written locally, see [`crate::proof`]. Only the
+    /// slots written since the last call are rehashed.
+    pub fn storage_tree(&self) -> Result<StorageTree, String> {
+        let mut fork_storage = self
+            .fork_storage
+            .inner
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        Ok(fork_storage.update_storage_tree().clone())
+    }
 
-        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
+    /// Returns the root of [`Self::storage_tree`], reported as the `stateRoot` of the blocks.
+    pub fn state_root(&self) -> Result<H256, String> {
+        let mut fork_storage = self
+            .fork_storage
+            .inner
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        Ok(fork_storage.update_storage_tree().root())
+    }
 
-        let vm_block_result = vm.execute_till_block_end(BootloaderJobType::TransactionExecution);
+    /// Returns the balance of `token` held by `address` on the latest block, read with `balanceOf`.
+    pub fn token_balance(&self, token: H160, address: H160) -> Result<U256, String> {
+        // balanceOf(address)
+        let mut data = vec![0x70, 0xa0, 0x82, 0x31];
+        data.extend_from_slice(H256::from(address).as_bytes());
+        let request = zksync_types::transaction_request::CallRequest {
+            from: Some(H160::zero()),
+            to: Some(token),
+            data: Some(data.into()),
+            ..Default::default()
+        };
+        let mut l2_tx = L2Tx::from_request(request.into(), MAX_TX_SIZE)
+            .map_err(|err| format!("invalid balanceOf call: {}", err))?;
+        l2_tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
 
-        match vm_block_result.full_result.revert_reason {
-            None => Ok(vm_block_result),
-            Some(revert) => Err(revert.revert_reason),
+        let result = self.run_l2_call(l2_tx, None, &StateOverride::default())?;
+        if let Some(revert) = result.full_result.revert_reason {
+            return Err(format!("balanceOf reverted: {}", revert.revert_reason));
         }
+        result
+            .full_result
+            .return_data
+            .first()
+            .copied()
+            .ok_or_else(|| String::from("balanceOf returned no data"))
     }
-}
 
-fn not_implemented<T: Send + 'static>(
-    method_name: &str,
-) -> jsonrpc_core::BoxFuture<Result<T, jsonrpc_core::Error>> {
-    log::warn!("Method {} is not implemented", method_name);
-    Err(jsonrpc_core::Error {
-        data: None,
-        code: jsonrpc_core::ErrorCode::MethodNotFound,
-        message: format!("Method {} is not implemented", method_name),
-    })
-    .into_boxed_future()
-}
+    /// Returns the non-zero balances of `address`: ETH under the zero address, and the tokens
+    /// in [`Self::known_tokens`]. On a fork, the tokens held on the forked network are included
+    /// too, with their balance read locally so that the local changes are taken into account.
+    pub fn all_account_balances(&self, address: H160) -> Result<HashMap<H160, U256>, String> {
+        let mut tokens = self.known_tokens.clone();
+        let fork_balances = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?
+            .fork
+            .as_ref()
+            .map(|fork| fork.fork_source.get_all_account_balances(address));
+        match fork_balances {
+            Some(Ok(fork_balances)) => tokens.extend(fork_balances.into_keys()),
+            Some(Err(err)) => log::warn!("failed fetching the balances from the fork: {:?}", err),
+            None => {}
+        }
 
-/// In-memory node, that can be used for local & unit testing.
-/// It also supports the option of forking testnet/mainnet.
-/// All contents are removed when object is destroyed.
-pub struct InMemoryNode<S> {
-    inner: Arc<RwLock<InMemoryNodeInner<S>>>,
-}
+        let mut balances = HashMap::new();
+        let mut storage = &self.fork_storage;
+        let eth_balance = h256_to_u256(storage.read_value(&storage_key_for_eth_balance(&address)));
+        if !eth_balance.is_zero() {
+            balances.insert(H160::zero(), eth_balance);
+        }
+        for token in tokens {
+            if token.is_zero() || token == L2_ETH_TOKEN_ADDRESS {
+                continue;
+            }
+            match self.token_balance(token, address) {
+                Ok(balance) if !balance.is_zero() => {
+                    balances.insert(token, balance);
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!(
+                    "skipping token {:?}, reading the balance failed: {}",
+                    token,
+                    err
+                ),
+            }
+        }
+        Ok(balances)
+    }
 
-fn contract_address_from_tx_result(execution_result: &VmTxExecutionResult) -> Option<H160> {
-    for query in execution_result.result.logs.storage_logs.iter().rev() {
-        if query.log_type == StorageLogQueryType::InitialWrite
-            && query.log_query.address == ACCOUNT_CODE_STORAGE_ADDRESS
+    /// Returns the zkSync specific details of a block. The blocks up to the fork point are read
+    /// from the forked network, the local blocks are never committed to L1 so their status is
+    /// `sealed` and the L1 transaction hashes are `null`.
+    pub fn block_details(&self, number: MiniblockNumber) -> Result<Option<BlockDetails>, String> {
+        let fork_details = match &self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?
+            .fork
         {
-            return Some(h256_to_account_address(&u256_to_h256(query.log_query.key)));
+            Some(fork) if number.0 as u64 <= fork.l2_miniblock => Some(
+                fork.fork_source
+                    .get_block_details(number)
+                    .map_err(|err| format!("failed fetching the block from the fork: {:?}", err))?,
+            ),
+            _ => None,
+        };
+        if let Some(details) = fork_details {
+            return Ok(details);
         }
+
+        let block = match self
+            .block_hashes
+            .get(&(number.0 as u64))
+            .and_then(|hash| self.blocks.get(hash))
+        {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let (l1_gas_price, fair_l2_gas_price) = self
+            .block_gas_prices
+            .get(&(number.0 as u64))
+            .copied()
+            .ok_or_else(|| format!("missing gas prices of block {}", number))?;
+        let l1_tx_count = block
+            .transactions
+            .iter()
+            .filter(|tx| {
+                let tx_hash = match tx {
+                    TransactionVariant::Full(tx) => tx.hash,
+                    TransactionVariant::Hash(hash) => *hash,
+                };
+                self.tx_results.get(&tx_hash).map_or(false, |info| {
+                    matches!(info.tx.common_data, ExecuteTransactionCommon::L1(_))
+                })
+            })
+            .count();
+        Ok(Some(BlockDetails {
+            number,
+            l1_batch_number: L1BatchNumber(block.l1_batch_number.unwrap_or_default().as_u32()),
+            base: BlockDetailsBase {
+                timestamp: block.timestamp.as_u64(),
+                l1_tx_count,
+                l2_tx_count: block.transactions.len() - l1_tx_count,
+                root_hash: Some(block.state_root),
+                status: BlockStatus::Sealed,
+                commit_tx_hash: None,
+                committed_at: None,
+                prove_tx_hash: None,
+                proven_at: None,
+                execute_tx_hash: None,
+                executed_at: None,
+                l1_gas_price,
+                l2_fair_gas_price: fair_l2_gas_price,
+                base_system_contracts_hashes: self.system_contracts.baseline_contracts.hashes(),
+            },
+            operator_address: H160::zero(),
+            protocol_version: None,
+        }))
     }
-    None
-}
 
-impl<S: ForkSource + std::fmt::Debug> Default for InMemoryNode<S> {
-    fn default() -> Self {
-        InMemoryNode::new(
-            None,
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
-        )
+    /// Returns the messages sent with `L1Messenger.sendToL1` by the transactions of the blocks
+    /// `from_block` to `to_block` (included), in execution order.
+    pub fn l2_to_l1_messages(&self, from_block: u64, to_block: u64) -> Vec<L2ToL1Message> {
+        let mut messages = vec![];
+        for block_number in from_block..=to_block.min(self.current_miniblock) {
+            let block = match self
+                .block_hashes
+                .get(&block_number)
+                .and_then(|hash| self.blocks.get(hash))
+            {
+                Some(block) => block,
+                None => continue,
+            };
+            for tx in &block.transactions {
+                let tx_hash = match tx {
+                    TransactionVariant::Full(tx) => tx.hash,
+                    TransactionVariant::Hash(hash) => *hash,
+                };
+                let info = match self.tx_results.get(&tx_hash) {
+                    Some(info) => info,
+                    None => continue,
+                };
+                let logs = &info.result.result.logs;
+                for (index, log) in logs.l2_to_l1_logs.iter().enumerate() {
+                    if log.sender != L1_MESSENGER_ADDRESS {
+                        continue;
+                    }
+                    messages.push(L2ToL1Message {
+                        block_number: U64::from(block_number),
+                        l1_batch_number: U64::from(info.batch_number),
+                        transaction_hash: tx_hash,
+                        l2_to_l1_log_index: U64::from(index),
+                        sender: h256_to_account_address(&log.key),
+                        hash: log.value,
+                        message: l1_message(&logs.events, log.value).unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        messages
     }
-}
 
-impl<S: ForkSource + std::fmt::Debug> InMemoryNode<S> {
-    pub fn new(
-        fork: Option<ForkDetails<S>>,
-        show_calls: ShowCalls,
-        show_storage_logs: ShowStorageLogs,
-        show_vm_details: ShowVMDetails,
-        show_gas_details: ShowGasDetails,
-        resolve_hashes: bool,
-        system_contracts_options: &system_contracts::Options,
-    ) -> Self {
-        let inner = if let Some(f) = &fork {
-            let mut block_hashes = HashMap::<u64, H256>::new();
-            block_hashes.insert(f.l2_block.number.as_u64(), f.l2_block.hash);
-            let mut blocks = HashMap::<H256, Block<TransactionVariant>>::new();
-            blocks.insert(f.l2_block.hash, f.l2_block.clone());
+    /// Returns a proof of the L2→L1 log `index` of a transaction, in the format of the mainnet
+    /// API. Every transaction is in its own batch, so the Merkle tree only holds its logs.
+    pub fn l2_to_l1_log_proof(&self, tx_hash: H256, index: usize) -> Option<L2ToL1LogProof> {
+        let logs = &self
+            .tx_results
+            .get(&tx_hash)?
+            .result
+            .result
+            .logs
+            .l2_to_l1_logs;
+        if index >= logs.len() {
+            return None;
+        }
+        let (root, proof) = l2_to_l1_logs_merkle_proof(logs, index);
+        Some(L2ToL1LogProof {
+            proof,
+            id: index as u32,
+            root,
+        })
+    }
 
-            InMemoryNodeInner {
-                current_timestamp: f.block_timestamp + 1,
-                current_batch: f.l1_block.0 + 1,
-                current_miniblock: f.l2_miniblock,
-                l1_gas_price: f.l1_gas_price,
-                tx_results: Default::default(),
-                blocks,
-                block_hashes,
-                fork_storage: ForkStorage::new(fork, system_contracts_options),
-                show_calls,
-                show_storage_logs,
-                show_vm_details,
-                show_gas_details,
-                resolve_hashes,
-                console_log_handler: ConsoleLogHandler::default(),
-                system_contracts: SystemContracts::from_options(system_contracts_options),
-            }
-        } else {
-            let mut block_hashes = HashMap::<u64, H256>::new();
-            block_hashes.insert(0, H256::zero());
-            let mut blocks = HashMap::<H256, Block<TransactionVariant>>::new();
-            blocks.insert(
-                H256::zero(),
-                Block::<TransactionVariant> {
-                    gas_limit: U256::from(ETH_CALL_GAS_LIMIT),
-                    ..Default::default()
-                },
-            );
-
-            InMemoryNodeInner {
-                current_timestamp: NON_FORK_FIRST_BLOCK_TIMESTAMP,
-                current_batch: 1,
-                current_miniblock: 0,
-                l1_gas_price: L1_GAS_PRICE,
-                tx_results: Default::default(),
-                blocks,
-                block_hashes,
-                fork_storage: ForkStorage::new(fork, system_contracts_options),
-                show_calls,
-                show_storage_logs,
-                show_vm_details,
-                show_gas_details,
-                resolve_hashes,
-                console_log_handler: ConsoleLogHandler::default(),
-                system_contracts: SystemContracts::from_options(system_contracts_options),
-            }
+    /// Returns the transactions of a block, as they were executed. The blocks up to the fork point
+    /// are read from the forked network. Unknown blocks have no transactions.
+    pub fn raw_block_transactions(
+        &self,
+        number: MiniblockNumber,
+    ) -> Result<Vec<Transaction>, String> {
+        let fork_transactions = match &self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?
+            .fork
+        {
+            Some(fork) if number.0 as u64 <= fork.l2_miniblock => Some(
+                fork.fork_source
+                    .get_raw_block_transactions(number)
+                    .map_err(|err| {
+                        format!("failed fetching the transactions from the fork: {:?}", err)
+                    })?,
+            ),
+            _ => None,
         };
-
-        InMemoryNode {
-            inner: Arc::new(RwLock::new(inner)),
+        if let Some(transactions) = fork_transactions {
+            return Ok(transactions);
         }
-    }
 
-    pub fn get_inner(&self) -> Arc<RwLock<InMemoryNodeInner<S>>> {
-        self.inner.clone()
+        let block = match self
+            .block_hashes
+            .get(&(number.0 as u64))
+            .and_then(|hash| self.blocks.get(hash))
+        {
+            Some(block) => block,
+            None => return Ok(vec![]),
+        };
+        Ok(block
+            .transactions
+            .iter()
+            .filter_map(|tx| {
+                let hash = match tx {
+                    TransactionVariant::Full(tx) => tx.hash,
+                    TransactionVariant::Hash(hash) => *hash,
+                };
+                self.tx_results.get(&hash).map(|info| info.tx.clone())
+            })
+            .collect())
     }
 
-    /// Applies multiple transactions - but still one per L1 batch.
-    pub fn apply_txs(&self, txs: Vec<L2Tx>) -> Result<(), String> {
-        log::info!("Running {:?} transactions (one per batch)", txs.len());
+    /// Returns the bytecode with the given hash: a factory dep stored locally, one of the
+    /// default accounts of the system contracts, or a bytecode known to the forked network.
+    /// The bootloaders are not returned, as they are never decommitted.
+    pub fn bytecode_by_hash(&self, hash: H256) -> Result<Option<Vec<u8>>, String> {
+        let storage = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?;
+        if let Some(bytecode) = storage.raw_storage.factory_deps.get(&hash) {
+            return Ok(Some(bytecode.clone()));
+        }
 
-        for tx in txs {
-            self.run_l2_tx(tx, TxExecutionMode::VerifyExecute)?;
+        let default_account = [
+            &self.system_contracts.baseline_contracts,
+            &self.system_contracts.playground_contracts,
+            &self.system_contracts.fee_estimate_contracts,
+            &self.system_contracts.impersonating_contracts,
+        ]
+        .into_iter()
+        .map(|contracts| &contracts.default_aa)
+        .find(|default_aa| default_aa.hash == hash);
+        if let Some(default_aa) = default_account {
+            return Ok(Some(be_words_to_bytes(&default_aa.code)));
         }
 
-        Ok(())
+        match &storage.fork {
+            Some(fork) => match storage.factory_dep_cache.get(&hash) {
+                Some(bytecode) => Ok(bytecode.clone()),
+                None => fork.fork_source.get_bytecode_by_hash(hash).map_err(|err| {
+                    format!("failed fetching the bytecode from the fork: {:?}", err)
+                }),
+            },
+            None => Ok(None),
+        }
     }
 
-    /// Adds a lot of tokens to a given account.
-    pub fn set_rich_account(&self, address: H160) {
-        let key = storage_key_for_eth_balance(&address);
+    /// Returns the USD price of a token: the price set with `config_setTokenPrice`, otherwise the
+    /// price on the forked network if [`Self::forward_token_prices`] is set, otherwise
+    /// [`DEFAULT_ETH_PRICE`] for ETH (the zero address) and 1 for any other token.
+    pub fn token_price(&self, token: H160) -> BigDecimal {
+        if let Some(price) = self.token_prices.get(&token) {
+            return price.clone();
+        }
+        if self.forward_token_prices {
+            let fork_price = self.fork_storage.inner.read().ok().and_then(|storage| {
+                storage
+                    .fork
+                    .as_ref()
+                    .map(|fork| fork.fork_source.get_token_price(token))
+            });
+            match fork_price {
+                Some(Ok(price)) => return price,
+                Some(Err(err)) => log::warn!(
+                    "failed fetching the price of {:?} from the fork, using the default: {:?}",
+                    token,
+                    err
+                ),
+                None => {}
+            }
+        }
+        if token == H160::zero() {
+            BigDecimal::from(DEFAULT_ETH_PRICE)
+        } else {
+            BigDecimal::from(1)
+        }
+    }
 
-        let mut inner = match self.inner.write() {
-            Ok(guard) => guard,
-            Err(e) => {
-                log::info!("Failed to acquire write lock: {}", e);
-                return;
+    /// Adds a token to the ones returned by `zks_getConfirmedTokens`, replacing any token with the
+    /// same L2 address. Used to test frontends listing the bridgeable tokens.
+    pub fn register_token(&mut self, token: Token) {
+        self.registered_tokens
+            .retain(|registered| registered.l2_address != token.l2_address);
+        self.registered_tokens.push(token);
+    }
+
+    /// Returns the tokens `from..from + limit` of the confirmed tokens sorted by symbol, as the
+    /// real server does: ETH (or the tokens of the forked network) and the registered tokens.
+    pub fn confirmed_tokens(&self, from: u32, limit: u8) -> Result<Vec<Token>, String> {
+        // the first `end` tokens of the merged list are among the first `end` of each list
+        let end = from as usize + limit as usize + self.registered_tokens.len();
+        let mut tokens = match &self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|err| format!("failed acquiring read lock on storage: {:?}", err))?
+            .fork
+        {
+            Some(fork) => {
+                let mut tokens = vec![];
+                loop {
+                    let page = fork
+                        .fork_source
+                        .get_confirmed_tokens(tokens.len() as u32, u8::MAX)
+                        .map_err(|err| {
+                            format!("failed fetching the tokens from the fork: {:?}", err)
+                        })?;
+                    let last_page = page.len() < u8::MAX as usize;
+                    tokens.extend(page);
+                    if last_page || tokens.len() >= end {
+                        break tokens;
+                    }
+                }
             }
+            None => vec![Token {
+                l1_address: H160::zero(),
+                l2_address: H160::zero(),
+                name: String::from("Ether"),
+                symbol: String::from("ETH"),
+                decimals: 18,
+            }],
         };
 
-        let keys = {
-            let mut storage_view = StorageView::new(&inner.fork_storage);
-            storage_view.set_value(key, u256_to_h256(U256::from(10u128.pow(30))));
-            storage_view.modified_storage_keys().clone()
-        };
+        tokens.retain(|token| {
+            !self
+                .registered_tokens
+                .iter()
+                .any(|registered| registered.l2_address == token.l2_address)
+        });
+        tokens.extend(self.registered_tokens.iter().cloned());
+        tokens.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Ok(tokens
+            .into_iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .collect())
+    }
 
-        for (key, value) in keys.iter() {
-            inner.fork_storage.set_value(*key, *value);
-        }
+    /// Returns the block a transaction executed by the node was sealed in, with the position of
+    /// the transaction in it.
+    pub fn transaction_position(&self, hash: H256) -> Option<(&Block<TransactionVariant>, usize)> {
+        let info = self.tx_results.get(&hash)?;
+        let block = self
+            .block_hashes
+            .get(&info.miniblock_number)
+            .and_then(|block_hash| self.blocks.get(block_hash))?;
+        let index = block.transactions.iter().position(|tx| match tx {
+            TransactionVariant::Full(tx) => tx.hash == hash,
+            TransactionVariant::Hash(tx_hash) => *tx_hash == hash,
+        })?;
+        Some((block, index))
     }
 
-    /// Runs L2 'eth call' method - that doesn't commit to a block.
-    fn run_l2_call(&self, l2_tx: L2Tx) -> Result<VmBlockResult, String> {
-        let execution_mode = TxExecutionMode::EthCall {
-            missed_storage_invocation_limit: 1000000,
-        };
+    /// Returns a transaction executed by the node, as listed in the full transactions of its
+    /// block, so that it is the same object whether it is read by hash or with its block.
+    pub fn block_transaction(&self, hash: H256) -> Option<zksync_types::api::Transaction> {
+        let (block, index) = self.transaction_position(hash)?;
+        transaction_at_index(block, U64::from(index))
+    }
 
-        let inner = self
+    /// Returns the receipt of a transaction executed by the node.
+    pub fn transaction_receipt(&self, hash: H256) -> Option<TransactionReceipt> {
+        let info = self.tx_results.get(&hash)?;
+        let (block, index) = self.transaction_position(hash)?;
+        // gas used by the transactions of the block up to this one (included)
+        let cumulative_gas_used = block.transactions[..=index]
+            .iter()
+            .filter_map(|tx| match tx {
+                TransactionVariant::Full(tx) => self.tx_results.get(&tx.hash),
+                TransactionVariant::Hash(tx_hash) => self.tx_results.get(tx_hash),
+            })
+            .fold(U256::zero(), |total, info| total + info.gas_used());
+
+        Some(TransactionReceipt {
+            transaction_hash: hash,
+            transaction_index: U64::from(index),
+            block_hash: Some(block.hash),
+            block_number: Some(U64::from(info.miniblock_number)),
+            l1_batch_tx_index: None,
+            l1_batch_number: Some(U64::from(info.batch_number as u64)),
+            from: info.tx.initiator_account(),
+            to: Some(info.tx.execute.contract_address),
+            cumulative_gas_used,
+            gas_used: Some(info.gas_used()),
+            contract_address: info.contract_address,
+            logs: self
+                .get_block_logs(info.miniblock_number)
+                .into_iter()
+                .filter(|log| log.transaction_hash == Some(hash))
+                .collect(),
+            l2_to_l1_logs: vec![],
+            status: Some(if info.result.status == TxExecutionStatus::Success {
+                U64::from(1)
+            } else {
+                U64::from(0)
+            }),
+            effective_gas_price: Some(info.gas_price.into()),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the first block of the node, which the `earliest` tag refers to: the fork point in
+    /// fork mode, as older blocks belong to the forked network, and the genesis block otherwise.
+    pub fn earliest_block(&self) -> u64 {
+        self.fork_storage
             .inner
-            .write()
-            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+            .read()
+            .expect("failed reading fork storage")
+            .fork
+            .as_ref()
+            .map_or(0, |fork| fork.l2_miniblock)
+    }
 
-        let mut storage_view = StorageView::new(&inner.fork_storage);
+    /// Resolves a block tag to a block number on the node, see [`resolve_block_number`]. `earliest`
+    /// is the [first block](Self::earliest_block) of the node.
+    pub fn resolve_block_number(&self, block: BlockNumber) -> u64 {
+        match block {
+            BlockNumber::Earliest => self.earliest_block(),
+            block => resolve_block_number(block, self.current_miniblock),
+        }
+    }
 
-        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryEnabled);
+    /// Resolves the block an `eth_call` runs on. Returns `None` for the latest block, and the block
+    /// number for blocks at or below the fork point, whose state is read from the forked network.
+    /// Older locally produced blocks are rejected, as their state is not retained.
+    pub fn historical_fork_block(
+        &self,
+        block: Option<BlockIdVariant>,
+    ) -> Result<Option<u64>, String> {
+        let number = match block.map(BlockId::from) {
+            None => return Ok(None),
+            Some(BlockId::Number(BlockNumber::Number(number))) => number.as_u64(),
+            Some(BlockId::Number(BlockNumber::Earliest)) => self.earliest_block(),
+            Some(BlockId::Number(_)) => return Ok(None),
+            Some(BlockId::Hash(hash)) => self
+                .blocks
+                .get(&hash)
+                .map(|block| block.number.as_u64())
+                .ok_or_else(|| format!("block {:?} not found", hash))?,
+        };
+        if number == self.current_miniblock {
+            return Ok(None);
+        }
+        if number > self.current_miniblock {
+            return Err(format!("block {} not found", number));
+        }
 
-        let bootloader_code = &inner.system_contracts.contacts_for_l2_call();
+        let fork_storage = self
+            .fork_storage
+            .inner
+            .read()
+            .map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        match &fork_storage.fork {
+            Some(fork) if number <= fork.l2_miniblock => Ok(Some(number)),
+            _ => Err(format!(
+                "historical state not available for block {}, only the latest block ({}) can be queried",
+                number, self.current_miniblock
+            )),
+        }
+    }
 
-        let block_context = inner.create_block_context();
-        let block_properties = InMemoryNodeInner::<S>::create_block_properties(bootloader_code);
+    pub fn create_block_context(&self) -> BlockContext {
+        BlockContext {
+            block_number: self.current_batch,
+            block_timestamp: self.current_timestamp,
+            l1_gas_price: self.l1_gas_price,
+            fair_l2_gas_price: self.fair_l2_gas_price,
+            operator_address: H160::zero(),
+        }
+    }
 
-        // init vm
-        let mut vm = init_vm_inner(
-            &mut oracle_tools,
-            BlockContextMode::NewBlock(block_context.into(), Default::default()),
-            &block_properties,
-            BLOCK_GAS_LIMIT,
-            bootloader_code,
-            execution_mode,
-        );
+    pub fn create_block_properties(contracts: &BaseSystemContracts) -> BlockProperties {
+        BlockProperties {
+            default_aa_code_hash: h256_to_u256(contracts.default_aa.hash),
+            zkporter_is_available: false,
+        }
+    }
 
-        let tx: Transaction = l2_tx.into();
+    /// Returns the logs of the local blocks matching `filter`, ordered by block number and log index.
+    pub fn get_logs(&self, filter: &LogFilter) -> Vec<Log> {
+        let latest_block = self.current_miniblock;
+        let mut block_numbers: Vec<u64> = match filter.block_hash {
+            Some(hash) => self
+                .blocks
+                .get(&hash)
+                .map(|block| block.number.as_u64())
+                .into_iter()
+                .collect(),
+            None => self
+                .block_hashes
+                .keys()
+                .copied()
+                .filter(|number| filter.matches_block(*number, latest_block))
+                .collect(),
+        };
+        block_numbers.sort_unstable();
 
-        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
+        block_numbers
+            .into_iter()
+            .flat_map(|number| self.get_block_logs(number))
+            .filter(|log| filter.matches(log, latest_block))
+            .collect()
+    }
 
-        let vm_block_result =
-            vm.execute_till_block_end_with_call_tracer(BootloaderJobType::TransactionExecution);
+    /// Returns the logs emitted in the given local block, in execution order.
+    ///
+    /// The `logIndex` is counted across the whole block, while `transactionLogIndex` restarts
+    /// for every transaction.
+    pub fn get_block_logs(&self, block_number: u64) -> Vec<Log> {
+        let block = match self
+            .block_hashes
+            .get(&block_number)
+            .and_then(|hash| self.blocks.get(hash))
+        {
+            Some(block) => block,
+            None => return vec![],
+        };
 
-        if let Some(revert_reason) = &vm_block_result.full_result.revert_reason {
-            log::info!("Call {} {:?}", "FAILED".red(), revert_reason.revert_reason);
-        } else {
-            log::info!("Call {}", "SUCCESS".green());
-        }
-        if let VmTrace::CallTrace(call_trace) = &vm_block_result.full_result.trace {
-            log::info!("=== Console Logs: ");
-            for call in call_trace {
-                inner.console_log_handler.handle_call_recurive(call);
-            }
+        let mut logs = vec![];
+        for (transaction_index, tx) in block.transactions.iter().enumerate() {
+            let tx_hash = match tx {
+                TransactionVariant::Full(tx) => tx.hash,
+                TransactionVariant::Hash(hash) => *hash,
+            };
+            let info = match self.tx_results.get(&tx_hash) {
+                Some(info) => info,
+                None => continue,
+            };
 
-            log::info!("=== Call traces:");
-            for call in call_trace {
-                formatter::print_call(call, 0, &inner.show_calls, inner.resolve_hashes);
+            for (transaction_log_index, event) in info.result.result.logs.events.iter().enumerate()
+            {
+                logs.push(Log {
+                    address: event.address,
+                    topics: event.indexed_topics.clone(),
+                    data: zksync_types::Bytes(event.value.clone()),
+                    block_hash: Some(block.hash),
+                    block_number: Some(U64::from(block_number)),
+                    l1_batch_number: Some(U64::from(info.batch_number as u64)),
+                    transaction_hash: Some(tx_hash),
+                    transaction_index: Some(U64::from(transaction_index)),
+                    log_index: Some(U256::from(logs.len())),
+                    transaction_log_index: Some(U256::from(transaction_log_index)),
+                    log_type: None,
+                    removed: Some(false),
+                });
             }
         }
 
-        Ok(vm_block_result)
+        logs
     }
 
-    fn display_detailed_gas_info<H: HistoryMode>(
+    /// Estimates the gas required for a given call request.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with a `Fee` representing the estimated gas related data.
+    pub fn estimate_gas_impl(
         &self,
-        vm: &VmInstance<H>,
-        spent_on_pubdata: u32,
-    ) -> eyre::Result<()> {
-        let debug = BootloaderDebug::load_from_memory(vm)?;
-
-        log::info!("┌─────────────────────────┐");
-        log::info!("│       GAS DETAILS       │");
-        log::info!("└─────────────────────────┘");
+        req: zksync_types::transaction_request::CallRequest,
+    ) -> jsonrpc_core::Result<Fee> {
+        self.estimate_fee_breakdown(req)
+            .map(|breakdown| breakdown.fee)
+    }
 
-        // Total amount of gas (should match tx.gas_limit).
-        let total_gas_limit = debug
-            .total_gas_limit_from_user
-            .saturating_sub(debug.reserved_gas);
+    /// Estimates the fee of a call request like [`Self::estimate_gas_impl`], and returns the
+    /// components its gas limit is made of.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with a `FeeBreakdown`, whose `gas_limit` is the sum of the components.
+    pub fn estimate_fee_breakdown(
+        &self,
+        mut req: zksync_types::transaction_request::CallRequest,
+    ) -> jsonrpc_core::Result<FeeBreakdown> {
+        req.from.get_or_insert_with(Default::default);
+        let mut l2_tx = match L2Tx::from_request(req.into(), MAX_TX_SIZE) {
+            Ok(tx) => tx,
+            Err(e) => {
+                let error = Web3Error::SerializationError(e);
+                return Err(into_jsrpc_error(error));
+            }
+        };
 
-        let intrinsic_gas = total_gas_limit - debug.gas_limit_after_intrinsic;
-        let gas_for_validation = debug.gas_limit_after_intrinsic - debug.gas_after_validation;
+        let tx: Transaction = l2_tx.clone().into();
+        let fair_l2_gas_price = self.fair_l2_gas_price;
 
-        let gas_spent_on_compute =
-            debug.gas_spent_on_execution - debug.gas_spent_on_bytecode_preparation;
+        // Calculate Adjusted L1 Price
+        let l1_gas_price = {
+            let current_l1_gas_price =
+                ((self.l1_gas_price as f64) * ESTIMATE_GAS_L1_GAS_PRICE_SCALE_FACTOR) as u64;
 
-        let gas_used = intrinsic_gas
-            + gas_for_validation
-            + debug.gas_spent_on_bytecode_preparation
-            + gas_spent_on_compute;
+            // In order for execution to pass smoothly, we need to ensure that block's required gasPerPubdata will be
+            // <= to the one in the transaction itself.
+            adjust_l1_gas_price_for_tx(
+                current_l1_gas_price,
+                fair_l2_gas_price,
+                tx.gas_per_pubdata_byte_limit(),
+            )
+        };
 
-        log::info!(
-            "Gas - Limit: {} | Used: {} | Refunded: {}",
-            to_human_size(total_gas_limit),
-            to_human_size(gas_used),
-            to_human_size(debug.refund_by_operator)
-        );
+        let (base_fee, gas_per_pubdata_byte) =
+            derive_base_fee_and_gas_per_pubdata(l1_gas_price, fair_l2_gas_price);
+        let gas_per_pubdata_byte = self.gas_per_pubdata.unwrap_or(gas_per_pubdata_byte);
 
-        if debug.total_gas_limit_from_user != total_gas_limit {
-            log::info!(
-                "{}",
-                format!(
-                "  WARNING: user actually provided more gas {}, but system had a lower max limit.",
-                to_human_size(debug.total_gas_limit_from_user)
-            )
-                .yellow()
-            );
+        // Properly format signature
+        if l2_tx.common_data.signature.is_empty() {
+            l2_tx.common_data.signature = vec![0u8; 65];
+            l2_tx.common_data.signature[64] = 27;
         }
-        if debug.refund_computed != debug.refund_by_operator {
-            log::info!(
-                "{}",
-                format!(
-                    "  WARNING: Refund by VM: {}, but operator refunded more: {}",
-                    to_human_size(debug.refund_computed),
-                    to_human_size(debug.refund_by_operator)
-                )
-                .yellow()
-            );
-        }
-
-        if debug.refund_computed + gas_used != total_gas_limit {
-            log::info!(
-                "{}",
-                format!(
-                    "  WARNING: Gas totals don't match. {} != {} , delta: {}",
-                    to_human_size(debug.refund_computed + gas_used),
-                    to_human_size(total_gas_limit),
-                    to_human_size(total_gas_limit.abs_diff(debug.refund_computed + gas_used))
-                )
-                .yellow()
-            );
-        }
-
-        let bytes_published = spent_on_pubdata / debug.gas_per_pubdata.as_u32();
 
-        log::info!(
-            "During execution published {} bytes to L1, @{} each - in total {} gas",
-            to_human_size(bytes_published.into()),
-            to_human_size(debug.gas_per_pubdata),
-            to_human_size(spent_on_pubdata.into())
-        );
+        l2_tx.common_data.fee.gas_per_pubdata_limit = MAX_GAS_PER_PUBDATA_BYTE.into();
+        l2_tx.common_data.fee.max_fee_per_gas = base_fee.into();
+        l2_tx.common_data.fee.max_priority_fee_per_gas = base_fee.into();
 
-        log::info!("Out of {} gas used, we spent:", to_human_size(gas_used));
-        log::info!(
-            "  {:>15} gas ({:>2}%) for transaction setup",
-            to_human_size(intrinsic_gas),
-            to_human_size(intrinsic_gas * 100 / gas_used)
-        );
-        log::info!(
-            "  {:>15} gas ({:>2}%) for bytecode preparation (decompression etc)",
-            to_human_size(debug.gas_spent_on_bytecode_preparation),
-            to_human_size(debug.gas_spent_on_bytecode_preparation * 100 / gas_used)
-        );
-        log::info!(
-            "  {:>15} gas ({:>2}%) for account validation",
-            to_human_size(gas_for_validation),
-            to_human_size(gas_for_validation * 100 / gas_used)
-        );
-        log::info!(
-            "  {:>15} gas ({:>2}%) for computations (opcodes)",
-            to_human_size(gas_spent_on_compute),
-            to_human_size(gas_spent_on_compute * 100 / gas_used)
-        );
+        let mut storage_view = StorageView::new(&self.fork_storage);
 
-        log::info!("");
-        log::info!("");
-        log::info!(
-            "{}",
-            "=== Transaction setup cost breakdown ===".to_owned().bold(),
-        );
+        // Calculate gas_for_bytecodes_pubdata
+        let pubdata_for_factory_deps = l2_tx
+            .execute
+            .factory_deps
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|bytecode| {
+                if storage_view.is_bytecode_known(&hash_bytecode(bytecode)) {
+                    return 0;
+                }
 
-        log::info!("Total cost: {}", to_human_size(intrinsic_gas).bold());
-        log::info!(
-            "  {:>15} gas ({:>2}%) fixed cost",
-            to_human_size(debug.intrinsic_overhead),
-            to_human_size(debug.intrinsic_overhead * 100 / intrinsic_gas)
-        );
-        log::info!(
-            "  {:>15} gas ({:>2}%) operator cost",
-            to_human_size(debug.operator_overhead),
-            to_human_size(debug.operator_overhead * 100 / intrinsic_gas)
-        );
+                let length = if let Ok(compressed) = compress_bytecode(bytecode) {
+                    compressed.len()
+                } else {
+                    bytecode.len()
+                };
+                length as u32 + ESTIMATE_GAS_PUBLISH_BYTE_OVERHEAD
+            })
+            .sum::<u32>();
 
-        log::info!("");
-        log::info!(
-            "  FYI: operator could have charged up to: {}, so you got {}% discount",
-            to_human_size(debug.required_overhead),
-            to_human_size(
-                (debug.required_overhead - debug.operator_overhead) * 100 / debug.required_overhead
-            )
-        );
+        if pubdata_for_factory_deps > MAX_PUBDATA_PER_BLOCK {
+            return Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
+                "exceeds limit for published pubdata".into(),
+                Default::default(),
+            )));
+        }
 
-        let publish_block_l1_bytes = BLOCK_OVERHEAD_PUBDATA;
-        log::info!(
-            "Publishing full block costs the operator up to: {}, where {} is due to {} bytes published to L1",
-            to_human_size(debug.total_overhead_for_block),
-            to_human_size(debug.gas_per_pubdata * publish_block_l1_bytes),
-            to_human_size(publish_block_l1_bytes.into())
-        );
-        log::info!("Your transaction has contributed to filling up the block in the following way (we take the max contribution as the cost):");
-        log::info!(
-            "  Circuits overhead:{:>15} ({}% of the full block: {})",
-            to_human_size(debug.overhead_for_circuits),
-            to_human_size(debug.overhead_for_circuits * 100 / debug.total_overhead_for_block),
-            to_human_size(debug.total_overhead_for_block)
-        );
-        log::info!(
-            "  Length overhead:  {:>15}",
-            to_human_size(debug.overhead_for_length)
-        );
-        log::info!(
-            "  Slot overhead:    {:>15}",
-            to_human_size(debug.overhead_for_slot)
-        );
-        Ok(())
-    }
+        let gas_for_bytecodes_pubdata: u32 =
+            pubdata_for_factory_deps * (gas_per_pubdata_byte as u32);
 
-    /// Executes the given L2 transaction and returns all the VM logs.
-    pub fn run_l2_tx_inner(
-        &self,
-        l2_tx: L2Tx,
-        execution_mode: TxExecutionMode,
-    ) -> Result<L2TxResult, String> {
-        let inner = self
-            .inner
-            .write()
-            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        let block_context = self.create_block_context();
 
-        let mut storage_view = StorageView::new(&inner.fork_storage);
+        // We are using binary search to find the minimal values of gas_limit under which the transaction succeeds
+        let mut lower_bound = 0;
+        let mut upper_bound = MAX_L2_TX_GAS_LIMIT as u32;
 
-        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryEnabled);
+        while lower_bound + ESTIMATE_GAS_ACCEPTABLE_OVERESTIMATION < upper_bound {
+            let mid = (lower_bound + upper_bound) / 2;
+            let try_gas_limit = gas_for_bytecodes_pubdata + mid;
 
-        let bootloader_code = inner.system_contracts.contracts(execution_mode);
+            let estimate_gas_result = InMemoryNodeInner::estimate_gas_step(
+                l2_tx.clone(),
+                gas_per_pubdata_byte,
+                try_gas_limit,
+                l1_gas_price,
+                base_fee,
+                block_context,
+                &self.fork_storage,
+                self.system_contracts.contracts_for_fee_estimate(),
+            );
 
-        let block_context = inner.create_block_context();
-        let block_properties = InMemoryNodeInner::<S>::create_block_properties(bootloader_code);
+            if estimate_gas_result.is_err() {
+                lower_bound = mid + 1;
+            } else {
+                upper_bound = mid;
+            }
+        }
 
-        // init vm
-        let mut vm = init_vm_inner(
-            &mut oracle_tools,
-            BlockContextMode::NewBlock(block_context.into(), Default::default()),
-            &block_properties,
-            BLOCK_GAS_LIMIT,
-            bootloader_code,
-            execution_mode,
+        let tx_body_gas_limit = cmp::min(
+            MAX_L2_TX_GAS_LIMIT as u32,
+            (upper_bound as f32 * ESTIMATE_GAS_SCALE_FACTOR) as u32,
         );
-        let spent_on_pubdata_before = vm.state.local_state.spent_pubdata_counter;
+        let suggested_gas_limit = tx_body_gas_limit + gas_for_bytecodes_pubdata;
 
-        let tx: Transaction = l2_tx.clone().into();
-        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
-        let tx_result = vm
-            .execute_next_tx(u32::MAX, true)
-            .map_err(|e| format!("Failed to execute next transaction: {}", e))?;
+        let estimate_gas_result = InMemoryNodeInner::estimate_gas_step(
+            l2_tx.clone(),
+            gas_per_pubdata_byte,
+            suggested_gas_limit,
+            l1_gas_price,
+            base_fee,
+            block_context,
+            &self.fork_storage,
+            self.system_contracts.contracts_for_fee_estimate(),
+        );
 
-        let spent_on_pubdata = vm.state.local_state.spent_pubdata_counter - spent_on_pubdata_before;
+        let overhead: u32 = derive_gas_estimation_overhead(
+            suggested_gas_limit,
+            gas_per_pubdata_byte as u32,
+            tx.encoding_len(),
+        );
 
-        log::info!("┌─────────────────────────┐");
-        log::info!("│   TRANSACTION SUMMARY   │");
-        log::info!("└─────────────────────────┘");
+        match estimate_gas_result {
+            Err(tx_revert_reason) => {
+                log::info!("{}", format!("Unable to estimate gas for the request with our suggested gas limit of {}. The transaction is most likely unexecutable. Breakdown of estimation:", suggested_gas_limit + overhead).red());
+                log::info!(
+                    "{}",
+                    format!(
+                        "\tEstimated transaction body gas cost: {}",
+                        tx_body_gas_limit
+                    )
+                    .red()
+                );
+                log::info!(
+                    "{}",
+                    format!("\tGas for pubdata: {}", gas_for_bytecodes_pubdata).red()
+                );
+                log::info!("{}", format!("\tOverhead: {}", overhead).red());
+                let error = revert_error(&tx_revert_reason);
+                log::info!("{}", error.message.on_red());
+                Err(error)
+            }
+            Ok(_) => {
+                let full_gas_limit = match tx_body_gas_limit
+                    .overflowing_add(gas_for_bytecodes_pubdata + overhead)
+                {
+                    (value, false) => value,
+                    (_, true) => {
+                        log::info!("{}", "Overflow when calculating gas estimation. We've exceeded the block gas limit by summing the following values:".red());
+                        log::info!(
+                            "{}",
+                            format!(
+                                "\tEstimated transaction body gas cost: {}",
+                                tx_body_gas_limit
+                            )
+                            .red()
+                        );
+                        log::info!(
+                            "{}",
+                            format!("\tGas for pubdata: {}", gas_for_bytecodes_pubdata).red()
+                        );
+                        log::info!("{}", format!("\tOverhead: {}", overhead).red());
+                        return Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
+                            "exceeds block gas limit".into(),
+                            Default::default(),
+                        )));
+                    }
+                };
 
-        match tx_result.status {
-            TxExecutionStatus::Success => log::info!("Transaction: {}", "SUCCESS".green()),
-            TxExecutionStatus::Failure => log::info!("Transaction: {}", "FAILED".red()),
+                let fee = Fee {
+                    max_fee_per_gas: base_fee.into(),
+                    max_priority_fee_per_gas: 0u32.into(),
+                    gas_limit: full_gas_limit.into(),
+                    gas_per_pubdata_limit: gas_per_pubdata_byte.into(),
+                };
+                Ok(FeeBreakdown {
+                    fee,
+                    tx_body_gas: tx_body_gas_limit.into(),
+                    pubdata_cost: gas_for_bytecodes_pubdata.into(),
+                    overhead: overhead.into(),
+                })
+            }
         }
+    }
 
-        log::info!("Initiator: {:?}", tx.initiator_account());
-        log::info!("Payer: {:?}", tx.payer());
-        log::info!(
-            "Gas - Limit: {} | Used: {} | Refunded: {}",
-            to_human_size(tx.gas_limit()),
-            to_human_size(tx.gas_limit() - tx_result.gas_refunded),
-            to_human_size(tx_result.gas_refunded.into())
+    /// Runs fee estimation against a sandbox vm with the given gas_limit.
+    #[allow(clippy::too_many_arguments)]
+    fn estimate_gas_step(
+        mut l2_tx: L2Tx,
+        gas_per_pubdata_byte: u64,
+        tx_gas_limit: u32,
+        l1_gas_price: u64,
+        base_fee: u64,
+        mut block_context: BlockContext,
+        fork_storage: &ForkStorage<S>,
+        bootloader_code: &BaseSystemContracts,
+    ) -> Result<VmBlockResult, TxRevertReason> {
+        let tx: Transaction = l2_tx.clone().into();
+        let l1_gas_price = adjust_l1_gas_price_for_tx(
+            l1_gas_price,
+            block_context.fair_l2_gas_price,
+            tx.gas_per_pubdata_byte_limit(),
         );
 
-        match inner.show_gas_details {
-            ShowGasDetails::None => log::info!(
-                "Use --show-gas-details flag or call config_setShowGasDetails to display more info"
-            ),
-            ShowGasDetails::All => {
-                if self
-                    .display_detailed_gas_info(&vm, spent_on_pubdata)
-                    .is_err()
-                {
-                    log::info!(
-                        "{}",
-                        "!!! FAILED TO GET DETAILED GAS INFO !!!".to_owned().red()
-                    );
+        // Set gas_limit for transaction
+        let gas_limit_with_overhead = tx_gas_limit
+            + derive_gas_estimation_overhead(
+                tx_gas_limit,
+                gas_per_pubdata_byte as u32,
+                tx.encoding_len(),
+            );
+        l2_tx.common_data.fee.gas_limit = gas_limit_with_overhead.into();
+
+        let mut storage_view = StorageView::new(fork_storage);
+
+        // The nonce needs to be updated
+        let nonce = l2_tx.nonce();
+        let nonce_key = get_nonce_key(&l2_tx.initiator_account());
+        let full_nonce = storage_view.read_value(&nonce_key);
+        let (_, deployment_nonce) = decompose_full_nonce(h256_to_u256(full_nonce));
+        let enforced_full_nonce = nonces_to_full_nonce(U256::from(nonce.0), deployment_nonce);
+        storage_view.set_value(nonce_key, u256_to_h256(enforced_full_nonce));
+        fund_default_sender(&mut storage_view, &l2_tx);
+
+        // We need to explicitly put enough balance into the account of the users
+        let payer = l2_tx.payer();
+        let balance_key = storage_key_for_eth_balance(&payer);
+        let mut current_balance = h256_to_u256(storage_view.read_value(&balance_key));
+        let added_balance = l2_tx.common_data.fee.gas_limit * l2_tx.common_data.fee.max_fee_per_gas;
+        current_balance += added_balance;
+        storage_view.set_value(balance_key, u256_to_h256(current_balance));
+
+        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryDisabled);
+
+        block_context.l1_gas_price = l1_gas_price;
+        let derived_block_context = DerivedBlockContext {
+            context: block_context,
+            base_fee,
+        };
+
+        let block_properties = InMemoryNodeInner::<S>::create_block_properties(bootloader_code);
+
+        let execution_mode = TxExecutionMode::EstimateFee {
+            missed_storage_invocation_limit: 1000000,
+        };
+
+        // init vm
+        let mut vm = init_vm_inner(
+            &mut oracle_tools,
+            BlockContextMode::OverrideCurrent(derived_block_context),
+            &block_properties,
+            BLOCK_GAS_LIMIT,
+            bootloader_code,
+            execution_mode,
+        );
+
+        let tx: Transaction = l2_tx.into();
+
+        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
+
+        let vm_block_result = vm.execute_till_block_end(BootloaderJobType::TransactionExecution);
+
+        match vm_block_result.full_result.revert_reason {
+            None => Ok(vm_block_result),
+            Some(revert) => Err(revert.revert_reason),
+        }
+    }
+}
+
+fn not_implemented<T: Send + 'static>(
+    method_name: &str,
+) -> jsonrpc_core::BoxFuture<Result<T, jsonrpc_core::Error>> {
+    log::warn!("Method {} is not implemented", method_name);
+    Err(jsonrpc_core::Error {
+        data: None,
+        code: jsonrpc_core::ErrorCode::MethodNotFound,
+        message: format!("Method {} is not implemented", method_name),
+    })
+    .into_boxed_future()
+}
+
+/// Returns true for the `Transfer(address,address,uint256)` events of ERC20 tokens (ERC721 ones
+/// have a third indexed topic). The transfers of `L2EthToken` are ETH transfers.
+fn is_erc20_transfer(log: &Log) -> bool {
+    log.address != L2_ETH_TOKEN_ADDRESS
+        && log.topics.len() == 3
+        && log.topics[0] == H256(keccak256(b"Transfer(address,address,uint256)"))
+}
+
+/// Calls and gas estimations without `from` are sent from the zero address, as in geth. Since it
+/// can't send real transactions, it is funded for the duration of the call, so that paying the
+/// fee or transferring `value` doesn't fail with an insufficient balance.
+fn fund_default_sender<T: ReadStorage>(storage_view: &mut StorageView<T>, l2_tx: &L2Tx) {
+    if l2_tx.initiator_account() != zksync_basic_types::Address::zero() {
+        return;
+    }
+    let balance_key = storage_key_for_eth_balance(&l2_tx.initiator_account());
+    let balance = h256_to_u256(storage_view.read_value(&balance_key));
+    let balance = cmp::max(balance, U256::from(DEFAULT_SENDER_BALANCE));
+    storage_view.set_value(balance_key, u256_to_h256(balance));
+}
+
+/// Builds the error returned when a call or a gas estimation reverts, following geth: code 3, an
+/// `execution reverted` message with the revert string (if any), and the raw revert bytes in
+/// `data`, so that clients can decode custom errors.
+pub fn revert_error(revert_reason: &TxRevertReason) -> jsonrpc_core::Error {
+    let (message, data) = match revert_reason {
+        TxRevertReason::EthCall(reason) | TxRevertReason::TxReverted(reason) => match reason {
+            VmRevertReason::General { msg, .. } => (msg.clone(), reason.encoded_data()),
+            // custom errors are only decoded by the client, from `data`
+            VmRevertReason::Unknown { .. } => (String::new(), reason.encoded_data()),
+            _ => (reason.to_string(), vec![]),
+        },
+        _ => (revert_reason.to_string(), vec![]),
+    };
+    let message = if message.is_empty() {
+        String::from("execution reverted")
+    } else {
+        format!("execution reverted: {}", message)
+    };
+    into_jsrpc_error(Web3Error::SubmitTransactionError(message, data))
+}
+
+/// In-memory node, that can be used for local & unit testing.
+/// It also supports the option of forking testnet/mainnet.
+/// All contents are removed when object is destroyed.
+pub struct InMemoryNode<S> {
+    inner: Arc<RwLock<InMemoryNodeInner<S>>>,
+}
+
+impl<S> InMemoryNode<S> {
+    /// Returns a handle to the node owning `inner`, as shared with the namespaces.
+    pub fn from_inner(inner: Arc<RwLock<InMemoryNodeInner<S>>>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Clone for InMemoryNode<S> {
+    /// Returns a handle to the same underlying node state.
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// Returns the full transaction at the given position of the block (if any).
+fn transaction_at_index(
+    block: &Block<TransactionVariant>,
+    index: zksync_basic_types::web3::types::Index,
+) -> Option<zksync_types::api::Transaction> {
+    match block.transactions.get(index.as_usize())? {
+        TransactionVariant::Full(tx) => Some(tx.clone()),
+        TransactionVariant::Hash(_) => None,
+    }
+}
+
+/// Returns the content of the message sent to L1 with the given hash, read from the
+/// `L1MessageSent` event of the `L1Messenger`.
+fn l1_message(events: &[VmEvent], hash: H256) -> Option<Bytes> {
+    let event = events.iter().find(|event| {
+        event.address == L1_MESSENGER_ADDRESS && event.indexed_topics.get(2) == Some(&hash)
+    })?;
+    let tokens = ethabi::decode(&[ethabi::ParamType::Bytes], &event.value).ok()?;
+    tokens.into_iter().next()?.into_bytes().map(Bytes)
+}
+
+/// Returns the L2 transaction wrapped in `tx`, or `None` for L1 transactions.
+fn as_l2_tx(tx: &Transaction) -> Option<L2Tx> {
+    match &tx.common_data {
+        ExecuteTransactionCommon::L2(common_data) => Some(L2Tx {
+            execute: tx.execute.clone(),
+            common_data: common_data.clone(),
+            received_timestamp_ms: tx.received_timestamp_ms,
+        }),
+        _ => None,
+    }
+}
+
+/// Converts the transaction to its API representation, without the block fields. L1 transactions
+/// use their serial id as nonce and the priority operation transaction type.
+fn api_transaction(tx: &Transaction, chain_id: L2ChainId) -> zksync_types::api::Transaction {
+    if let Some(l2_tx) = as_l2_tx(tx) {
+        return zksync_types::api::Transaction::from(l2_tx);
+    }
+
+    let (nonce, max_fee_per_gas) = match &tx.common_data {
+        ExecuteTransactionCommon::L1(common_data) => (
+            U256::from(common_data.serial_id.0),
+            common_data.max_fee_per_gas,
+        ),
+        _ => (U256::zero(), U256::zero()),
+    };
+    zksync_types::api::Transaction {
+        hash: tx.hash(),
+        nonce,
+        block_hash: None,
+        block_number: None,
+        transaction_index: None,
+        from: Some(tx.initiator_account()),
+        to: Some(tx.recipient_account()),
+        value: tx.execute.value,
+        gas_price: Some(max_fee_per_gas),
+        gas: tx.gas_limit(),
+        input: tx.execute.calldata.clone().into(),
+        v: None,
+        r: None,
+        s: None,
+        raw: None,
+        transaction_type: Some(U64::from(PRIORITY_OPERATION_L2_TX_TYPE)),
+        access_list: None,
+        max_fee_per_gas: Some(max_fee_per_gas),
+        max_priority_fee_per_gas: Some(U256::zero()),
+        chain_id: U256::from(chain_id.0),
+        l1_batch_number: None,
+        l1_batch_tx_index: None,
+    }
+}
+
+fn contract_address_from_tx_result(execution_result: &VmTxExecutionResult) -> Option<H160> {
+    for query in execution_result.result.logs.storage_logs.iter().rev() {
+        if query.log_type == StorageLogQueryType::InitialWrite
+            && query.log_query.address == ACCOUNT_CODE_STORAGE_ADDRESS
+        {
+            return Some(h256_to_account_address(&u256_to_h256(query.log_query.key)));
+        }
+    }
+    None
+}
+
+impl<S: ForkSource + std::fmt::Debug> Default for InMemoryNode<S> {
+    fn default() -> Self {
+        InMemoryNode::new(None, &NodeConfig::default())
+    }
+}
+
+impl<S: ForkSource + std::fmt::Debug> InMemoryNode<S> {
+    /// Creates a node starting from the `fork` block, or from a local genesis.
+    ///
+    /// # Panics
+    ///
+    /// If the system contracts can't be loaded, see [`InMemoryNode::try_new`].
+    pub fn new(fork: Option<ForkDetails<S>>, config: &NodeConfig) -> Self {
+        Self::try_new(fork, config).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a node starting from the `fork` block, or from a local genesis.
+    /// Fails if the system contracts can't be loaded.
+    pub fn try_new(fork: Option<ForkDetails<S>>, config: &NodeConfig) -> Result<Self, String> {
+        let NodeConfig {
+            show_calls,
+            max_call_depth,
+            gas_highlight_threshold,
+            print_events,
+            print_storage_logs,
+            show_storage_logs,
+            show_vm_details,
+            show_gas_details,
+            formatter_output,
+            resolve_hashes,
+            system_contracts_options,
+            fee_config,
+            genesis,
+        } = config.clone();
+        let system_contracts_options = &system_contracts_options;
+        let local_genesis = fork.is_none();
+        let mut inner = if let Some(f) = &fork {
+            let mut block_hashes = HashMap::<u64, H256>::new();
+            block_hashes.insert(f.l2_block.number.as_u64(), f.l2_block.hash);
+            let mut blocks = HashMap::<H256, Block<TransactionVariant>>::new();
+            blocks.insert(f.l2_block.hash, f.l2_block.clone());
+
+            InMemoryNodeInner {
+                current_timestamp: f.block_timestamp + 1,
+                current_batch: f.l1_block.0 + 1,
+                current_miniblock: f.l2_miniblock,
+                time_offset: 0,
+                l1_gas_price: f.l1_gas_price,
+                l2_gas_price: fee_config.l2_gas_price,
+                fair_l2_gas_price: fee_config.fair_l2_gas_price,
+                gas_per_pubdata: fee_config.gas_per_pubdata,
+                tx_results: Default::default(),
+                blocks,
+                block_hashes,
+                block_gas_prices: Default::default(),
+                fork_storage: ForkStorage::new(fork, system_contracts_options)?,
+                show_calls,
+                max_call_depth,
+                gas_highlight_threshold,
+                print_events,
+                print_storage_logs,
+                show_storage_logs,
+                show_vm_details,
+                show_gas_details,
+                formatter_output,
+                resolve_hashes,
+                console_log_handler: ConsoleLogHandler::default(),
+                system_contracts: SystemContracts::from_options(system_contracts_options)?,
+                system_contracts_options: system_contracts_options.clone(),
+                genesis: genesis.clone(),
+                filters: Default::default(),
+                impersonated_accounts: Default::default(),
+                code_history: Default::default(),
+                pending_transactions: Default::default(),
+                automine: true,
+                mempool: Default::default(),
+                priority_queue: Default::default(),
+                next_priority_op_id: 0,
+                transaction_order: TransactionOrder::Fees,
+                subscriptions: Default::default(),
+                interval_mining: None,
+                snapshots: Default::default(),
+                last_snapshot_id: U64::zero(),
+                known_tokens: Default::default(),
+                registered_tokens: Default::default(),
+                token_prices: Default::default(),
+                forward_token_prices: false,
+            }
+        } else {
+            let mut block_hashes = HashMap::<u64, H256>::new();
+            block_hashes.insert(0, H256::zero());
+            let mut blocks = HashMap::<H256, Block<TransactionVariant>>::new();
+            blocks.insert(
+                H256::zero(),
+                Block::<TransactionVariant> {
+                    gas_limit: U256::from(ETH_CALL_GAS_LIMIT),
+                    ..Default::default()
+                },
+            );
+
+            InMemoryNodeInner {
+                current_timestamp: NON_FORK_FIRST_BLOCK_TIMESTAMP,
+                current_batch: 1,
+                current_miniblock: 0,
+                time_offset: 0,
+                l1_gas_price: L1_GAS_PRICE,
+                l2_gas_price: fee_config.l2_gas_price,
+                fair_l2_gas_price: fee_config.fair_l2_gas_price,
+                gas_per_pubdata: fee_config.gas_per_pubdata,
+                tx_results: Default::default(),
+                blocks,
+                block_hashes,
+                block_gas_prices: HashMap::from([(
+                    0,
+                    (L1_GAS_PRICE, fee_config.fair_l2_gas_price),
+                )]),
+                fork_storage: ForkStorage::new(fork, system_contracts_options)?,
+                show_calls,
+                max_call_depth,
+                gas_highlight_threshold,
+                print_events,
+                print_storage_logs,
+                show_storage_logs,
+                show_vm_details,
+                show_gas_details,
+                formatter_output,
+                resolve_hashes,
+                console_log_handler: ConsoleLogHandler::default(),
+                system_contracts: SystemContracts::from_options(system_contracts_options)?,
+                system_contracts_options: system_contracts_options.clone(),
+                genesis: genesis.clone(),
+                filters: Default::default(),
+                impersonated_accounts: Default::default(),
+                code_history: Default::default(),
+                pending_transactions: Default::default(),
+                automine: true,
+                mempool: Default::default(),
+                priority_queue: Default::default(),
+                next_priority_op_id: 0,
+                transaction_order: TransactionOrder::Fees,
+                subscriptions: Default::default(),
+                interval_mining: None,
+                snapshots: Default::default(),
+                last_snapshot_id: U64::zero(),
+                known_tokens: Default::default(),
+                registered_tokens: Default::default(),
+                token_prices: Default::default(),
+                forward_token_prices: false,
+            }
+        };
+
+        if let Some(genesis) = &genesis {
+            genesis.apply(&mut inner.fork_storage);
+        }
+        if local_genesis {
+            let state_root = inner
+                .state_root()
+                .expect("failed computing the genesis state root");
+            if let Some(genesis) = inner.blocks.get_mut(&H256::zero()) {
+                genesis.state_root = state_root;
+            }
+        }
+
+        Ok(InMemoryNode {
+            inner: Arc::new(RwLock::new(inner)),
+        })
+    }
+
+    pub fn get_inner(&self) -> Arc<RwLock<InMemoryNodeInner<S>>> {
+        self.inner.clone()
+    }
+
+    /// Submits a transaction received by the node: it is validated, then executed in a new block
+    /// right away, or added to the mempool when automine is disabled.
+    pub fn submit_tx(&self, l2_tx: L2Tx) -> jsonrpc_core::Result<()> {
+        let tx_hash = l2_tx.hash();
+        {
+            let mut inner = self
+                .inner
+                .write()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            if let Err(err) = inner.validate_tx(&l2_tx) {
+                log::info!("{}", format!("Rejected {:?}: {}", tx_hash, err).red());
+                return Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
+                    err.to_string(),
+                    vec![],
+                )));
+            }
+            inner
+                .pending_transactions
+                .insert(tx_hash, l2_tx.initiator_account());
+            if !inner.automine {
+                log::info!("👷 Transaction {:?} added to the mempool", tx_hash);
+                inner.mempool.push(l2_tx);
+                return Ok(());
+            }
+        }
+
+        self.run_tx(l2_tx.into(), TxExecutionMode::VerifyExecute)
+            .map_err(|err| {
+                // the transaction is dropped, it no longer counts towards the pending nonce
+                if let Ok(mut inner) = self.inner.write() {
+                    inner.pending_transactions.remove(&tx_hash);
+                }
+                into_jsrpc_error(Web3Error::SubmitTransactionError(
+                    format!("Execution error: {}", err),
+                    tx_hash.as_bytes().to_vec(),
+                ))
+            })
+    }
+
+    /// Adds an L1 transaction to the priority queue. With automine, it is executed right away,
+    /// otherwise it is included in the next mined block.
+    pub fn submit_priority_tx(&self, tx: Transaction) -> Result<(), String> {
+        {
+            let mut inner = self
+                .inner
+                .write()
+                .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+            if !inner.automine {
+                log::info!(
+                    "👷 L1 transaction {:?} added to the priority queue",
+                    tx.hash()
+                );
+                inner.priority_queue.push(tx);
+                return Ok(());
+            }
+        }
+
+        self.run_tx(tx, TxExecutionMode::VerifyExecute)
+    }
+
+    /// Executes the L1 transactions of the priority queue, in the order they were received, and
+    /// then the transactions waiting in the mempool, in the configured order, and seals them in
+    /// a single block. Transactions that can't be executed are dropped.
+    ///
+    /// Returns the number of mined blocks: 1, or 0 if no transaction could be executed.
+    pub fn mine_mempool(&self) -> Result<u64, String> {
+        let (priority_txs, txs) = {
+            let mut inner = self
+                .inner
+                .write()
+                .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+            let priority_txs = std::mem::take(&mut inner.priority_queue);
+            let mempool = std::mem::take(&mut inner.mempool);
+            (priority_txs, inner.transaction_order.sort(mempool))
+        };
+
+        let mut executed = vec![];
+        for tx in priority_txs {
+            let tx_hash = tx.hash();
+            match self.execute_tx(tx, TxExecutionMode::VerifyExecute) {
+                Ok(tx) => executed.push(tx),
+                Err(err) => log::error!("Dropping L1 transaction {:?}: {}", tx_hash, err),
+            }
+        }
+        for tx in txs {
+            let tx_hash = tx.hash();
+            match self.execute_tx(tx.into(), TxExecutionMode::VerifyExecute) {
+                Ok(tx) => executed.push(tx),
+                Err(err) => {
+                    log::error!("Dropping transaction {:?}: {}", tx_hash, err);
+                    self.inner
+                        .write()
+                        .map_err(|e| format!("Failed to acquire write lock: {}", e))?
+                        .pending_transactions
+                        .remove(&tx_hash);
                 }
             }
         }
+        if executed.is_empty() {
+            return Ok(0);
+        }
+        self.seal_block(executed)?;
+        Ok(1)
+    }
+
+    /// Applies multiple transactions - but still one per L1 batch.
+    pub fn apply_txs(&self, txs: Vec<L2Tx>) -> Result<(), String> {
+        log::info!("Running {:?} transactions (one per batch)", txs.len());
+
+        for tx in txs {
+            self.run_tx(tx.into(), TxExecutionMode::VerifyExecute)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a lot of tokens to a given account.
+    pub fn set_rich_account(&self, address: H160) {
+        let key = storage_key_for_eth_balance(&address);
+
+        let mut inner = match self.inner.write() {
+            Ok(guard) => guard,
+            Err(e) => {
+                log::info!("Failed to acquire write lock: {}", e);
+                return;
+            }
+        };
+
+        let keys = {
+            let mut storage_view = StorageView::new(&inner.fork_storage);
+            storage_view.set_value(key, u256_to_h256(U256::from(10u128.pow(30))));
+            storage_view.modified_storage_keys().clone()
+        };
+
+        for (key, value) in keys.iter() {
+            inner.fork_storage.set_value(*key, *value);
+        }
+    }
+
+    /// Runs L2 'eth call' method - that doesn't commit to a block.
+    /// The `state_override` is only visible to this call and never reaches the node storage.
+    fn run_l2_call(
+        &self,
+        l2_tx: L2Tx,
+        block: Option<BlockIdVariant>,
+        state_override: &StateOverride,
+    ) -> Result<VmBlockResult, String> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+
+        inner.run_l2_call(l2_tx, block, state_override)
+    }
+
+    /// Same as `eth_call`, with `state_override` applied on top of the node storage for the
+    /// duration of the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The call request containing the function name and arguments.
+    /// * `block` - The block to run the call on, the latest one by default.
+    /// * `state_override` - The balance, nonce, code and storage to override per account.
+    ///
+    /// # Returns
+    ///
+    /// A boxed future containing the result of the function call.
+    pub fn call_with_state_override(
+        &self,
+        mut req: zksync_types::transaction_request::CallRequest,
+        block: Option<BlockIdVariant>,
+        state_override: StateOverride,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Bytes>> {
+        req.from.get_or_insert_with(Default::default);
+        match L2Tx::from_request(req.into(), MAX_TX_SIZE) {
+            Ok(mut tx) => {
+                tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
+                let result = self.run_l2_call(tx, block, &state_override);
+
+                match result {
+                    Ok(vm_block_result) => match vm_block_result.full_result.revert_reason {
+                        Some(revert) => {
+                            let error = revert_error(&revert.revert_reason);
+                            log::info!("{}", error.message.on_red());
+                            Err(error).into_boxed_future()
+                        }
+                        None => Ok(vm_block_result
+                            .full_result
+                            .return_data
+                            .into_iter()
+                            .flat_map(|val| {
+                                let bytes: [u8; 32] = val.into();
+                                bytes.to_vec()
+                            })
+                            .collect::<Vec<_>>()
+                            .into())
+                        .into_boxed_future(),
+                    },
+                    Err(e) => {
+                        let error = Web3Error::InvalidTransactionData(
+                            zksync_types::ethabi::Error::InvalidName(e),
+                        );
+                        Err(into_jsrpc_error(error)).into_boxed_future()
+                    }
+                }
+            }
+            Err(e) => {
+                let error = Web3Error::SerializationError(e);
+                Err(into_jsrpc_error(error)).into_boxed_future()
+            }
+        }
+    }
+
+    fn display_detailed_gas_info<H: HistoryMode>(
+        &self,
+        vm: &VmInstance<H>,
+        spent_on_pubdata: u32,
+    ) -> eyre::Result<()> {
+        let debug = BootloaderDebug::load_from_memory(vm)?;
+
+        log::info!("┌─────────────────────────┐");
+        log::info!("│       GAS DETAILS       │");
+        log::info!("└─────────────────────────┘");
+
+        // Total amount of gas (should match tx.gas_limit).
+        let total_gas_limit = debug
+            .total_gas_limit_from_user
+            .saturating_sub(debug.reserved_gas);
+
+        let intrinsic_gas = total_gas_limit - debug.gas_limit_after_intrinsic;
+        let gas_for_validation = debug.gas_limit_after_intrinsic - debug.gas_after_validation;
+
+        let gas_spent_on_compute =
+            debug.gas_spent_on_execution - debug.gas_spent_on_bytecode_preparation;
+
+        let gas_used = intrinsic_gas
+            + gas_for_validation
+            + debug.gas_spent_on_bytecode_preparation
+            + gas_spent_on_compute;
+
+        log::info!(
+            "Gas - Limit: {} | Used: {} | Refunded: {}",
+            to_human_size(total_gas_limit),
+            to_human_size(gas_used),
+            to_human_size(debug.refund_by_operator)
+        );
+
+        if debug.total_gas_limit_from_user != total_gas_limit {
+            log::info!(
+                "{}",
+                format!(
+                "  WARNING: user actually provided more gas {}, but system had a lower max limit.",
+                to_human_size(debug.total_gas_limit_from_user)
+            )
+                .yellow()
+            );
+        }
+        if debug.refund_computed != debug.refund_by_operator {
+            log::info!(
+                "{}",
+                format!(
+                    "  WARNING: Refund by VM: {}, but operator refunded more: {}",
+                    to_human_size(debug.refund_computed),
+                    to_human_size(debug.refund_by_operator)
+                )
+                .yellow()
+            );
+        }
+
+        if debug.refund_computed + gas_used != total_gas_limit {
+            log::info!(
+                "{}",
+                format!(
+                    "  WARNING: Gas totals don't match. {} != {} , delta: {}",
+                    to_human_size(debug.refund_computed + gas_used),
+                    to_human_size(total_gas_limit),
+                    to_human_size(total_gas_limit.abs_diff(debug.refund_computed + gas_used))
+                )
+                .yellow()
+            );
+        }
+
+        let bytes_published = spent_on_pubdata / debug.gas_per_pubdata.as_u32();
+
+        log::info!(
+            "During execution published {} bytes to L1, @{} each - in total {} gas",
+            to_human_size(bytes_published.into()),
+            to_human_size(debug.gas_per_pubdata),
+            to_human_size(spent_on_pubdata.into())
+        );
+
+        log::info!("Out of {} gas used, we spent:", to_human_size(gas_used));
+        log::info!(
+            "  {:>15} gas ({:>2}%) for transaction setup",
+            to_human_size(intrinsic_gas),
+            to_human_size(intrinsic_gas * 100 / gas_used)
+        );
+        log::info!(
+            "  {:>15} gas ({:>2}%) for bytecode preparation (decompression etc)",
+            to_human_size(debug.gas_spent_on_bytecode_preparation),
+            to_human_size(debug.gas_spent_on_bytecode_preparation * 100 / gas_used)
+        );
+        log::info!(
+            "  {:>15} gas ({:>2}%) for account validation",
+            to_human_size(gas_for_validation),
+            to_human_size(gas_for_validation * 100 / gas_used)
+        );
+        log::info!(
+            "  {:>15} gas ({:>2}%) for computations (opcodes)",
+            to_human_size(gas_spent_on_compute),
+            to_human_size(gas_spent_on_compute * 100 / gas_used)
+        );
+
+        log::info!("");
+        log::info!("");
+        log::info!(
+            "{}",
+            "=== Transaction setup cost breakdown ===".to_owned().bold(),
+        );
+
+        log::info!("Total cost: {}", to_human_size(intrinsic_gas).bold());
+        log::info!(
+            "  {:>15} gas ({:>2}%) fixed cost",
+            to_human_size(debug.intrinsic_overhead),
+            to_human_size(debug.intrinsic_overhead * 100 / intrinsic_gas)
+        );
+        log::info!(
+            "  {:>15} gas ({:>2}%) operator cost",
+            to_human_size(debug.operator_overhead),
+            to_human_size(debug.operator_overhead * 100 / intrinsic_gas)
+        );
+
+        log::info!("");
+        log::info!(
+            "  FYI: operator could have charged up to: {}, so you got {}% discount",
+            to_human_size(debug.required_overhead),
+            to_human_size(
+                (debug.required_overhead - debug.operator_overhead) * 100 / debug.required_overhead
+            )
+        );
+
+        let publish_block_l1_bytes = BLOCK_OVERHEAD_PUBDATA;
+        log::info!(
+            "Publishing full block costs the operator up to: {}, where {} is due to {} bytes published to L1",
+            to_human_size(debug.total_overhead_for_block),
+            to_human_size(debug.gas_per_pubdata * publish_block_l1_bytes),
+            to_human_size(publish_block_l1_bytes.into())
+        );
+        log::info!("Your transaction has contributed to filling up the block in the following way (we take the max contribution as the cost):");
+        log::info!(
+            "  Circuits overhead:{:>15} ({}% of the full block: {})",
+            to_human_size(debug.overhead_for_circuits),
+            to_human_size(debug.overhead_for_circuits * 100 / debug.total_overhead_for_block),
+            to_human_size(debug.total_overhead_for_block)
+        );
+        log::info!(
+            "  Length overhead:  {:>15}",
+            to_human_size(debug.overhead_for_length)
+        );
+        log::info!(
+            "  Slot overhead:    {:>15}",
+            to_human_size(debug.overhead_for_slot)
+        );
+        Ok(())
+    }
+
+    /// Executes the given transaction and returns all the VM logs. L1 transactions always run
+    /// with the baseline contracts, as their sender is never impersonated.
+    pub fn run_tx_inner(
+        &self,
+        tx: Transaction,
+        execution_mode: TxExecutionMode,
+    ) -> Result<L2TxResult, String> {
+        let inner = self
+            .inner
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+
+        let mut storage_view = StorageView::new(&inner.fork_storage);
+
+        let mut oracle_tools = OracleTools::new(&mut storage_view, HistoryEnabled);
+
+        let bootloader_code = if matches!(execution_mode, TxExecutionMode::VerifyExecute)
+            && matches!(tx.common_data, ExecuteTransactionCommon::L2(_))
+            && inner
+                .impersonated_accounts
+                .contains(&tx.initiator_account())
+        {
+            inner.system_contracts.contracts_for_impersonation()
+        } else {
+            inner.system_contracts.contracts(execution_mode)
+        };
+
+        let block_context = inner.create_block_context();
+        let block_properties = InMemoryNodeInner::<S>::create_block_properties(bootloader_code);
+
+        // init vm
+        let mut vm = init_vm_inner(
+            &mut oracle_tools,
+            BlockContextMode::NewBlock(block_context.into(), Default::default()),
+            &block_properties,
+            BLOCK_GAS_LIMIT,
+            bootloader_code,
+            execution_mode,
+        );
+        let spent_on_pubdata_before = vm.state.local_state.spent_pubdata_counter;
+
+        push_transaction_to_bootloader_memory(&mut vm, &tx, execution_mode, None);
+        let tx_result = vm
+            .execute_next_tx(u32::MAX, true)
+            .map_err(|e| format!("Failed to execute next transaction: {}", e))?;
+
+        let spent_on_pubdata = vm.state.local_state.spent_pubdata_counter - spent_on_pubdata_before;
+
+        log::info!("┌─────────────────────────┐");
+        log::info!("│   TRANSACTION SUMMARY   │");
+        log::info!("└─────────────────────────┘");
+
+        match tx_result.status {
+            TxExecutionStatus::Success => log::info!("Transaction: {}", "SUCCESS".green()),
+            TxExecutionStatus::Failure => log::info!("Transaction: {}", "FAILED".red()),
+        }
+
+        log::info!("Initiator: {:?}", tx.initiator_account());
+        log::info!("Payer: {:?}", tx.payer());
+        log::info!(
+            "Gas - Limit: {} | Used: {} | Refunded: {}",
+            to_human_size(tx.gas_limit()),
+            to_human_size(tx.gas_limit() - tx_result.gas_refunded),
+            to_human_size(tx_result.gas_refunded.into())
+        );
+
+        match inner.show_gas_details {
+            ShowGasDetails::None => log::info!(
+                "Use --show-gas-details flag or call config_setShowGasDetails to display more info"
+            ),
+            ShowGasDetails::All => {
+                if self
+                    .display_detailed_gas_info(&vm, spent_on_pubdata)
+                    .is_err()
+                {
+                    log::info!(
+                        "{}",
+                        "!!! FAILED TO GET DETAILED GAS INFO !!!".to_owned().red()
+                    );
+                }
+            }
+        }
+
+        let show_storage_logs = if inner.print_storage_logs {
+            inner.show_storage_logs.clone()
+        } else {
+            ShowStorageLogs::None
+        };
+        if show_storage_logs != ShowStorageLogs::None {
+            log::info!("");
+            log::info!("┌──────────────────┐");
+            log::info!("│   STORAGE LOGS   │");
+            log::info!("└──────────────────┘");
+        }
+
+        if show_storage_logs != ShowStorageLogs::None {
+            for log in &tx_result.result.logs.l2_to_l1_logs {
+                formatter::print_l2_to_l1_log(log, &inner.formatter_output);
+            }
+        }
+
+        for log_query in &tx_result.result.logs.storage_logs {
+            match show_storage_logs {
+                ShowStorageLogs::Write => {
+                    if matches!(
+                        log_query.log_type,
+                        StorageLogQueryType::RepeatedWrite | StorageLogQueryType::InitialWrite
+                    ) {
+                        formatter::print_logs(log_query, &inner.formatter_output);
+                    }
+                }
+                ShowStorageLogs::Read => {
+                    if log_query.log_type == StorageLogQueryType::Read {
+                        formatter::print_logs(log_query, &inner.formatter_output);
+                    }
+                }
+                ShowStorageLogs::All => {
+                    formatter::print_logs(log_query, &inner.formatter_output);
+                }
+                _ => {}
+            }
+        }
+
+        if inner.show_vm_details != ShowVMDetails::None {
+            formatter::print_vm_details(&tx_result.result);
+        }
+
+        log::info!("");
+        log::info!("==== Console logs: ");
+        for call in &tx_result.call_traces {
+            inner.console_log_handler.handle_call_recurive(call);
+        }
+
+        log::info!("");
+        log::info!(
+            "==== {} Use --show-calls flag or call config_setShowCalls to display more info.",
+            format!("{:?} call traces. ", tx_result.call_traces.len()).bold()
+        );
+
+        if inner.show_calls != ShowCalls::None {
+            for call in &tx_result.call_traces {
+                formatter::print_call(
+                    call,
+                    0,
+                    &inner.show_calls,
+                    inner.max_call_depth,
+                    inner.gas_highlight_threshold,
+                    inner.resolve_hashes,
+                    &inner.formatter_output,
+                );
+            }
+        }
+
+        if inner.print_events {
+            log::info!("");
+            log::info!(
+                "==== {}",
+                format!("{} events", tx_result.result.logs.events.len()).bold()
+            );
+            for event in &tx_result.result.logs.events {
+                formatter::print_event(event, inner.resolve_hashes, &inner.formatter_output);
+            }
+        }
+
+        // Compute gas details
+        let debug = BootloaderDebug::load_from_memory(&vm).map_err(|err| err.to_string())?;
+
+        // Total amount of gas (should match tx.gas_limit).
+        let gas_limit = debug
+            .total_gas_limit_from_user
+            .saturating_sub(debug.reserved_gas);
+
+        let intrinsic_gas = gas_limit - debug.gas_limit_after_intrinsic;
+        let gas_for_validation = debug.gas_limit_after_intrinsic - debug.gas_after_validation;
+
+        let gas_spent_on_compute =
+            debug.gas_spent_on_execution - debug.gas_spent_on_bytecode_preparation;
+
+        let gas_used = intrinsic_gas
+            + gas_for_validation
+            + debug.gas_spent_on_bytecode_preparation
+            + gas_spent_on_compute;
+
+        // The computed block hash here will be different than that in production.
+        let hash = compute_hash(block_context.block_number, tx.hash());
+        let block_number = U64::from(inner.current_miniblock.saturating_add(1));
+        let l1_batch_number = U64::from(block_context.block_number);
+        let transaction = zksync_types::api::Transaction {
+            block_hash: Some(hash),
+            block_number: Some(block_number),
+            transaction_index: Some(U64::zero()),
+            l1_batch_number: Some(l1_batch_number),
+            ..api_transaction(&tx, inner.fork_storage.chain_id)
+        };
+        let block = Block {
+            hash,
+            parent_hash: inner
+                .block_hashes
+                .get(&inner.current_miniblock)
+                .cloned()
+                .unwrap_or_default(),
+            number: block_number,
+            timestamp: U256::from(block_context.block_timestamp),
+            l1_batch_number: Some(l1_batch_number),
+            transactions: vec![TransactionVariant::Full(transaction)],
+            gas_used,
+            gas_limit,
+            ..Default::default()
+        };
+
+        log::info!("");
+        log::info!("");
+
+        vm.execute_till_block_end(BootloaderJobType::BlockPostprocessing);
+
+        let bytecodes = vm
+            .state
+            .decommittment_processor
+            .known_bytecodes
+            .inner()
+            .clone();
+
+        let modified_keys = storage_view.modified_storage_keys().clone();
+        Ok((modified_keys, tx_result, block, bytecodes))
+    }
+
+    /// Runs the transaction and commits it to a new block.
+    fn run_tx(&self, tx: Transaction, execution_mode: TxExecutionMode) -> Result<(), String> {
+        let executed = self.execute_tx(tx, execution_mode)?;
+        self.seal_block(vec![executed])
+    }
+
+    /// Runs the transaction in its own L1 batch and commits its storage changes. It becomes
+    /// visible once the block it belongs to (the next one) is sealed with
+    /// [`InMemoryNode::seal_block`].
+    fn execute_tx(
+        &self,
+        tx: Transaction,
+        execution_mode: TxExecutionMode,
+    ) -> Result<(TxExecutionInfo, Block<TransactionVariant>), String> {
+        let tx_hash = tx.hash();
+        log::info!("");
+        log::info!("Executing {}", format!("{:?}", tx_hash).bold());
+        let (keys, result, block, bytecodes) = self.run_tx_inner(tx.clone(), execution_mode)?;
+        // Write all the mutated keys (storage slots).
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        let current_miniblock = inner.current_miniblock.saturating_add(1);
+        inner.pending_transactions.remove(&tx_hash);
+        inner.record_code_changes(keys.iter(), current_miniblock)?;
+        for (key, value) in keys.iter() {
+            inner.fork_storage.set_value(*key, *value);
+        }
+
+        // Write all the factory deps.
+        for (hash, code) in bytecodes.iter() {
+            inner.fork_storage.store_factory_dep(
+                u256_to_h256(*hash),
+                code.iter()
+                    .flat_map(|entry| {
+                        let mut bytes = vec![0u8; 32];
+                        entry.to_big_endian(&mut bytes);
+                        bytes.to_vec()
+                    })
+                    .collect(),
+            )
+        }
+        // every transaction runs in a new batch, which needs a later timestamp
+        inner.current_timestamp += 1;
+        inner.current_batch += 1;
+
+        let info = TxExecutionInfo {
+            tx,
+            batch_number: block.l1_batch_number.unwrap_or_default().as_u32(),
+            miniblock_number: current_miniblock,
+            gas_price: inner.l2_gas_price,
+            contract_address: contract_address_from_tx_result(&result),
+            result,
+        };
+        Ok((info, block))
+    }
+
+    /// Seals the next block with the transactions run by [`InMemoryNode::execute_tx`], in the
+    /// given order.
+    fn seal_block(
+        &self,
+        executed: Vec<(TxExecutionInfo, Block<TransactionVariant>)>,
+    ) -> Result<(), String> {
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        let current_miniblock = inner.current_miniblock.saturating_add(1);
+
+        let mut block: Option<Block<TransactionVariant>> = None;
+        let mut tx_hashes = vec![];
+        for (transaction_index, (info, tx_block)) in executed.into_iter().enumerate() {
+            // the block takes the hash, number and timestamp of its first transaction
+            let block = block.get_or_insert_with(|| Block {
+                transactions: vec![],
+                gas_used: U256::zero(),
+                gas_limit: U256::zero(),
+                ..tx_block.clone()
+            });
+            block.gas_used += tx_block.gas_used;
+            block.gas_limit += tx_block.gas_limit;
+            for transaction in tx_block.transactions {
+                block.transactions.push(match transaction {
+                    TransactionVariant::Full(transaction) => {
+                        TransactionVariant::Full(zksync_types::api::Transaction {
+                            block_hash: Some(block.hash),
+                            transaction_index: Some(U64::from(transaction_index)),
+                            ..transaction
+                        })
+                    }
+                    TransactionVariant::Hash(hash) => TransactionVariant::Hash(hash),
+                });
+            }
+            let tx_hash = info.tx.hash();
+            inner.tx_results.insert(tx_hash, info);
+            tx_hashes.push(tx_hash);
+        }
+        let mut block = block.ok_or_else(|| String::from("no transactions to seal"))?;
+        block.state_root = inner.state_root()?;
+
+        let block_hash = block.hash;
+        inner.block_hashes.insert(current_miniblock, block_hash);
+        let gas_prices = (inner.l1_gas_price, inner.fair_l2_gas_price);
+        inner.block_gas_prices.insert(current_miniblock, gas_prices);
+        inner.subscriptions.notify_new_block(&block);
+        inner.blocks.insert(block_hash, block);
+        inner.current_miniblock = current_miniblock;
+
+        for tx_hash in tx_hashes {
+            inner.filters.notify_new_pending_transaction(tx_hash);
+        }
+        inner.filters.notify_new_block(block_hash);
+        let logs = inner.get_block_logs(current_miniblock);
+        for log in &logs {
+            inner.filters.notify_new_log(log, current_miniblock);
+            if is_erc20_transfer(log) {
+                inner.known_tokens.insert(log.address);
+            }
+        }
+        inner
+            .subscriptions
+            .notify_new_logs(&logs, current_miniblock);
+
+        Ok(())
+    }
+}
+
+impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EthNamespaceT for InMemoryNode<S> {
+    /// Returns the chain ID of the node.
+    fn chain_id(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::U64>> {
+        match self.inner.read() {
+            Ok(inner) => Ok(U64::from(inner.fork_storage.chain_id.0 as u64)).into_boxed_future(),
+            Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)).into_boxed_future(),
+        }
+    }
+
+    /// Calls the specified function on the L2 contract with the given arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The call request containing the function name and arguments.
+    /// * `block` - The block to run the call on. Older blocks are only available at or below the
+    ///   fork point.
+    ///
+    /// # Returns
+    ///
+    /// A boxed future containing the result of the function call.
+    fn call(
+        &self,
+        req: zksync_types::transaction_request::CallRequest,
+        block: Option<zksync_types::api::BlockIdVariant>,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Bytes>> {
+        self.call_with_state_override(req, block, StateOverride::default())
+    }
+
+    /// Returns the balance of the specified address.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to get the balance of.
+    /// * `_block` - The block ID variant (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` that resolves to a `Result` containing the balance of the specified address as a `U256` or a `jsonrpc_core::Error` if an error occurred.
+    fn get_balance(
+        &self,
+        address: zksync_basic_types::Address,
+        _block: Option<zksync_types::api::BlockIdVariant>,
+    ) -> BoxFuture<Result<U256, jsonrpc_core::Error>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let balance_key = storage_key_for_standard_token_balance(
+                AccountTreeId::new(L2_ETH_TOKEN_ADDRESS),
+                &address,
+            );
+
+            match inner.write() {
+                Ok(mut inner_guard) => {
+                    let balance = inner_guard.fork_storage.read_value(&balance_key);
+                    Ok(h256_to_u256(balance))
+                }
+                Err(_) => {
+                    let web3_error = Web3Error::InternalError;
+                    Err(into_jsrpc_error(web3_error))
+                }
+            }
+        })
+    }
+
+    /// Returns a block by its number.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_number` - A `BlockNumber` enum variant representing the block number to retrieve.
+    /// * `full_transactions` - A boolean value indicating whether to retrieve full transactions or not.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of `zksync_types::api::Block<zksync_types::api::TransactionVariant>`.
+    fn get_block_by_number(
+        &self,
+        block_number: zksync_types::api::BlockNumber,
+        full_transactions: bool,
+    ) -> BoxFuture<
+        jsonrpc_core::Result<
+            Option<zksync_types::api::Block<zksync_types::api::TransactionVariant>>,
+        >,
+    > {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let maybe_block = {
+                let reader = match inner.read() {
+                    Ok(r) => r,
+                    Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+                };
+                match block_number {
+                    zksync_types::api::BlockNumber::Latest
+                    | zksync_types::api::BlockNumber::Pending
+                    | zksync_types::api::BlockNumber::Finalized
+                    | zksync_types::api::BlockNumber::Committed => reader
+                        .block_hashes
+                        .get(&reader.current_miniblock)
+                        .and_then(|hash| reader.blocks.get(hash))
+                        .cloned()
+                        .or_else(|| {
+                            reader
+                                .fork_storage
+                                .inner
+                                .read()
+                                .expect("failed reading fork storage")
+                                .fork
+                                .as_ref()
+                                .and_then(|fork| {
+                                    fork.fork_source
+                                        .get_block_by_number(block_number, true)
+                                        .ok()
+                                        .flatten()
+                                })
+                        }),
+                    zksync_types::api::BlockNumber::Number(ask_number) => {
+                        let block = reader
+                            .block_hashes
+                            .get(&ask_number.as_u64())
+                            .and_then(|hash| reader.blocks.get(hash))
+                            .cloned()
+                            .or_else(|| {
+                                reader
+                                    .fork_storage
+                                    .inner
+                                    .read()
+                                    .expect("failed reading fork storage")
+                                    .fork
+                                    .as_ref()
+                                    .and_then(|fork| {
+                                        fork.fork_source
+                                            .get_block_by_number(block_number, true)
+                                            .ok()
+                                            .flatten()
+                                    })
+                            });
+                        block
+                    }
+                    zksync_types::api::BlockNumber::Earliest => reader
+                        .block_hashes
+                        .get(&reader.earliest_block())
+                        .and_then(|hash| reader.blocks.get(hash))
+                        .cloned(),
+                }
+            };
+
+            match maybe_block {
+                Some(mut block) => {
+                    let block_hash = block.hash;
+                    block.transactions = block
+                        .transactions
+                        .into_iter()
+                        .map(|transaction| match &transaction {
+                            TransactionVariant::Full(inner) => {
+                                if full_transactions {
+                                    transaction
+                                } else {
+                                    TransactionVariant::Hash(inner.hash)
+                                }
+                            }
+                            TransactionVariant::Hash(_) => {
+                                if full_transactions {
+                                    panic!(
+                                        "unexpected non full transaction for block {}",
+                                        block_hash
+                                    )
+                                } else {
+                                    transaction
+                                }
+                            }
+                        })
+                        .collect();
+
+                    Ok(Some(block))
+                }
+                None => Err(into_jsrpc_error(Web3Error::NoBlock)),
+            }
+        })
+    }
+
+    /// Returns the code stored at the specified address.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to retrieve the code from.
+    /// * `block` - The block to retrieve the code at, defaults to the latest one. Blocks at or below
+    ///   the fork point are read from the forked network.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing the result of the operation, which is a `jsonrpc_core::Result` containing
+    /// the code as a `zksync_basic_types::Bytes` object.
+    fn get_code(
+        &self,
+        address: zksync_basic_types::Address,
+        block: Option<zksync_types::api::BlockIdVariant>,
+    ) -> BoxFuture<jsonrpc_core::Result<zksync_basic_types::Bytes>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            match inner.read() {
+                Ok(reader) => {
+                    let code_hash = reader
+                        .code_hash_at(address, block)
+                        .map_err(jsonrpc_core::Error::invalid_params)?;
+
+                    let code = reader
+                        .fork_storage
+                        .load_factory_dep_internal(code_hash)
+                        .unwrap_or_default();
+
+                    Ok(Bytes::from(code))
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    /// Returns the transaction count for a given address.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to get the transaction count for.
+    /// * `block` - Optional block ID variant. With `pending`, the transactions of the address that
+    ///   are still being executed are counted as well.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `BoxFuture` containing the transaction count as a `U256` wrapped in a `jsonrpc_core::Result`.
+    fn get_transaction_count(
+        &self,
+        address: zksync_basic_types::Address,
+        block: Option<zksync_types::api::BlockIdVariant>,
+    ) -> BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let nonce_key = get_nonce_key(&address);
+
+            match inner.write() {
+                Ok(mut guard) => {
+                    let result = guard.fork_storage.read_value(&nonce_key);
+                    let pending = match block.map(BlockId::from) {
+                        Some(BlockId::Number(BlockNumber::Pending)) => {
+                            guard.pending_transaction_count(address)
+                        }
+                        _ => 0,
+                    };
+                    Ok((h256_to_u64(result) + pending as u64).into())
+                }
+                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            }
+        })
+    }
+
+    /// Retrieves the transaction receipt for a given transaction hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the transaction to retrieve the receipt for.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` that resolves to an `Option` of a `TransactionReceipt` or an error.
+    fn get_transaction_receipt(
+        &self,
+        hash: zksync_basic_types::H256,
+    ) -> BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::TransactionReceipt>>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let reader = match inner.read() {
+                Ok(r) => r,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
+
+            let receipt = reader.transaction_receipt(hash);
+
+            Ok(receipt).map_err(|_: jsonrpc_core::Error| into_jsrpc_error(Web3Error::InternalError))
+        })
+    }
+
+    /// Sends a raw transaction to the L2 network.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_bytes` - The transaction bytes to send.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to the hash of the transaction if successful, or an error if the transaction is invalid or execution fails.
+    fn send_raw_transaction(
+        &self,
+        tx_bytes: zksync_basic_types::Bytes,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::H256>> {
+        let chain_id = match self.inner.read() {
+            Ok(reader) => reader.fork_storage.chain_id,
+            Err(_) => {
+                return futures::future::err(into_jsrpc_error(Web3Error::InternalError)).boxed()
+            }
+        };
+
+        let (tx_req, hash) = match TransactionRequest::from_bytes(&tx_bytes.0, chain_id.0) {
+            Ok(result) => result,
+            Err(e) => {
+                return futures::future::err(into_jsrpc_error(Web3Error::SerializationError(e)))
+                    .boxed()
+            }
+        };
+
+        let mut l2_tx: L2Tx = match L2Tx::from_request(tx_req, MAX_TX_SIZE) {
+            Ok(tx) => tx,
+            Err(e) => {
+                return futures::future::err(into_jsrpc_error(Web3Error::SerializationError(e)))
+                    .boxed()
+            }
+        };
+
+        l2_tx.set_input(tx_bytes.0, hash);
+        if hash != l2_tx.hash() {
+            return futures::future::err(into_jsrpc_error(Web3Error::InvalidTransactionData(
+                zksync_types::ethabi::Error::InvalidData,
+            )))
+            .boxed();
+        };
+
+        self.submit_tx(l2_tx).map(|_| hash).into_boxed_future()
+    }
+
+    /// Returns a block by its hash. Blocks that are not in memory are fetched from the fork (if any).
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - A `H256` type representing the hash of the block to retrieve.
+    /// * `full_transactions` - A boolean value indicating whether to retrieve full transactions or not.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of `zksync_types::api::Block<zksync_types::api::TransactionVariant>`.
+    fn get_block_by_hash(
+        &self,
+        hash: zksync_basic_types::H256,
+        full_transactions: bool,
+    ) -> jsonrpc_core::BoxFuture<
+        jsonrpc_core::Result<
+            Option<zksync_types::api::Block<zksync_types::api::TransactionVariant>>,
+        >,
+    > {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let maybe_block = {
+                let reader = inner
+                    .read()
+                    .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+
+                // try retrieving block from memory, and if unavailable subsequently from the fork
+                reader.blocks.get(&hash).cloned().or_else(|| {
+                    reader
+                        .fork_storage
+                        .inner
+                        .read()
+                        .expect("failed reading fork storage")
+                        .fork
+                        .as_ref()
+                        .and_then(|fork| {
+                            fork.fork_source
+                                .get_block_by_hash(hash, true)
+                                .ok()
+                                .flatten()
+                        })
+                })
+            };
+
+            match maybe_block {
+                Some(mut block) => {
+                    let block_hash = block.hash;
+                    block.transactions = block
+                        .transactions
+                        .into_iter()
+                        .map(|transaction| match &transaction {
+                            TransactionVariant::Full(inner) => {
+                                if full_transactions {
+                                    transaction
+                                } else {
+                                    TransactionVariant::Hash(inner.hash)
+                                }
+                            }
+                            TransactionVariant::Hash(_) => {
+                                if full_transactions {
+                                    panic!(
+                                        "unexpected non full transaction for block {}",
+                                        block_hash
+                                    )
+                                } else {
+                                    transaction
+                                }
+                            }
+                        })
+                        .collect();
+
+                    Ok(Some(block))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Returns a future that resolves to an optional transaction with the given hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - A 32-byte hash of the transaction.
+    ///
+    /// # Returns
+    ///
+    /// A `jsonrpc_core::BoxFuture` that resolves to a `jsonrpc_core::Result` containing an optional `zksync_types::api::Transaction`.
+    fn get_transaction_by_hash(
+        &self,
+        hash: zksync_basic_types::H256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(reader.block_transaction(hash))
+        })
+    }
+
+    /// Returns the current block number as a `U64` wrapped in a `BoxFuture`.
+    fn get_block_number(
+        &self,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::U64>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(U64::from(reader.current_miniblock))
+        })
+    }
+
+    /// Estimates the gas required for a given call request.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
+    /// * `_block` - An optional `BlockNumber` struct representing the block number to estimate gas for.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `U256` representing the estimated gas required.
+    fn estimate_gas(
+        &self,
+        req: zksync_types::transaction_request::CallRequest,
+        _block: Option<zksync_types::api::BlockNumber>,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
+        let reader = match inner.read() {
+            Ok(r) => r,
+            Err(_) => {
+                return futures::future::err(into_jsrpc_error(Web3Error::InternalError)).boxed()
+            }
+        };
+
+        let result: jsonrpc_core::Result<Fee> = reader.estimate_gas_impl(req);
+        match result {
+            Ok(fee) => Ok(fee.gas_limit).into_boxed_future(),
+            Err(err) => return futures::future::err(err).boxed(),
+        }
+    }
+
+    /// Returns the current gas price in U256 format.
+    fn gas_price(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(U256::from(reader.l2_gas_price))
+        })
+    }
+
+    // Methods below are not currently implemented.
+
+    /// Installs a log filter, to be polled for new logs with `eth_getFilterChanges`.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The filter object with the block range, addresses and topics to match.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the id of the new filter.
+    fn new_filter(&self, filter: Filter) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
+
+            Ok(writer.filters.add_log_filter(filter))
+        })
+    }
 
-        if inner.show_storage_logs != ShowStorageLogs::None {
-            log::info!("");
-            log::info!("┌──────────────────┐");
-            log::info!("│   STORAGE LOGS   │");
-            log::info!("└──────────────────┘");
-        }
+    /// Installs a block filter, to be polled for the hashes of new blocks with `eth_getFilterChanges`.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the id of the new filter.
+    fn new_block_filter(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
 
-        for log_query in &tx_result.result.logs.storage_logs {
-            match inner.show_storage_logs {
-                ShowStorageLogs::Write => {
-                    if matches!(
-                        log_query.log_type,
-                        StorageLogQueryType::RepeatedWrite | StorageLogQueryType::InitialWrite
-                    ) {
-                        formatter::print_logs(log_query);
-                    }
-                }
-                ShowStorageLogs::Read => {
-                    if log_query.log_type == StorageLogQueryType::Read {
-                        formatter::print_logs(log_query);
-                    }
-                }
-                ShowStorageLogs::All => {
-                    formatter::print_logs(log_query);
-                }
-                _ => {}
-            }
-        }
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
 
-        if inner.show_vm_details != ShowVMDetails::None {
-            formatter::print_vm_details(&tx_result.result);
-        }
+            Ok(writer.filters.add_block_filter())
+        })
+    }
 
-        log::info!("");
-        log::info!("==== Console logs: ");
-        for call in &tx_result.call_traces {
-            inner.console_log_handler.handle_call_recurive(call);
-        }
+    /// Uninstalls a filter with the given id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the filter to uninstall.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to `true` if the filter
+    /// was found and removed, `false` otherwise.
+    fn uninstall_filter(&self, id: U256) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
+        let inner = Arc::clone(&self.inner);
 
-        log::info!("");
-        log::info!(
-            "==== {} Use --show-calls flag or call config_setShowCalls to display more info.",
-            format!("{:?} call traces. ", tx_result.call_traces.len()).bold()
-        );
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
 
-        if inner.show_calls != ShowCalls::None {
-            for call in &tx_result.call_traces {
-                formatter::print_call(call, 0, &inner.show_calls, inner.resolve_hashes);
-            }
-        }
+            Ok(writer.filters.remove_filter(id))
+        })
+    }
 
-        log::info!("");
-        log::info!(
-            "==== {}",
-            format!("{} events", tx_result.result.logs.events.len()).bold()
-        );
-        for event in &tx_result.result.logs.events {
-            formatter::print_event(event, inner.resolve_hashes);
-        }
+    /// Installs a pending transaction filter, to be polled for the hashes of new transactions
+    /// with `eth_getFilterChanges`.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the id of the new filter.
+    fn new_pending_transaction_filter(
+        &self,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        let inner = Arc::clone(&self.inner);
 
-        // Compute gas details
-        let debug = BootloaderDebug::load_from_memory(&vm).map_err(|err| err.to_string())?;
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
 
-        // Total amount of gas (should match tx.gas_limit).
-        let gas_limit = debug
-            .total_gas_limit_from_user
-            .saturating_sub(debug.reserved_gas);
+            Ok(writer.filters.add_pending_transaction_filter())
+        })
+    }
 
-        let intrinsic_gas = gas_limit - debug.gas_limit_after_intrinsic;
-        let gas_for_validation = debug.gas_limit_after_intrinsic - debug.gas_after_validation;
+    /// Returns the logs of the locally produced blocks that match the given filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The filter object with the block range, addresses and topics to match.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the matching logs,
+    /// ordered by block number and log index.
+    fn get_logs(
+        &self,
+        filter: Filter,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_types::api::Log>>> {
+        let inner = Arc::clone(&self.inner);
 
-        let gas_spent_on_compute =
-            debug.gas_spent_on_execution - debug.gas_spent_on_bytecode_preparation;
+        Box::pin(async move {
+            let reader = match inner.read() {
+                Ok(r) => r,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
 
-        let gas_used = intrinsic_gas
-            + gas_for_validation
-            + debug.gas_spent_on_bytecode_preparation
-            + gas_spent_on_compute;
+            Ok(reader.get_logs(&LogFilter::new(filter)))
+        })
+    }
 
-        // The computed block hash here will be different than that in production.
-        let hash = compute_hash(block_context.block_number, l2_tx.hash());
-        let block = Block {
-            hash,
-            number: U64::from(inner.current_miniblock.saturating_add(1)),
-            timestamp: U256::from(block_context.block_timestamp),
-            l1_batch_number: Some(U64::from(block_context.block_number)),
-            transactions: vec![TransactionVariant::Full(
-                zksync_types::api::Transaction::from(l2_tx),
-            )],
-            gas_used,
-            gas_limit,
-            ..Default::default()
-        };
+    /// Returns all the logs matching an installed log filter, whether they were already polled
+    /// with `eth_getFilterChanges` or not. The pending changes of the filter are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter_index` - The id of the log filter.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the matching logs,
+    /// or an error if the filter does not exist or is not a log filter.
+    fn get_filter_logs(
+        &self,
+        filter_index: U256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FilterChanges>> {
+        let inner = Arc::clone(&self.inner);
 
-        log::info!("");
-        log::info!("");
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
 
-        vm.execute_till_block_end(BootloaderJobType::BlockPostprocessing);
+            let log_filter = writer
+                .filters
+                .get_log_filter(filter_index)
+                .ok_or_else(|| into_jsrpc_error(Web3Error::FilterNotFound))?;
+            Ok(FilterChanges::Logs(writer.get_logs(&log_filter)))
+        })
+    }
 
-        let bytecodes = vm
-            .state
-            .decommittment_processor
-            .known_bytecodes
-            .inner()
-            .clone();
+    /// Returns the changes of a filter since it was last polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter_index` - The id of the filter to poll.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the logs (for log filters),
+    /// or block/transaction hashes (for block and pending transaction filters) produced since the
+    /// last poll, or an error if the filter does not exist.
+    fn get_filter_changes(
+        &self,
+        filter_index: U256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FilterChanges>> {
+        let inner = Arc::clone(&self.inner);
 
-        let modified_keys = storage_view.modified_storage_keys().clone();
-        Ok((modified_keys, tx_result, block, bytecodes))
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
+
+            writer
+                .filters
+                .get_new_changes(filter_index)
+                .ok_or_else(|| into_jsrpc_error(Web3Error::FilterNotFound))
+        })
     }
 
-    /// Runs L2 transaction and commits it to a new block.
-    fn run_l2_tx(&self, l2_tx: L2Tx, execution_mode: TxExecutionMode) -> Result<(), String> {
-        let tx_hash = l2_tx.hash();
-        log::info!("");
-        log::info!("Executing {}", format!("{:?}", tx_hash).bold());
-        let (keys, result, block, bytecodes) =
-            self.run_l2_tx_inner(l2_tx.clone(), execution_mode)?;
-        // Write all the mutated keys (storage slots).
-        let mut inner = self
-            .inner
-            .write()
-            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
-        for (key, value) in keys.iter() {
-            inner.fork_storage.set_value(*key, *value);
-        }
+    fn get_block_transaction_count_by_number(
+        &self,
+        block_number: zksync_types::api::BlockNumber,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
+        let inner = Arc::clone(&self.inner);
 
-        // Write all the factory deps.
-        for (hash, code) in bytecodes.iter() {
-            inner.fork_storage.store_factory_dep(
-                u256_to_h256(*hash),
-                code.iter()
-                    .flat_map(|entry| {
-                        let mut bytes = vec![0u8; 32];
-                        entry.to_big_endian(&mut bytes);
-                        bytes.to_vec()
-                    })
-                    .collect(),
-            )
-        }
-        let current_miniblock = inner.current_miniblock.saturating_add(1);
-        inner.tx_results.insert(
-            tx_hash,
-            TxExecutionInfo {
-                tx: l2_tx,
-                batch_number: block.l1_batch_number.unwrap_or_default().as_u32(),
-                miniblock_number: current_miniblock,
-                result,
-            },
-        );
-        inner.block_hashes.insert(current_miniblock, block.hash);
-        inner.blocks.insert(block.hash, block);
-        {
-            inner.current_timestamp += 1;
-            inner.current_batch += 1;
-            inner.current_miniblock = current_miniblock;
-        }
+        Box::pin(async move {
+            let reader = match inner.read() {
+                Ok(r) => r,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
+            let number = reader.resolve_block_number(block_number);
 
-        Ok(())
+            // try retrieving block from memory, and if unavailable subsequently from the fork
+            let maybe_result = reader
+                .block_hashes
+                .get(&number)
+                .and_then(|hash| reader.blocks.get(hash))
+                .map(|block| U256::from(block.transactions.len()))
+                .or_else(|| {
+                    reader
+                        .fork_storage
+                        .inner
+                        .read()
+                        .expect("failed reading fork storage")
+                        .fork
+                        .as_ref()
+                        .and_then(|fork| {
+                            fork.fork_source
+                                .get_block_transaction_count_by_number(block_number)
+                                .ok()
+                                .flatten()
+                        })
+                });
+
+            Ok(maybe_result)
+        })
     }
-}
 
-impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EthNamespaceT for InMemoryNode<S> {
-    /// Returns the chain ID of the node.
-    fn chain_id(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::U64>> {
-        match self.inner.read() {
-            Ok(inner) => Ok(U64::from(inner.fork_storage.chain_id.0 as u64)).into_boxed_future(),
-            Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)).into_boxed_future(),
-        }
+    fn get_block_transaction_count_by_hash(
+        &self,
+        block_hash: zksync_basic_types::H256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+
+            // try retrieving block from memory, and if unavailable subsequently from the fork
+            let maybe_result = reader
+                .blocks
+                .get(&block_hash)
+                .map(|block| U256::from(block.transactions.len()))
+                .or_else(|| {
+                    reader
+                        .fork_storage
+                        .inner
+                        .read()
+                        .expect("failed reading fork storage")
+                        .fork
+                        .as_ref()
+                        .and_then(|fork| {
+                            fork.fork_source
+                                .get_block_transaction_count_by_hash(block_hash)
+                                .ok()
+                                .flatten()
+                        })
+                });
+
+            Ok(maybe_result)
+        })
     }
 
-    /// Calls the specified function on the L2 contract with the given arguments.
+    /// Returns the value stored at the given storage slot of an address.
+    /// Slots that were never written locally are read from the forked network (if any).
     ///
     /// # Arguments
     ///
-    /// * `req` - The call request containing the function name and arguments.
-    /// * `_block` - The block ID variant (unused).
+    /// * `address` - The address of the contract to read the storage of.
+    /// * `idx` - The index of the storage slot.
+    /// * `block` - Optional block ID variant, only the latest block is supported.
     ///
     /// # Returns
     ///
-    /// A boxed future containing the result of the function call.
-    fn call(
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to the 32-byte value of the slot (zero if untouched).
+    fn get_storage(
         &self,
-        req: zksync_types::transaction_request::CallRequest,
-        _block: Option<zksync_types::api::BlockIdVariant>,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Bytes>> {
-        match L2Tx::from_request(req.into(), MAX_TX_SIZE) {
-            Ok(mut tx) => {
-                tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
-                let result = self.run_l2_call(tx);
+        address: zksync_basic_types::Address,
+        idx: U256,
+        block: Option<zksync_types::api::BlockIdVariant>,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::H256>> {
+        let inner = Arc::clone(&self.inner);
 
-                match result {
-                    Ok(vm_block_result) => match vm_block_result.full_result.revert_reason {
-                        Some(revert) => {
-                            let message = revert.revert_reason.to_string();
-                            let pretty_message = format!(
-                                "execution reverted{}{}",
-                                if message.is_empty() { "" } else { ": " },
-                                message
-                            );
-                            let data = match revert.revert_reason {
-                                TxRevertReason::EthCall(vm_revert_reason) => {
-                                    vm_revert_reason.encoded_data()
-                                }
-                                TxRevertReason::TxReverted(vm_revert_reason) => {
-                                    vm_revert_reason.encoded_data()
-                                }
-                                _ => vec![],
-                            };
-                            log::info!("{}", pretty_message.on_red());
-                            Err(into_jsrpc_error(Web3Error::SubmitTransactionError(
-                                pretty_message,
-                                data,
-                            )))
-                            .into_boxed_future()
-                        }
-                        None => Ok(vm_block_result
-                            .full_result
-                            .return_data
-                            .into_iter()
-                            .flat_map(|val| {
-                                let bytes: [u8; 32] = val.into();
-                                bytes.to_vec()
-                            })
-                            .collect::<Vec<_>>()
-                            .into())
-                        .into_boxed_future(),
-                    },
-                    Err(e) => {
-                        let error = Web3Error::InvalidTransactionData(
-                            zksync_types::ethabi::Error::InvalidName(e),
-                        );
-                        Err(into_jsrpc_error(error)).into_boxed_future()
-                    }
+        Box::pin(async move {
+            let mut writer = match inner.write() {
+                Ok(w) => w,
+                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
+            };
+
+            let is_latest = match block.map(BlockId::from) {
+                None => true,
+                Some(BlockId::Number(BlockNumber::Number(number))) => {
+                    number.as_u64() == writer.current_miniblock
                 }
+                Some(BlockId::Number(BlockNumber::Earliest)) => {
+                    writer.earliest_block() == writer.current_miniblock
+                }
+                Some(BlockId::Number(_)) => true,
+                Some(BlockId::Hash(hash)) => {
+                    writer.block_hashes.get(&writer.current_miniblock) == Some(&hash)
+                }
+            };
+            if !is_latest {
+                return Err(jsonrpc_core::Error::invalid_params(
+                    "storage is only available for the latest block",
+                ));
             }
-            Err(e) => {
-                let error = Web3Error::SerializationError(e);
-                Err(into_jsrpc_error(error)).into_boxed_future()
-            }
-        }
+
+            let storage_key = StorageKey::new(AccountTreeId::new(address), u256_to_h256(idx));
+            Ok(writer.fork_storage.read_value(&storage_key))
+        })
     }
 
-    /// Returns the balance of the specified address.
+    /// Returns information about a transaction by block hash and transaction index position.
     ///
     /// # Arguments
     ///
-    /// * `address` - The address to get the balance of.
-    /// * `_block` - The block ID variant (optional).
+    /// * `block_hash` - The hash of the block containing the transaction.
+    /// * `index` - The position of the transaction in the block.
     ///
     /// # Returns
     ///
-    /// A `BoxFuture` that resolves to a `Result` containing the balance of the specified address as a `U256` or a `jsonrpc_core::Error` if an error occurred.
-    fn get_balance(
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of the transaction (`None` for unknown blocks or out of range indices).
+    fn get_transaction_by_block_hash_and_index(
         &self,
-        address: zksync_basic_types::Address,
-        _block: Option<zksync_types::api::BlockIdVariant>,
-    ) -> BoxFuture<Result<U256, jsonrpc_core::Error>> {
+        block_hash: zksync_basic_types::H256,
+        index: zksync_basic_types::web3::types::Index,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
         let inner = Arc::clone(&self.inner);
 
         Box::pin(async move {
-            let balance_key = storage_key_for_standard_token_balance(
-                AccountTreeId::new(L2_ETH_TOKEN_ADDRESS),
-                &address,
-            );
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
 
-            match inner.write() {
-                Ok(mut inner_guard) => {
-                    let balance = inner_guard.fork_storage.read_value(&balance_key);
-                    Ok(h256_to_u256(balance))
-                }
-                Err(_) => {
-                    let web3_error = Web3Error::InternalError;
-                    Err(into_jsrpc_error(web3_error))
-                }
-            }
+            // try retrieving block from memory, and if unavailable subsequently from the fork
+            let maybe_tx = match reader.blocks.get(&block_hash) {
+                Some(block) => transaction_at_index(block, index),
+                None => reader
+                    .fork_storage
+                    .inner
+                    .read()
+                    .expect("failed reading fork storage")
+                    .fork
+                    .as_ref()
+                    .and_then(|fork| {
+                        fork.fork_source
+                            .get_transaction_by_block_hash_and_index(block_hash, index)
+                            .ok()
+                            .flatten()
+                    }),
+            };
+
+            Ok(maybe_tx)
         })
     }
 
-    /// Returns a block by its number.
+    /// Returns information about a transaction by block number and transaction index position.
     ///
     /// # Arguments
     ///
-    /// * `block_number` - A `BlockNumber` enum variant representing the block number to retrieve.
-    /// * `full_transactions` - A boolean value indicating whether to retrieve full transactions or not.
+    /// * `block_number` - The number of the block containing the transaction.
+    /// * `index` - The position of the transaction in the block.
     ///
     /// # Returns
     ///
-    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of `zksync_types::api::Block<zksync_types::api::TransactionVariant>`.
-    fn get_block_by_number(
+    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of the transaction (`None` for unknown blocks or out of range indices).
+    fn get_transaction_by_block_number_and_index(
         &self,
         block_number: zksync_types::api::BlockNumber,
-        full_transactions: bool,
-    ) -> BoxFuture<
-        jsonrpc_core::Result<
-            Option<zksync_types::api::Block<zksync_types::api::TransactionVariant>>,
-        >,
-    > {
+        index: zksync_basic_types::web3::types::Index,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
         let inner = Arc::clone(&self.inner);
 
         Box::pin(async move {
-            let maybe_block = {
-                let reader = match inner.read() {
-                    Ok(r) => r,
-                    Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
-                };
-                match block_number {
-                    zksync_types::api::BlockNumber::Latest
-                    | zksync_types::api::BlockNumber::Pending
-                    | zksync_types::api::BlockNumber::Finalized
-                    | zksync_types::api::BlockNumber::Committed => reader
-                        .block_hashes
-                        .get(&reader.current_miniblock)
-                        .and_then(|hash| reader.blocks.get(hash))
-                        .cloned()
-                        .or_else(|| {
-                            reader
-                                .fork_storage
-                                .inner
-                                .read()
-                                .expect("failed reading fork storage")
-                                .fork
-                                .as_ref()
-                                .and_then(|fork| {
-                                    fork.fork_source
-                                        .get_block_by_number(block_number, true)
-                                        .ok()
-                                        .flatten()
-                                })
-                        }),
-                    zksync_types::api::BlockNumber::Number(ask_number) => {
-                        let block = reader
-                            .block_hashes
-                            .get(&ask_number.as_u64())
-                            .and_then(|hash| reader.blocks.get(hash))
-                            .cloned()
-                            .or_else(|| {
-                                reader
-                                    .fork_storage
-                                    .inner
-                                    .read()
-                                    .expect("failed reading fork storage")
-                                    .fork
-                                    .as_ref()
-                                    .and_then(|fork| {
-                                        fork.fork_source
-                                            .get_block_by_number(block_number, true)
-                                            .ok()
-                                            .flatten()
-                                    })
-                            });
-                        block
-                    }
-                    zksync_types::api::BlockNumber::Earliest => reader
-                        .block_hashes
-                        .get(&0)
-                        .and_then(|hash| reader.blocks.get(hash))
-                        .cloned()
-                        .or_else(|| {
-                            reader
-                                .fork_storage
-                                .inner
-                                .read()
-                                .expect("failed reading fork storage")
-                                .fork
-                                .as_ref()
-                                .and_then(|fork| {
-                                    fork.fork_source
-                                        .get_block_by_number(block_number, true)
-                                        .ok()
-                                        .flatten()
-                                })
-                        }),
-                }
-            };
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            let number = reader.resolve_block_number(block_number);
 
-            match maybe_block {
-                Some(mut block) => {
-                    let block_hash = block.hash;
-                    block.transactions = block
-                        .transactions
-                        .into_iter()
-                        .map(|transaction| match &transaction {
-                            TransactionVariant::Full(inner) => {
-                                if full_transactions {
-                                    transaction
-                                } else {
-                                    TransactionVariant::Hash(inner.hash)
-                                }
-                            }
-                            TransactionVariant::Hash(_) => {
-                                if full_transactions {
-                                    panic!(
-                                        "unexpected non full transaction for block {}",
-                                        block_hash
-                                    )
-                                } else {
-                                    transaction
-                                }
-                            }
-                        })
-                        .collect();
+            // try retrieving block from memory, and if unavailable subsequently from the fork
+            let maybe_tx = match reader
+                .block_hashes
+                .get(&number)
+                .and_then(|hash| reader.blocks.get(hash))
+            {
+                Some(block) => transaction_at_index(block, index),
+                None => reader
+                    .fork_storage
+                    .inner
+                    .read()
+                    .expect("failed reading fork storage")
+                    .fork
+                    .as_ref()
+                    .and_then(|fork| {
+                        fork.fork_source
+                            .get_transaction_by_block_number_and_index(block_number, index)
+                            .ok()
+                            .flatten()
+                    }),
+            };
 
-                    Ok(Some(block))
-                }
-                None => Err(into_jsrpc_error(Web3Error::NoBlock)),
-            }
+            Ok(maybe_tx)
         })
     }
 
-    /// Returns the code stored at the specified address.
+    fn protocol_version(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<String>> {
+        not_implemented("protocol_version")
+    }
+
+    fn syncing(
+        &self,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::web3::types::SyncState>>
+    {
+        Ok(zksync_basic_types::web3::types::SyncState::NotSyncing).into_boxed_future()
+    }
+
+    fn accounts(
+        &self,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_basic_types::Address>>> {
+        not_implemented("accounts")
+    }
+
+    fn coinbase(
+        &self,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Address>> {
+        not_implemented("coinbase")
+    }
+
+    fn compilers(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<String>>> {
+        not_implemented("compilers")
+    }
+
+    fn hashrate(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
+        not_implemented("hashrate")
+    }
+
+    fn get_uncle_count_by_block_hash(
+        &self,
+        _hash: zksync_basic_types::H256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
+        not_implemented("get_uncle_count_by_block_hash")
+    }
+
+    fn get_uncle_count_by_block_number(
+        &self,
+        _number: zksync_types::api::BlockNumber,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
+        not_implemented("get_uncle_count_by_block_number")
+    }
+
+    fn mining(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
+        not_implemented("mining")
+    }
+
+    /// Returns the fee history for a given range of blocks.
+    ///
+    /// Note: This implementation is limited to using the current L2 gas price
+    /// as the history gas price
     ///
     /// # Arguments
     ///
-    /// * `address` - The address to retrieve the code from.
-    /// * `_block` - An optional block ID variant.
+    /// * `block_count` - The number of blocks in the requested range. Between 1 and 1024 blocks can be requested in a single query. It will return less than the requested range if not all blocks are available.
+    /// * `newest_block` - The highest number block of the requested range. Must not be beyond the latest block.
+    /// * `reward_percentiles` - A list of percentile values between 0 and 100 with a monotonic increase in value.
     ///
     /// # Returns
     ///
-    /// A `BoxFuture` containing the result of the operation, which is a `jsonrpc_core::Result` containing
-    /// the code as a `zksync_basic_types::Bytes` object.
-    fn get_code(
+    /// A `BoxFuture` containing a `Result` with a `FeeHistory` representing the fee history of the specified range of blocks.
+    fn fee_history(
         &self,
-        address: zksync_basic_types::Address,
-        _block: Option<zksync_types::api::BlockIdVariant>,
-    ) -> BoxFuture<jsonrpc_core::Result<zksync_basic_types::Bytes>> {
+        block_count: U64,
+        newest_block: zksync_types::api::BlockNumber,
+        reward_percentiles: Vec<f32>,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FeeHistory>> {
         let inner = Arc::clone(&self.inner);
 
         Box::pin(async move {
-            let code_key = get_code_key(&address);
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
 
-            match inner.write() {
-                Ok(mut guard) => {
-                    let code_hash = guard.fork_storage.read_value(&code_key);
+            let mut previous_percentile = 0.0;
+            for percentile in &reward_percentiles {
+                if !(0.0..=100.0).contains(percentile) || *percentile < previous_percentile {
+                    return Err(jsonrpc_core::Error::invalid_params(format!(
+                        "invalid reward percentile: {}",
+                        percentile
+                    )));
+                }
+                previous_percentile = *percentile;
+            }
 
-                    let code = guard
-                        .fork_storage
-                        .load_factory_dep_internal(code_hash)
-                        .unwrap_or_default();
+            let newest_block = reader.resolve_block_number(newest_block);
+            if newest_block > reader.current_miniblock {
+                return Err(jsonrpc_core::Error::invalid_params(format!(
+                    "request beyond head block: requested {}, head {}",
+                    newest_block, reader.current_miniblock
+                )));
+            }
 
-                    Ok(Bytes::from(code))
-                }
-                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
+            let block_count = block_count
+                .as_u64()
+                .min(1024)
+                // Can't be more than the total number of blocks up to `newest_block`
+                .min(newest_block + 1);
+            if block_count == 0 {
+                return Ok(FeeHistory {
+                    oldest_block: web3::types::BlockNumber::Number(U64::zero()),
+                    base_fee_per_gas: vec![],
+                    gas_used_ratio: vec![],
+                    reward: None,
+                });
             }
+
+            let mut base_fee_per_gas = vec![U256::from(reader.l2_gas_price); block_count as usize];
+
+            let oldest_block = newest_block + 1 - base_fee_per_gas.len() as u64;
+            // We do not store gas used ratio for blocks, returns array of zeroes as a placeholder.
+            let gas_used_ratio = vec![0.0; base_fee_per_gas.len()];
+            // Effective priority gas price is currently 0.
+            let reward = Some(vec![
+                vec![U256::zero(); reward_percentiles.len()];
+                base_fee_per_gas.len()
+            ]);
+
+            // `base_fee_per_gas` for next miniblock cannot be calculated, appending last fee as a placeholder.
+            base_fee_per_gas.push(*base_fee_per_gas.last().unwrap());
+
+            Ok(FeeHistory {
+                oldest_block: web3::types::BlockNumber::Number(oldest_block.into()),
+                base_fee_per_gas,
+                gas_used_ratio,
+                reward,
+            })
         })
     }
+}
 
-    /// Returns the transaction count for a given address.
-    ///
-    /// # Arguments
-    ///
-    /// * `address` - The address to get the transaction count for.
-    /// * `_block` - Optional block ID variant.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `BoxFuture` containing the transaction count as a `U256` wrapped in a `jsonrpc_core::Result`.
-    fn get_transaction_count(
-        &self,
-        address: zksync_basic_types::Address,
-        _block: Option<zksync_types::api::BlockIdVariant>,
-    ) -> BoxFuture<jsonrpc_core::Result<U256>> {
-        let inner = Arc::clone(&self.inner);
+#[cfg(test)]
+mod tests {
+    use crate::{
+        http_fork_source::HttpForkSource,
+        node::InMemoryNode,
+        testing::{self, ForkBlockConfig, MockServer},
+    };
+    use zksync_types::api::BlockNumber;
+    use zksync_web3_decl::types::SyncState;
+
+    use super::*;
+
+    #[test]
+    fn test_revert_error_keeps_revert_data() {
+        // Error(string) with "nope"
+        let encoded = hex::decode(
+            "08c379a0\
+             0000000000000000000000000000000000000000000000000000000000000020\
+             0000000000000000000000000000000000000000000000000000000000000004\
+             6e6f706500000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        let error = revert_error(&TxRevertReason::EthCall(VmRevertReason::General {
+            msg: String::from("nope"),
+            data: encoded.clone(),
+        }));
+        assert_eq!(jsonrpc_core::ErrorCode::ServerError(3), error.code);
+        assert_eq!("execution reverted: nope", error.message);
+        assert_eq!(
+            Some(serde_json::Value::String(format!(
+                "0x{}",
+                hex::encode(&encoded)
+            ))),
+            error.data
+        );
+
+        // custom error `Unauthorized(address)`
+        let encoded = hex::decode(
+            "8e4a23d6\
+             0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let error = revert_error(&TxRevertReason::TxReverted(VmRevertReason::Unknown {
+            function_selector: encoded[..4].to_vec(),
+            data: encoded.clone(),
+        }));
+        assert_eq!(jsonrpc_core::ErrorCode::ServerError(3), error.code);
+        assert_eq!("execution reverted", error.message);
+        assert_eq!(
+            Some(serde_json::Value::String(format!(
+                "0x{}",
+                hex::encode(&encoded)
+            ))),
+            error.data
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eth_syncing() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let syncing = node.syncing().await.expect("failed syncing");
+        assert!(matches!(syncing, SyncState::NotSyncing));
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_with_1_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let fee_history = node
+            .fee_history(U64::from(1), BlockNumber::Latest, vec![25.0, 50.0, 75.0])
+            .await
+            .expect("fee_history failed");
+
+        assert_eq!(
+            fee_history.oldest_block,
+            web3::types::BlockNumber::Number(U64::from(0))
+        );
+        assert_eq!(
+            fee_history.base_fee_per_gas,
+            vec![U256::from(L2_GAS_PRICE); 2]
+        );
+        assert_eq!(fee_history.gas_used_ratio, vec![0.0]);
+        assert_eq!(fee_history.reward, Some(vec![vec![U256::from(0); 3]]));
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_with_no_reward_percentiles() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let fee_history = node
+            .fee_history(U64::from(1), BlockNumber::Latest, vec![])
+            .await
+            .expect("fee_history failed");
+
+        assert_eq!(
+            fee_history.oldest_block,
+            web3::types::BlockNumber::Number(U64::from(0))
+        );
+        assert_eq!(
+            fee_history.base_fee_per_gas,
+            vec![U256::from(L2_GAS_PRICE); 2]
+        );
+        assert_eq!(fee_history.gas_used_ratio, vec![0.0]);
+        assert_eq!(fee_history.reward, Some(vec![vec![]]));
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_with_multiple_blocks() {
+        // Arrange
+        let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        // Act
+        let fee_history = node
+            .fee_history(U64::from(2), BlockNumber::Latest, vec![25.0, 50.0, 75.0])
+            .await
+            .expect("fee_history failed");
+
+        // Assert
+        assert_eq!(
+            fee_history.oldest_block,
+            web3::types::BlockNumber::Number(U64::from(0))
+        );
+        assert_eq!(
+            fee_history.base_fee_per_gas,
+            vec![U256::from(L2_GAS_PRICE); 3]
+        );
+        assert_eq!(fee_history.gas_used_ratio, vec![0.0, 0.0]);
+        assert_eq!(fee_history.reward, Some(vec![vec![U256::from(0); 3]; 2]));
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_ends_at_newest_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        let fee_history = node
+            .fee_history(U64::from(10), BlockNumber::Number(U64::from(1)), vec![50.0])
+            .await
+            .expect("fee_history failed");
+
+        assert_eq!(
+            fee_history.oldest_block,
+            web3::types::BlockNumber::Number(U64::from(0))
+        );
+        assert_eq!(
+            fee_history.base_fee_per_gas,
+            vec![U256::from(L2_GAS_PRICE); 3]
+        );
+        assert_eq!(fee_history.gas_used_ratio, vec![0.0, 0.0]);
+        assert_eq!(fee_history.reward, Some(vec![vec![U256::from(0)]; 2]));
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_with_0_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let fee_history = node
+            .fee_history(U64::from(0), BlockNumber::Latest, vec![50.0])
+            .await
+            .expect("fee_history failed");
+
+        assert!(fee_history.base_fee_per_gas.is_empty());
+        assert!(fee_history.gas_used_ratio.is_empty());
+        assert_eq!(fee_history.reward, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_history_rejects_invalid_requests() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let beyond_head = node
+            .fee_history(U64::from(1), BlockNumber::Number(U64::from(5)), vec![])
+            .await;
+        assert!(beyond_head.is_err());
+
+        for percentiles in [vec![101.0], vec![-1.0], vec![50.0, 25.0]] {
+            let result = node
+                .fee_history(U64::from(1), BlockNumber::Latest, percentiles.clone())
+                .await;
+            assert!(result.is_err(), "accepted percentiles {:?}", percentiles);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_block_by_hash_returns_none_for_non_existing_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let result = node
+            .get_block_by_hash(H256::repeat_byte(0x01), false)
+            .await
+            .expect("failed fetching block by hash");
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_node_run_has_genesis_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+
+        let block = node
+            .get_block_by_number(BlockNumber::Latest, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+
+        assert_eq!(0, block.number.as_u64());
+        assert_eq!(H256::zero(), block.hash);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_by_hash_for_produced_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let expected_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        let actual_block = node
+            .get_block_by_hash(expected_block_hash, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+
+        assert_eq!(expected_block_hash, actual_block.hash);
+        assert_eq!(U64::from(1), actual_block.number);
+        assert_eq!(Some(U64::from(1)), actual_block.l1_batch_number);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_by_hash_honors_full_transactions() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        let block_hash = testing::apply_tx(&node, tx_hash);
+
+        let block_with_hashes = node
+            .get_block_by_hash(block_hash, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+        let block_with_txs = node
+            .get_block_by_hash(block_hash, true)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+
+        match block_with_hashes.transactions.as_slice() {
+            [TransactionVariant::Hash(hash)] => assert_eq!(tx_hash, *hash),
+            _ => panic!("expected a single transaction hash"),
+        }
+        match block_with_txs.transactions.as_slice() {
+            [TransactionVariant::Full(tx)] => assert_eq!(tx_hash, tx.hash),
+            _ => panic!("expected a single full transaction"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_block_mapping_is_correctly_populated_when_using_fork_source() {
+        let input_block_number = 8;
+        let input_block_hash = H256::repeat_byte(0x01);
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: input_block_number,
+            hash: input_block_hash,
+            transaction_count: 0,
+        });
 
-        Box::pin(async move {
-            let nonce_key = get_nonce_key(&address);
+        let node = testing::forked_node(&mock_server).await;
 
-            match inner.write() {
-                Ok(mut guard) => {
-                    let result = guard.fork_storage.read_value(&nonce_key);
-                    Ok(h256_to_u64(result).into())
-                }
-                Err(_) => Err(into_jsrpc_error(Web3Error::InternalError)),
-            }
-        })
+        let inner = node.inner.read().unwrap();
+        assert!(
+            inner.blocks.contains_key(&input_block_hash),
+            "block wasn't cached"
+        );
+        assert!(
+            inner.block_hashes.contains_key(&input_block_number),
+            "block number wasn't cached"
+        );
     }
 
-    /// Retrieves the transaction receipt for a given transaction hash.
-    ///
-    /// # Arguments
-    ///
-    /// * `hash` - The hash of the transaction to retrieve the receipt for.
-    ///
-    /// # Returns
-    ///
-    /// A `BoxFuture` that resolves to an `Option` of a `TransactionReceipt` or an error.
-    fn get_transaction_receipt(
-        &self,
-        hash: zksync_basic_types::H256,
-    ) -> BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::TransactionReceipt>>> {
-        let inner = Arc::clone(&self.inner);
+    #[tokio::test]
+    async fn test_get_block_by_hash_uses_fork_source() {
+        let input_block_hash = H256::repeat_byte(0x01);
 
-        Box::pin(async move {
-            let reader = match inner.read() {
-                Ok(r) => r,
-                Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
-            };
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let mock_block_number = 8;
+        let block_response = testing::BlockResponseBuilder::new()
+            .set_hash(input_block_hash)
+            .set_number(mock_block_number)
+            .build();
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockByHash",
+                "params": [
+                    format!("{input_block_hash:#x}"),
+                    true
+                ],
+            }),
+            block_response,
+        );
+        let node = testing::forked_node(&mock_server).await;
 
-            let tx_result = reader.tx_results.get(&hash);
-
-            let receipt = tx_result.map(|info| TransactionReceipt {
-                transaction_hash: hash,
-                transaction_index: U64::from(1),
-                block_hash: reader.block_hashes.get(&info.miniblock_number).cloned(),
-                block_number: Some(U64::from(info.miniblock_number)),
-                l1_batch_tx_index: None,
-                l1_batch_number: Some(U64::from(info.batch_number as u64)),
-                from: Default::default(),
-                to: Some(info.tx.execute.contract_address),
-                cumulative_gas_used: Default::default(),
-                gas_used: Some(info.tx.common_data.fee.gas_limit - info.result.gas_refunded),
-                contract_address: contract_address_from_tx_result(&info.result),
-                logs: info
-                    .result
-                    .result
-                    .logs
-                    .events
-                    .iter()
-                    .map(|log| Log {
-                        address: log.address,
-                        topics: log.indexed_topics.clone(),
-                        data: zksync_types::Bytes(log.value.clone()),
-                        block_hash: Some(hash),
-                        block_number: Some(U64::from(info.miniblock_number)),
-                        l1_batch_number: Some(U64::from(info.batch_number as u64)),
-                        transaction_hash: Some(hash),
-                        transaction_index: Some(U64::from(1)),
-                        log_index: Some(U256::default()),
-                        transaction_log_index: Some(U256::default()),
-                        log_type: None,
-                        removed: None,
-                    })
-                    .collect(),
-                l2_to_l1_logs: vec![],
-                status: Some(if info.result.status == TxExecutionStatus::Success {
-                    U64::from(1)
-                } else {
-                    U64::from(0)
-                }),
-                effective_gas_price: Some(L2_GAS_PRICE.into()),
-                ..Default::default()
-            });
+        let actual_block = node
+            .get_block_by_hash(input_block_hash, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
 
-            Ok(receipt).map_err(|_: jsonrpc_core::Error| into_jsrpc_error(Web3Error::InternalError))
-        })
+        assert_eq!(input_block_hash, actual_block.hash);
+        assert_eq!(U64::from(mock_block_number), actual_block.number);
+        assert_eq!(Some(U64::from(6)), actual_block.l1_batch_number);
     }
 
-    /// Sends a raw transaction to the L2 network.
-    ///
-    /// # Arguments
-    ///
-    /// * `tx_bytes` - The transaction bytes to send.
-    ///
-    /// # Returns
-    ///
-    /// A future that resolves to the hash of the transaction if successful, or an error if the transaction is invalid or execution fails.
-    fn send_raw_transaction(
-        &self,
-        tx_bytes: zksync_basic_types::Bytes,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::H256>> {
-        let chain_id = match self.inner.read() {
-            Ok(reader) => reader.fork_storage.chain_id,
-            Err(_) => {
-                return futures::future::err(into_jsrpc_error(Web3Error::InternalError)).boxed()
-            }
-        };
-
-        let (tx_req, hash) = match TransactionRequest::from_bytes(&tx_bytes.0, chain_id.0) {
-            Ok(result) => result,
-            Err(e) => {
-                return futures::future::err(into_jsrpc_error(Web3Error::SerializationError(e)))
-                    .boxed()
-            }
-        };
-
-        let mut l2_tx: L2Tx = match L2Tx::from_request(tx_req, MAX_TX_SIZE) {
-            Ok(tx) => tx,
-            Err(e) => {
-                return futures::future::err(into_jsrpc_error(Web3Error::SerializationError(e)))
-                    .boxed()
-            }
-        };
+    #[tokio::test]
+    async fn test_get_block_by_number_produces_no_block_error_for_non_existing_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
 
-        l2_tx.set_input(tx_bytes.0, hash);
-        if hash != l2_tx.hash() {
-            return futures::future::err(into_jsrpc_error(Web3Error::InvalidTransactionData(
-                zksync_types::ethabi::Error::InvalidData,
-            )))
-            .boxed();
-        };
+        let expected_err = into_jsrpc_error(Web3Error::NoBlock);
+        let result = node
+            .get_block_by_number(BlockNumber::Number(U64::from(42)), false)
+            .await;
 
-        match self.run_l2_tx(l2_tx.clone(), TxExecutionMode::VerifyExecute) {
-            Ok(_) => Ok(hash).into_boxed_future(),
-            Err(e) => {
-                let error_message = format!("Execution error: {}", e);
-                futures::future::err(into_jsrpc_error(Web3Error::SubmitTransactionError(
-                    error_message,
-                    l2_tx.hash().as_bytes().to_vec(),
-                )))
-                .boxed()
-            }
-        }
+        assert_eq!(expected_err, result.unwrap_err());
     }
 
-    /// Returns a block by its hash. Currently, only hashes for blocks in memory are supported.
-    ///
-    /// # Arguments
-    ///
-    /// * `hash` - A `H256` type representing the hash of the block to retrieve.
-    /// * `full_transactions` - A boolean value indicating whether to retrieve full transactions or not.
-    ///
-    /// # Returns
-    ///
-    /// A `BoxFuture` containing a `jsonrpc_core::Result` that resolves to an `Option` of `zksync_types::api::Block<zksync_types::api::TransactionVariant>`.
-    fn get_block_by_hash(
-        &self,
-        hash: zksync_basic_types::H256,
-        full_transactions: bool,
-    ) -> jsonrpc_core::BoxFuture<
-        jsonrpc_core::Result<
-            Option<zksync_types::api::Block<zksync_types::api::TransactionVariant>>,
-        >,
-    > {
-        let inner = Arc::clone(&self.inner);
+    #[tokio::test]
+    async fn test_get_block_by_number_for_produced_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let expected_block_number = 1;
 
-        Box::pin(async move {
-            let maybe_block = {
-                let reader = inner
-                    .read()
-                    .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+        let actual_block = node
+            .get_block_by_number(BlockNumber::Number(U64::from(expected_block_number)), false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
 
-                // try retrieving block from memory, and if unavailable subsequently from the fork
-                reader.blocks.get(&hash).cloned().or_else(|| {
-                    reader
-                        .fork_storage
-                        .inner
-                        .read()
-                        .expect("failed reading fork storage")
-                        .fork
-                        .as_ref()
-                        .and_then(|fork| {
-                            fork.fork_source
-                                .get_block_by_hash(hash, true)
-                                .ok()
-                                .flatten()
-                        })
-                })
-            };
+        assert_eq!(U64::from(expected_block_number), actual_block.number);
+        assert_eq!(1, actual_block.transactions.len());
+    }
 
-            match maybe_block {
-                Some(mut block) => {
-                    let block_hash = block.hash;
-                    block.transactions = block
-                        .transactions
-                        .into_iter()
-                        .map(|transaction| match &transaction {
-                            TransactionVariant::Full(inner) => {
-                                if full_transactions {
-                                    transaction
-                                } else {
-                                    TransactionVariant::Hash(inner.hash)
-                                }
-                            }
-                            TransactionVariant::Hash(_) => {
-                                if full_transactions {
-                                    panic!(
-                                        "unexpected non full transaction for block {}",
-                                        block_hash
-                                    )
-                                } else {
-                                    transaction
-                                }
-                            }
-                        })
-                        .collect();
+    #[tokio::test]
+    async fn test_get_block_by_number_returns_full_transactions() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        let block_hash = testing::apply_tx(&node, tx_hash);
 
-                    Ok(Some(block))
-                }
-                None => Err(into_jsrpc_error(Web3Error::NoBlock)),
+        let block = node
+            .get_block_by_number(BlockNumber::Number(U64::from(1)), true)
+            .await
+            .expect("failed fetching block by number")
+            .expect("no block");
+
+        match block.transactions.as_slice() {
+            [TransactionVariant::Full(tx)] => {
+                assert_eq!(tx_hash, tx.hash);
+                assert_eq!(Some(block_hash), tx.block_hash);
+                assert_eq!(Some(U64::from(1)), tx.block_number);
+                assert_eq!(Some(U64::zero()), tx.transaction_index);
+                assert!(tx.from.is_some());
+                assert!(tx.to.is_some());
             }
-        })
+            _ => panic!("expected a single full transaction"),
+        }
     }
 
-    /// Returns a future that resolves to an optional transaction with the given hash.
-    ///
-    /// # Arguments
-    ///
-    /// * `hash` - A 32-byte hash of the transaction.
-    ///
-    /// # Returns
-    ///
-    /// A `jsonrpc_core::BoxFuture` that resolves to a `jsonrpc_core::Result` containing an optional `zksync_types::api::Transaction`.
-    fn get_transaction_by_hash(
-        &self,
-        hash: zksync_basic_types::H256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
-        let inner = Arc::clone(&self.inner);
+    #[tokio::test]
+    async fn test_get_block_by_number_uses_fork_source_if_missing_number() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let mock_block_number = 8;
+        let block_response = testing::BlockResponseBuilder::new()
+            .set_number(mock_block_number)
+            .build();
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockByNumber",
+                "params": [
+                    "0x8",
+                    true
+                ],
+            }),
+            block_response,
+        );
+        let node = testing::forked_node(&mock_server).await;
 
-        Box::pin(async move {
-            let reader = inner
-                .read()
-                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
-            let tx_result = reader.tx_results.get(&hash);
-
-            Ok(tx_result.and_then(|info| {
-                let input_data = info.tx.common_data.input.clone().or(None)?;
-
-                let chain_id = info.tx.extract_chain_id().or(None)?;
-
-                Some(zksync_types::api::Transaction {
-                    hash,
-                    nonce: U256::from(info.tx.common_data.nonce.0),
-                    block_hash: Some(hash),
-                    block_number: Some(U64::from(info.miniblock_number)),
-                    transaction_index: Some(U64::from(1)),
-                    from: Some(info.tx.initiator_account()),
-                    to: Some(info.tx.recipient_account()),
-                    value: info.tx.execute.value,
-                    gas_price: Default::default(),
-                    gas: Default::default(),
-                    input: input_data.data.into(),
-                    v: Some(chain_id.into()),
-                    r: Some(U256::zero()),
-                    s: Some(U256::zero()),
-                    raw: None,
-                    transaction_type: {
-                        let tx_type = match info.tx.common_data.transaction_type {
-                            zksync_types::l2::TransactionType::LegacyTransaction => 0,
-                            zksync_types::l2::TransactionType::EIP2930Transaction => 1,
-                            zksync_types::l2::TransactionType::EIP1559Transaction => 2,
-                            zksync_types::l2::TransactionType::EIP712Transaction => 113,
-                            zksync_types::l2::TransactionType::PriorityOpTransaction => 255,
-                            zksync_types::l2::TransactionType::ProtocolUpgradeTransaction => 254,
-                        };
-                        Some(tx_type.into())
-                    },
-                    access_list: None,
-                    max_fee_per_gas: Some(info.tx.common_data.fee.max_fee_per_gas),
-                    max_priority_fee_per_gas: Some(
-                        info.tx.common_data.fee.max_priority_fee_per_gas,
-                    ),
-                    chain_id: chain_id.into(),
-                    l1_batch_number: Some(U64::from(info.batch_number as u64)),
-                    l1_batch_tx_index: None,
-                })
-            }))
-        })
+        let actual_block = node
+            .get_block_by_number(BlockNumber::Number(U64::from(8)), false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+        assert_eq!(U64::from(mock_block_number), actual_block.number);
     }
 
-    /// Returns the current block number as a `U64` wrapped in a `BoxFuture`.
-    fn get_block_number(
-        &self,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::U64>> {
-        let inner = Arc::clone(&self.inner);
+    #[tokio::test]
+    async fn test_get_block_by_number_for_latest_block_produced_locally() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let latest_block_number = 1;
 
-        Box::pin(async move {
-            let reader = inner
-                .read()
-                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
-            Ok(U64::from(reader.current_miniblock))
-        })
+        let actual_block = node
+            .get_block_by_number(BlockNumber::Latest, true)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+
+        assert_eq!(U64::from(latest_block_number), actual_block.number);
+        assert_eq!(1, actual_block.transactions.len());
     }
 
-    /// Estimates the gas required for a given call request.
-    ///
-    /// # Arguments
-    ///
-    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
-    /// * `_block` - An optional `BlockNumber` struct representing the block number to estimate gas for.
-    ///
-    /// # Returns
-    ///
-    /// A `BoxFuture` containing a `Result` with a `U256` representing the estimated gas required.
-    fn estimate_gas(
-        &self,
-        req: zksync_types::transaction_request::CallRequest,
-        _block: Option<zksync_types::api::BlockNumber>,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        let inner = Arc::clone(&self.inner);
-        let reader = match inner.read() {
-            Ok(r) => r,
-            Err(_) => {
-                return futures::future::err(into_jsrpc_error(Web3Error::InternalError)).boxed()
-            }
-        };
+    #[tokio::test]
+    async fn test_get_block_by_number_uses_locally_available_block_for_latest_block() {
+        let input_block_number = 10;
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: input_block_number,
+            hash: H256::repeat_byte(0x01),
+            transaction_count: 0,
+        });
 
-        let result: jsonrpc_core::Result<Fee> = reader.estimate_gas_impl(req);
-        match result {
-            Ok(fee) => Ok(fee.gas_limit).into_boxed_future(),
-            Err(err) => return futures::future::err(err).boxed(),
-        }
-    }
+        let node = testing::forked_node(&mock_server).await;
 
-    /// Returns the current gas price in U256 format.
-    fn gas_price(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        let fair_l2_gas_price: u64 = L2_GAS_PRICE;
-        Ok(U256::from(fair_l2_gas_price)).into_boxed_future()
+        let actual_block = node
+            .get_block_by_number(BlockNumber::Latest, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+        assert_eq!(U64::from(input_block_number), actual_block.number);
     }
 
-    // Methods below are not currently implemented.
+    #[tokio::test]
+    async fn test_get_block_by_number_returns_the_fork_block_for_earliest_block() {
+        let input_block_number = 10;
+        let input_block_hash = H256::repeat_byte(0xab);
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: input_block_number,
+            hash: input_block_hash,
+            transaction_count: 0,
+        });
+        let node = testing::forked_node(&mock_server).await;
 
-    fn new_filter(&self, _filter: Filter) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        not_implemented("new_filter")
+        let actual_block = node
+            .get_block_by_number(BlockNumber::Earliest, false)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no block");
+        assert_eq!(U64::from(input_block_number), actual_block.number);
+        assert_eq!(input_block_hash, actual_block.hash);
     }
 
-    fn new_block_filter(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        not_implemented("new_block_filter")
-    }
+    #[tokio::test]
+    async fn test_get_block_by_number_uses_locally_available_for_latest_alike_blocks() {
+        for block_number in [
+            BlockNumber::Pending,
+            BlockNumber::Committed,
+            BlockNumber::Finalized,
+        ] {
+            let input_block_number = 10;
+            let mock_server = MockServer::run_with_config(ForkBlockConfig {
+                number: input_block_number,
+                hash: H256::repeat_byte(0xab),
+                transaction_count: 0,
+            });
+            let node = testing::forked_node(&mock_server).await;
 
-    fn uninstall_filter(&self, _idx: U256) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
-        not_implemented("uninstall_filter")
+            let actual_block = node
+                .get_block_by_number(block_number, false)
+                .await
+                .expect("failed fetching block by hash")
+                .expect("no block");
+            assert_eq!(
+                U64::from(input_block_number),
+                actual_block.number,
+                "case {}",
+                block_number,
+            );
+        }
     }
 
-    fn new_pending_transaction_filter(
-        &self,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        not_implemented("new_pending_transaction_filter")
-    }
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_hash_for_produced_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
 
-    fn get_logs(
-        &self,
-        _filter: Filter,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_types::api::Log>>> {
-        not_implemented("get_logs")
-    }
+        let expected_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_hash(expected_block_hash)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no result");
 
-    fn get_filter_logs(
-        &self,
-        _filter_index: U256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FilterChanges>> {
-        not_implemented("get_filter_logs")
+        assert_eq!(U256::from(1), actual_transaction_count);
     }
 
-    fn get_filter_changes(
-        &self,
-        _filter_index: U256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FilterChanges>> {
-        not_implemented("get_filter_changes")
-    }
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_hash_returns_none_for_unknown_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
 
-    fn get_block_transaction_count_by_number(
-        &self,
-        block_number: zksync_types::api::BlockNumber,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
-        let inner = Arc::clone(&self.inner);
+        let result = node
+            .get_block_transaction_count_by_hash(H256::repeat_byte(0xab))
+            .await
+            .expect("failed fetching block by hash");
 
-        Box::pin(async move {
-            let maybe_result = {
-                let reader = match inner.read() {
-                    Ok(r) => r,
-                    Err(_) => return Err(into_jsrpc_error(Web3Error::InternalError)),
-                };
-                let number = match block_number {
-                    zksync_types::api::BlockNumber::Latest
-                    | zksync_types::api::BlockNumber::Pending
-                    | zksync_types::api::BlockNumber::Finalized
-                    | zksync_types::api::BlockNumber::Committed => reader.current_miniblock,
-                    zksync_types::api::BlockNumber::Number(ask_number) => ask_number.as_u64(),
-                    zksync_types::api::BlockNumber::Earliest => 0,
-                };
+        assert!(result.is_none());
+    }
 
-                reader
-                    .block_hashes
-                    .get(&number)
-                    .and_then(|hash| reader.blocks.get(hash))
-                    .map(|block| U256::from(block.transactions.len()))
-                    .or_else(|| {
-                        reader
-                            .fork_storage
-                            .inner
-                            .read()
-                            .expect("failed reading fork storage")
-                            .fork
-                            .as_ref()
-                            .and_then(|fork| {
-                                fork.fork_source
-                                    .get_block_transaction_count_by_number(block_number)
-                                    .ok()
-                                    .flatten()
-                            })
-                    })
-            };
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_hash_uses_fork_source() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let input_block_hash = H256::repeat_byte(0x01);
+        let input_transaction_count = 1;
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockTransactionCountByHash",
+                "params": [
+                    format!("{:#x}", input_block_hash),
+                ],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("{:#x}", input_transaction_count),
+            }),
+        );
+        let node = testing::forked_node(&mock_server).await;
 
-            match maybe_result {
-                Some(value) => Ok(Some(value)),
-                None => Err(into_jsrpc_error(Web3Error::NoBlock)),
-            }
-        })
-    }
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_hash(input_block_hash)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no result");
 
-    fn get_block_transaction_count_by_hash(
-        &self,
-        block_hash: zksync_basic_types::H256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
-        let inner = Arc::clone(&self.inner);
+        assert_eq!(
+            U256::from(input_transaction_count),
+            actual_transaction_count
+        );
+    }
 
-        Box::pin(async move {
-            let reader = inner
-                .read()
-                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_for_produced_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
 
-            // try retrieving block from memory, and if unavailable subsequently from the fork
-            let maybe_result = reader
-                .blocks
-                .get(&block_hash)
-                .map(|block| U256::from(block.transactions.len()))
-                .or_else(|| {
-                    reader
-                        .fork_storage
-                        .inner
-                        .read()
-                        .expect("failed reading fork storage")
-                        .fork
-                        .as_ref()
-                        .and_then(|fork| {
-                            fork.fork_source
-                                .get_block_transaction_count_by_hash(block_hash)
-                                .ok()
-                                .flatten()
-                        })
-                });
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(1)))
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no result");
 
-            match maybe_result {
-                Some(value) => Ok(Some(value)),
-                None => Err(into_jsrpc_error(Web3Error::NoBlock)),
-            }
-        })
+        assert_eq!(U256::from(1), actual_transaction_count);
     }
 
-    fn get_storage(
-        &self,
-        _address: zksync_basic_types::Address,
-        _idx: U256,
-        _block: Option<zksync_types::api::BlockIdVariant>,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::H256>> {
-        not_implemented("get_storage")
-    }
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_for_block_with_several_transactions() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let private_key = H256::repeat_byte(0x0e);
+        let address = zksync_types::PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.set_rich_account(address);
+        node.inner.write().unwrap().automine = false;
+        for nonce in 0..3 {
+            node.submit_tx(signed_tx(&private_key, nonce, 250_000_000))
+                .expect("failed submitting tx");
+        }
+        assert_eq!(1, node.mine_mempool().expect("failed mining"));
 
-    fn get_transaction_by_block_hash_and_index(
-        &self,
-        _block_hash: zksync_basic_types::H256,
-        _index: zksync_basic_types::web3::types::Index,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
-        not_implemented("get_transaction_by_block_hash_and_index")
-    }
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(1)))
+            .await
+            .expect("failed fetching block by number")
+            .expect("no result");
 
-    fn get_transaction_by_block_number_and_index(
-        &self,
-        _block_number: zksync_types::api::BlockNumber,
-        _index: zksync_basic_types::web3::types::Index,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::Transaction>>> {
-        not_implemented("get_transaction_by_block_number_and_index")
+        assert_eq!(U256::from(3), actual_transaction_count);
     }
 
-    fn protocol_version(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<String>> {
-        not_implemented("protocol_version")
-    }
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_returns_none_for_unknown_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
 
-    fn syncing(
-        &self,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::web3::types::SyncState>>
-    {
-        Ok(zksync_basic_types::web3::types::SyncState::NotSyncing).into_boxed_future()
-    }
+        let result = node
+            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(42)))
+            .await
+            .expect("failed fetching block by number");
 
-    fn accounts(
-        &self,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_basic_types::Address>>> {
-        not_implemented("accounts")
+        assert!(result.is_none());
     }
 
-    fn coinbase(
-        &self,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Address>> {
-        not_implemented("coinbase")
-    }
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_uses_fork_source() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let input_block_number = 1;
+        let input_transaction_count = 1;
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "eth_getBlockTransactionCountByNumber",
+                "params": [
+                    format!("{:#x}", input_block_number),
+                ],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("{:#x}", input_transaction_count),
+            }),
+        );
 
-    fn compilers(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<String>>> {
-        not_implemented("compilers")
-    }
+        let node = testing::forked_node(&mock_server).await;
 
-    fn hashrate(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<U256>> {
-        not_implemented("hashrate")
-    }
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(1)))
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no result");
 
-    fn get_uncle_count_by_block_hash(
-        &self,
-        _hash: zksync_basic_types::H256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
-        not_implemented("get_uncle_count_by_block_hash")
+        assert_eq!(
+            U256::from(input_transaction_count),
+            actual_transaction_count
+        );
     }
 
-    fn get_uncle_count_by_block_number(
-        &self,
-        _number: zksync_types::api::BlockNumber,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<U256>>> {
-        not_implemented("get_uncle_count_by_block_number")
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_earliest_is_the_fork_block() {
+        let input_transaction_count = 2;
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: input_transaction_count,
+        });
+
+        let node = testing::forked_node(&mock_server).await;
+
+        let actual_transaction_count = node
+            .get_block_transaction_count_by_number(BlockNumber::Earliest)
+            .await
+            .expect("failed fetching block by hash")
+            .expect("no result");
+
+        assert_eq!(
+            U256::from(input_transaction_count),
+            actual_transaction_count
+        );
     }
 
-    fn mining(&self) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<bool>> {
-        not_implemented("mining")
+    #[tokio::test]
+    async fn test_get_block_transaction_count_by_number_latest_alike_uses_fork_source() {
+        for block_number in [
+            BlockNumber::Latest,
+            BlockNumber::Pending,
+            BlockNumber::Committed,
+            BlockNumber::Finalized,
+        ] {
+            let input_transaction_count = 1;
+            let mock_server = MockServer::run_with_config(ForkBlockConfig {
+                number: 10,
+                transaction_count: input_transaction_count,
+                hash: H256::repeat_byte(0xab),
+            });
+
+            let node = testing::forked_node(&mock_server).await;
+
+            let actual_transaction_count = node
+                .get_block_transaction_count_by_number(block_number)
+                .await
+                .expect("failed fetching block by hash")
+                .expect("no result");
+
+            assert_eq!(
+                U256::from(input_transaction_count),
+                actual_transaction_count,
+                "case {}",
+                block_number,
+            );
+        }
     }
 
-    /// Returns the fee history for a given range of blocks.
-    ///
-    /// Note: This implementation is limited to using the hard-coded value
-    /// of L2_GAS_PRICE as the history gas price
-    ///
-    /// # Arguments
-    ///
-    /// * `block_count` - The number of blocks in the requested range. Between 1 and 1024 blocks can be requested in a single query. It will return less than the requested range if not all blocks are available.
-    /// * `newest_block` - The highest number block of the requested range. As this implementation is using hard-coded values, this argument is ignored.
-    /// * `reward_percentiles` - A list of percentile values with a monotonic increase in value.
-    ///
-    /// # Returns
-    ///
-    /// A `BoxFuture` containing a `Result` with a `FeeHistory` representing the fee history of the specified range of blocks.
-    fn fee_history(
-        &self,
-        block_count: U64,
-        _newest_block: zksync_types::api::BlockNumber,
-        reward_percentiles: Vec<f32>,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<FeeHistory>> {
-        let inner = Arc::clone(&self.inner);
+    #[tokio::test]
+    async fn test_get_transaction_receipt_uses_produced_block_hash() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        let expected_block_hash = testing::apply_tx(&node, tx_hash);
 
-        Box::pin(async move {
-            let reader = inner
-                .read()
-                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+        let actual_tx_receipt = node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .expect("failed fetching transaction receipt by hash")
+            .expect("no transaction receipt");
 
-            let block_count = block_count
-                .as_u64()
-                .min(1024)
-                // Can't be more than the total number of blocks
-                .min(reader.current_miniblock + 1)
-                .max(1);
+        assert_eq!(Some(expected_block_hash), actual_tx_receipt.block_hash);
+    }
 
-            let mut base_fee_per_gas = vec![U256::from(L2_GAS_PRICE); block_count as usize];
+    #[tokio::test]
+    async fn test_get_transaction_receipt_reports_position_in_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let private_key = H256::repeat_byte(0x0c);
+        let address = zksync_types::PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.set_rich_account(address);
+        node.inner.write().unwrap().automine = false;
+        let txs = (0..3)
+            .map(|nonce| signed_tx(&private_key, nonce, 250_000_000))
+            .collect::<Vec<_>>();
+        for tx in &txs {
+            node.submit_tx(tx.clone()).expect("failed submitting tx");
+        }
+        assert_eq!(1, node.mine_mempool().expect("failed mining"));
 
-            let oldest_block = reader.current_miniblock + 1 - base_fee_per_gas.len() as u64;
-            // We do not store gas used ratio for blocks, returns array of zeroes as a placeholder.
-            let gas_used_ratio = vec![0.0; base_fee_per_gas.len()];
-            // Effective priority gas price is currently 0.
-            let reward = Some(vec![
-                vec![U256::zero(); reward_percentiles.len()];
-                base_fee_per_gas.len()
-            ]);
+        let block_hash = node.inner.read().unwrap().block_hashes[&1];
+        let mut cumulative_gas_used = U256::zero();
+        for (index, tx) in txs.iter().enumerate() {
+            let receipt = node
+                .get_transaction_receipt(tx.hash())
+                .await
+                .expect("failed fetching transaction receipt")
+                .expect("no transaction receipt");
+            let gas_used = receipt.gas_used.expect("no gas used");
+            cumulative_gas_used += gas_used;
+
+            assert_eq!(U64::from(index), receipt.transaction_index);
+            assert_eq!(Some(block_hash), receipt.block_hash);
+            assert_eq!(address, receipt.from);
+            assert!(!gas_used.is_zero());
+            assert_eq!(cumulative_gas_used, receipt.cumulative_gas_used);
+        }
+    }
 
-            // `base_fee_per_gas` for next miniblock cannot be calculated, appending last fee as a placeholder.
-            base_fee_per_gas.push(*base_fee_per_gas.last().unwrap());
+    #[tokio::test]
+    async fn test_get_transaction_by_hash_matches_block_transactions() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let private_key = H256::repeat_byte(0x0d);
+        let address = zksync_types::PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.set_rich_account(address);
+        node.inner.write().unwrap().automine = false;
+        for nonce in 0..2 {
+            node.submit_tx(signed_tx(&private_key, nonce, 250_000_000))
+                .expect("failed submitting tx");
+        }
+        assert_eq!(1, node.mine_mempool().expect("failed mining"));
 
-            Ok(FeeHistory {
-                oldest_block: web3::types::BlockNumber::Number(oldest_block.into()),
-                base_fee_per_gas,
-                gas_used_ratio,
-                reward,
-            })
-        })
+        let block = node
+            .get_block_by_number(BlockNumber::Number(U64::from(1)), true)
+            .await
+            .expect("failed fetching block")
+            .expect("no block");
+        assert_eq!(2, block.transactions.len());
+        for transaction in block.transactions {
+            let expected = match transaction {
+                TransactionVariant::Full(tx) => tx,
+                TransactionVariant::Hash(_) => panic!("expected full transactions"),
+            };
+            let actual = node
+                .get_transaction_by_hash(expected.hash)
+                .await
+                .expect("failed fetching transaction")
+                .expect("no transaction");
+            assert_eq!(
+                serde_json::to_value(expected).unwrap(),
+                serde_json::to_value(actual).unwrap()
+            );
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        cache::CacheConfig,
-        http_fork_source::HttpForkSource,
-        node::InMemoryNode,
-        testing::{self, ForkBlockConfig, MockServer},
-    };
-    use zksync_types::api::BlockNumber;
-    use zksync_web3_decl::types::SyncState;
+    #[tokio::test]
+    async fn test_get_logs_returns_logs_ordered_by_block_and_log_index() {
+        // Arrange
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let block_hash_1 = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let block_hash_2 = testing::apply_tx(&node, H256::repeat_byte(0x02));
 
-    use super::*;
+        // Act
+        let logs = node
+            .get_logs(Filter {
+                from_block: Some(BlockNumber::Earliest),
+                to_block: Some(BlockNumber::Latest),
+                ..Default::default()
+            })
+            .await
+            .expect("failed getting logs");
+
+        // Assert
+        assert!(!logs.is_empty());
+        let block_1_logs: Vec<_> = logs
+            .iter()
+            .filter(|log| log.block_hash == Some(block_hash_1))
+            .collect();
+        let block_2_logs: Vec<_> = logs
+            .iter()
+            .filter(|log| log.block_hash == Some(block_hash_2))
+            .collect();
+        assert_eq!(logs.len(), block_1_logs.len() + block_2_logs.len());
+        assert!(logs
+            .windows(2)
+            .all(|pair| pair[0].block_number <= pair[1].block_number));
+        for (index, log) in block_1_logs.iter().enumerate() {
+            assert_eq!(Some(U64::from(1)), log.block_number);
+            assert_eq!(Some(U256::from(index)), log.log_index);
+            assert_eq!(Some(U64::from(0)), log.transaction_index);
+        }
+        for (index, log) in block_2_logs.iter().enumerate() {
+            assert_eq!(Some(U64::from(2)), log.block_number);
+            assert_eq!(Some(U256::from(index)), log.log_index);
+        }
+    }
 
     #[tokio::test]
-    async fn test_eth_syncing() {
+    async fn test_get_logs_filters_by_block_range_and_address() {
+        // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
-        let syncing = node.syncing().await.expect("failed syncing");
-        assert!(matches!(syncing, SyncState::NotSyncing));
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        // Act
+        let latest_logs = node
+            .get_logs(Filter::default())
+            .await
+            .expect("failed getting logs");
+        let eth_token_logs = node
+            .get_logs(Filter {
+                from_block: Some(BlockNumber::Number(U64::from(1))),
+                to_block: Some(BlockNumber::Number(U64::from(1))),
+                address: Some(zksync_web3_decl::types::ValueOrArray(vec![
+                    L2_ETH_TOKEN_ADDRESS,
+                ])),
+                ..Default::default()
+            })
+            .await
+            .expect("failed getting logs");
+        let unknown_address_logs = node
+            .get_logs(Filter {
+                from_block: Some(BlockNumber::Earliest),
+                address: Some(zksync_web3_decl::types::ValueOrArray(vec![H160::random()])),
+                ..Default::default()
+            })
+            .await
+            .expect("failed getting logs");
+
+        // Assert
+        assert!(!latest_logs.is_empty());
+        assert!(latest_logs
+            .iter()
+            .all(|log| log.block_number == Some(U64::from(2))));
+        assert!(!eth_token_logs.is_empty());
+        assert!(eth_token_logs.iter().all(|log| {
+            log.address == L2_ETH_TOKEN_ADDRESS && log.block_number == Some(U64::from(1))
+        }));
+        assert!(unknown_address_logs.is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_fee_history_with_1_block() {
+    async fn test_get_logs_by_block_hash() {
+        // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
+        let block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        testing::apply_tx(&node, H256::repeat_byte(0x02));
 
-        let fee_history = node
-            .fee_history(U64::from(1), BlockNumber::Latest, vec![25.0, 50.0, 75.0])
+        // Act
+        let logs = node
+            .get_logs(Filter {
+                block_hash: Some(block_hash),
+                ..Default::default()
+            })
             .await
-            .expect("fee_history failed");
+            .expect("failed getting logs");
+        let unknown_block_logs = node
+            .get_logs(Filter {
+                block_hash: Some(H256::repeat_byte(0xff)),
+                ..Default::default()
+            })
+            .await
+            .expect("failed getting logs");
 
-        assert_eq!(
-            fee_history.oldest_block,
-            web3::types::BlockNumber::Number(U64::from(0))
-        );
-        assert_eq!(
-            fee_history.base_fee_per_gas,
-            vec![U256::from(L2_GAS_PRICE); 2]
-        );
-        assert_eq!(fee_history.gas_used_ratio, vec![0.0]);
-        assert_eq!(fee_history.reward, Some(vec![vec![U256::from(0); 3]]));
+        // Assert
+        assert!(!logs.is_empty());
+        assert!(logs.iter().all(
+            |log| log.block_hash == Some(block_hash) && log.block_number == Some(U64::from(1))
+        ));
+        assert!(unknown_block_logs.is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_fee_history_with_no_reward_percentiles() {
+    async fn test_get_transaction_receipt_logs_match_get_logs() {
+        // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
 
-        let fee_history = node
-            .fee_history(U64::from(1), BlockNumber::Latest, vec![])
+        // Act
+        let receipt = node
+            .get_transaction_receipt(tx_hash)
             .await
-            .expect("fee_history failed");
+            .expect("failed fetching transaction receipt")
+            .expect("no transaction receipt");
+        let logs = node
+            .get_logs(Filter::default())
+            .await
+            .expect("failed getting logs");
 
-        assert_eq!(
-            fee_history.oldest_block,
-            web3::types::BlockNumber::Number(U64::from(0))
-        );
-        assert_eq!(
-            fee_history.base_fee_per_gas,
-            vec![U256::from(L2_GAS_PRICE); 2]
-        );
-        assert_eq!(fee_history.gas_used_ratio, vec![0.0]);
-        assert_eq!(fee_history.reward, Some(vec![vec![]]));
+        // Assert
+        assert_eq!(logs, receipt.logs);
     }
 
     #[tokio::test]
-    async fn test_get_fee_history_with_multiple_blocks() {
+    async fn test_get_filter_changes_returns_logs_since_last_poll() {
         // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
         testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let filter_id = node
+            .new_filter(Filter {
+                address: Some(zksync_web3_decl::types::ValueOrArray(vec![
+                    L2_ETH_TOKEN_ADDRESS,
+                ])),
+                ..Default::default()
+            })
+            .await
+            .expect("failed creating filter");
 
         // Act
-        let fee_history = node
-            .fee_history(U64::from(2), BlockNumber::Latest, vec![25.0, 50.0, 75.0])
+        let changes_before_tx = node
+            .get_filter_changes(filter_id)
             .await
-            .expect("fee_history failed");
+            .expect("failed polling filter");
+        let block_hash = testing::apply_tx(&node, H256::repeat_byte(0x02));
+        let changes_after_tx = node
+            .get_filter_changes(filter_id)
+            .await
+            .expect("failed polling filter");
+        let changes_after_second_poll = node
+            .get_filter_changes(filter_id)
+            .await
+            .expect("failed polling filter");
 
         // Assert
-        assert_eq!(
-            fee_history.oldest_block,
-            web3::types::BlockNumber::Number(U64::from(0))
-        );
-        assert_eq!(
-            fee_history.base_fee_per_gas,
-            vec![U256::from(L2_GAS_PRICE); 3]
-        );
-        assert_eq!(fee_history.gas_used_ratio, vec![0.0, 0.0]);
-        assert_eq!(fee_history.reward, Some(vec![vec![U256::from(0); 3]; 2]));
+        match changes_before_tx {
+            FilterChanges::Logs(logs) => assert!(logs.is_empty()),
+            _ => panic!("expected logs"),
+        }
+        match changes_after_tx {
+            FilterChanges::Logs(logs) => {
+                assert!(!logs.is_empty());
+                assert!(logs
+                    .iter()
+                    .all(|log| log.block_hash == Some(block_hash)
+                        && log.address == L2_ETH_TOKEN_ADDRESS));
+            }
+            _ => panic!("expected logs"),
+        }
+        match changes_after_second_poll {
+            FilterChanges::Logs(logs) => assert!(logs.is_empty()),
+            _ => panic!("expected logs"),
+        }
     }
 
     #[tokio::test]
-    async fn test_get_block_by_hash_produces_no_block_error_for_non_existing_block() {
+    async fn test_get_filter_changes_returns_only_the_logs_of_new_blocks() {
+        // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
+        let filter_id = node
+            .new_filter(Filter {
+                from_block: Some(BlockNumber::Earliest),
+                to_block: Some(BlockNumber::Latest),
+                ..Default::default()
+            })
+            .await
+            .expect("failed creating filter");
+        let logs = |changes: FilterChanges| match changes {
+            FilterChanges::Logs(logs) => logs,
+            _ => panic!("expected logs"),
+        };
 
-        let expected_err = into_jsrpc_error(Web3Error::NoBlock);
-        let result = node.get_block_by_hash(H256::repeat_byte(0x01), false).await;
+        // Act
+        let block_hash_1 = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let first_poll = logs(node.get_filter_changes(filter_id).await.unwrap());
+        let block_hash_2 = testing::apply_tx(&node, H256::repeat_byte(0x02));
+        let second_poll = logs(node.get_filter_changes(filter_id).await.unwrap());
+        let third_poll = logs(node.get_filter_changes(filter_id).await.unwrap());
+        let all_logs = logs(node.get_filter_logs(filter_id).await.unwrap());
 
-        assert_eq!(expected_err, result.unwrap_err());
+        // Assert
+        assert!(!first_poll.is_empty());
+        assert!(first_poll
+            .iter()
+            .all(|log| log.block_hash == Some(block_hash_1)));
+        assert!(!second_poll.is_empty());
+        assert!(second_poll
+            .iter()
+            .all(|log| log.block_hash == Some(block_hash_2)));
+        assert!(third_poll.is_empty());
+        assert_eq!([first_poll, second_poll].concat(), all_logs);
     }
 
     #[tokio::test]
-    async fn test_node_run_has_genesis_block() {
+    async fn test_get_filter_logs_rejects_non_log_filters() {
         let node = InMemoryNode::<HttpForkSource>::default();
+        let block_filter_id = node
+            .new_block_filter()
+            .await
+            .expect("failed creating block filter");
 
-        let block = node
-            .get_block_by_number(BlockNumber::Latest, false)
+        assert!(node.get_filter_logs(block_filter_id).await.is_err());
+        assert!(node.get_filter_logs(U256::from(100)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_filter() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let filter_id = node
+            .new_filter(Filter::default())
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
+            .expect("failed creating filter");
 
-        assert_eq!(0, block.number.as_u64());
-        assert_eq!(H256::zero(), block.hash);
+        assert!(node
+            .uninstall_filter(filter_id)
+            .await
+            .expect("failed uninstalling filter"));
+        assert!(!node
+            .uninstall_filter(filter_id)
+            .await
+            .expect("failed uninstalling filter"));
+        assert!(node.get_filter_changes(filter_id).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_get_block_by_hash_for_produced_block() {
+    async fn test_block_and_pending_transaction_filters() {
+        // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
-        let expected_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let block_filter_id = node
+            .new_block_filter()
+            .await
+            .expect("failed creating block filter");
+        let tx_filter_id = node
+            .new_pending_transaction_filter()
+            .await
+            .expect("failed creating pending transaction filter");
+        let log_filter_id = node
+            .new_filter(Filter::default())
+            .await
+            .expect("failed creating log filter");
 
-        let actual_block = node
-            .get_block_by_hash(expected_block_hash, false)
+        // Act
+        let tx_hash = H256::repeat_byte(0x01);
+        let block_hash = testing::apply_tx(&node, tx_hash);
+        let block_changes = node
+            .get_filter_changes(block_filter_id)
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
+            .expect("failed polling block filter");
+        let tx_changes = node
+            .get_filter_changes(tx_filter_id)
+            .await
+            .expect("failed polling pending transaction filter");
+        let log_changes = node
+            .get_filter_changes(log_filter_id)
+            .await
+            .expect("failed polling log filter");
 
-        assert_eq!(expected_block_hash, actual_block.hash);
-        assert_eq!(U64::from(1), actual_block.number);
-        assert_eq!(Some(U64::from(1)), actual_block.l1_batch_number);
+        // Assert
+        match block_changes {
+            FilterChanges::Hashes(hashes) => assert_eq!(vec![block_hash], hashes),
+            _ => panic!("expected block hashes"),
+        }
+        match tx_changes {
+            FilterChanges::Hashes(hashes) => assert_eq!(vec![tx_hash], hashes),
+            _ => panic!("expected transaction hashes"),
+        }
+        match log_changes {
+            FilterChanges::Logs(logs) => assert!(!logs.is_empty()),
+            _ => panic!("expected logs"),
+        }
+        assert!(block_filter_id < tx_filter_id && tx_filter_id < log_filter_id);
     }
 
     #[tokio::test]
-    async fn test_node_block_mapping_is_correctly_populated_when_using_fork_source() {
-        let input_block_number = 8;
-        let input_block_hash = H256::repeat_byte(0x01);
-        let mock_server = MockServer::run_with_config(ForkBlockConfig {
-            number: input_block_number,
-            hash: input_block_hash,
-            transaction_count: 0,
-        });
-
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
+    async fn test_get_storage_returns_local_values_and_zero_for_untouched_slots() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let address = H160::repeat_byte(0x01);
+        let value = H256::repeat_byte(0x03);
+        node.inner.write().unwrap().fork_storage.set_value(
+            StorageKey::new(AccountTreeId::new(address), u256_to_h256(U256::from(2))),
+            value,
         );
 
-        let inner = node.inner.read().unwrap();
-        assert!(
-            inner.blocks.contains_key(&input_block_hash),
-            "block wasn't cached"
-        );
-        assert!(
-            inner.block_hashes.contains_key(&input_block_number),
-            "block number wasn't cached"
-        );
+        let written = node
+            .get_storage(address, U256::from(2), None)
+            .await
+            .expect("failed reading storage");
+        let untouched = node
+            .get_storage(
+                address,
+                U256::from(3),
+                Some(zksync_types::api::BlockIdVariant::BlockNumber(
+                    BlockNumber::Latest,
+                )),
+            )
+            .await
+            .expect("failed reading storage");
+
+        assert_eq!(value, written);
+        assert_eq!(H256::zero(), untouched);
     }
 
     #[tokio::test]
-    async fn test_get_block_by_hash_uses_fork_source() {
-        let input_block_hash = H256::repeat_byte(0x01);
-
+    async fn test_get_storage_falls_back_to_fork_source() {
+        let address = H160::repeat_byte(0x01);
+        let value = H256::repeat_byte(0x03);
         let mock_server = MockServer::run_with_config(ForkBlockConfig {
             number: 10,
             hash: H256::repeat_byte(0xab),
             transaction_count: 0,
         });
-        let mock_block_number = 8;
-        let block_response = testing::BlockResponseBuilder::new()
-            .set_hash(input_block_hash)
-            .set_number(mock_block_number)
-            .build();
         mock_server.expect(
             serde_json::json!({
                 "jsonrpc": "2.0",
                 "id": 0,
-                "method": "eth_getBlockByHash",
+                "method": "eth_getStorageAt",
                 "params": [
-                    format!("{input_block_hash:#x}"),
-                    true
+                    format!("{address:#x}"),
+                    "0x2",
+                    "0xa",
                 ],
             }),
-            block_response,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("{value:#x}"),
+            }),
         );
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
+        let node = testing::forked_node(&mock_server).await;
+
+        let actual = node
+            .get_storage(address, U256::from(2), None)
+            .await
+            .expect("failed reading storage");
+
+        assert_eq!(value, actual);
+    }
+
+    #[tokio::test]
+    async fn test_get_storage_prefers_local_zero_over_fork_source() {
+        let address = H160::repeat_byte(0x01);
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            hash: H256::repeat_byte(0xab),
+            transaction_count: 0,
+        });
+        let node = testing::forked_node(&mock_server).await;
+        node.inner.write().unwrap().fork_storage.set_value(
+            StorageKey::new(AccountTreeId::new(address), u256_to_h256(U256::from(2))),
+            H256::zero(),
         );
 
-        let actual_block = node
-            .get_block_by_hash(input_block_hash, false)
+        // no `eth_getStorageAt` expectation is set, the fork must not be queried
+        let actual = node
+            .get_storage(address, U256::from(2), None)
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
+            .expect("failed reading storage");
 
-        assert_eq!(input_block_hash, actual_block.hash);
-        assert_eq!(U64::from(mock_block_number), actual_block.number);
-        assert_eq!(Some(U64::from(6)), actual_block.l1_batch_number);
+        assert_eq!(H256::zero(), actual);
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_produces_no_block_error_for_non_existing_block() {
+    async fn test_get_storage_rejects_historical_blocks() {
         let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
 
-        let expected_err = into_jsrpc_error(Web3Error::NoBlock);
         let result = node
-            .get_block_by_number(BlockNumber::Number(U64::from(42)), false)
+            .get_storage(
+                H160::repeat_byte(0x01),
+                U256::from(2),
+                Some(zksync_types::api::BlockIdVariant::BlockNumber(
+                    BlockNumber::Number(U64::from(0)),
+                )),
+            )
             .await;
 
-        assert_eq!(expected_err, result.unwrap_err());
+        assert!(result.is_err());
+    }
+
+    /// Returns an empty transaction with a gas limit of 1_000_000, signed with `private_key`.
+    fn signed_tx(private_key: &H256, nonce: u32, max_fee_per_gas: u64) -> L2Tx {
+        L2Tx::new_signed(
+            H160::repeat_byte(0x01),
+            vec![],
+            zksync_types::Nonce(nonce),
+            zksync_types::fee::Fee {
+                gas_limit: U256::from(1_000_000),
+                max_fee_per_gas: U256::from(max_fee_per_gas),
+                max_priority_fee_per_gas: U256::zero(),
+                gas_per_pubdata_limit: U256::from(20000),
+            },
+            U256::zero(),
+            zksync_types::L2ChainId(260),
+            private_key,
+            None,
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fees_order_keeps_nonce_order_per_sender() {
+        let tx = signed_tx;
+        let (alice, bob) = (H256::repeat_byte(0x0a), H256::repeat_byte(0x0b));
+        let txs = vec![
+            tx(&alice, 1, 500),
+            tx(&bob, 0, 300),
+            tx(&alice, 0, 100),
+            tx(&bob, 1, 400),
+        ];
+        let order = |sorted: Vec<L2Tx>| {
+            sorted
+                .iter()
+                .map(|sorted| {
+                    txs.iter()
+                        .position(|tx| tx.hash() == sorted.hash())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            order(TransactionOrder::Fifo.sort(txs.clone()))
+        );
+        // alice's second transaction pays the most, but can't be mined before her first one
+        assert_eq!(
+            vec![1, 3, 2, 0],
+            order(TransactionOrder::Fees.sort(txs.clone()))
+        );
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_for_produced_block() {
+    async fn test_get_transaction_count_counts_pending_transactions() {
+        use crate::eth_extra::{EthTestNodeNamespaceT, TransactionArgs};
+
         let node = InMemoryNode::<HttpForkSource>::default();
-        testing::apply_tx(&node, H256::repeat_byte(0x01));
-        let expected_block_number = 1;
+        let address = H160::from_str(RICH_WALLETS[0].0).unwrap();
+        node.set_rich_account(address);
+        node.inner.write().unwrap().automine = false;
+        let pending = Some(zksync_types::api::BlockIdVariant::BlockNumber(
+            BlockNumber::Pending,
+        ));
+        let latest = Some(zksync_types::api::BlockIdVariant::BlockNumber(
+            BlockNumber::Latest,
+        ));
+
+        for _ in 0..2 {
+            let raw = node
+                .sign_transaction(TransactionArgs {
+                    from: address,
+                    to: Some(H160::repeat_byte(0x02)),
+                    value: Some(U256::from(100)),
+                    ..Default::default()
+                })
+                .await
+                .expect("eth_signTransaction failed");
+            node.send_raw_transaction(raw)
+                .await
+                .expect("eth_sendRawTransaction failed");
+        }
 
-        let actual_block = node
-            .get_block_by_number(BlockNumber::Number(U64::from(expected_block_number)), false)
-            .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(address, pending).await.unwrap()
+        );
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(address, latest).await.unwrap()
+        );
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(address, None).await.unwrap()
+        );
+        assert_eq!(
+            U256::zero(),
+            node.get_transaction_count(H160::repeat_byte(0x02), pending)
+                .await
+                .unwrap()
+        );
 
-        assert_eq!(U64::from(expected_block_number), actual_block.number);
-        assert_eq!(1, actual_block.transactions.len());
+        // once mined, the transactions are counted in the latest state instead
+        assert_eq!(1, node.mine_mempool().expect("failed mining"));
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(address, pending).await.unwrap()
+        );
+        assert_eq!(
+            U256::from(2),
+            node.get_transaction_count(address, latest).await.unwrap()
+        );
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_uses_fork_source_if_missing_number() {
-        let mock_server = MockServer::run_with_config(ForkBlockConfig {
-            number: 10,
-            hash: H256::repeat_byte(0xab),
-            transaction_count: 0,
-        });
-        let mock_block_number = 8;
-        let block_response = testing::BlockResponseBuilder::new()
-            .set_number(mock_block_number)
-            .build();
-        mock_server.expect(
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 0,
-                "method": "eth_getBlockByNumber",
-                "params": [
-                    "0x8",
-                    true
-                ],
-            }),
-            block_response,
+    async fn test_submit_tx_rejects_invalid_nonces_like_geth() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let private_key = H256::repeat_byte(0x0a);
+        let address = zksync_types::PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.set_rich_account(address);
+        node.inner
+            .write()
+            .unwrap()
+            .fork_storage
+            .set_value(get_nonce_key(&address), u256_to_h256(U256::from(2)));
+
+        let error = node
+            .submit_tx(signed_tx(&private_key, 1, 250_000_000))
+            .expect_err("accepted a used nonce");
+        assert_eq!(jsonrpc_core::ErrorCode::ServerError(3), error.code);
+        assert_eq!(
+            format!("nonce too low: address {:?}, tx: 1 state: 2", address),
+            error.message
         );
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
+
+        let error = node
+            .submit_tx(signed_tx(&private_key, 5, 250_000_000))
+            .expect_err("accepted a nonce gap");
+        assert_eq!(jsonrpc_core::ErrorCode::ServerError(3), error.code);
+        assert_eq!(
+            format!("nonce too high: address {:?}, tx: 5 state: 2", address),
+            error.message
+        );
+
+        // without automine, the missing transactions may still be sent
+        node.inner.write().unwrap().automine = false;
+        node.submit_tx(signed_tx(&private_key, 5, 250_000_000))
+            .expect("rejected a queued transaction");
+        assert_eq!(1, node.inner.read().unwrap().pending_transactions.len());
+    }
+
+    #[tokio::test]
+    async fn test_submit_tx_rejects_insufficient_funds_like_geth() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let private_key = H256::repeat_byte(0x0b);
+        let address = zksync_types::PackedEthSignature::address_from_private_key(&private_key)
+            .expect("failed generating address");
+        node.inner.write().unwrap().fork_storage.set_value(
+            storage_key_for_eth_balance(&address),
+            u256_to_h256(U256::from(1000)),
+        );
+
+        let error = node
+            .submit_tx(signed_tx(&private_key, 0, 250_000_000))
+            .expect_err("accepted a transaction the sender can't pay for");
+        assert_eq!(jsonrpc_core::ErrorCode::ServerError(3), error.code);
+        assert_eq!(
+            format!(
+                "insufficient funds for gas * price + value: address {:?} have 1000 want 250000000000000",
+                address
+            ),
+            error.message
         );
+        assert!(node.inner.read().unwrap().pending_transactions.is_empty());
+    }
 
-        let actual_block = node
-            .get_block_by_number(BlockNumber::Number(U64::from(8)), false)
-            .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
-        assert_eq!(U64::from(mock_block_number), actual_block.number);
+    #[tokio::test]
+    async fn test_executed_transaction_is_no_longer_pending() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        node.inner
+            .write()
+            .unwrap()
+            .pending_transactions
+            .insert(tx_hash, H160::repeat_byte(0x01));
+
+        testing::apply_tx(&node, tx_hash);
+
+        assert!(node.inner.read().unwrap().pending_transactions.is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_for_latest_block_produced_locally() {
+    async fn test_get_code_honors_block_parameter() {
         let node = InMemoryNode::<HttpForkSource>::default();
         testing::apply_tx(&node, H256::repeat_byte(0x01));
-        let latest_block_number = 1;
-
-        let actual_block = node
-            .get_block_by_number(BlockNumber::Latest, true)
+        // any valid bytecode can be deployed, the L2 ETH token has no constructor arguments
+        let code = node
+            .get_code(L2_ETH_TOKEN_ADDRESS, None)
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
-
-        assert_eq!(U64::from(latest_block_number), actual_block.number);
-        assert_eq!(1, actual_block.transactions.len());
+            .expect("failed getting code")
+            .0;
+        let tx_hash = testing::deploy_contract(&node, code.clone());
+        let address = node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .expect("failed getting receipt")
+            .and_then(|receipt| receipt.contract_address)
+            .expect("no contract was deployed");
+        testing::apply_tx(&node, H256::repeat_byte(0x02));
+
+        let code_at = |number: u64| {
+            node.get_code(
+                address,
+                Some(zksync_types::api::BlockIdVariant::BlockNumber(
+                    BlockNumber::Number(U64::from(number)),
+                )),
+            )
+        };
+        assert!(code_at(1).await.expect("failed getting code").0.is_empty());
+        assert_eq!(code, code_at(2).await.expect("failed getting code").0);
+        assert_eq!(
+            code,
+            node.get_code(address, None)
+                .await
+                .expect("failed getting code")
+                .0
+        );
+        assert!(code_at(5).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_uses_locally_available_block_for_latest_block() {
-        let input_block_number = 10;
-        let mock_server = MockServer::run_with_config(ForkBlockConfig {
-            number: input_block_number,
-            hash: H256::repeat_byte(0x01),
-            transaction_count: 0,
-        });
+    async fn test_call_rejects_historical_local_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let current_block = node.inner.read().unwrap().current_miniblock;
+        // decimals()
+        let request = zksync_types::transaction_request::CallRequest {
+            to: Some(L2_ETH_TOKEN_ADDRESS),
+            data: Some(Bytes(vec![0x31, 0x3c, 0xe5, 0x67])),
+            ..Default::default()
+        };
+        let at_block = |number: u64| {
+            Some(BlockIdVariant::BlockNumber(BlockNumber::Number(U64::from(
+                number,
+            ))))
+        };
 
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
+        let error = node
+            .call(request.clone(), at_block(current_block - 1))
+            .await
+            .expect_err("called a past local block");
+        assert!(
+            error.message.contains("historical state not available"),
+            "unexpected error: {}",
+            error.message
         );
+        assert!(node
+            .call(request.clone(), at_block(current_block + 1))
+            .await
+            .is_err());
 
-        let actual_block = node
-            .get_block_by_number(BlockNumber::Latest, false)
+        let result = node
+            .call(request, at_block(current_block))
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
-        assert_eq!(U64::from(input_block_number), actual_block.number);
+            .expect("call on the latest block failed");
+        assert_eq!(H256::from_low_u64_be(18).as_bytes(), &result.0[..]);
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_uses_fork_source_for_earliest_block() {
-        let mock_server = MockServer::run_with_config(ForkBlockConfig {
-            number: 10,
-            hash: H256::repeat_byte(0xab),
-            transaction_count: 0,
-        });
-        let input_block_number = 1;
-        mock_server.expect(
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 0,
-                "method": "eth_getBlockByNumber",
-                "params": [
-                    "earliest",
-                    true
-                ],
-            }),
-            testing::BlockResponseBuilder::new()
-                .set_number(input_block_number)
-                .build(),
+    async fn test_call_runs_overridden_system_contract() {
+        let l2_eth_token = crate::deps::system_contracts::bytecode_from_slice(
+            "L2EthToken",
+            include_bytes!("deps/contracts/L2EthToken.json"),
         );
+        let options = system_contracts::Options::BuiltIn
+            .with_override("BytecodeCompressor", l2_eth_token)
+            .expect("invalid override");
         let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
+            None,
+            &NodeConfig {
+                system_contracts_options: options,
+                ..Default::default()
+            },
         );
+        // decimals()
+        let request = zksync_types::transaction_request::CallRequest {
+            to: Some(zksync_types::BYTECODE_COMPRESSOR_ADDRESS),
+            data: Some(Bytes(vec![0x31, 0x3c, 0xe5, 0x67])),
+            ..Default::default()
+        };
 
-        let actual_block = node
-            .get_block_by_number(BlockNumber::Earliest, false)
+        let result = node
+            .call(request.clone(), None)
             .await
-            .expect("failed fetching block by hash")
-            .expect("no block");
-        assert_eq!(U64::from(input_block_number), actual_block.number);
+            .expect("call to the overridden contract failed");
+        assert_eq!(H256::from_low_u64_be(18).as_bytes(), &result.0[..]);
+
+        assert!(InMemoryNode::<HttpForkSource>::default()
+            .call(request, None)
+            .await
+            .is_err());
+        assert!(system_contracts::Options::BuiltIn
+            .with_override("Unknown", vec![0u8; 32])
+            .is_err());
     }
 
     #[tokio::test]
-    async fn test_get_block_by_number_uses_locally_available_for_latest_alike_blocks() {
-        for block_number in [
-            BlockNumber::Pending,
-            BlockNumber::Committed,
-            BlockNumber::Finalized,
+    async fn test_try_new_fails_on_missing_system_contracts() {
+        for options in [
+            system_contracts::Options::Custom {
+                bootloader_path: "/nonexistent/bootloader.zbin".into(),
+                default_account_path: "/nonexistent/DefaultAccount.zbin".into(),
+            },
+            system_contracts::Options::LocalFrom("/nonexistent/system-contracts".into()),
         ] {
-            let input_block_number = 10;
-            let mock_server = MockServer::run_with_config(ForkBlockConfig {
-                number: input_block_number,
-                hash: H256::repeat_byte(0xab),
-                transaction_count: 0,
-            });
-            let node = InMemoryNode::<HttpForkSource>::new(
-                Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-                crate::node::ShowCalls::None,
-                ShowStorageLogs::None,
-                ShowVMDetails::None,
-                ShowGasDetails::None,
-                false,
-                &system_contracts::Options::BuiltIn,
+            let result = InMemoryNode::<HttpForkSource>::try_new(
+                None,
+                &NodeConfig {
+                    system_contracts_options: options,
+                    ..Default::default()
+                },
             );
 
-            let actual_block = node
-                .get_block_by_number(block_number, false)
-                .await
-                .expect("failed fetching block by hash")
-                .expect("no block");
-            assert_eq!(
-                U64::from(input_block_number),
-                actual_block.number,
-                "case {}",
-                block_number,
-            );
+            let err = result.err().expect("node creation should fail");
+            assert!(err.starts_with("Failed reading bytecode"), "{}", err);
         }
     }
 
     #[tokio::test]
-    async fn test_get_block_transaction_count_by_hash_for_produced_block() {
+    async fn test_call_and_estimate_without_from_use_a_funded_zero_address() {
         let node = InMemoryNode::<HttpForkSource>::default();
+        let request = zksync_types::transaction_request::CallRequest {
+            to: Some(H160::repeat_byte(0x01)),
+            value: Some(U256::from(10u64.pow(18))),
+            ..Default::default()
+        };
 
-        let expected_block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
-        let actual_transaction_count = node
-            .get_block_transaction_count_by_hash(expected_block_hash)
+        node.call(request.clone(), None)
             .await
-            .expect("failed fetching block by hash")
-            .expect("no result");
-
-        assert_eq!(U256::from(1), actual_transaction_count);
+            .expect("call without from failed");
+        node.inner
+            .read()
+            .unwrap()
+            .estimate_gas_impl(request)
+            .expect("estimation without from failed");
+
+        // the balance is only credited for the duration of the call
+        let balance = node
+            .get_balance(H160::zero(), None)
+            .await
+            .expect("failed getting balance");
+        assert_eq!(U256::zero(), balance);
     }
 
     #[tokio::test]
-    async fn test_get_block_transaction_count_by_hash_uses_fork_source() {
+    async fn test_call_on_fork_block_reads_state_at_that_block() {
+        let address = H160::repeat_byte(0x01);
+        let value = H256::repeat_byte(0x03);
         let mock_server = MockServer::run_with_config(ForkBlockConfig {
             number: 10,
             hash: H256::repeat_byte(0xab),
             transaction_count: 0,
         });
-        let input_block_hash = H256::repeat_byte(0x01);
-        let input_transaction_count = 1;
         mock_server.expect(
             serde_json::json!({
                 "jsonrpc": "2.0",
                 "id": 0,
-                "method": "eth_getBlockTransactionCountByHash",
+                "method": "eth_getStorageAt",
                 "params": [
-                    format!("{:#x}", input_block_hash),
+                    format!("{address:#x}"),
+                    "0x2",
+                    "0x8",
                 ],
             }),
             serde_json::json!({
                 "jsonrpc": "2.0",
                 "id": 0,
-                "result": format!("{:#x}", input_transaction_count),
+                "result": format!("{value:#x}"),
             }),
         );
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
-        );
-
-        let actual_transaction_count = node
-            .get_block_transaction_count_by_hash(input_block_hash)
-            .await
-            .expect("failed fetching block by hash")
-            .expect("no result");
-
-        assert_eq!(
-            U256::from(input_transaction_count),
-            actual_transaction_count
-        );
+        let node = testing::forked_node(&mock_server).await;
+        let key = StorageKey::new(AccountTreeId::new(address), u256_to_h256(U256::from(2)));
+        let mut inner = node.inner.write().unwrap();
+        // pretend two blocks were produced locally on top of the fork
+        inner.current_miniblock += 2;
+        inner.fork_storage.set_value(key, H256::repeat_byte(0x04));
+
+        let at_block = |number: u64| {
+            Some(BlockIdVariant::BlockNumber(BlockNumber::Number(U64::from(
+                number,
+            ))))
+        };
+        assert_eq!(Ok(None), inner.historical_fork_block(None));
+        assert_eq!(Ok(None), inner.historical_fork_block(at_block(12)));
+        assert_eq!(Ok(Some(10)), inner.historical_fork_block(at_block(10)));
+        assert_eq!(Ok(Some(8)), inner.historical_fork_block(at_block(8)));
+        assert!(inner.historical_fork_block(at_block(11)).is_err());
+        assert!(inner.historical_fork_block(at_block(13)).is_err());
+
+        // the local write is not visible at the fork block
+        let mut storage = ForkStorageAtBlock::new(&inner.fork_storage, 8);
+        assert_eq!(value, storage.read_value(&key));
     }
 
     #[tokio::test]
-    async fn test_get_block_transaction_count_by_number_for_produced_block() {
+    async fn test_get_transaction_by_block_hash_and_index_for_produced_block() {
         let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        let block_hash = testing::apply_tx(&node, tx_hash);
 
-        testing::apply_tx(&node, H256::repeat_byte(0x01));
-        let actual_transaction_count = node
-            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(1)))
+        let actual_tx = node
+            .get_transaction_by_block_hash_and_index(block_hash, U64::from(0))
             .await
-            .expect("failed fetching block by hash")
-            .expect("no result");
+            .expect("failed fetching transaction")
+            .expect("no transaction");
+        let out_of_range = node
+            .get_transaction_by_block_hash_and_index(block_hash, U64::from(1))
+            .await
+            .expect("failed fetching transaction");
 
-        assert_eq!(U256::from(1), actual_transaction_count);
+        assert_eq!(tx_hash, actual_tx.hash);
+        assert_eq!(Some(block_hash), actual_tx.block_hash);
+        assert!(out_of_range.is_none());
     }
 
     #[tokio::test]
-    async fn test_get_block_transaction_count_by_number_uses_fork_source() {
-        let mock_server = MockServer::run_with_config(ForkBlockConfig {
-            number: 10,
-            hash: H256::repeat_byte(0xab),
-            transaction_count: 0,
-        });
-        let input_block_number = 1;
-        let input_transaction_count = 1;
-        mock_server.expect(
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 0,
-                "method": "eth_getBlockTransactionCountByNumber",
-                "params": [
-                    format!("{:#x}", input_block_number),
-                ],
-            }),
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 0,
-                "result": format!("{:#x}", input_transaction_count),
-            }),
-        );
-
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
-        );
+    async fn test_get_transaction_by_block_number_and_index_for_produced_block() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let tx_hash = H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
 
-        let actual_transaction_count = node
-            .get_block_transaction_count_by_number(BlockNumber::Number(U64::from(1)))
+        let actual_tx = node
+            .get_transaction_by_block_number_and_index(BlockNumber::Latest, U64::from(0))
             .await
-            .expect("failed fetching block by hash")
-            .expect("no result");
+            .expect("failed fetching transaction")
+            .expect("no transaction");
+        let out_of_range = node
+            .get_transaction_by_block_number_and_index(
+                BlockNumber::Number(U64::from(1)),
+                U64::from(1),
+            )
+            .await
+            .expect("failed fetching transaction");
 
-        assert_eq!(
-            U256::from(input_transaction_count),
-            actual_transaction_count
-        );
+        assert_eq!(tx_hash, actual_tx.hash);
+        assert_eq!(Some(U64::from(1)), actual_tx.block_number);
+        assert!(out_of_range.is_none());
     }
 
     #[tokio::test]
-    async fn test_get_block_transaction_count_by_number_earliest_uses_fork_source() {
+    async fn test_get_transaction_by_block_hash_and_index_uses_fork_source() {
         let mock_server = MockServer::run_with_config(ForkBlockConfig {
             number: 10,
             hash: H256::repeat_byte(0xab),
             transaction_count: 0,
         });
-        let input_transaction_count = 1;
+        let input_block_hash = H256::repeat_byte(0x01);
+        let input_tx_hash = H256::repeat_byte(0x02);
         mock_server.expect(
             serde_json::json!({
                 "jsonrpc": "2.0",
                 "id": 0,
-                "method": "eth_getBlockTransactionCountByNumber",
+                "method": "eth_getTransactionByBlockHashAndIndex",
                 "params": [
-                    "earliest",
+                    format!("{:#x}", input_block_hash),
+                    "0x1",
                 ],
             }),
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 0,
-                "result": format!("{:#x}", input_transaction_count),
-            }),
-        );
-
-        let node = InMemoryNode::<HttpForkSource>::new(
-            Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-            crate::node::ShowCalls::None,
-            ShowStorageLogs::None,
-            ShowVMDetails::None,
-            ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
-        );
-
-        let actual_transaction_count = node
-            .get_block_transaction_count_by_number(BlockNumber::Earliest)
-            .await
-            .expect("failed fetching block by hash")
-            .expect("no result");
-
-        assert_eq!(
-            U256::from(input_transaction_count),
-            actual_transaction_count
+            testing::TransactionResponseBuilder::new()
+                .set_hash(input_tx_hash)
+                .build(),
         );
-    }
-
-    #[tokio::test]
-    async fn test_get_block_transaction_count_by_number_latest_alike_uses_fork_source() {
-        for block_number in [
-            BlockNumber::Latest,
-            BlockNumber::Pending,
-            BlockNumber::Committed,
-            BlockNumber::Finalized,
-        ] {
-            let input_transaction_count = 1;
-            let mock_server = MockServer::run_with_config(ForkBlockConfig {
-                number: 10,
-                transaction_count: input_transaction_count,
-                hash: H256::repeat_byte(0xab),
-            });
-
-            let node = InMemoryNode::<HttpForkSource>::new(
-                Some(ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None).await),
-                crate::node::ShowCalls::None,
-                ShowStorageLogs::None,
-                ShowVMDetails::None,
-                ShowGasDetails::None,
-                false,
-                &system_contracts::Options::BuiltIn,
-            );
-
-            let actual_transaction_count = node
-                .get_block_transaction_count_by_number(block_number)
-                .await
-                .expect("failed fetching block by hash")
-                .expect("no result");
-
-            assert_eq!(
-                U256::from(input_transaction_count),
-                actual_transaction_count,
-                "case {}",
-                block_number,
-            );
-        }
-    }
-
-    #[tokio::test]
-    async fn test_get_transaction_receipt_uses_produced_block_hash() {
-        let node = InMemoryNode::<HttpForkSource>::default();
-        let tx_hash = H256::repeat_byte(0x01);
-        let expected_block_hash = testing::apply_tx(&node, tx_hash);
+        let node = testing::forked_node(&mock_server).await;
 
-        let actual_tx_receipt = node
-            .get_transaction_receipt(tx_hash)
+        let actual_tx = node
+            .get_transaction_by_block_hash_and_index(input_block_hash, U64::from(1))
             .await
-            .expect("failed fetching transaction receipt by hash")
-            .expect("no transaction receipt");
+            .expect("failed fetching transaction")
+            .expect("no transaction");
 
-        assert_eq!(Some(expected_block_hash), actual_tx_receipt.block_hash);
+        assert_eq!(input_tx_hash, actual_tx.hash);
     }
 }
@@ -0,0 +1,339 @@
+//! Merkle commitment over the node storage, used to serve `eth_getProof`.
+//!
+//! zkSync keeps all the state in a flat map from storage keys to values: balances, nonces and
+//! code hashes are slots of system contracts. The commitment is a sparse Merkle tree of depth 256
+//! keyed by [`StorageKey::hashed_key`], where:
+//! - a leaf is `keccak256(hashed_key ‖ value)`, or zero if the value is zero,
+//! - an inner node is `keccak256(left ‖ right)`, or zero if both children are zero.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use zksync_basic_types::{web3::signing::keccak256, Address, H256, U256};
+use zksync_types::{StorageKey, StorageValue};
+
+/// Depth of the tree: one level per bit of the hashed storage key.
+pub const TREE_DEPTH: usize = 256;
+
+/// Proof of the value of a single storage slot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageProof {
+    /// The slot, in the storage of the contract the proof was requested for.
+    pub key: H256,
+    pub value: H256,
+    /// Sibling hashes from the leaf up to the root.
+    pub proof: Vec<H256>,
+}
+
+/// Response of `eth_getProof`. All the proofs verify against `storageHash`, the state root of
+/// the block the proof was requested for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: Address,
+    pub balance: U256,
+    pub nonce: U256,
+    pub code_hash: H256,
+    pub storage_hash: H256,
+    /// Proofs of the balance (`L2EthToken`), nonce (`NonceHolder`) and code hash
+    /// (`AccountCodeStorage`) slots of the account, in this order.
+    pub account_proof: Vec<StorageProof>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// Sparse Merkle tree over the storage, updated slot by slot as the storage changes.
+///
+/// Only the hashes of the nodes with leaves under both children are kept. The other nodes lie on
+/// the path above a single leaf or branch, and are hashed when needed.
+#[derive(Debug, Default, Clone)]
+pub struct StorageTree {
+    /// Leaf hashes of the non-zero slots, by hashed key.
+    leaves: BTreeMap<H256, H256>,
+    /// Hashes of the nodes with leaves under both children, by depth and path (the hashed key
+    /// of any leaf under the node, with the bits from `depth` on cleared).
+    branches: HashMap<(usize, H256), H256>,
+}
+
+impl StorageTree {
+    pub fn new(storage: impl IntoIterator<Item = (StorageKey, StorageValue)>) -> Self {
+        let leaves: BTreeMap<H256, H256> = storage
+            .into_iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(key, value)| {
+                let hashed_key = key.hashed_key();
+                (hashed_key, leaf_hash(hashed_key, value))
+            })
+            .collect();
+        let mut tree = Self {
+            leaves,
+            branches: Default::default(),
+        };
+        let leaves = tree.leaves.clone().into_iter().collect::<Vec<_>>();
+        tree.hash_branches(&leaves, 0);
+        tree
+    }
+
+    /// Sets the value of the slot with the given hashed key, and rehashes the nodes on its path.
+    pub fn update(&mut self, hashed_key: H256, value: StorageValue) {
+        let leaf = leaf_hash(hashed_key, value);
+        let unchanged = if leaf.is_zero() {
+            self.leaves.remove(&hashed_key).is_none()
+        } else {
+            self.leaves.insert(hashed_key, leaf) == Some(leaf)
+        };
+        if unchanged {
+            return;
+        }
+
+        for depth in (0..TREE_DEPTH).rev() {
+            let path = clear_bits_from(hashed_key, depth);
+            let right_path = set_bit(path, depth);
+            if self.has_leaves(depth + 1, path) && self.has_leaves(depth + 1, right_path) {
+                let hash = branch_hash(
+                    self.node_hash(depth + 1, path),
+                    self.node_hash(depth + 1, right_path),
+                );
+                self.branches.insert((depth, path), hash);
+            } else {
+                self.branches.remove(&(depth, path));
+            }
+        }
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root(&self) -> H256 {
+        self.node_hash(0, H256::zero())
+    }
+
+    /// Returns the sibling hashes on the path of `hashed_key`, from the leaf up to the root.
+    pub fn proof(&self, hashed_key: H256) -> Vec<H256> {
+        (0..TREE_DEPTH)
+            .rev()
+            .map(|depth| {
+                let path = clear_bits_from(hashed_key, depth);
+                let sibling_path = if bit(&hashed_key, depth) {
+                    path
+                } else {
+                    set_bit(path, depth)
+                };
+                self.node_hash(depth + 1, sibling_path)
+            })
+            .collect()
+    }
+
+    /// Returns true if there is a leaf under the node at `depth` on `path`.
+    fn has_leaves(&self, depth: usize, path: H256) -> bool {
+        self.leaves
+            .range(path..=set_bits_from(path, depth))
+            .next()
+            .is_some()
+    }
+
+    /// Returns the hash of the node at `depth` on `path`, from the hash of the highest leaf or
+    /// branch under it.
+    fn node_hash(&self, depth: usize, path: H256) -> H256 {
+        let mut leaves = self.leaves.range(path..=set_bits_from(path, depth));
+        let (first_key, first_leaf) = match leaves.next() {
+            Some(first) => first,
+            None => return H256::zero(),
+        };
+        let (bottom, mut hash) = match leaves.next_back() {
+            None => (TREE_DEPTH, *first_leaf),
+            Some((last_key, _)) => {
+                let branch_depth = common_prefix_len(first_key, last_key);
+                let branch_path = clear_bits_from(*first_key, branch_depth);
+                (
+                    branch_depth,
+                    self.branches
+                        .get(&(branch_depth, branch_path))
+                        .copied()
+                        .unwrap_or_default(),
+                )
+            }
+        };
+        for height in (depth..bottom).rev() {
+            hash = if bit(first_key, height) {
+                branch_hash(H256::zero(), hash)
+            } else {
+                branch_hash(hash, H256::zero())
+            };
+        }
+        hash
+    }
+
+    /// Hashes the subtree at `depth` holding `leaves`, which all share the same path down to it,
+    /// and records the hashes of its branches.
+    fn hash_branches(&mut self, leaves: &[(H256, H256)], depth: usize) -> H256 {
+        match leaves {
+            [] => H256::zero(),
+            [(_, leaf)] if depth == TREE_DEPTH => *leaf,
+            [(key, _), ..] => {
+                let (left, right) =
+                    leaves.split_at(leaves.partition_point(|(key, _)| !bit(key, depth)));
+                let hash = branch_hash(
+                    self.hash_branches(left, depth + 1),
+                    self.hash_branches(right, depth + 1),
+                );
+                if !left.is_empty() && !right.is_empty() {
+                    self.branches
+                        .insert((depth, clear_bits_from(*key, depth)), hash);
+                }
+                hash
+            }
+        }
+    }
+}
+
+/// Checks that `value` is stored at `hashed_key` in the tree with the given `root`.
+pub fn verify_proof(root: H256, hashed_key: H256, value: H256, proof: &[H256]) -> bool {
+    if proof.len() != TREE_DEPTH {
+        return false;
+    }
+    let mut hash = leaf_hash(hashed_key, value);
+    for (height, sibling) in proof.iter().enumerate() {
+        hash = if bit(&hashed_key, TREE_DEPTH - 1 - height) {
+            branch_hash(*sibling, hash)
+        } else {
+            branch_hash(hash, *sibling)
+        };
+    }
+    hash == root
+}
+
+fn leaf_hash(hashed_key: H256, value: H256) -> H256 {
+    if value.is_zero() {
+        return H256::zero();
+    }
+    H256(keccak256(
+        &[hashed_key.as_bytes(), value.as_bytes()].concat(),
+    ))
+}
+
+fn branch_hash(left: H256, right: H256) -> H256 {
+    if left.is_zero() && right.is_zero() {
+        return H256::zero();
+    }
+    H256(keccak256(&[left.as_bytes(), right.as_bytes()].concat()))
+}
+
+/// Returns the bit of `key` at `depth`, starting from the most significant one.
+fn bit(key: &H256, depth: usize) -> bool {
+    key.0[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+fn set_bit(mut key: H256, depth: usize) -> H256 {
+    key.0[depth / 8] |= 0x80 >> (depth % 8);
+    key
+}
+
+/// Clears the bits of `key` from `depth` on, giving the path of the node at `depth` above it.
+fn clear_bits_from(key: H256, depth: usize) -> H256 {
+    let mut path = H256::zero();
+    for index in 0..depth {
+        if bit(&key, index) {
+            path = set_bit(path, index);
+        }
+    }
+    path
+}
+
+/// Sets the bits of `path` from `depth` on, giving the last key under the node at `depth`.
+fn set_bits_from(mut path: H256, depth: usize) -> H256 {
+    for index in depth..TREE_DEPTH {
+        path = set_bit(path, index);
+    }
+    path
+}
+
+/// Returns the number of leading bits `a` and `b` have in common.
+fn common_prefix_len(a: &H256, b: &H256) -> usize {
+    (0..TREE_DEPTH)
+        .find(|&depth| bit(a, depth) != bit(b, depth))
+        .unwrap_or(TREE_DEPTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn storage(entries: &[(u64, u64)]) -> HashMap<StorageKey, StorageValue> {
+        entries
+            .iter()
+            .map(|(slot, value)| {
+                (
+                    StorageKey::new(
+                        Address::repeat_byte(0x01).into(),
+                        H256::from_low_u64_be(*slot),
+                    ),
+                    H256::from_low_u64_be(*value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        let storage = storage(&[(1, 10), (2, 20), (3, 0), (4, 40)]);
+        let tree = StorageTree::new(storage.clone());
+        let root = tree.root();
+
+        for (key, value) in &storage {
+            let proof = tree.proof(key.hashed_key());
+            assert!(verify_proof(root, key.hashed_key(), *value, &proof));
+            assert!(!verify_proof(
+                root,
+                key.hashed_key(),
+                H256::from_low_u64_be(99),
+                &proof
+            ));
+        }
+
+        // slots that were never written are proven to be zero
+        let missing = StorageKey::new(Address::repeat_byte(0x02).into(), H256::zero());
+        let proof = tree.proof(missing.hashed_key());
+        assert!(verify_proof(
+            root,
+            missing.hashed_key(),
+            H256::zero(),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_root_ignores_zero_values_and_changes_with_state() {
+        let root = StorageTree::new(storage(&[(1, 10)])).root();
+
+        assert_eq!(root, StorageTree::new(storage(&[(1, 10), (2, 0)])).root());
+        assert_ne!(root, StorageTree::new(storage(&[(1, 11)])).root());
+        assert_eq!(H256::zero(), StorageTree::default().root());
+    }
+
+    #[test]
+    fn test_updates_match_a_tree_built_from_scratch() {
+        let mut tree = StorageTree::default();
+        let mut expected = storage(&[]);
+        for (slot, value) in [(1, 10), (2, 20), (3, 30), (2, 21), (1, 0), (4, 40), (3, 0)] {
+            let key = StorageKey::new(
+                Address::repeat_byte(0x01).into(),
+                H256::from_low_u64_be(slot),
+            );
+            tree.update(key.hashed_key(), H256::from_low_u64_be(value));
+            expected.insert(key, H256::from_low_u64_be(value));
+
+            let rebuilt = StorageTree::new(expected.clone());
+            assert_eq!(rebuilt.root(), tree.root());
+            for key in expected.keys() {
+                assert_eq!(
+                    rebuilt.proof(key.hashed_key()),
+                    tree.proof(key.hashed_key())
+                );
+            }
+        }
+
+        for (key, _) in expected {
+            tree.update(key.hashed_key(), H256::zero());
+        }
+        assert_eq!(H256::zero(), tree.root());
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    filters::LogFilter, fork::ForkSource, logging_middleware::Meta, node::InMemoryNodeInner,
+};
+use jsonrpc_core::{Error, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{
+    typed::{Sink, Subscriber},
+    SubscriptionId,
+};
+use serde::Serialize;
+use zksync_basic_types::U256;
+use zksync_types::api::{Block, Log, TransactionVariant};
+use zksync_web3_decl::types::Filter;
+
+/// Subscription type that pushes a header every time a block is sealed.
+const SUBSCRIPTION_NEW_HEADS: &str = "newHeads";
+/// Subscription type that pushes every log matching the `{address, topics}` filter.
+const SUBSCRIPTION_LOGS: &str = "logs";
+
+/// Payload of an `eth_subscription` notification.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum PubSubResult {
+    /// Block without its transactions, sent to `newHeads` subscribers.
+    Header(Block<TransactionVariant>),
+    /// Log in the same shape as returned by `eth_getLogs`, sent to `logs` subscribers.
+    Log(Log),
+}
+
+/// An active subscription together with the sink used to push its notifications.
+enum Subscription {
+    NewHeads(Sink<PubSubResult>),
+    Logs {
+        filter: LogFilter,
+        sink: Sink<PubSubResult>,
+    },
+}
+
+/// Keeps track of the active `eth_subscribe` subscriptions.
+#[derive(Default)]
+pub struct EthSubscriptions {
+    id_counter: U256,
+    subscriptions: HashMap<SubscriptionId, Subscription>,
+}
+
+impl EthSubscriptions {
+    /// Assigns a new id to the subscriber and registers it for `newHeads` notifications.
+    pub fn subscribe_new_heads(&mut self, subscriber: Subscriber<PubSubResult>) {
+        if let Some((id, sink)) = self.assign_id(subscriber) {
+            self.subscriptions.insert(id, Subscription::NewHeads(sink));
+        }
+    }
+
+    /// Assigns a new id to the subscriber and registers it for the logs matching `filter`.
+    pub fn subscribe_logs(&mut self, subscriber: Subscriber<PubSubResult>, filter: LogFilter) {
+        if let Some((id, sink)) = self.assign_id(subscriber) {
+            self.subscriptions
+                .insert(id, Subscription::Logs { filter, sink });
+        }
+    }
+
+    fn assign_id(
+        &mut self,
+        subscriber: Subscriber<PubSubResult>,
+    ) -> Option<(SubscriptionId, Sink<PubSubResult>)> {
+        self.id_counter = self.id_counter.saturating_add(U256::one());
+        let id = SubscriptionId::String(format!("{:#x}", self.id_counter));
+        match subscriber.assign_id(id.clone()) {
+            Ok(sink) => Some((id, sink)),
+            Err(_) => {
+                log::warn!(
+                    "subscriber disconnected before subscription {:?} was assigned",
+                    id
+                );
+                None
+            }
+        }
+    }
+
+    /// Removes the subscription, returning `true` if it existed.
+    pub fn unsubscribe(&mut self, id: &SubscriptionId) -> bool {
+        self.subscriptions.remove(id).is_some()
+    }
+
+    /// Pushes the header of a freshly sealed block to every `newHeads` subscriber.
+    /// Subscriptions whose client has gone away are dropped.
+    pub fn notify_new_block(&mut self, block: &Block<TransactionVariant>) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+
+        let header = PubSubResult::Header(Block {
+            transactions: vec![],
+            ..block.clone()
+        });
+        self.subscriptions
+            .retain(|_, subscription| match subscription {
+                Subscription::NewHeads(sink) => sink.notify(Ok(header.clone())).is_ok(),
+                Subscription::Logs { .. } => true,
+            });
+    }
+
+    /// Pushes the logs of a freshly sealed block, in the order they were emitted, to every `logs`
+    /// subscriber whose filter matches them. Subscriptions whose client has gone away are dropped.
+    pub fn notify_new_logs(&mut self, logs: &[Log], latest_block: u64) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+
+        self.subscriptions
+            .retain(|_, subscription| match subscription {
+                Subscription::NewHeads(_) => true,
+                Subscription::Logs { filter, sink } => logs
+                    .iter()
+                    .filter(|log| filter.matches(log, latest_block))
+                    .all(|log| sink.notify(Ok(PubSubResult::Log(log.clone()))).is_ok()),
+            });
+    }
+}
+
+/// Implementation of EthPubSubNamespaceImpl
+pub struct EthPubSubNamespaceImpl<S> {
+    node: Arc<RwLock<InMemoryNodeInner<S>>>,
+}
+
+impl<S> EthPubSubNamespaceImpl<S> {
+    /// Creates a new `EthPubSub` instance with the given `node`.
+    pub fn new(node: Arc<RwLock<InMemoryNodeInner<S>>>) -> Self {
+        Self { node }
+    }
+}
+
+#[rpc]
+pub trait EthPubSubNamespaceT {
+    type Metadata;
+
+    /// Starts a subscription. Only available over WebSocket connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The subscription type, either `newHeads` or `logs`
+    /// * `params` - The `{address, topics}` filter for `logs` subscriptions
+    #[pubsub(subscription = "eth_subscription", subscribe, name = "eth_subscribe")]
+    fn subscribe(
+        &self,
+        meta: Self::Metadata,
+        subscriber: Subscriber<PubSubResult>,
+        kind: String,
+        params: Option<serde_json::Value>,
+    );
+
+    /// Cancels a subscription. Subscriptions are also cancelled when the client disconnects.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id returned by `eth_subscribe`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with a `bool` representing whether the subscription existed.
+    #[pubsub(
+        subscription = "eth_subscription",
+        unsubscribe,
+        name = "eth_unsubscribe"
+    )]
+    fn unsubscribe(&self, meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool>;
+}
+
+impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> EthPubSubNamespaceT
+    for EthPubSubNamespaceImpl<S>
+{
+    type Metadata = Meta;
+
+    fn subscribe(
+        &self,
+        _meta: Self::Metadata,
+        subscriber: Subscriber<PubSubResult>,
+        kind: String,
+        params: Option<serde_json::Value>,
+    ) {
+        let log_filter = match kind.as_str() {
+            SUBSCRIPTION_NEW_HEADS => None,
+            SUBSCRIPTION_LOGS => {
+                let filter = match params {
+                    Some(params) => serde_json::from_value::<Filter>(params),
+                    None => Ok(Filter::default()),
+                };
+                match filter {
+                    Ok(filter) => Some(LogFilter::new(filter)),
+                    Err(err) => {
+                        let _ = subscriber.reject(Error::invalid_params(format!(
+                            "invalid logs filter: {}",
+                            err
+                        )));
+                        return;
+                    }
+                }
+            }
+            _ => {
+                let _ = subscriber.reject(Error::invalid_params(format!(
+                    "unsupported subscription type '{}'",
+                    kind
+                )));
+                return;
+            }
+        };
+
+        let mut inner = match self.node.write() {
+            Ok(inner) => inner,
+            Err(_) => {
+                let _ = subscriber.reject(Error::internal_error());
+                return;
+            }
+        };
+        match log_filter {
+            Some(filter) => inner.subscriptions.subscribe_logs(subscriber, filter),
+            None => inner.subscriptions.subscribe_new_heads(subscriber),
+        }
+    }
+
+    fn unsubscribe(&self, _meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+        match self.node.write() {
+            Ok(mut inner) => Ok(inner.subscriptions.unsubscribe(&id)),
+            Err(_) => Err(Error::internal_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode, testing};
+    use futures::StreamExt;
+    use zksync_basic_types::{H160, H256};
+    use zksync_types::L2_ETH_TOKEN_ADDRESS;
+
+    #[tokio::test]
+    async fn test_new_heads_subscription_receives_sealed_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+        let (subscriber, id_rx, mut notifications) = Subscriber::new_test("eth_subscription");
+
+        pubsub.subscribe(Meta::default(), subscriber, String::from("newHeads"), None);
+        let id = id_rx
+            .await
+            .expect("subscription id was not sent")
+            .expect("subscription was rejected");
+
+        let block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        let notification = notifications
+            .next()
+            .await
+            .expect("no notification received");
+        assert!(notification.contains(&format!("{:#x}", block_hash)));
+
+        assert!(pubsub.unsubscribe(None, id.clone()).unwrap());
+        assert!(!pubsub.unsubscribe(None, id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_new_heads_subscription_is_removed_when_client_disconnects() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+        let (subscriber, id_rx, notifications) = Subscriber::new_test("eth_subscription");
+
+        pubsub.subscribe(Meta::default(), subscriber, String::from("newHeads"), None);
+        let id = id_rx.await.unwrap().unwrap();
+        drop(notifications);
+
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        assert!(!pubsub.unsubscribe(None, id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_are_removed_when_the_session_is_dropped() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let mut io = jsonrpc_core::MetaIoHandler::<Meta>::default();
+        io.extend_with(EthPubSubNamespaceImpl::new(node.get_inner()).to_delegate());
+        let (sender, _receiver) = futures::channel::mpsc::unbounded();
+        let session = Arc::new(jsonrpc_pubsub::Session::new(sender));
+
+        let response = io
+            .handle_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"eth_subscribe","params":["newHeads"]}"#,
+                Meta::new(Arc::clone(&session)),
+            )
+            .await
+            .expect("no response");
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let id = SubscriptionId::String(
+            response["result"]
+                .as_str()
+                .expect("subscription was rejected")
+                .to_string(),
+        );
+
+        let is_subscribed = || {
+            node.get_inner()
+                .read()
+                .map(|inner| inner.subscriptions.subscriptions.contains_key(&id))
+                .expect("failed reading subscriptions")
+        };
+        assert!(is_subscribed());
+
+        // the session unsubscribes its subscriptions in the background when it is dropped
+        drop(session);
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while is_subscribed() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("subscription was not removed");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_subscription_is_rejected() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+        let (subscriber, id_rx, _notifications) = Subscriber::new_test("eth_subscription");
+
+        pubsub.subscribe(Meta::default(), subscriber, String::from("syncing"), None);
+
+        assert!(id_rx.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_logs_subscriptions_use_independent_filters() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+        let (eth_subscriber, eth_id_rx, mut eth_notifications) =
+            Subscriber::new_test("eth_subscription");
+        let (other_subscriber, other_id_rx, mut other_notifications) =
+            Subscriber::new_test("eth_subscription");
+
+        pubsub.subscribe(
+            Meta::default(),
+            eth_subscriber,
+            String::from("logs"),
+            Some(serde_json::json!({ "address": L2_ETH_TOKEN_ADDRESS })),
+        );
+        pubsub.subscribe(
+            Meta::default(),
+            other_subscriber,
+            String::from("logs"),
+            Some(serde_json::json!({ "address": H160::repeat_byte(0xab) })),
+        );
+        eth_id_rx.await.unwrap().unwrap();
+        other_id_rx.await.unwrap().unwrap();
+
+        testing::apply_tx(&node, H256::repeat_byte(0x01));
+
+        let notification = eth_notifications
+            .next()
+            .await
+            .expect("no notification received");
+        assert!(notification.contains(&format!("{:#x}", L2_ETH_TOKEN_ADDRESS)));
+        assert!(other_notifications.try_next().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_logs_subscription_with_invalid_filter_is_rejected() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let pubsub = EthPubSubNamespaceImpl::new(node.get_inner());
+        let (subscriber, id_rx, _notifications) = Subscriber::new_test("eth_subscription");
+
+        pubsub.subscribe(
+            Meta::default(),
+            subscriber,
+            String::from("logs"),
+            Some(serde_json::json!({ "address": "not an address" })),
+        );
+
+        assert!(id_rx.await.unwrap().is_err());
+    }
+}
@@ -4,7 +4,9 @@ use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::Deserialize;
 use std::iter::FromIterator;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
@@ -14,18 +16,31 @@ use std::{
 use tokio::sync::RwLock;
 use tracing::warn;
 
+/// Default selector database, used unless another one is set with [set_resolver_url] or the
+/// `RESOLVER_URL` environment variable.
 static SELECTOR_DATABASE_URL: &str = "https://sig.eth.samczsun.com/api/v1/signatures";
 
+/// Whether selectors can be resolved over the network, see [set_enabled].
+static RESOLVER_ENABLED: AtomicBool = AtomicBool::new(true);
+
 /// The standard request timeout for API requests
 const REQ_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// How many request can time out before we decide this is a spurious connection
 const MAX_TIMEDOUT_REQ: usize = 4usize;
 
+/// Default maximum number of resolved selectors kept in the cache.
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// Maximum number of resolved selectors kept in the cache, see [set_cache_capacity].
+static CACHE_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CACHE_CAPACITY);
+
 /// A client that can request API data from `https://sig.eth.samczsun.com/api`
 #[derive(Debug, Clone)]
 pub struct SignEthClient {
     inner: reqwest::Client,
+    /// URL of the signatures endpoint of the selector database
+    base_url: String,
     /// Whether the connection is spurious, or API is down
     spurious_connection: Arc<AtomicBool>,
     /// How many requests timed out
@@ -50,12 +65,60 @@ lazy_static! {
             .map(|entry| (entry.abi, entry.name))
             .collect()
     };
-    static ref CACHE: RwLock<HashMap<String, Option<String>>> = RwLock::new(HashMap::new());
+    static ref CACHE: RwLock<SelectorCache> = RwLock::new(SelectorCache::default());
+    static ref RESOLVER_URL: std::sync::RwLock<Option<String>> = std::sync::RwLock::new(None);
+    /// Signatures resolved without the selector database, keyed by lowercase `0x` prefixed
+    /// selector. Holds common ERC-20/ERC-721 selectors, more can be added with
+    /// [load_signatures_from_file].
+    static ref LOCAL_SIGNATURES: std::sync::RwLock<HashMap<String, String>> =
+        std::sync::RwLock::new(
+            serde_json::from_slice(include_bytes!("data/signatures.json")).unwrap()
+        );
+}
+
+/// Resolved selectors, shared by all the transactions of the node session.
+/// Once full, the oldest entries are evicted first.
+#[derive(Debug, Default)]
+struct SelectorCache {
+    entries: HashMap<String, Option<String>>,
+    /// Keys in insertion order.
+    order: VecDeque<String>,
+}
+
+impl SelectorCache {
+    fn get(&self, key: &str) -> Option<&Option<String>> {
+        self.entries.get(key)
+    }
+
+    fn insert(&mut self, key: String, value: Option<String>) {
+        let capacity = CACHE_CAPACITY.load(Ordering::Relaxed);
+        if !self.entries.contains_key(&key) {
+            while self.entries.len() >= capacity {
+                match self.order.pop_front() {
+                    Some(oldest) => self.entries.remove(&oldest),
+                    None => return,
+                };
+            }
+            self.order.push_back(key.clone());
+        }
+        self.entries.insert(key, value);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
 }
 
 impl SignEthClient {
-    /// Creates a new client with default settings
+    /// Creates a new client with default settings, using the selector database from [resolver_url].
     pub fn new() -> reqwest::Result<Self> {
+        Self::with_url(resolver_url())
+    }
+
+    /// Creates a new client querying the selector database at `base_url`, which must serve the
+    /// same API as `https://sig.eth.samczsun.com/api/v1/signatures`.
+    pub fn with_url(base_url: impl Into<String>) -> reqwest::Result<Self> {
         let inner = reqwest::Client::builder()
             .default_headers(HeaderMap::from_iter([(
                 HeaderName::from_static("user-agent"),
@@ -65,6 +128,7 @@ impl SignEthClient {
             .build()?;
         Ok(Self {
             inner,
+            base_url: base_url.into(),
             spurious_connection: Arc::new(Default::default()),
             timedout_requests: Arc::new(Default::default()),
             max_timedout_requests: MAX_TIMEDOUT_REQ,
@@ -135,6 +199,13 @@ impl SignEthClient {
         selector: &str,
         selector_type: SelectorType,
     ) -> eyre::Result<Option<String>> {
+        if let Some(signature) = local_signature(selector) {
+            return Ok(Some(signature));
+        }
+        // skip the lookup when offline
+        if !is_enabled() {
+            return Ok(None);
+        }
         // exit early if spurious connection
         self.ensure_not_spurious()?;
 
@@ -159,8 +230,8 @@ impl SignEthClient {
         // using samczsun signature database over 4byte
         // see https://github.com/foundry-rs/foundry/issues/1672
         let url = match selector_type {
-            SelectorType::Function => format!("{SELECTOR_DATABASE_URL}?function={selector}"),
-            SelectorType::Event => format!("{SELECTOR_DATABASE_URL}?event={selector}"),
+            SelectorType::Function => format!("{}?function={selector}", self.base_url),
+            SelectorType::Event => format!("{}?event={selector}", self.base_url),
         };
 
         let res = self.get_text(&url).await?;
@@ -180,9 +251,13 @@ impl SignEthClient {
             SelectorType::Event => api_response.result.event,
         };
 
-        Ok(decoded
-            .get(selector)
-            .ok_or(eyre::eyre!("No signature found"))?
+        // An unknown selector is not an error - it is cached like any other result.
+        let candidates = match decoded.get(selector) {
+            Some(candidates) => candidates,
+            None => return Ok(None),
+        };
+
+        Ok(candidates
             .iter()
             .filter(|d| !d.filtered)
             .map(|d| d.name.clone())
@@ -212,40 +287,261 @@ pub enum SelectorType {
     Function,
     Event,
 }
+
+/// Returns the URL of the selector database: the one set with [set_resolver_url], otherwise the
+/// `RESOLVER_URL` environment variable, otherwise the default one.
+pub fn resolver_url() -> String {
+    RESOLVER_URL
+        .read()
+        .ok()
+        .and_then(|url| url.clone())
+        .or_else(|| std::env::var("RESOLVER_URL").ok())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| SELECTOR_DATABASE_URL.to_string())
+}
+
+/// Sets the URL of the selector database, overriding the `RESOLVER_URL` environment variable.
+pub fn set_resolver_url(url: String) {
+    if let Ok(mut resolver_url) = RESOLVER_URL.write() {
+        *resolver_url = Some(url);
+    }
+}
+
+/// Enables or disables resolving selectors over the network. When disabled (offline mode), the
+/// lookups that would need the selector database return `Ok(None)` without making any network call.
+pub fn set_enabled(enabled: bool) {
+    RESOLVER_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns false if the resolver is in offline mode.
+pub fn is_enabled() -> bool {
+    RESOLVER_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Merges the signatures from a user supplied JSON file into the local signature database. The
+/// file maps function selectors or event topics to signatures, like a `4byte.json` file:
+/// `{"0xa9059cbb": "transfer(address,uint256)"}`. User entries override the built-in ones.
+///
+/// Returns the number of loaded entries.
+pub fn load_signatures_from_file(path: &Path) -> Result<usize, String> {
+    let contents = fs::read(path)
+        .map_err(|err| format!("failed reading signatures '{:?}': {:?}", path, err))?;
+    let entries: HashMap<String, String> = serde_json::from_slice(&contents)
+        .map_err(|err| format!("failed parsing signatures '{:?}': {:?}", path, err))?;
+
+    let mut local_signatures = LOCAL_SIGNATURES
+        .write()
+        .map_err(|err| format!("failed acquiring lock for signatures: {:?}", err))?;
+    let count = entries.len();
+    for (selector, signature) in entries {
+        local_signatures.insert(cache_key(&selector), signature);
+    }
+
+    Ok(count)
+}
+
+/// Returns the signature of the selector from the local signature database (if any).
+fn local_signature(selector: &str) -> Option<String> {
+    LOCAL_SIGNATURES
+        .read()
+        .ok()
+        .and_then(|local_signatures| local_signatures.get(&cache_key(selector)).cloned())
+}
+
+/// Returns the cache key for the selector - the lowercase selector with a `0x` prefix,
+/// so that the same selector is only fetched once regardless of how it was formatted.
+fn cache_key(selector: &str) -> String {
+    format!(
+        "0x{}",
+        selector
+            .strip_prefix("0x")
+            .unwrap_or(selector)
+            .to_lowercase()
+    )
+}
+
+/// Returns the cached result for the selector, if it was already resolved.
+async fn get_cached(selector: &str) -> Option<Option<String>> {
+    CACHE.read().await.get(&cache_key(selector)).cloned()
+}
+
+/// Caches the result of a successful lookup. Failed lookups are not cached, so they are retried.
+async fn cache_result(selector: &str, result: &eyre::Result<Option<String>>) {
+    if let Ok(result) = result {
+        CACHE
+            .write()
+            .await
+            .insert(cache_key(selector), result.clone());
+    }
+}
+
+/// Sets the maximum number of resolved selectors kept in the cache. `0` disables the cache.
+pub fn set_cache_capacity(capacity: usize) {
+    CACHE_CAPACITY.store(capacity, Ordering::Relaxed);
+}
+
+/// Removes all the resolved selectors from the cache.
+pub async fn clear_cache() {
+    CACHE.write().await.clear();
+}
+
 /// Fetches a function signature given the selector using sig.eth.samczsun.com
 pub async fn decode_function_selector(selector: &str) -> eyre::Result<Option<String>> {
-    {
-        let cache = CACHE.read().await;
-        if let Some(result) = cache.get(selector) {
-            return Ok(result.clone());
-        }
+    if let Some(signature) = local_signature(selector) {
+        return Ok(Some(signature));
+    }
+    if let Some(result) = get_cached(selector).await {
+        return Ok(result);
+    }
+    if !is_enabled() {
+        return Ok(None);
     }
     let result = SignEthClient::new()?
         .decode_function_selector(selector)
         .await;
-    if let Ok(result) = &result {
-        let mut cache = CACHE.write().await;
-        cache.insert(selector.to_string(), result.clone());
-    }
+    cache_result(selector, &result).await;
     result
 }
 
+/// Fetches an event signature given the topic hash using sig.eth.samczsun.com
 pub async fn decode_event_selector(selector: &str) -> eyre::Result<Option<String>> {
-    {
-        let cache = CACHE.read().await;
-        if let Some(result) = cache.get(selector) {
-            return Ok(result.clone());
-        }
+    if let Some(signature) = local_signature(selector) {
+        return Ok(Some(signature));
+    }
+    if let Some(result) = get_cached(selector).await {
+        return Ok(result);
     }
     if let Some(r) = KNOWN_SIGNATURES.get(selector) {
         return Ok(Some(r.clone()));
     }
+    if !is_enabled() {
+        return Ok(None);
+    }
     let result = SignEthClient::new()?
         .decode_selector(selector, SelectorType::Event)
         .await;
-    if let Ok(result) = &result {
-        let mut cache = CACHE.write().await;
-        cache.insert(selector.to_string(), result.clone());
-    }
+    cache_result(selector, &result).await;
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cached_selectors_skip_lookup_until_cleared() {
+        // Both selectors are invalid, so they can only be resolved from the cache.
+        let function_selector = "0xZZZZZZZZ";
+        let event_selector = "0xYYYY";
+        CACHE.write().await.insert(
+            cache_key(function_selector),
+            Some("cached(uint256)".to_string()),
+        );
+        CACHE.write().await.insert(cache_key(event_selector), None);
+
+        assert_eq!(
+            Some("cached(uint256)".to_string()),
+            decode_function_selector("zzzzzzzz")
+                .await
+                .expect("failed decoding function selector")
+        );
+        assert_eq!(
+            None,
+            decode_event_selector(event_selector)
+                .await
+                .expect("failed decoding event selector")
+        );
+
+        clear_cache().await;
+
+        assert!(get_cached(function_selector).await.is_none());
+        assert!(get_cached(event_selector).await.is_none());
+    }
+
+    #[test]
+    fn test_selector_cache_evicts_oldest_entries() {
+        let mut cache = SelectorCache::default();
+        let capacity = CACHE_CAPACITY.load(Ordering::Relaxed);
+        for index in 0..capacity + 2 {
+            cache.insert(format!("0x{:08x}", index), None);
+        }
+        // updating an existing entry doesn't evict anything
+        cache.insert(format!("0x{:08x}", capacity + 1), Some("a()".to_string()));
+
+        assert_eq!(capacity, cache.entries.len());
+        assert_eq!(capacity, cache.order.len());
+        assert!(cache.get("0x00000000").is_none());
+        assert!(cache.get("0x00000001").is_none());
+        assert_eq!(
+            Some(&Some("a()".to_string())),
+            cache.get(&format!("0x{:08x}", capacity + 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_skips_network_calls() {
+        let _settings = crate::testing::GLOBAL_SETTINGS_LOCK.lock().await;
+        // nothing listens on this port, so any lookup would fail
+        let client = SignEthClient::with_url("http://127.0.0.1:1/api/v1/signatures")
+            .expect("failed creating client");
+        assert!(client
+            .decode_selector("0xabcdef01", SelectorType::Function)
+            .await
+            .is_err());
+
+        set_enabled(false);
+        let result = client
+            .decode_selector("0xabcdef01", SelectorType::Function)
+            .await;
+        // not cached, so these would go to the selector database
+        let function_result = decode_function_selector("0x12345678").await;
+        let event_result = decode_event_selector(
+            "0x1234567812345678123456781234567812345678123456781234567812345678",
+        )
+        .await;
+        set_enabled(true);
+
+        assert_eq!(None, result.expect("offline lookup failed"));
+        assert_eq!(None, function_result.expect("offline lookup failed"));
+        assert_eq!(None, event_result.expect("offline lookup failed"));
+        // offline results are not cached, they are looked up again once back online
+        assert!(get_cached("0x12345678").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_signatures_skip_the_selector_database() {
+        let dir = tempdir::TempDir::new("signatures").expect("failed creating temporary dir");
+        let path = dir.path().join("4byte.json");
+        fs::write(&path, r#"{"0xDEADBEEF": "customCall(uint256)"}"#)
+            .expect("failed writing signatures");
+        // built-in signatures are available before anything is loaded
+        assert_eq!(
+            Some("approve(address,uint256)".to_string()),
+            local_signature("0x095ea7b3")
+        );
+
+        assert_eq!(1, load_signatures_from_file(&path).expect("failed loading"));
+
+        // nothing listens on this port, so any lookup that is not local would fail
+        let client = SignEthClient::with_url("http://127.0.0.1:1/api/v1/signatures")
+            .expect("failed creating client");
+        assert_eq!(
+            Some("customCall(uint256)".to_string()),
+            client
+                .decode_function_selector("deadbeef")
+                .await
+                .expect("local lookup failed")
+        );
+        assert_eq!(
+            Some("Transfer(address,address,uint256)".to_string()),
+            client
+                .decode_selector(
+                    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                    SelectorType::Event
+                )
+                .await
+                .expect("local lookup failed")
+        );
+        assert!(load_signatures_from_file(&dir.path().join("missing.json")).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! State file written by `anvil_dumpState` and read by `anvil_loadState`, used to persist the
+//! node across restarts.
+//!
+//! The file is a JSON object with a `version` field, followed by the local storage (accounts,
+//! balances, nonces, contract storage), the known bytecodes, the block history, the executed and
+//! queued transactions, the tokens and the clock:
+//! ```json
+//! {
+//!   "version": 3,
+//!   "currentTimestamp": 1700000000000,
+//!   "storage": [{ "address": "0x...", "key": "0x...", "value": "0x..." }],
+//!   "factoryDeps": { "0x...": "0x..." },
+//!   "blocks": [...],
+//!   "transactions": [...],
+//!   ...
+//! }
+//! ```
+//! Only the part of the transaction results the receipts, logs and traces are built from is kept,
+//! the storage logs of the executed transactions are not part of it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use zksync_basic_types::{Address, Bytes, H256};
+use zksync_types::{
+    api::{Block, TransactionVariant},
+    l2::L2Tx,
+    l2_to_l1_log::L2ToL1Log,
+    vm_trace::Call,
+    Transaction, VmEvent,
+};
+use zksync_web3_decl::types::Token;
+
+/// Version of the state files written by this node. Files with another version are rejected.
+pub const STATE_VERSION: u32 = 3;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializableState {
+    pub version: u32,
+    pub current_timestamp: u64,
+    pub current_batch: u32,
+    pub current_miniblock: u64,
+    pub time_offset: u64,
+    pub l1_gas_price: u64,
+    pub l2_gas_price: u64,
+    pub fair_l2_gas_price: u64,
+    pub gas_per_pubdata: Option<u64>,
+    /// Every storage slot written locally, sorted by address and key.
+    pub storage: Vec<StorageEntry>,
+    /// Bytecodes stored locally, by hash.
+    pub factory_deps: BTreeMap<H256, Bytes>,
+    /// Local blocks, sorted by number.
+    pub blocks: Vec<Block<TransactionVariant>>,
+    pub block_hashes: BTreeMap<u64, H256>,
+    /// L1 gas price and fair L2 gas price each block was sealed with.
+    pub block_gas_prices: BTreeMap<u64, (u64, u64)>,
+    pub impersonated_accounts: BTreeSet<Address>,
+    pub code_history: BTreeMap<Address, Vec<(u64, H256)>>,
+    /// Transactions executed locally, sorted by block.
+    pub transactions: Vec<SerializableTransaction>,
+    /// Transactions waiting in the mempool, in the order they were received.
+    pub mempool: Vec<L2Tx>,
+    /// L1 transactions waiting in the priority queue, in the order they were received.
+    pub priority_queue: Vec<Transaction>,
+    pub next_priority_op_id: u64,
+    pub known_tokens: BTreeSet<Address>,
+    pub registered_tokens: Vec<Token>,
+    /// USD prices set locally, as decimal strings.
+    pub token_prices: BTreeMap<Address, String>,
+}
+
+/// Transaction executed by the node, with the part of its result the receipts, logs and traces
+/// are built from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializableTransaction {
+    pub tx: Transaction,
+    pub batch_number: u32,
+    pub miniblock_number: u64,
+    /// L2 gas price the transaction was executed at.
+    pub gas_price: u64,
+    pub success: bool,
+    pub revert_reason: Option<String>,
+    pub gas_refunded: u32,
+    /// Contract deployed by the transaction, if any.
+    pub contract_address: Option<Address>,
+    pub events: Vec<VmEvent>,
+    pub l2_to_l1_logs: Vec<L2ToL1Log>,
+    pub call_traces: Vec<Call>,
+    pub contracts_used: usize,
+    pub cycles_used: u32,
+    pub computational_gas_used: u32,
+}
+
+/// Value of a single storage slot.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageEntry {
+    pub address: Address,
+    pub key: H256,
+    pub value: H256,
+}
+
+impl SerializableState {
+    /// Reads the state file at `path`.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("failed opening state file {:?}: {}", path, err))?;
+        let state: SerializableState = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| format!("failed parsing state file {:?}: {}", path, err))?;
+        if state.version != STATE_VERSION {
+            return Err(format!(
+                "unsupported state file version {}, expected {}",
+                state.version, STATE_VERSION
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Writes the state to `path`, replacing the file if it exists.
+    pub fn to_file(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path)
+            .map_err(|err| format!("failed creating state file {:?}: {}", path, err))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .map_err(|err| format!("failed writing state file {:?}: {}", path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file_round_trip_and_version_check() {
+        let dir = tempdir::TempDir::new("state").expect("failed creating temporary dir");
+        let path = dir.path().join("state.json");
+        let state = SerializableState {
+            version: STATE_VERSION,
+            current_timestamp: 1000,
+            storage: vec![StorageEntry {
+                address: Address::repeat_byte(0x01),
+                key: H256::repeat_byte(0x02),
+                value: H256::repeat_byte(0x03),
+            }],
+            factory_deps: BTreeMap::from([(H256::repeat_byte(0x04), Bytes(vec![0u8; 32]))]),
+            block_hashes: BTreeMap::from([(1, H256::repeat_byte(0x05))]),
+            ..Default::default()
+        };
+
+        state.to_file(&path).expect("failed writing state");
+        assert_eq!(state, SerializableState::from_file(&path).unwrap());
+
+        SerializableState {
+            version: STATE_VERSION + 1,
+            ..state
+        }
+        .to_file(&path)
+        .expect("failed writing state");
+        assert!(SerializableState::from_file(&path).is_err());
+    }
+}
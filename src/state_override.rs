@@ -0,0 +1,208 @@
+//! State overrides accepted by `eth_call`, applied on top of the node storage for the duration
+//! of a single call.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use zksync_basic_types::{Address, Bytes, H256, U256};
+use zksync_state::ReadStorage;
+use zksync_types::{
+    get_code_key, get_known_code_key, get_nonce_key,
+    utils::{decompose_full_nonce, nonces_to_full_nonce, storage_key_for_eth_balance},
+    StorageKey, StorageValue,
+};
+use zksync_utils::{bytecode::hash_bytecode, h256_to_u256, u256_to_h256};
+
+use crate::utils::validate_bytecode;
+
+/// Overrides keyed by the account they apply to.
+pub type StateOverride = HashMap<Address, AccountOverride>;
+
+/// Fields of an account to replace during a call, in the format used by geth's `eth_call`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U256>,
+    pub code: Option<Bytes>,
+    /// Replaces the whole storage of the account: slots that are not listed read as zero.
+    pub state: Option<HashMap<H256, H256>>,
+    /// Replaces only the listed slots, keeping the rest of the storage.
+    pub state_diff: Option<HashMap<H256, H256>>,
+}
+
+impl AccountOverride {
+    /// Checks that the override can be applied: `state` and `stateDiff` are mutually exclusive,
+    /// and `code` must be a valid zkSync bytecode.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.state.is_some() && self.state_diff.is_some() {
+            return Err(String::from(
+                "`state` and `stateDiff` cannot be set for the same account",
+            ));
+        }
+        if let Some(code) = &self.code {
+            validate_bytecode(&code.0).map_err(|err| format!("invalid `code`: {}", err))?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates every account override, prefixing errors with the account they relate to.
+pub fn validate_state_override(state_override: &StateOverride) -> Result<(), String> {
+    for (address, account_override) in state_override {
+        account_override
+            .validate()
+            .map_err(|err| format!("{:?}: {}", address, err))?;
+    }
+    Ok(())
+}
+
+/// Read-only view of `S` with a [`StateOverride`] applied. Writes still go to the `StorageView`
+/// wrapping it, so the underlying storage is never modified.
+#[derive(Debug)]
+pub struct OverriddenStorage<S> {
+    storage: S,
+    values: HashMap<StorageKey, StorageValue>,
+    /// Accounts whose storage was replaced with `state`.
+    cleared_accounts: HashSet<Address>,
+    factory_deps: HashMap<H256, Vec<u8>>,
+}
+
+impl<S: ReadStorage> OverriddenStorage<S> {
+    /// Wraps `storage`, translating `state_override` into storage slots and factory deps.
+    pub fn new(mut storage: S, state_override: &StateOverride) -> Self {
+        let mut values = HashMap::new();
+        let mut cleared_accounts = HashSet::new();
+        let mut factory_deps = HashMap::new();
+
+        for (address, account_override) in state_override {
+            if let Some(balance) = account_override.balance {
+                values.insert(storage_key_for_eth_balance(address), u256_to_h256(balance));
+            }
+            if let Some(nonce) = account_override.nonce {
+                let nonce_key = get_nonce_key(address);
+                let full_nonce = h256_to_u256(storage.read_value(&nonce_key));
+                let (_, deployment_nonce) = decompose_full_nonce(full_nonce);
+                values.insert(
+                    nonce_key,
+                    u256_to_h256(nonces_to_full_nonce(nonce, deployment_nonce)),
+                );
+            }
+            if let Some(code) = &account_override.code {
+                let code_hash = hash_bytecode(&code.0);
+                values.insert(get_code_key(address), code_hash);
+                values.insert(get_known_code_key(&code_hash), u256_to_h256(U256::one()));
+                factory_deps.insert(code_hash, code.0.clone());
+            }
+            if let Some(state) = &account_override.state {
+                cleared_accounts.insert(*address);
+                for (slot, value) in state {
+                    values.insert(StorageKey::new((*address).into(), *slot), *value);
+                }
+            }
+            if let Some(state_diff) = &account_override.state_diff {
+                for (slot, value) in state_diff {
+                    values.insert(StorageKey::new((*address).into(), *slot), *value);
+                }
+            }
+        }
+
+        Self {
+            storage,
+            values,
+            cleared_accounts,
+            factory_deps,
+        }
+    }
+}
+
+impl<S: ReadStorage> ReadStorage for OverriddenStorage<S> {
+    fn read_value(&mut self, key: &StorageKey) -> StorageValue {
+        if let Some(value) = self.values.get(key) {
+            return *value;
+        }
+        if self.cleared_accounts.contains(key.account().address()) {
+            return StorageValue::zero();
+        }
+        self.storage.read_value(key)
+    }
+
+    fn is_write_initial(&mut self, key: &StorageKey) -> bool {
+        self.storage.is_write_initial(key)
+    }
+
+    fn load_factory_dep(&mut self, hash: H256) -> Option<Vec<u8>> {
+        if let Some(code) = self.factory_deps.get(&hash) {
+            return Some(code.clone());
+        }
+        self.storage.load_factory_dep(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fork::ForkStorage, http_fork_source::HttpForkSource, system_contracts};
+
+    #[test]
+    fn test_validate_rejects_state_and_state_diff_together() {
+        let account_override = AccountOverride {
+            state: Some(HashMap::new()),
+            state_diff: Some(HashMap::new()),
+            ..Default::default()
+        };
+
+        assert!(account_override.validate().is_err());
+        assert!(AccountOverride {
+            code: Some(Bytes(vec![0u8; 31])),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_state_replaces_storage_and_state_diff_keeps_it() {
+        let mut storage =
+            ForkStorage::<HttpForkSource>::new(None, &system_contracts::Options::BuiltIn);
+        let replaced = Address::repeat_byte(0x01);
+        let patched = Address::repeat_byte(0x02);
+        for address in [replaced, patched] {
+            storage.set_value(
+                StorageKey::new(address.into(), H256::from_low_u64_be(1)),
+                H256::from_low_u64_be(10),
+            );
+        }
+
+        let slot = H256::from_low_u64_be(2);
+        let value = H256::from_low_u64_be(20);
+        let state_override = StateOverride::from([
+            (
+                replaced,
+                AccountOverride {
+                    state: Some(HashMap::from([(slot, value)])),
+                    ..Default::default()
+                },
+            ),
+            (
+                patched,
+                AccountOverride {
+                    state_diff: Some(HashMap::from([(slot, value)])),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let mut overridden = OverriddenStorage::new(&storage, &state_override);
+
+        let key = |address: Address, slot: u64| {
+            StorageKey::new(address.into(), H256::from_low_u64_be(slot))
+        };
+        assert_eq!(H256::zero(), overridden.read_value(&key(replaced, 1)));
+        assert_eq!(value, overridden.read_value(&key(replaced, 2)));
+        assert_eq!(
+            H256::from_low_u64_be(10),
+            overridden.read_value(&key(patched, 1))
+        );
+        assert_eq!(value, overridden.read_value(&key(patched, 2)));
+    }
+}
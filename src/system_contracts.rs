@@ -1,14 +1,50 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
 use vm::vm_with_bootloader::TxExecutionMode;
-use zksync_contracts::{
-    read_playground_block_bootloader_bytecode, read_proved_block_bootloader_bytecode,
-    read_sys_contract_bytecode, read_zbin_bytecode, BaseSystemContracts, ContractLanguage,
-    SystemContractCode,
+use zksync_basic_types::{AccountTreeId, Address};
+use zksync_contracts::{BaseSystemContracts, SystemContractCode};
+use zksync_types::{
+    block::DeployedContract, ACCOUNT_CODE_STORAGE_ADDRESS, BOOTLOADER_ADDRESS,
+    BOOTLOADER_UTILITIES_ADDRESS, BYTECODE_COMPRESSOR_ADDRESS, CONTRACT_DEPLOYER_ADDRESS,
+    ECRECOVER_PRECOMPILE_ADDRESS, EVENT_WRITER_ADDRESS, IMMUTABLE_SIMULATOR_STORAGE_ADDRESS,
+    KECCAK256_PRECOMPILE_ADDRESS, KNOWN_CODES_STORAGE_ADDRESS, L1_MESSENGER_ADDRESS,
+    L2_ETH_TOKEN_ADDRESS, MSG_VALUE_SIMULATOR_ADDRESS, NONCE_HOLDER_ADDRESS,
+    SHA256_PRECOMPILE_ADDRESS, SYSTEM_CONTEXT_ADDRESS,
 };
-use zksync_types::system_contracts::get_system_smart_contracts;
 use zksync_utils::{bytecode::hash_bytecode, bytes_to_be_words};
 
-use crate::deps::system_contracts::{bytecode_from_slice, COMPILED_IN_SYSTEM_CONTRACTS};
+use crate::{
+    deps::system_contracts::{
+        bytecode_from_slice, try_bytecode_from_slice, COMPILED_IN_SYSTEM_CONTRACTS,
+    },
+    utils::validate_bytecode,
+};
 
+/// Names of the deployed system contracts whose bytecode can be replaced with
+/// [`Options::with_override`].
+pub const SYSTEM_CONTRACT_NAMES: [(&str, Address); 15] = [
+    ("AccountCodeStorage", ACCOUNT_CODE_STORAGE_ADDRESS),
+    ("NonceHolder", NONCE_HOLDER_ADDRESS),
+    ("KnownCodesStorage", KNOWN_CODES_STORAGE_ADDRESS),
+    ("ImmutableSimulator", IMMUTABLE_SIMULATOR_STORAGE_ADDRESS),
+    ("ContractDeployer", CONTRACT_DEPLOYER_ADDRESS),
+    ("L1Messenger", L1_MESSENGER_ADDRESS),
+    ("MsgValueSimulator", MSG_VALUE_SIMULATOR_ADDRESS),
+    ("L2EthToken", L2_ETH_TOKEN_ADDRESS),
+    ("SystemContext", SYSTEM_CONTEXT_ADDRESS),
+    ("BootloaderUtilities", BOOTLOADER_UTILITIES_ADDRESS),
+    ("BytecodeCompressor", BYTECODE_COMPRESSOR_ADDRESS),
+    ("Keccak256", KECCAK256_PRECOMPILE_ADDRESS),
+    ("SHA256", SHA256_PRECOMPILE_ADDRESS),
+    ("Ecrecover", ECRECOVER_PRECOMPILE_ADDRESS),
+    ("EventWriter", EVENT_WRITER_ADDRESS),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Options {
     // Use the compiled-in contracts
     BuiltIn,
@@ -16,38 +52,149 @@ pub enum Options {
     Local,
     // Don't verify the signatures (used only for testing - for example Forge).
     BuiltInWithoutSecurity,
+    // Load the bootloaders from ZKSYNC_HOME, but don't verify the signatures (compiled-in default account).
+    LocalWithoutSecurity,
+    // Load the bootloaders and the default account from a checkout of the system contracts
+    // repository at the given path (like `Local` without ZKSYNC_HOME), the other system
+    // contracts are compiled-in.
+    LocalFrom(PathBuf),
+    // Load the bootloader and the default account bytecode (.zbin) from the given paths,
+    // the other system contracts are compiled-in.
+    Custom {
+        bootloader_path: PathBuf,
+        default_account_path: PathBuf,
+    },
+    // Use the `base` options, with the bytecode of some deployed system contracts replaced
+    // (keyed by their address). Built with `Options::with_override`.
+    Overridden {
+        base: Box<Options>,
+        overrides: BTreeMap<Address, Vec<u8>>,
+    },
+}
+
+impl Options {
+    /// Replaces the bytecode of the system contract `name` (one of [`SYSTEM_CONTRACT_NAMES`])
+    /// deployed at genesis, on top of the current options. The bootloaders and the default
+    /// account are not affected.
+    pub fn with_override(self, name: &str, bytecode: Vec<u8>) -> Result<Self, String> {
+        let address = SYSTEM_CONTRACT_NAMES
+            .iter()
+            .find(|(contract_name, _)| *contract_name == name)
+            .map(|(_, address)| *address)
+            .ok_or_else(|| format!("unknown system contract {:?}", name))?;
+        validate_bytecode(&bytecode)
+            .map_err(|err| format!("invalid {} bytecode: {}", name, err))?;
+
+        Ok(match self {
+            Options::Overridden {
+                base,
+                mut overrides,
+            } => {
+                overrides.insert(address, bytecode);
+                Options::Overridden { base, overrides }
+            }
+            base => Options::Overridden {
+                base: Box::new(base),
+                overrides: BTreeMap::from([(address, bytecode)]),
+            },
+        })
+    }
+
+    /// Returns the options the bootloaders and the default account are loaded with.
+    fn base(&self) -> &Options {
+        match self {
+            Options::Overridden { base, .. } => base.base(),
+            options => options,
+        }
+    }
 }
 
 /// Holds the system contracts (and bootloader) that are used by the in-memory node.
+#[derive(Clone)]
 pub struct SystemContracts {
     pub baseline_contracts: BaseSystemContracts,
     pub playground_contracts: BaseSystemContracts,
     pub fee_estimate_contracts: BaseSystemContracts,
+    pub impersonating_contracts: BaseSystemContracts,
 }
 
-pub fn get_deployed_contracts(options: &Options) -> Vec<zksync_types::block::DeployedContract> {
-    match options {
-        Options::BuiltIn | Options::BuiltInWithoutSecurity => COMPILED_IN_SYSTEM_CONTRACTS.clone(),
-        Options::Local => get_system_smart_contracts(),
-    }
+/// Returns the system contracts deployed at genesis. Fails if the contracts read from
+/// ZKSYNC_HOME are missing or invalid.
+pub fn get_deployed_contracts(options: &Options) -> Result<Vec<DeployedContract>, String> {
+    Ok(match options {
+        Options::BuiltIn
+        | Options::BuiltInWithoutSecurity
+        | Options::LocalFrom(_)
+        | Options::Custom { .. } => COMPILED_IN_SYSTEM_CONTRACTS.clone(),
+        Options::Local | Options::LocalWithoutSecurity => read_deployed_contracts(&local_root())?,
+        Options::Overridden { base, overrides } => get_deployed_contracts(base)?
+            .into_iter()
+            .map(|mut contract| {
+                if let Some(bytecode) = overrides.get(contract.account_id.address()) {
+                    contract.bytecode = bytecode.clone();
+                }
+                contract
+            })
+            .collect(),
+    })
 }
 
+/// The compiled-in contracts never change, so they are only parsed and hashed once.
+static BUILT_IN_CONTRACTS: Lazy<SystemContracts> = Lazy::new(|| {
+    SystemContracts::load(&Options::BuiltIn).expect("invalid compiled-in system contracts")
+});
+static BUILT_IN_WITHOUT_SECURITY_CONTRACTS: Lazy<SystemContracts> = Lazy::new(|| {
+    SystemContracts::load(&Options::BuiltInWithoutSecurity)
+        .expect("invalid compiled-in system contracts")
+});
+
 impl Default for SystemContracts {
     /// Creates SystemContracts that use compiled-in contracts.
     fn default() -> Self {
-        SystemContracts::from_options(&Options::BuiltIn)
+        BUILT_IN_CONTRACTS.clone()
     }
 }
 
 impl SystemContracts {
     /// Creates the SystemContracts that use the complied contracts from ZKSYNC_HOME path.
     /// These are loaded at binary runtime.
-    pub fn from_options(options: &Options) -> Self {
-        Self {
-            baseline_contracts: baseline_contracts(options),
-            playground_contracts: playground(options),
-            fee_estimate_contracts: fee_estimate_contracts(options),
+    /// The compiled-in contracts are cached, while the files of [`Options::Local`],
+    /// [`Options::LocalFrom`] and [`Options::Custom`] are read every time, since they may change.
+    /// Fails if these files are missing or invalid.
+    pub fn from_options(options: &Options) -> Result<Self, String> {
+        match options {
+            Options::BuiltIn => Ok(BUILT_IN_CONTRACTS.clone()),
+            Options::BuiltInWithoutSecurity => Ok(BUILT_IN_WITHOUT_SECURITY_CONTRACTS.clone()),
+            _ => Self::load(options),
+        }
+    }
+
+    fn load(options: &Options) -> Result<Self, String> {
+        Ok(Self {
+            baseline_contracts: baseline_contracts(options)?,
+            playground_contracts: playground(options)?,
+            fee_estimate_contracts: fee_estimate_contracts(options)?,
+            impersonating_contracts: impersonating_contracts(options)?,
+        })
+    }
+
+    /// Re-reads all the bootloaders and default accounts, so that contracts recompiled in
+    /// ZKSYNC_HOME are picked up without restarting the node.
+    /// Only [`Options::Local`], [`Options::LocalWithoutSecurity`] and [`Options::LocalFrom`] can
+    /// be reloaded, the other options use compiled-in contracts.
+    pub fn reload(&mut self, options: &Options) -> Result<(), String> {
+        if !matches!(
+            options.base(),
+            Options::Local | Options::LocalWithoutSecurity | Options::LocalFrom(_)
+        ) {
+            return Err(format!(
+                "system contracts are compiled in ({:?}), there is nothing to reload",
+                options
+            ));
         }
+        // the current contracts are kept if any of the artifacts is missing or malformed
+        *self = Self::from_options(options)?;
+        Ok(())
     }
     pub fn contacts_for_l2_call(&self) -> &BaseSystemContracts {
         self.contracts(TxExecutionMode::EthCall {
@@ -61,6 +208,11 @@ impl SystemContracts {
         })
     }
 
+    /// Contracts used for the transactions sent from impersonated accounts - these skip the signature validation.
+    pub fn contracts_for_impersonation(&self) -> &BaseSystemContracts {
+        &self.impersonating_contracts
+    }
+
     pub fn contracts(&self, execution_mode: TxExecutionMode) -> &BaseSystemContracts {
         match execution_mode {
             // 'real' contracts, that do all the checks.
@@ -74,11 +226,75 @@ impl SystemContracts {
     }
 }
 
+/// Reads the raw bytecode of a custom bootloader or default account.
+fn read_bytecode_file(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|err| format!("Failed reading bytecode from {:?}: {}", path, err))
+}
+
+/// Returns the checkout of the system contracts repository used by [`Options::Local`]:
+/// `etc/system-contracts` in ZKSYNC_HOME (or in the current directory when it is not set).
+fn local_root() -> PathBuf {
+    let zksync_home = std::env::var("ZKSYNC_HOME").unwrap_or_else(|_| ".".into());
+    Path::new(&zksync_home).join("etc/system-contracts")
+}
+
+/// Reads a compiled bootloader from the system contracts repository checked out at `root`.
+fn read_bootloader_artifact(root: &Path, name: &str) -> Result<Vec<u8>, String> {
+    read_bytecode_file(&root.join(format!(
+        "bootloader/build/artifacts/{0}.yul/{0}.yul.zbin",
+        name
+    )))
+}
+
+/// Reads the bytecode of a compiled Solidity contract from the system contracts repository
+/// checked out at `root`.
+fn read_sol_artifact(root: &Path, name: &str) -> Result<Vec<u8>, String> {
+    let path = root.join(format!(
+        "artifacts-zk/cache-zk/solpp-generated-contracts/{0}.sol/{0}.json",
+        name
+    ));
+    try_bytecode_from_slice(name, &read_bytecode_file(&path)?)
+}
+
+/// Reads the bytecode of a compiled Yul contract from the `dir` directory of the system
+/// contracts repository checked out at `root`.
+fn read_yul_artifact(root: &Path, dir: &str, name: &str) -> Result<Vec<u8>, String> {
+    read_bytecode_file(&root.join(format!(
+        "contracts/{1}artifacts/{0}.yul/{0}.yul.zbin",
+        name, dir
+    )))
+}
+
+/// Reads the system contracts deployed at genesis from the system contracts repository checked
+/// out at `root`, like [`COMPILED_IN_SYSTEM_CONTRACTS`].
+fn read_deployed_contracts(root: &Path) -> Result<Vec<DeployedContract>, String> {
+    let mut contracts = vec![];
+    for (name, address) in SYSTEM_CONTRACT_NAMES {
+        let bytecode = match name {
+            "Keccak256" | "SHA256" | "Ecrecover" => read_yul_artifact(root, "precompiles/", name)?,
+            "EventWriter" => read_yul_artifact(root, "", name)?,
+            _ => read_sol_artifact(root, name)?,
+        };
+        contracts.push(DeployedContract {
+            account_id: AccountTreeId::new(address),
+            bytecode,
+        });
+    }
+    let empty_bytecode = read_sol_artifact(root, "EmptyContract")?;
+    for address in [Address::zero(), BOOTLOADER_ADDRESS] {
+        contracts.push(DeployedContract {
+            account_id: AccountTreeId::new(address),
+            bytecode: empty_bytecode.clone(),
+        });
+    }
+    Ok(contracts)
+}
+
 /// Creates BaseSystemContracts object with a specific bootloader.
 fn bsc_load_with_bootloader(
     bootloader_bytecode: Vec<u8>,
     options: &Options,
-) -> BaseSystemContracts {
+) -> Result<BaseSystemContracts, String> {
     let hash = hash_bytecode(&bootloader_bytecode);
 
     let bootloader = SystemContractCode {
@@ -91,11 +307,19 @@ fn bsc_load_with_bootloader(
             "DefaultAccount",
             include_bytes!("deps/contracts/DefaultAccount.json"),
         ),
-        Options::Local => read_sys_contract_bytecode("", "DefaultAccount", ContractLanguage::Sol),
-        Options::BuiltInWithoutSecurity => bytecode_from_slice(
+        Options::Local => read_sol_artifact(&local_root(), "DefaultAccount")?,
+        Options::LocalFrom(root) => read_sol_artifact(root, "DefaultAccount")?,
+        Options::BuiltInWithoutSecurity | Options::LocalWithoutSecurity => bytecode_from_slice(
             "DefaultAccountNoSecurity",
             include_bytes!("deps/contracts/DefaultAccountNoSecurity.json"),
         ),
+        Options::Custom {
+            default_account_path,
+            ..
+        } => read_bytecode_file(default_account_path)?,
+        Options::Overridden { base, .. } => {
+            return bsc_load_with_bootloader(bootloader_bytecode, base)
+        }
     };
 
     let hash = hash_bytecode(&bytecode);
@@ -105,19 +329,26 @@ fn bsc_load_with_bootloader(
         hash,
     };
 
-    BaseSystemContracts {
+    Ok(BaseSystemContracts {
         bootloader,
         default_aa,
-    }
+    })
 }
 
 /// BaseSystemContracts with playground bootloader -  used for handling 'eth_calls'.
-pub fn playground(options: &Options) -> BaseSystemContracts {
+pub fn playground(options: &Options) -> Result<BaseSystemContracts, String> {
     let bootloader_bytecode = match options {
         Options::BuiltIn | Options::BuiltInWithoutSecurity => {
             include_bytes!("deps/contracts/playground_block.yul.zbin").to_vec()
         }
-        Options::Local => read_playground_block_bootloader_bytecode(),
+        Options::Local | Options::LocalWithoutSecurity => {
+            read_bootloader_artifact(&local_root(), "playground_block")?
+        }
+        Options::LocalFrom(root) => read_bootloader_artifact(root, "playground_block")?,
+        Options::Custom {
+            bootloader_path, ..
+        } => read_bytecode_file(bootloader_path)?,
+        Options::Overridden { base, .. } => return playground(base),
     };
 
     bsc_load_with_bootloader(bootloader_bytecode, options)
@@ -133,25 +364,228 @@ pub fn playground(options: &Options) -> BaseSystemContracts {
 ///
 /// A `BaseSystemContracts` struct containing the system contracts used for handling 'eth_estimateGas'.
 /// It sets ENSURE_RETURNED_MAGIC to 0 and BOOTLOADER_TYPE to 'playground_block'
-pub fn fee_estimate_contracts(options: &Options) -> BaseSystemContracts {
+pub fn fee_estimate_contracts(options: &Options) -> Result<BaseSystemContracts, String> {
     let bootloader_bytecode = match options {
-        Options::BuiltIn |
-        Options::BuiltInWithoutSecurity => {
+        Options::BuiltIn | Options::BuiltInWithoutSecurity => {
             include_bytes!("deps/contracts/fee_estimate.yul.zbin").to_vec()
         }
-        Options::Local =>
-            read_zbin_bytecode("etc/system-contracts/bootloader/build/artifacts/fee_estimate.yul/fee_estimate.yul.zbin")
+        Options::Local | Options::LocalWithoutSecurity => {
+            read_bootloader_artifact(&local_root(), "fee_estimate")?
+        }
+        Options::LocalFrom(root) => read_bootloader_artifact(root, "fee_estimate")?,
+        Options::Custom {
+            bootloader_path, ..
+        } => read_bytecode_file(bootloader_path)?,
+        Options::Overridden { base, .. } => return fee_estimate_contracts(base),
     };
 
     bsc_load_with_bootloader(bootloader_bytecode, options)
 }
 
-pub fn baseline_contracts(options: &Options) -> BaseSystemContracts {
-    let bootloader_bytecode = match options {
+fn baseline_bootloader_bytecode(options: &Options) -> Result<Vec<u8>, String> {
+    Ok(match options {
         Options::BuiltIn | Options::BuiltInWithoutSecurity => {
             include_bytes!("deps/contracts/proved_block.yul.zbin").to_vec()
         }
-        Options::Local => read_proved_block_bootloader_bytecode(),
-    };
-    bsc_load_with_bootloader(bootloader_bytecode, options)
+        Options::Local | Options::LocalWithoutSecurity => {
+            read_bootloader_artifact(&local_root(), "proved_block")?
+        }
+        Options::LocalFrom(root) => read_bootloader_artifact(root, "proved_block")?,
+        Options::Custom {
+            bootloader_path, ..
+        } => read_bytecode_file(bootloader_path)?,
+        Options::Overridden { base, .. } => baseline_bootloader_bytecode(base)?,
+    })
+}
+
+pub fn baseline_contracts(options: &Options) -> Result<BaseSystemContracts, String> {
+    bsc_load_with_bootloader(baseline_bootloader_bytecode(options)?, options)
+}
+
+/// BaseSystemContracts with the baseline bootloader, but with the default account that doesn't
+/// verify signatures - used for handling transactions from impersonated accounts.
+pub fn impersonating_contracts(options: &Options) -> Result<BaseSystemContracts, String> {
+    bsc_load_with_bootloader(
+        baseline_bootloader_bytecode(options)?,
+        &Options::BuiltInWithoutSecurity,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_options_read_bytecode_from_paths() {
+        let dir = tempdir::TempDir::new("system-contracts").expect("failed creating temporary dir");
+        let bootloader_path = dir.path().join("proved_block.yul.zbin");
+        let default_account_path = dir.path().join("DefaultAccount.zbin");
+        std::fs::write(
+            &bootloader_path,
+            include_bytes!("deps/contracts/proved_block.yul.zbin"),
+        )
+        .unwrap();
+        std::fs::write(
+            &default_account_path,
+            bytecode_from_slice(
+                "DefaultAccount",
+                include_bytes!("deps/contracts/DefaultAccount.json"),
+            ),
+        )
+        .unwrap();
+
+        let custom = SystemContracts::from_options(&Options::Custom {
+            bootloader_path,
+            default_account_path,
+        })
+        .unwrap();
+        let built_in = SystemContracts::from_options(&Options::BuiltIn).unwrap();
+
+        assert_eq!(
+            built_in.baseline_contracts.bootloader.hash,
+            custom.baseline_contracts.bootloader.hash
+        );
+        assert_eq!(
+            built_in.baseline_contracts.default_aa.hash,
+            custom.baseline_contracts.default_aa.hash
+        );
+        assert_eq!(
+            built_in.baseline_contracts.bootloader.hash,
+            custom.fee_estimate_contracts.bootloader.hash
+        );
+        assert_eq!(
+            built_in.impersonating_contracts.default_aa.hash,
+            custom.impersonating_contracts.default_aa.hash
+        );
+    }
+
+    #[test]
+    fn test_local_from_reads_the_artifacts_under_the_root() {
+        let dir = tempdir::TempDir::new("system-contracts").expect("failed creating temporary dir");
+        for (name, contents) in [
+            (
+                "proved_block",
+                &include_bytes!("deps/contracts/proved_block.yul.zbin")[..],
+            ),
+            (
+                "playground_block",
+                &include_bytes!("deps/contracts/playground_block.yul.zbin")[..],
+            ),
+            (
+                "fee_estimate",
+                &include_bytes!("deps/contracts/fee_estimate.yul.zbin")[..],
+            ),
+        ] {
+            let path = dir
+                .path()
+                .join(format!("bootloader/build/artifacts/{0}.yul", name));
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join(format!("{}.yul.zbin", name)), contents).unwrap();
+        }
+        let path = dir
+            .path()
+            .join("artifacts-zk/cache-zk/solpp-generated-contracts/DefaultAccount.sol");
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(
+            path.join("DefaultAccount.json"),
+            include_bytes!("deps/contracts/DefaultAccount.json"),
+        )
+        .unwrap();
+
+        let options = Options::LocalFrom(dir.path().to_path_buf());
+        let mut local = SystemContracts::from_options(&options).unwrap();
+        let built_in = SystemContracts::from_options(&Options::BuiltIn).unwrap();
+
+        assert_eq!(
+            built_in.baseline_contracts.bootloader.hash,
+            local.baseline_contracts.bootloader.hash
+        );
+        assert_eq!(
+            built_in.playground_contracts.bootloader.hash,
+            local.playground_contracts.bootloader.hash
+        );
+        assert_eq!(
+            built_in.fee_estimate_contracts.bootloader.hash,
+            local.fee_estimate_contracts.bootloader.hash
+        );
+        assert_eq!(
+            built_in.baseline_contracts.default_aa.hash,
+            local.baseline_contracts.default_aa.hash
+        );
+        assert!(local.reload(&options).is_ok());
+    }
+
+    #[test]
+    fn test_local_without_security_uses_the_no_security_default_account() {
+        let bootloader = include_bytes!("deps/contracts/proved_block.yul.zbin").to_vec();
+
+        let contracts =
+            bsc_load_with_bootloader(bootloader, &Options::LocalWithoutSecurity).unwrap();
+
+        assert_eq!(
+            SystemContracts::from_options(&Options::BuiltInWithoutSecurity)
+                .unwrap()
+                .baseline_contracts
+                .default_aa
+                .hash,
+            contracts.default_aa.hash
+        );
+        assert_ne!(
+            SystemContracts::from_options(&Options::BuiltIn)
+                .unwrap()
+                .baseline_contracts
+                .default_aa
+                .hash,
+            contracts.default_aa.hash
+        );
+    }
+
+    #[test]
+    fn test_built_in_contracts_are_cached() {
+        for options in [Options::BuiltIn, Options::BuiltInWithoutSecurity] {
+            let cached = SystemContracts::from_options(&options).unwrap();
+            let loaded = SystemContracts::load(&options).unwrap();
+
+            assert_eq!(
+                loaded.baseline_contracts.bootloader.hash,
+                cached.baseline_contracts.bootloader.hash
+            );
+            assert_eq!(
+                loaded.playground_contracts.default_aa.hash,
+                cached.playground_contracts.default_aa.hash
+            );
+            assert_eq!(
+                loaded.fee_estimate_contracts.bootloader.hash,
+                cached.fee_estimate_contracts.bootloader.hash
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_options_fail_on_missing_file() {
+        let result = SystemContracts::from_options(&Options::Custom {
+            bootloader_path: PathBuf::from("/nonexistent/bootloader.zbin"),
+            default_account_path: PathBuf::from("/nonexistent/DefaultAccount.zbin"),
+        });
+
+        let err = result.err().expect("loading should fail");
+        assert!(err.starts_with("Failed reading bytecode"), "{}", err);
+    }
+
+    #[test]
+    fn test_reload_keeps_the_current_contracts_on_missing_artifacts() {
+        let dir = tempdir::TempDir::new("system-contracts").expect("failed creating temporary dir");
+        let mut contracts = SystemContracts::default();
+
+        let result = contracts.reload(&Options::LocalFrom(dir.path().to_path_buf()));
+
+        assert!(result.is_err());
+        assert_eq!(
+            SystemContracts::default()
+                .baseline_contracts
+                .bootloader
+                .hash,
+            contracts.baseline_contracts.bootloader.hash
+        );
+    }
 }
@@ -5,8 +5,12 @@
 
 #![cfg(test)]
 
-use crate::node::InMemoryNode;
-use crate::{fork::ForkSource, node::compute_hash};
+use crate::node::{InMemoryNode, NodeConfig};
+use crate::{cache::CacheConfig, http_fork_source::HttpForkSource};
+use crate::{
+    fork::{ForkDetails, ForkSource},
+    node::compute_hash,
+};
 
 use httptest::{
     matchers::{eq, json_decoded, request},
@@ -14,8 +18,19 @@ use httptest::{
     Expectation, Server,
 };
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use std::str::FromStr;
-use zksync_types::{fee::Fee, l2::L2Tx, Address, L2ChainId, Nonce, PackedEthSignature, H256, U256};
+use zksync_types::{
+    fee::Fee, l2::L2Tx, Address, L2ChainId, Nonce, PackedEthSignature, CONTRACT_DEPLOYER_ADDRESS,
+    H256, L1_MESSENGER_ADDRESS, U256,
+};
+use zksync_utils::bytecode::hash_bytecode;
+
+lazy_static! {
+    /// Serializes the tests changing process wide settings (the resolver settings and the
+    /// formatter's address map), as the tests of the crate run concurrently.
+    pub static ref GLOBAL_SETTINGS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
 
 /// Configuration for the [MockServer]'s initial block.
 #[derive(Default, Debug, Clone)]
@@ -167,6 +182,14 @@ impl MockServer {
     }
 }
 
+/// Creates a node with the default settings, forked from the `mock_server`.
+pub async fn forked_node(mock_server: &MockServer) -> InMemoryNode<HttpForkSource> {
+    let fork = ForkDetails::from_network(&mock_server.url(), None, CacheConfig::None)
+        .await
+        .expect("failed forking the mock server");
+    InMemoryNode::new(Some(fork), &NodeConfig::default())
+}
+
 /// A mock response builder for a block
 #[derive(Default, Debug, Clone)]
 pub struct BlockResponseBuilder {
@@ -385,9 +408,90 @@ pub fn apply_tx<T: ForkSource + std::fmt::Debug>(node: &InMemoryNode<T>, tx_hash
     produced_block_hash
 }
 
+/// Sends `message` to L1 with `L1Messenger.sendToL1` from a new account, and returns the hash of
+/// the transaction.
+pub fn send_l1_message<T: ForkSource + std::fmt::Debug>(
+    node: &InMemoryNode<T>,
+    message: &[u8],
+) -> H256 {
+    let private_key = H256::random();
+    let from_account = PackedEthSignature::address_from_private_key(&private_key)
+        .expect("failed generating address");
+    node.set_rich_account(from_account);
+    let mut calldata = ethabi::short_signature("sendToL1", &[ethabi::ParamType::Bytes]).to_vec();
+    calldata.extend(ethabi::encode(&[ethabi::Token::Bytes(message.to_vec())]));
+    let tx = L2Tx::new_signed(
+        L1_MESSENGER_ADDRESS,
+        calldata,
+        Nonce(0),
+        Fee {
+            gas_limit: U256::from(10_000_000),
+            max_fee_per_gas: U256::from(250_000_000),
+            max_priority_fee_per_gas: U256::from(250_000_000),
+            gas_per_pubdata_limit: U256::from(20000),
+        },
+        U256::zero(),
+        L2ChainId(260),
+        &private_key,
+        None,
+        Default::default(),
+    )
+    .unwrap();
+    let tx_hash = tx.hash();
+    node.apply_txs(vec![tx]).expect("failed applying tx");
+
+    tx_hash
+}
+
+/// Deploys `bytecode` with `ContractDeployer.create` from a new account, and returns the hash of
+/// the transaction.
+pub fn deploy_contract<T: ForkSource + std::fmt::Debug>(
+    node: &InMemoryNode<T>,
+    bytecode: Vec<u8>,
+) -> H256 {
+    let private_key = H256::random();
+    let from_account = PackedEthSignature::address_from_private_key(&private_key)
+        .expect("failed generating address");
+    node.set_rich_account(from_account);
+    let mut calldata = ethabi::short_signature(
+        "create",
+        &[
+            ethabi::ParamType::FixedBytes(32),
+            ethabi::ParamType::FixedBytes(32),
+            ethabi::ParamType::Bytes,
+        ],
+    )
+    .to_vec();
+    calldata.extend(ethabi::encode(&[
+        ethabi::Token::FixedBytes(vec![0; 32]),
+        ethabi::Token::FixedBytes(hash_bytecode(&bytecode).as_bytes().to_vec()),
+        ethabi::Token::Bytes(vec![]),
+    ]));
+    let tx = L2Tx::new_signed(
+        CONTRACT_DEPLOYER_ADDRESS,
+        calldata,
+        Nonce(0),
+        Fee {
+            gas_limit: U256::from(50_000_000),
+            max_fee_per_gas: U256::from(250_000_000),
+            max_priority_fee_per_gas: U256::from(250_000_000),
+            gas_per_pubdata_limit: U256::from(50000),
+        },
+        U256::zero(),
+        L2ChainId(260),
+        &private_key,
+        Some(vec![bytecode]),
+        Default::default(),
+    )
+    .unwrap();
+    let tx_hash = tx.hash();
+    node.apply_txs(vec![tx]).expect("failed applying tx");
+
+    tx_hash
+}
+
 mod test {
     use super::*;
-    use crate::http_fork_source::HttpForkSource;
 
     #[test]
     fn test_block_response_builder_set_hash() {
@@ -1,6 +1,7 @@
 use std::pin::Pin;
 
 use futures::Future;
+use serde::{de, Deserialize, Deserializer};
 use vm::{
     utils::BLOCK_GAS_LIMIT,
     vm_with_bootloader::{
@@ -10,11 +11,14 @@ use vm::{
     },
     HistoryEnabled, OracleTools,
 };
-use zksync_basic_types::{H256, U256};
+use zksync_basic_types::{web3::signing::keccak256, H256, U256};
 use zksync_state::StorageView;
 use zksync_state::WriteStorage;
 use zksync_types::{
-    api::Block, zk_evm::zkevm_opcode_defs::system_params::MAX_TX_ERGS_LIMIT, MAX_TXS_IN_BLOCK,
+    api::{Block, BlockNumber, TransactionVariant},
+    l2_to_l1_log::L2ToL1Log,
+    zk_evm::zkevm_opcode_defs::system_params::MAX_TX_ERGS_LIMIT,
+    MAX_TXS_IN_BLOCK,
 };
 use zksync_utils::{ceil_div_u256, u256_to_h256};
 
@@ -36,6 +40,36 @@ where
 {
 }
 
+/// A `U256` quantity passed as a JSON-RPC parameter. Unlike `U256`, the hex string is accepted
+/// both with and without the `0x` prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexQuantity(pub U256);
+
+impl From<U256> for HexQuantity {
+    fn from(value: U256) -> Self {
+        HexQuantity(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for HexQuantity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let digits = value.strip_prefix("0x").unwrap_or(&value);
+        if digits.is_empty() {
+            return Err(de::Error::custom(format!(
+                "invalid hex quantity '{}'",
+                value
+            )));
+        }
+        U256::from_str_radix(digits, 16)
+            .map(HexQuantity)
+            .map_err(|_| de::Error::custom(format!("invalid hex quantity '{}'", value)))
+    }
+}
+
 /// Derives the gas estimation overhead based on the given gas limit, gas price per pubdata, and encoded length.
 ///
 /// # Arguments
@@ -165,20 +199,29 @@ pub fn mine_empty_blocks<S: std::fmt::Debug + ForkSource>(
     node: &mut InMemoryNodeInner<S>,
     num_blocks: u64,
     interval_ms: u64,
-) {
-    // build and insert new blocks
+) -> Result<(), String> {
+    // build new blocks, inserted once the vm is rolled to report the resulting state root
+    let mut blocks: Vec<Block<TransactionVariant>> = vec![];
     for _ in 0..num_blocks {
+        let parent_hash = match blocks.last() {
+            Some(Block { hash, .. }) => *hash,
+            None => node
+                .block_hashes
+                .get(&node.current_miniblock)
+                .cloned()
+                .unwrap_or_default(),
+        };
         node.current_miniblock = node.current_miniblock.saturating_add(1);
 
         let block = Block {
             hash: compute_hash(node.current_miniblock as u32, H256::zero()),
+            parent_hash,
             number: node.current_miniblock.into(),
             timestamp: node.current_timestamp.into(),
             ..Default::default()
         };
 
-        node.block_hashes.insert(node.current_miniblock, block.hash);
-        node.blocks.insert(block.hash, block);
+        blocks.push(block);
 
         // leave node state ready for next interaction
         node.current_timestamp = node.current_timestamp.saturating_add(interval_ms);
@@ -241,15 +284,156 @@ pub fn mine_empty_blocks<S: std::fmt::Debug + ForkSource>(
         )
     }
 
+    let state_root = node.state_root()?;
+    for mut block in blocks {
+        block.state_root = state_root;
+        node.block_hashes.insert(block.number.as_u64(), block.hash);
+        node.block_gas_prices.insert(
+            block.number.as_u64(),
+            (node.l1_gas_price, node.fair_l2_gas_price),
+        );
+        node.filters.notify_new_block(block.hash);
+        node.subscriptions.notify_new_block(&block);
+        node.blocks.insert(block.hash, block);
+    }
+
     // increment batch
     node.current_batch = node.current_batch.saturating_add(1);
+    Ok(())
+}
+
+/// Checks that `code` has the layout required by the zkSync VM: an odd number of 32-byte words,
+/// with the word count fitting in 16 bits.
+pub fn validate_bytecode(code: &[u8]) -> std::result::Result<(), String> {
+    if code.len() % 32 != 0 {
+        return Err(format!(
+            "bytecode length must be a multiple of 32 bytes, got {}",
+            code.len()
+        ));
+    }
+    let words = code.len() / 32;
+    if words % 2 == 0 {
+        return Err(format!(
+            "bytecode must contain an odd number of 32-byte words, got {}",
+            words
+        ));
+    }
+    if words >= 1 << 16 {
+        return Err(format!(
+            "bytecode must contain less than 65536 words, got {}",
+            words
+        ));
+    }
+    Ok(())
+}
+
+/// Number of leaves of the Merkle tree of the L2→L1 logs of a batch.
+pub const L2_TO_L1_LOGS_TREE_SIZE: usize = 512;
+
+/// Serializes the L2→L1 log as the L1 contracts hash it: shard id, is service, transaction number
+/// in the block, sender, key and value, for a total of 88 bytes.
+pub fn l2_to_l1_log_bytes(log: &L2ToL1Log) -> Vec<u8> {
+    let mut bytes = vec![log.shard_id, log.is_service as u8];
+    bytes.extend_from_slice(&log.tx_number_in_block.to_be_bytes());
+    bytes.extend_from_slice(log.sender.as_bytes());
+    bytes.extend_from_slice(log.key.as_bytes());
+    bytes.extend_from_slice(log.value.as_bytes());
+    bytes
+}
+
+/// Returns the root of the Merkle tree of the L2→L1 `logs` of a batch, padded with empty logs to
+/// [`L2_TO_L1_LOGS_TREE_SIZE`] leaves, and the sibling hashes proving the log at `index`.
+pub fn l2_to_l1_logs_merkle_proof(logs: &[L2ToL1Log], index: usize) -> (H256, Vec<H256>) {
+    let empty_leaf = keccak256(&[0u8; 88]);
+    let mut level = logs
+        .iter()
+        .map(|log| keccak256(&l2_to_l1_log_bytes(log)))
+        .collect::<Vec<_>>();
+    level.resize(
+        L2_TO_L1_LOGS_TREE_SIZE.max(logs.len().next_power_of_two()),
+        empty_leaf,
+    );
+
+    let mut index = index;
+    let mut proof = vec![];
+    while level.len() > 1 {
+        proof.push(H256(level[index ^ 1]));
+        level = level
+            .chunks(2)
+            .map(|pair| keccak256(&[pair[0], pair[1]].concat()))
+            .collect();
+        index /= 2;
+    }
+    (H256(level[0]), proof)
+}
+
+/// Resolves a block tag to a block number. Blocks are sealed right away and never reorganized, so
+/// `latest`, `pending`, `committed` and `finalized` (which `safe` is parsed as, see
+/// [`crate::block_tags`]) are all the latest sealed block. `earliest` is the genesis block, which
+/// the node methods resolve to its first local block instead, see
+/// [`crate::node::InMemoryNodeInner::resolve_block_number`].
+pub fn resolve_block_number(block: BlockNumber, latest_block: u64) -> u64 {
+    match block {
+        BlockNumber::Earliest => 0,
+        BlockNumber::Number(number) => number.as_u64(),
+        BlockNumber::Latest
+        | BlockNumber::Pending
+        | BlockNumber::Committed
+        | BlockNumber::Finalized => latest_block,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use zksync_basic_types::U256;
 
-    use crate::utils::to_human_size;
+    use super::*;
+    use crate::utils::{to_human_size, HexQuantity};
+
+    #[test]
+    fn test_l2_to_l1_logs_merkle_proof_leads_to_the_root() {
+        let logs = (0..3u8)
+            .map(|i| L2ToL1Log {
+                shard_id: 0,
+                is_service: true,
+                tx_number_in_block: i as u16,
+                sender: zksync_basic_types::Address::repeat_byte(i),
+                key: H256::repeat_byte(i),
+                value: H256::repeat_byte(0xff - i),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(88, l2_to_l1_log_bytes(&logs[0]).len());
+
+        let (root, proof) = l2_to_l1_logs_merkle_proof(&logs, 1);
+
+        assert_eq!(9, proof.len());
+        let mut hash = keccak256(&l2_to_l1_log_bytes(&logs[1]));
+        let mut index = 1;
+        for sibling in proof {
+            hash = if index % 2 == 0 {
+                keccak256(&[hash, sibling.0].concat())
+            } else {
+                keccak256(&[sibling.0, hash].concat())
+            };
+            index /= 2;
+        }
+        assert_eq!(root, H256(hash));
+        assert_ne!(root, l2_to_l1_logs_merkle_proof(&logs[..2], 1).0);
+    }
+
+    #[test]
+    fn test_resolve_block_number_maps_tags_to_the_latest_block() {
+        for tag in [
+            BlockNumber::Latest,
+            BlockNumber::Pending,
+            BlockNumber::Committed,
+            BlockNumber::Finalized,
+        ] {
+            assert_eq!(7, resolve_block_number(tag, 7));
+        }
+        assert_eq!(0, resolve_block_number(BlockNumber::Earliest, 7));
+        assert_eq!(3, resolve_block_number(BlockNumber::Number(3.into()), 7));
+    }
 
     #[test]
     fn test_human_sizes() {
@@ -260,4 +444,30 @@ mod tests {
         assert_eq!("1", to_human_size(U256::from(1)));
         assert_eq!("250_000_000", to_human_size(U256::from(250000000u64)));
     }
+
+    #[test]
+    fn test_hex_quantity_accepts_optional_prefix() {
+        let prefixed: HexQuantity = serde_json::from_str("\"0x1bc16d674ec80000\"").unwrap();
+        let bare: HexQuantity = serde_json::from_str("\"1bc16d674ec80000\"").unwrap();
+
+        assert_eq!(U256::from(2_000_000_000_000_000_000u64), prefixed.0);
+        assert_eq!(prefixed, bare);
+    }
+
+    #[test]
+    fn test_hex_quantity_rejects_invalid_values() {
+        for value in [
+            "\"0x\"",
+            "\"\"",
+            "\"0xzz\"",
+            "12",
+            &format!("\"{}\"", "f".repeat(65)),
+        ] {
+            assert!(
+                serde_json::from_str::<HexQuantity>(value).is_err(),
+                "accepted {}",
+                value
+            );
+        }
+    }
 }
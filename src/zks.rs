@@ -1,19 +1,31 @@
 use std::sync::{Arc, RwLock};
 
 use bigdecimal::BigDecimal;
+use chrono::{TimeZone, Utc};
 use futures::FutureExt;
-use zksync_basic_types::{MiniblockNumber, U256};
+use jsonrpc_core::{BoxFuture, Result};
+use jsonrpc_derive::rpc;
+use zksync_basic_types::{Address, MiniblockNumber, U256, U64};
 use zksync_core::api_server::web3::backend_jsonrpc::{
     error::into_jsrpc_error, namespaces::zks::ZksNamespaceT,
 };
 use zksync_types::{
-    api::{BridgeAddresses, ProtocolVersion},
+    api::{BridgeAddresses, ProtocolVersion, TransactionDetails, TransactionStatus},
     fee::Fee,
+    get_code_key,
+    transaction_request::CallRequest,
+    tx::tx_execution_info::TxExecutionStatus,
+    ExecuteTransactionCommon,
 };
+use zksync_utils::bytecode::hash_bytecode;
 use zksync_web3_decl::error::Web3Error;
 
-use crate::{fork::ForkSource, node::InMemoryNodeInner, utils::IntoBoxedFuture};
-use colored::Colorize;
+use crate::{
+    fork::ForkSource,
+    node::{FeeBreakdown, InMemoryNodeInner},
+    system_contracts::get_deployed_contracts,
+    utils::IntoBoxedFuture,
+};
 
 /// Mock implementation of ZksNamespace - used only in the test node.
 pub struct ZkMockNamespaceImpl<S> {
@@ -27,6 +39,89 @@ impl<S> ZkMockNamespaceImpl<S> {
     }
 }
 
+/// Chain id of zkSync Era mainnet.
+const MAINNET_CHAIN_ID: u16 = 324;
+
+/// `zks_` methods that only exist on the test node.
+#[rpc]
+pub trait ZksTestNodeNamespaceT {
+    /// Reloads the bootloaders, default accounts and system contracts from ZKSYNC_HOME, so that
+    /// recompiled contracts are used by the following transactions without restarting the node.
+    /// Only available when the node runs with local system contracts, and not on a mainnet fork.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with a `bool` representing the success of the operation.
+    #[rpc(name = "zks_reloadSystemContracts")]
+    fn reload_system_contracts(&self) -> BoxFuture<Result<bool>>;
+
+    /// Estimates the fee of a call request, and also returns the components of the gas limit.
+    /// Replaces the zkSync `zks_estimateFee`, whose response only has the `Fee` fields. These
+    /// fields are returned as is, so clients reading only them are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A `CallRequest` struct representing the call request to estimate gas for.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the `Fee` fields, plus `tx_body_gas`,
+    /// `pubdata_cost` and `overhead` that add up to `gas_limit`.
+    #[rpc(name = "zks_estimateFee")]
+    fn estimate_fee_breakdown(&self, req: CallRequest) -> BoxFuture<Result<FeeBreakdown>>;
+}
+
+impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksTestNodeNamespaceT
+    for ZkMockNamespaceImpl<S>
+{
+    fn reload_system_contracts(&self) -> BoxFuture<Result<bool>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let mut inner = inner
+                .write()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            if inner.fork_storage.chain_id.0 == MAINNET_CHAIN_ID {
+                return Err(jsonrpc_core::Error::invalid_params(
+                    "system contracts can't be reloaded on a mainnet fork",
+                ));
+            }
+
+            // read everything before touching the node, so that it is left untouched on failure
+            let options = inner.system_contracts_options.clone();
+            let mut system_contracts = inner.system_contracts.clone();
+            system_contracts
+                .reload(&options)
+                .map_err(jsonrpc_core::Error::invalid_params)?;
+            let deployed_contracts =
+                get_deployed_contracts(&options).map_err(jsonrpc_core::Error::invalid_params)?;
+
+            inner.system_contracts = system_contracts;
+            for contract in deployed_contracts {
+                let code_hash = hash_bytecode(&contract.bytecode);
+                inner
+                    .fork_storage
+                    .store_factory_dep(code_hash, contract.bytecode);
+                inner
+                    .fork_storage
+                    .set_value(get_code_key(contract.account_id.address()), code_hash);
+            }
+
+            log::info!("👷 System contracts reloaded");
+            Ok(true)
+        })
+    }
+
+    fn estimate_fee_breakdown(&self, req: CallRequest) -> BoxFuture<Result<FeeBreakdown>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.estimate_fee_breakdown(req)
+        })
+    }
+}
+
 macro_rules! not_implemented {
     () => {
         Box::pin(async move { Err(jsonrpc_core::Error::method_not_found()) })
@@ -62,11 +157,31 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         }
     }
 
+    /// Returns the transactions of a block in their zkSync representation, with the
+    /// `common_data`, `execute` and `received_timestamp_ms` fields. The blocks up to the fork
+    /// point are read from the forked network.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_number` - The number of the block
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the transactions, empty for unknown blocks.
     fn get_raw_block_transactions(
         &self,
-        _block_number: MiniblockNumber,
+        block_number: MiniblockNumber,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_types::Transaction>>> {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.raw_block_transactions(block_number).map_err(|err| {
+                log::error!("failed reading the block transactions: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })
+        })
     }
 
     fn estimate_gas_l1_to_l2(
@@ -76,10 +191,16 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns the address of the zkSync contract on L1. The node has no L1, so this is always
+    /// the zero address.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the address of the main contract.
     fn get_main_contract(
         &self,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::Address>> {
-        not_implemented!()
+        Ok(Address::zero()).into_boxed_future()
     }
 
     fn get_testnet_paymaster(
@@ -88,68 +209,119 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns the addresses of the default bridges. No bridge is deployed on the node, so all
+    /// the addresses are zero.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the `BridgeAddresses`.
     fn get_bridge_contracts(
         &self,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<BridgeAddresses>> {
-        not_implemented!()
+        Ok(BridgeAddresses {
+            l1_erc20_default_bridge: Address::zero(),
+            l2_erc20_default_bridge: Address::zero(),
+            l1_weth_bridge: None,
+            l2_weth_bridge: None,
+        })
+        .into_boxed_future()
     }
 
+    /// Returns the chain id of the underlying L1. The node has no L1, so this is the chain id the
+    /// node was configured with.
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the chain id.
     fn l1_chain_id(
         &self,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<zksync_basic_types::U64>> {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(U64::from(reader.fork_storage.chain_id.0))
+        })
     }
 
+    /// Returns a page of the confirmed tokens sorted by symbol: ETH (or the tokens of the forked
+    /// network) and the tokens registered with `InMemoryNodeInner::register_token`.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The number of tokens to skip
+    /// * `limit` - The maximum number of tokens to return
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the tokens.
     fn get_confirmed_tokens(
         &self,
-        _from: u32,
-        _limit: u8,
+        from: u32,
+        limit: u8,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Vec<zksync_web3_decl::types::Token>>> {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.confirmed_tokens(from, limit).map_err(|err| {
+                log::error!("failed reading the confirmed tokens: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })
+        })
     }
 
+    /// Returns the USD price of a token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_address` - The address of the token, the zero address for ETH
+    ///
+    /// # Returns
+    ///
+    /// The price set with `config_setTokenPrice` or, with `--fork-token-prices`, the price on the
+    /// forked network. Otherwise ETH is worth 1500 and any other token 1.
     fn get_token_price(
         &self,
         token_address: zksync_basic_types::Address,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<BigDecimal>> {
-        match format!("{:?}", token_address).to_lowercase().as_str() {
-            "0x0000000000000000000000000000000000000000" => {
-                // ETH
-                Ok(1_500.into()).into_boxed_future()
-            }
-            "0x40609141db628beee3bfab8034fc2d8278d0cc78" => {
-                // LINK
-                Ok(1.into()).into_boxed_future()
-            }
-            "0x0bfce1d53451b4a8175dd94e6e029f7d8a701e9c" => {
-                // wBTC
-                Ok(1.into()).into_boxed_future()
-            }
-            "0x0faf6df7054946141266420b43783387a78d82a9" => {
-                // USDC
-                Ok(1.into()).into_boxed_future()
-            }
-            "0x3e7676937a7e96cfb7616f255b9ad9ff47363d4b" => {
-                // DAI
-                Ok(1.into()).into_boxed_future()
-            }
-            address => {
-                log::error!(
-                    "{}",
-                    format!("Token price requested for unknown address {:?}", address).red()
-                );
-                futures::future::err(into_jsrpc_error(Web3Error::InternalError)).boxed()
-            }
-        }
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(reader.token_price(token_address))
+        })
     }
 
+    /// Returns the non-zero balances of an account: ETH under the zero address, and the tokens
+    /// that emitted a `Transfer` event locally or, on a fork, that the account holds on the network.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The account to return the balances of
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the balances keyed by token address.
     fn get_all_account_balances(
         &self,
-        _address: zksync_basic_types::Address,
+        address: zksync_basic_types::Address,
     ) -> jsonrpc_core::BoxFuture<
         jsonrpc_core::Result<std::collections::HashMap<zksync_basic_types::Address, U256>>,
     > {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.all_account_balances(address).map_err(|err| {
+                log::error!("failed reading the account balances: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })
+        })
     }
 
     fn get_l2_to_l1_msg_proof(
@@ -163,13 +335,31 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns a mock proof of an L2→L1 log sent by a transaction executed by the node, in the
+    /// format of the mainnet API. Every transaction is in its own batch, so the proof is the path
+    /// to the log in the Merkle tree of the logs of the transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_hash` - The hash of the transaction that sent the log
+    /// * `index` - The index of the log among the L2→L1 logs of the transaction, default: 0
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the proof, or `None` for unknown transactions and logs.
     fn get_l2_to_l1_log_proof(
         &self,
-        _tx_hash: zksync_basic_types::H256,
-        _index: Option<usize>,
+        tx_hash: zksync_basic_types::H256,
+        index: Option<usize>,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::L2ToL1LogProof>>>
     {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            Ok(reader.l2_to_l1_log_proof(tx_hash, index.unwrap_or_default()))
+        })
     }
 
     fn get_l1_batch_number(
@@ -178,12 +368,32 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns the zkSync specific details of a block. The blocks up to the fork point are read
+    /// from the forked network. Local blocks are never committed to L1, so their status is
+    /// `sealed` and their L1 transaction hashes are `null`.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_number` - The number of the block
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the `BlockDetails`, or `None` for unknown blocks.
     fn get_block_details(
         &self,
-        _block_number: zksync_basic_types::MiniblockNumber,
+        block_number: zksync_basic_types::MiniblockNumber,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::BlockDetails>>>
     {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.block_details(block_number).map_err(|err| {
+                log::error!("failed reading the block details: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })
+        })
     }
 
     fn get_miniblock_range(
@@ -195,12 +405,80 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns the zkSync specific details of a transaction executed by the node.
+    /// The transactions are never committed to L1, so the L1 transaction hashes are always `null`.
+    /// Unknown hashes are read from the forked network, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the transaction
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the `TransactionDetails`, or `None` for unknown transactions.
     fn get_transaction_details(
         &self,
-        _hash: zksync_basic_types::H256,
-    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<zksync_types::api::TransactionDetails>>>
-    {
-        not_implemented!()
+        hash: zksync_basic_types::H256,
+    ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<TransactionDetails>>> {
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+
+            let local_details = reader.tx_results.get(&hash).map(|info| {
+                let gas_used = info.tx.gas_limit() - info.result.gas_refunded;
+                let (is_l1_originated, gas_per_pubdata) = match &info.tx.common_data {
+                    ExecuteTransactionCommon::L1(common_data) => {
+                        (true, Some(common_data.gas_per_pubdata_limit))
+                    }
+                    ExecuteTransactionCommon::L2(common_data) => {
+                        (false, Some(common_data.fee.gas_per_pubdata_limit))
+                    }
+                    _ => (false, None),
+                };
+                TransactionDetails {
+                    is_l1_originated,
+                    status: if info.result.status == TxExecutionStatus::Success {
+                        TransactionStatus::Included
+                    } else {
+                        TransactionStatus::Failed
+                    },
+                    fee: gas_used * info.gas_price,
+                    gas_per_pubdata,
+                    initiator_address: info.tx.initiator_account(),
+                    received_at: Utc
+                        .timestamp_millis_opt(info.tx.received_timestamp_ms as i64)
+                        .single()
+                        .unwrap_or_else(Utc::now),
+                    eth_commit_tx_hash: None,
+                    eth_prove_tx_hash: None,
+                    eth_execute_tx_hash: None,
+                }
+            });
+            if local_details.is_some() {
+                return Ok(local_details);
+            }
+
+            let fork_storage = reader
+                .fork_storage
+                .inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            match fork_storage.fork.as_ref() {
+                Some(fork) => fork
+                    .fork_source
+                    .get_transaction_details(hash)
+                    .map_err(|err| {
+                        log::error!(
+                            "failed fetching the transaction details from the fork: {}",
+                            err
+                        );
+                        into_jsrpc_error(Web3Error::InternalError)
+                    }),
+                None => Ok(None),
+            }
+        })
     }
 
     fn get_l1_batch_details(
@@ -211,11 +489,30 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
         not_implemented!()
     }
 
+    /// Returns the bytecode with the given hash, including the factory deps of the executed
+    /// transactions and the default account. Hashes unknown locally are looked up on the fork.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the bytecode
+    ///
+    /// # Returns
+    ///
+    /// A `BoxFuture` containing a `Result` with the bytecode, or `None` for unknown hashes.
     fn get_bytecode_by_hash(
         &self,
-        _hash: zksync_basic_types::H256,
+        hash: zksync_basic_types::H256,
     ) -> jsonrpc_core::BoxFuture<jsonrpc_core::Result<Option<Vec<u8>>>> {
-        not_implemented!()
+        let inner = Arc::clone(&self.node);
+        Box::pin(async move {
+            let reader = inner
+                .read()
+                .map_err(|_| into_jsrpc_error(Web3Error::InternalError))?;
+            reader.bytecode_by_hash(hash).map_err(|err| {
+                log::error!("failed reading the bytecode: {}", err);
+                into_jsrpc_error(Web3Error::InternalError)
+            })
+        })
     }
 
     fn get_l1_gas_price(
@@ -236,13 +533,18 @@ impl<S: Send + Sync + 'static + ForkSource + std::fmt::Debug> ZksNamespaceT
 mod tests {
     use std::str::FromStr;
 
-    use crate::node::ShowCalls;
-    use crate::system_contracts;
-    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode};
+    use crate::node::NodeConfig;
+    use crate::testing::{ForkBlockConfig, MockServer};
+    use crate::{http_fork_source::HttpForkSource, node::InMemoryNode, testing};
 
     use super::*;
-    use zksync_basic_types::Address;
-    use zksync_types::transaction_request::CallRequest;
+    use zksync_basic_types::{Address, H256};
+    use zksync_core::api_server::web3::backend_jsonrpc::namespaces::eth::EthNamespaceT;
+    use zksync_types::{
+        get_known_code_key, transaction_request::CallRequest, utils::storage_key_for_eth_balance,
+        AccountTreeId, StorageKey,
+    };
+    use zksync_utils::u256_to_h256;
 
     #[tokio::test]
     async fn test_estimate_fee() {
@@ -280,6 +582,96 @@ mod tests {
         assert_eq!(result.gas_per_pubdata_limit, U256::from(4080));
     }
 
+    #[tokio::test]
+    async fn test_estimate_fee_breakdown_adds_up_to_the_gas_limit() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let request = CallRequest {
+            from: Some(Address::repeat_byte(0x01)),
+            to: Some(Address::repeat_byte(0x02)),
+            data: Some(vec![0, 0].into()),
+            ..Default::default()
+        };
+
+        let fee = namespace.estimate_fee(request.clone()).await.unwrap();
+        let breakdown = ZksTestNodeNamespaceT::estimate_fee_breakdown(&namespace, request)
+            .await
+            .unwrap();
+
+        assert_eq!(fee.gas_limit, breakdown.fee.gas_limit);
+        assert_eq!(fee.max_fee_per_gas, breakdown.fee.max_fee_per_gas);
+        assert_eq!(U256::zero(), breakdown.pubdata_cost);
+        assert!(!breakdown.overhead.is_zero());
+        assert_eq!(
+            breakdown.fee.gas_limit,
+            breakdown.tx_body_gas + breakdown.pubdata_cost + breakdown.overhead
+        );
+        let json = serde_json::to_value(&breakdown).unwrap();
+        assert!(json.get("pubdata_cost").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_fee_rpc_returns_the_breakdown() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        // registered in the same order as by the server
+        let mut io = jsonrpc_core::IoHandler::new();
+        io.extend_with(ZksNamespaceT::to_delegate(ZkMockNamespaceImpl::new(
+            node.get_inner(),
+        )));
+        io.extend_with(ZksTestNodeNamespaceT::to_delegate(
+            ZkMockNamespaceImpl::new(node.get_inner()),
+        ));
+
+        let response = io
+            .handle_request(
+                r#"{"jsonrpc":"2.0","method":"zks_estimateFee","params":[{"from":"0x0101010101010101010101010101010101010101","to":"0x0202020202020202020202020202020202020202","data":"0x0000"}],"id":1}"#,
+            )
+            .await
+            .expect("no response");
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let result = &response["result"];
+
+        for field in [
+            "gas_limit",
+            "gas_per_pubdata_limit",
+            "max_fee_per_gas",
+            "max_priority_fee_per_gas",
+            "tx_body_gas",
+            "pubdata_cost",
+            "overhead",
+        ] {
+            assert!(result.get(field).is_some(), "missing {}", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_estimate_fee_uses_configured_fee_params() {
+        let node = InMemoryNode::<HttpForkSource>::new(
+            None,
+            &NodeConfig {
+                fee_config: crate::node::FeeConfig {
+                    l2_gas_price: 100_000_000,
+                    fair_l2_gas_price: 500_000_000,
+                    gas_per_pubdata: Some(800),
+                },
+                ..Default::default()
+            },
+        );
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let request = CallRequest {
+            from: Some(Address::repeat_byte(0x01)),
+            to: Some(Address::repeat_byte(0x02)),
+            value: Some(U256::zero()),
+            ..Default::default()
+        };
+
+        let fee = namespace.estimate_fee(request).await.unwrap();
+
+        assert_eq!(U256::from(100_000_000), node.gas_price().await.unwrap());
+        assert_eq!(U256::from(500_000_000), fee.max_fee_per_gas);
+        assert_eq!(U256::from(800), fee.gas_per_pubdata_limit);
+    }
+
     #[tokio::test]
     async fn test_get_token_price_given_eth_should_return_price() {
         // Arrange
@@ -299,15 +691,7 @@ mod tests {
     #[tokio::test]
     async fn test_get_token_price_given_capitalized_link_address_should_return_price() {
         // Arrange
-        let node = InMemoryNode::<HttpForkSource>::new(
-            None,
-            ShowCalls::None,
-            crate::node::ShowStorageLogs::None,
-            crate::node::ShowVMDetails::None,
-            crate::node::ShowGasDetails::None,
-            false,
-            &system_contracts::Options::BuiltIn,
-        );
+        let node = InMemoryNode::<HttpForkSource>::default();
         let namespace = ZkMockNamespaceImpl::new(node.get_inner());
 
         let mock_address = Address::from_str("0x40609141Db628BeEE3BfAB8034Fc2D8278D0Cc78")
@@ -321,7 +705,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_token_price_given_unknown_address_should_return_error() {
+    async fn test_get_token_price_given_unknown_address_should_return_one() {
         // Arrange
         let node = InMemoryNode::<HttpForkSource>::default();
         let namespace = ZkMockNamespaceImpl::new(node.get_inner());
@@ -330,9 +714,514 @@ mod tests {
             .expect("Failed to parse address");
 
         // Act
-        let result = namespace.get_token_price(mock_address).await;
+        let result = namespace.get_token_price(mock_address).await.unwrap();
+
+        // Assert
+        assert_eq!(result, BigDecimal::from(1));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_price_returns_the_configured_price() {
+        // Arrange
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let price = BigDecimal::from_str("1834.25").unwrap();
+        node.get_inner()
+            .write()
+            .unwrap()
+            .token_prices
+            .insert(Address::zero(), price.clone());
+
+        // Act
+        let result = namespace.get_token_price(Address::zero()).await.unwrap();
+
+        // Assert
+        assert_eq!(result, price);
+    }
+
+    #[tokio::test]
+    async fn test_get_token_price_forwards_to_the_fork() {
+        // Arrange
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            transaction_count: 0,
+            hash: H256::repeat_byte(0xab),
+        });
+        let token = Address::repeat_byte(0x01);
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getTokenPrice",
+                "params": [format!("{:#x}", token)],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": "0.99",
+            }),
+        );
+        let node = testing::forked_node(&mock_server).await;
+        node.get_inner().write().unwrap().forward_token_prices = true;
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        // Act
+        let result = namespace.get_token_price(token).await.unwrap();
 
         // Assert
+        assert_eq!(result, BigDecimal::from_str("0.99").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_reload_system_contracts_rejects_built_in_contracts() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let result = namespace.reload_system_contracts().await;
+
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_get_transaction_details() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let tx_hash = zksync_basic_types::H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
+        let gas_price = {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            inner.l2_gas_price *= 2;
+            inner.l2_gas_price / 2
+        };
+
+        let details = namespace
+            .get_transaction_details(tx_hash)
+            .await
+            .expect("get_transaction_details")
+            .expect("transaction details not found");
+        let receipt = node
+            .get_transaction_receipt(tx_hash)
+            .await
+            .unwrap()
+            .expect("receipt not found");
+
+        assert!(!details.is_l1_originated);
+        assert!(matches!(details.status, TransactionStatus::Included));
+        assert_eq!(receipt.from, details.initiator_address);
+        assert_eq!(Some(U256::from(gas_price)), receipt.effective_gas_price);
+        assert_eq!(receipt.gas_used.unwrap() * gas_price, details.fee);
+        assert_eq!(Some(U256::from(20000)), details.gas_per_pubdata);
+        assert_eq!(None, details.eth_commit_tx_hash);
+    }
+
+    #[tokio::test]
+    async fn test_get_l2_to_l1_log_proof_of_sent_message() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let tx_hash = testing::send_l1_message(&node, b"hello L1");
+
+        let proof = namespace
+            .get_l2_to_l1_log_proof(tx_hash, None)
+            .await
+            .expect("get_l2_to_l1_log_proof")
+            .expect("proof not found");
+
+        assert_eq!(0, proof.id);
+        assert_eq!(9, proof.proof.len());
+        assert_ne!(H256::zero(), proof.root);
+        assert!(namespace
+            .get_l2_to_l1_log_proof(tx_hash, Some(100))
+            .await
+            .expect("get_l2_to_l1_log_proof")
+            .is_none());
+        assert!(namespace
+            .get_l2_to_l1_log_proof(H256::repeat_byte(0x01), None)
+            .await
+            .expect("get_l2_to_l1_log_proof")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction_details_unknown_transaction() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let details = namespace
+            .get_transaction_details(zksync_basic_types::H256::repeat_byte(0x01))
+            .await
+            .expect("get_transaction_details");
+
+        assert!(details.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction_details_forwards_unknown_transactions_to_the_fork() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            transaction_count: 0,
+            hash: H256::repeat_byte(0xab),
+        });
+        let tx_hash = H256::repeat_byte(0x02);
+        let initiator = Address::repeat_byte(0x03);
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getTransactionDetails",
+                "params": [format!("{:#x}", tx_hash)],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": {
+                    "isL1Originated": false,
+                    "status": "verified",
+                    "fee": "0x10",
+                    "gasPerPubdata": "0x320",
+                    "initiatorAddress": format!("{:#x}", initiator),
+                    "receivedAt": "2023-10-10T10:00:00Z",
+                    "ethCommitTxHash": format!("{:#x}", H256::repeat_byte(0x04)),
+                    "ethProveTxHash": null,
+                    "ethExecuteTxHash": null,
+                },
+            }),
+        );
+        let node = testing::forked_node(&mock_server).await;
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let details = namespace
+            .get_transaction_details(tx_hash)
+            .await
+            .expect("get_transaction_details")
+            .expect("transaction details not found");
+
+        assert!(matches!(details.status, TransactionStatus::Verified));
+        assert_eq!(initiator, details.initiator_address);
+        assert_eq!(U256::from(16), details.fee);
+        assert_eq!(Some(H256::repeat_byte(0x04)), details.eth_commit_tx_hash);
+    }
+
+    #[tokio::test]
+    async fn test_l1_config_methods_return_local_defaults() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let bridges = namespace
+            .get_bridge_contracts()
+            .await
+            .expect("zks_getBridgeContracts failed");
+        let main_contract = namespace
+            .get_main_contract()
+            .await
+            .expect("zks_getMainContract failed");
+        let l1_chain_id = namespace.l1_chain_id().await.expect("zks_L1ChainId failed");
+
+        assert_eq!(Address::zero(), bridges.l1_erc20_default_bridge);
+        assert_eq!(Address::zero(), bridges.l2_erc20_default_bridge);
+        assert_eq!(Address::zero(), main_contract);
+        assert_eq!(
+            U64::from(node.get_inner().read().unwrap().fork_storage.chain_id.0),
+            l1_chain_id
+        );
+        // the same values are returned on every call
+        let bridges_again = namespace
+            .get_bridge_contracts()
+            .await
+            .expect("zks_getBridgeContracts failed");
+        assert_eq!(
+            bridges.l1_erc20_default_bridge,
+            bridges_again.l1_erc20_default_bridge
+        );
+        assert_eq!(
+            bridges.l2_erc20_default_bridge,
+            bridges_again.l2_erc20_default_bridge
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_block_details_of_local_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let block_hash = testing::apply_tx(&node, H256::repeat_byte(0x01));
+        let (l1_gas_price, fair_l2_gas_price) = {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            let gas_prices = (inner.l1_gas_price, inner.fair_l2_gas_price);
+            inner.l1_gas_price += 1;
+            inner.fair_l2_gas_price += 1;
+            gas_prices
+        };
+
+        let details = namespace
+            .get_block_details(MiniblockNumber(1))
+            .await
+            .expect("failed getting block details")
+            .expect("no block details");
+
+        let inner = node.get_inner();
+        let inner = inner.read().unwrap();
+        assert_eq!(MiniblockNumber(1), details.number);
+        assert_eq!(0, details.base.l1_tx_count);
+        assert_eq!(1, details.base.l2_tx_count);
+        assert_eq!(
+            Some(inner.blocks.get(&block_hash).unwrap().state_root),
+            details.base.root_hash
+        );
+        assert_ne!(Some(block_hash), details.base.root_hash);
+        assert_eq!(l1_gas_price, details.base.l1_gas_price);
+        assert_eq!(fair_l2_gas_price, details.base.l2_fair_gas_price);
+        assert_eq!(None, details.base.commit_tx_hash);
+        assert_eq!(
+            inner.system_contracts.baseline_contracts.hashes(),
+            details.base.base_system_contracts_hashes
+        );
+        assert!(namespace
+            .get_block_details(MiniblockNumber(2))
+            .await
+            .expect("failed getting block details")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_block_details_forwards_pre_fork_blocks() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            transaction_count: 0,
+            hash: H256::repeat_byte(0xab),
+        });
+        let node = testing::forked_node(&mock_server).await;
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getBlockDetails",
+                "params": [5],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": null,
+            }),
+        );
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let details = namespace
+            .get_block_details(MiniblockNumber(5))
+            .await
+            .expect("failed getting block details");
+
+        assert!(details.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_raw_block_transactions_of_local_blocks() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let tx_hash = H256::repeat_byte(0x01);
+        testing::apply_tx(&node, tx_hash);
+
+        let txs = namespace
+            .get_raw_block_transactions(MiniblockNumber(1))
+            .await
+            .expect("failed getting raw block transactions");
+
+        assert_eq!(1, txs.len());
+        assert_eq!(tx_hash, txs[0].hash());
+        let json = serde_json::to_value(&txs[0]).expect("failed serializing transaction");
+        for key in ["common_data", "execute", "received_timestamp_ms"] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+        assert!(namespace
+            .get_raw_block_transactions(MiniblockNumber(2))
+            .await
+            .expect("failed getting raw block transactions")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_raw_block_transactions_forwards_pre_fork_blocks() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            transaction_count: 0,
+            hash: H256::repeat_byte(0xab),
+        });
+        let node = testing::forked_node(&mock_server).await;
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getRawBlockTransactions",
+                "params": [5],
+            }),
+            testing::RawTransactionsResponseBuilder::new()
+                .add(1)
+                .add(2)
+                .build(),
+        );
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let txs = namespace
+            .get_raw_block_transactions(MiniblockNumber(5))
+            .await
+            .expect("failed getting raw block transactions");
+
+        assert_eq!(2, txs.len());
+    }
+
+    #[tokio::test]
+    async fn test_get_bytecode_by_hash_returns_local_and_system_bytecodes() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let code = vec![0u8; 32];
+        let code_hash = hash_bytecode(&code);
+        node.get_inner()
+            .write()
+            .unwrap()
+            .fork_storage
+            .store_factory_dep(code_hash, code.clone());
+        let default_aa = node
+            .get_inner()
+            .read()
+            .unwrap()
+            .system_contracts
+            .baseline_contracts
+            .default_aa
+            .clone();
+
+        let bytecode = namespace
+            .get_bytecode_by_hash(code_hash)
+            .await
+            .expect("failed getting bytecode");
+        assert_eq!(Some(code), bytecode);
+
+        let bytecode = namespace
+            .get_bytecode_by_hash(default_aa.hash)
+            .await
+            .expect("failed getting bytecode")
+            .expect("missing default account bytecode");
+        assert_eq!(default_aa.hash, hash_bytecode(&bytecode));
+
+        let bytecode = namespace
+            .get_bytecode_by_hash(H256::repeat_byte(0x01))
+            .await
+            .expect("failed getting bytecode");
+        assert!(bytecode.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_bytecode_by_hash_forwards_unknown_hashes_to_fork() {
+        let mock_server = MockServer::run_with_config(ForkBlockConfig {
+            number: 10,
+            transaction_count: 0,
+            hash: H256::repeat_byte(0xab),
+        });
+        let node = testing::forked_node(&mock_server).await;
+        let hash = H256::repeat_byte(0x12);
+        mock_server.expect(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "zks_getBytecodeByHash",
+                "params": [format!("{:#x}", hash)],
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": [1, 2, 3, 4],
+            }),
+        );
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+
+        let bytecode = namespace
+            .get_bytecode_by_hash(hash)
+            .await
+            .expect("failed getting bytecode");
+
+        assert_eq!(Some(vec![1, 2, 3, 4]), bytecode);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_account_balances_skips_tokens_without_erc20_balance_of() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let address = Address::repeat_byte(0x01);
+        let token = Address::repeat_byte(0x02);
+        node.set_rich_account(address);
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            let code = crate::deps::system_contracts::bytecode_from_slice(
+                "L2EthToken",
+                include_bytes!("deps/contracts/L2EthToken.json"),
+            );
+            let code_hash = hash_bytecode(&code);
+            inner.fork_storage.store_factory_dep(code_hash, code);
+            inner
+                .fork_storage
+                .set_value(get_known_code_key(&code_hash), u256_to_h256(U256::one()));
+            inner
+                .fork_storage
+                .set_value(get_code_key(&token), code_hash);
+            // L2EthToken only has `balanceOf(uint256)`, so the ERC20 `balanceOf(address)`
+            // call reverts even though the account has a balance
+            let balance_key = storage_key_for_eth_balance(&address);
+            inner.fork_storage.set_value(
+                StorageKey::new(AccountTreeId::new(token), *balance_key.key()),
+                u256_to_h256(U256::from(42)),
+            );
+            inner.known_tokens.insert(token);
+            // not a contract, `balanceOf` returns nothing
+            inner.known_tokens.insert(Address::repeat_byte(0x03));
+        }
+
+        let balances = namespace
+            .get_all_account_balances(address)
+            .await
+            .expect("failed getting balances");
+
+        assert_eq!(
+            std::collections::HashMap::from([(Address::zero(), U256::from(10u128.pow(30)))]),
+            balances
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_confirmed_tokens_paginates_eth_and_registered_tokens() {
+        let node = InMemoryNode::<HttpForkSource>::default();
+        let namespace = ZkMockNamespaceImpl::new(node.get_inner());
+        let token = |symbol: &str, byte: u8| zksync_web3_decl::types::Token {
+            l1_address: Address::repeat_byte(byte),
+            l2_address: Address::repeat_byte(byte),
+            name: symbol.to_string(),
+            symbol: symbol.to_string(),
+            decimals: 18,
+        };
+        {
+            let inner = node.get_inner();
+            let mut inner = inner.write().unwrap();
+            inner.register_token(token("USDC", 0x01));
+            inner.register_token(token("DAI", 0x02));
+        }
+
+        let symbols = |tokens: Vec<zksync_web3_decl::types::Token>| {
+            tokens
+                .into_iter()
+                .map(|token| token.symbol)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["DAI", "ETH", "USDC"],
+            symbols(namespace.get_confirmed_tokens(0, 10).await.unwrap())
+        );
+        assert_eq!(
+            vec!["ETH"],
+            symbols(namespace.get_confirmed_tokens(1, 1).await.unwrap())
+        );
+        assert!(namespace
+            .get_confirmed_tokens(3, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
 }